@@ -0,0 +1,90 @@
+//! Compares parsing into, and walking, a `Document` of `Rc<RefCell<Element>>` with the arena `Tree`
+//!
+//! Both trees are built by the parser from the same input, with `Parser::new` for the
+//! `Document` and `Parser::with_sink` for the `Tree`.
+use std::{cell::RefCell, fs, rc::Rc};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use html_parser::{
+    dom::{
+        arena::{NodeData, NodeId, Tree},
+        element::ChildNode,
+        Document, Element,
+    },
+    Parser,
+};
+
+const INPUTS: &[&str] = &["google.html", "twitter.html"];
+
+fn read(name: &str) -> Vec<u8> {
+    fs::read(format!("tests/testdata/{}", name)).unwrap()
+}
+
+fn parse_document(input: &[u8]) -> Document {
+    Parser::new(input).parse().unwrap()
+}
+
+fn parse_arena(input: &[u8]) -> Tree {
+    Parser::with_sink(input, Tree::new()).parse().unwrap()
+}
+
+/// The number of attributes, and the length of all the text
+fn walk_rc(element: &Rc<RefCell<Element>>) -> (usize, usize) {
+    let element = element.borrow();
+    let mut stats = (element.attributes().len(), 0);
+    for child in element.iter() {
+        match child {
+            ChildNode::Element(e) => {
+                let (attributes, text) = walk_rc(e);
+                stats.0 += attributes;
+                stats.1 += text;
+            }
+            ChildNode::Text(t) => stats.1 += t.borrow().len(),
+            ChildNode::ProcessingInstruction(_) | ChildNode::Comment(_) => {}
+        }
+    }
+    stats
+}
+
+/// See `walk_rc`
+fn walk_arena(tree: &Tree, root: NodeId) -> (usize, usize) {
+    let mut stats = (0, 0);
+    for id in std::iter::once(root).chain(tree.descendants(root)) {
+        match tree.data(id) {
+            NodeData::Element(e) => stats.0 += e.attributes().len(),
+            NodeData::Text(t) => stats.1 += t.len(),
+            _ => {}
+        }
+    }
+    stats
+}
+
+fn bench_parse(c: &mut Criterion) {
+    for name in INPUTS {
+        let input = read(name);
+        let mut group = c.benchmark_group(format!("parse {}", name));
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function("document", |b| b.iter(|| parse_document(black_box(&input))));
+        group.bench_function("arena", |b| b.iter(|| parse_arena(black_box(&input))));
+        group.finish();
+    }
+}
+
+fn bench_walk(c: &mut Criterion) {
+    for name in INPUTS {
+        let input = read(name);
+        let document = parse_document(&input).document_element().unwrap();
+        let tree = parse_arena(&input);
+        let root = tree.document_element().unwrap();
+        assert_eq!(walk_rc(&document), walk_arena(&tree, root));
+
+        let mut group = c.benchmark_group(format!("walk {}", name));
+        group.bench_function("document", |b| b.iter(|| walk_rc(black_box(&document))));
+        group.bench_function("arena", |b| b.iter(|| walk_arena(black_box(&tree), root)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_parse, bench_walk);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+//! Tokenizer and parser throughput on real pages, with and without collapsing character tokens
+//!
+//! Most of a page is plain text and attribute values, which the tokenizer consumes in runs
+//! without stepping through the state machine. The parser builds the tree from those runs too,
+//! rather than inserting each character.
+use std::fs;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use html_parser::{dom::Document, tokenizer::Token, Parser, Tokenizer};
+
+const INPUTS: &[&str] = &["google.html", "twitter.html", "charref.html"];
+
+/// The number of tokens, and how much of the text could be borrowed from the input
+fn tokenize(input: &[u8], collapse_chars: bool) -> (usize, usize) {
+    let mut tokenizer = Tokenizer::new(input, collapse_chars);
+    let mut stats = (0, 0);
+    while let Some((token, span)) = tokenizer.next() {
+        stats.0 += 1;
+        if let Token::Characters(_) = token {
+            if tokenizer.token_is_verbatim() {
+                stats.1 += span.end.offset - span.start.offset;
+            }
+        }
+    }
+    stats
+}
+
+fn parse(input: &[u8], collapse_chars: bool) -> Document {
+    let tokenizer = Tokenizer::new(input, collapse_chars);
+    Parser::with_tokenizer(tokenizer, Document::default())
+        .parse()
+        .unwrap()
+}
+
+fn bench_tokenize(c: &mut Criterion) {
+    for name in INPUTS {
+        let input = fs::read(format!("tests/testdata/{}", name)).unwrap();
+        let mut group = c.benchmark_group(format!("tokenize {}", name));
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function("characters", |b| {
+            b.iter(|| tokenize(black_box(&input), false))
+        });
+        group.bench_function("collapsed", |b| {
+            b.iter(|| tokenize(black_box(&input), true))
+        });
+        group.finish();
+    }
+}
+
+fn bench_parse(c: &mut Criterion) {
+    for name in INPUTS {
+        let input = fs::read(format!("tests/testdata/{}", name)).unwrap();
+        let mut group = c.benchmark_group(format!("parse {}", name));
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function("characters", |b| b.iter(|| parse(black_box(&input), false)));
+        group.bench_function("collapsed", |b| b.iter(|| parse(black_box(&input), true)));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_tokenize, bench_parse);
+criterion_main!(benches);
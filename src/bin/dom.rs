@@ -1,7 +1,7 @@
 use std::rc::Rc;
 use std::io;
 
-use html_parser::dom::{Comment, Document, DocumentType, Element};
+use html_parser::dom::{Comment, Document, DocumentType, Element, ToHtml};
 
 extern crate pretty_env_logger;
 
@@ -23,6 +23,7 @@ fn main() -> io::Result<()> {
     document.push_element(Rc::clone(&node));
 
     println!("Document: {:#?}", document);
+    println!("HTML: {}", document.to_html());
 
     Ok(())
 }
@@ -1,14 +1,21 @@
-use std::{env, fs, io};
+use std::{env, error::Error, fs, io};
 
 extern crate pretty_env_logger;
 
-fn main() -> io::Result<()> {
+fn main() -> Result<(), Box<dyn Error>> {
     pretty_env_logger::init();
 
-    let input = env::args().nth(1).unwrap();
-    let mut f = fs::File::open(input)?;
-
-    let mut parser = html_parser::Parser::new(&mut f);
-    parser.run();
+    match env::args().nth(1) {
+        Some(input) if input != "-" => {
+            let mut f = fs::File::open(input)?;
+            let mut parser = html_parser::Parser::new(&mut f);
+            parser.run()?;
+        }
+        _ => {
+            let stdin = io::stdin();
+            let mut parser = html_parser::Parser::new(stdin.lock());
+            parser.run()?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,880 @@
+//! A document tree stored in a single arena
+//!
+//! Every node lives in one `Vec` and is referred to by its `NodeId`, with the parent, child and
+//! sibling links stored as ids. This avoids an allocation and a `RefCell` per node, and the
+//! tree is `Send + Sync`, so it can be shared between threads once it's built.
+//!
+//! A `Tree` can be built with the same operations the parser uses (create a node, append it,
+//! insert it before a sibling, append text, detach it), or converted from a parsed `Document`.
+//! It's also a `TreeSink`, so the parser can build one directly with `Parser::with_sink`.
+use std::{cell::RefCell, convert::TryFrom, num::NonZeroU32, rc::Rc};
+
+use derive_more::{Deref, DerefMut};
+
+use crate::{
+    dom::{
+        document, element::ChildNode, foreign, Attribute, Comment, Document, DocumentType, Element,
+        Namespace, ProcessingInstruction, QuirksMode, ScriptFlags, SourceSpan, Text,
+    },
+    parser::{NodeOrText, TreeSink},
+    tokenizer::{Span, TagName},
+};
+
+/// The position of a node in its `Tree`
+///
+/// This is one more than the node's index, so that an `Option<NodeId>` is as small as a `u32`.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NodeId(NonZeroU32);
+
+impl NodeId {
+    fn index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NodeData {
+    /// The root of the tree
+    Document,
+    // Boxed as it's much larger than the other nodes, and there's only one
+    DocumentType(Box<DocumentType>),
+    /// The contents of a `template` element
+    DocumentFragment,
+    Element(ElementData),
+    Text(CharacterData),
+    ProcessingInstruction(CharacterData),
+    Comment(CharacterData),
+}
+
+/// The data of a text, comment or processing instruction node in a `Tree`
+///
+/// The nodes of a `Document` link to their parent, so their types can't be shared between
+/// threads and aren't used here.
+#[derive(Clone, Debug, Default, Deref, DerefMut, Eq, PartialEq)]
+pub struct CharacterData {
+    #[deref]
+    #[deref_mut]
+    data: String,
+    span: SourceSpan,
+}
+
+impl CharacterData {
+    #[must_use]
+    pub fn new(data: String) -> Self {
+        CharacterData {
+            data,
+            span: SourceSpan::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
+    /// See `Text::span` and `Comment::span`
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    /// See `Text::push_str_with_span`
+    pub fn push_str_with_span(&mut self, data: &str, span: Span) {
+        self.data.push_str(data);
+        self.span.0 = Some(self.span.0.map_or(span, |s| s.union(span)));
+    }
+}
+
+impl From<&Text> for CharacterData {
+    fn from(text: &Text) -> Self {
+        CharacterData {
+            data: text.to_string(),
+            span: SourceSpan(text.span()),
+        }
+    }
+}
+
+impl From<&Comment> for CharacterData {
+    fn from(comment: &Comment) -> Self {
+        CharacterData {
+            data: comment.data().to_string(),
+            span: SourceSpan(comment.span()),
+        }
+    }
+}
+
+impl From<&ProcessingInstruction> for CharacterData {
+    fn from(pi: &ProcessingInstruction) -> Self {
+        CharacterData::new(pi.data().to_string())
+    }
+}
+
+/// The name, namespace and attributes of an element in a `Tree`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ElementData {
+    pub name: TagName,
+    pub namespace: Namespace,
+    attributes: Vec<Attribute>,
+    script_flags: ScriptFlags,
+    template_contents: Option<NodeId>,
+    span: SourceSpan,
+}
+
+impl ElementData {
+    #[must_use]
+    pub fn name(&self) -> &TagName {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// See `Element::local_name`
+    #[must_use]
+    pub fn local_name(&self) -> String {
+        let name = self.name.to_string();
+        if self.namespace == Namespace::SVG {
+            if let Some(adjusted) = foreign::svg_tag_name(&name) {
+                return adjusted.to_string();
+            }
+        }
+        name
+    }
+
+    #[must_use]
+    pub fn is_html(&self) -> bool {
+        self.namespace == Namespace::HTML
+    }
+
+    /// The attribute list, in the order the attributes were added
+    #[must_use]
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// See `Element::script_flags`
+    #[must_use]
+    pub fn script_flags(&self) -> ScriptFlags {
+        self.script_flags
+    }
+
+    /// See `Element::template_contents`
+    #[must_use]
+    pub fn template_contents(&self) -> Option<NodeId> {
+        self.template_contents
+    }
+
+    /// See `Element::span`
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    /// See `Element::get_attribute`
+    #[must_use]
+    pub fn get_attribute(&self, qualified_name: &str) -> Option<&str> {
+        let qualified_name = if self.is_html() {
+            qualified_name.to_ascii_lowercase()
+        } else {
+            qualified_name.to_string()
+        };
+        self.attributes
+            .iter()
+            .find(|a| a.qualified_name() == qualified_name)
+            .map(Attribute::value)
+    }
+}
+
+/// The links from a node to its neighbours in the tree
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct Links {
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+}
+
+/// A document and all of its nodes
+///
+/// Nodes which are detached stay in the arena until the tree is dropped.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Tree {
+    data: Vec<NodeData>,
+    // Kept apart from the data, so walking the tree only touches the links
+    links: Vec<Links>,
+    mode: QuirksMode,
+}
+
+impl Default for Tree {
+    fn default() -> Self {
+        Tree::new()
+    }
+}
+
+impl Tree {
+    /// A tree containing only the document node
+    #[must_use]
+    pub fn new() -> Self {
+        Tree {
+            data: vec![NodeData::Document],
+            links: vec![Links::default()],
+            mode: QuirksMode::NoQuirks,
+        }
+    }
+
+    /// The number of nodes in the arena, including detached nodes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        // There's always a document node
+        false
+    }
+
+    /// The document node at the root of the tree
+    #[must_use]
+    pub fn document(&self) -> NodeId {
+        NodeId(NonZeroU32::new(1).unwrap())
+    }
+
+    /// <https://dom.spec.whatwg.org/#concept-document-mode>
+    #[must_use]
+    pub fn mode(&self) -> QuirksMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: QuirksMode) {
+        self.mode = mode;
+    }
+
+    /// The element child of the document
+    #[must_use]
+    pub fn document_element(&self) -> Option<NodeId> {
+        self.children(self.document())
+            .find(|&id| self.element(id).is_some())
+    }
+
+    #[must_use]
+    pub fn data(&self, id: NodeId) -> &NodeData {
+        &self.data[id.index()]
+    }
+
+    pub fn data_mut(&mut self, id: NodeId) -> &mut NodeData {
+        &mut self.data[id.index()]
+    }
+
+    /// The node's element data, if it's an element
+    #[must_use]
+    pub fn element(&self, id: NodeId) -> Option<&ElementData> {
+        match self.data(id) {
+            NodeData::Element(element) => Some(element),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].parent
+    }
+
+    #[must_use]
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].first_child
+    }
+
+    #[must_use]
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].last_child
+    }
+
+    #[must_use]
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].previous_sibling
+    }
+
+    #[must_use]
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id.index()].next_sibling
+    }
+
+    #[must_use]
+    pub fn children(&self, id: NodeId) -> Children<'_> {
+        Children {
+            tree: self,
+            next: self.first_child(id),
+        }
+    }
+
+    /// The contents of the node, if it's a `template` element
+    #[must_use]
+    pub fn template_contents(&self, id: NodeId) -> Option<NodeId> {
+        self.element(id)?.template_contents
+    }
+
+    /// The parent of the node, then its parent, and so on up to the document
+    #[must_use]
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_> {
+        Ancestors {
+            tree: self,
+            next: self.parent(id),
+        }
+    }
+
+    /// Every node under `id`, in tree order
+    #[must_use]
+    pub fn descendants(&self, id: NodeId) -> Descendants<'_> {
+        Descendants {
+            tree: self,
+            root: id,
+            next: self.first_child(id),
+        }
+    }
+
+    fn push(&mut self, data: NodeData) -> NodeId {
+        self.data.push(data);
+        self.links.push(Links::default());
+        let id = u32::try_from(self.data.len()).expect("too many nodes in the tree");
+        NodeId(NonZeroU32::new(id).unwrap())
+    }
+
+    /// Add a new element to the arena, without inserting it into the tree
+    ///
+    /// An HTML `template` element gets a document fragment node for its contents.
+    pub fn create_element(
+        &mut self,
+        name: TagName,
+        namespace: Namespace,
+        attributes: Vec<Attribute>,
+    ) -> NodeId {
+        let template_contents = if namespace == Namespace::HTML && name == TagName::Template {
+            Some(self.push(NodeData::DocumentFragment))
+        } else {
+            None
+        };
+        self.push(NodeData::Element(ElementData {
+            name,
+            namespace,
+            attributes,
+            script_flags: ScriptFlags::default(),
+            template_contents,
+            span: SourceSpan::default(),
+        }))
+    }
+
+    pub fn create_text<S: Into<String>>(&mut self, data: S) -> NodeId {
+        self.push(NodeData::Text(CharacterData::new(data.into())))
+    }
+
+    pub fn create_comment<S: Into<String>>(&mut self, data: S) -> NodeId {
+        self.push(NodeData::Comment(CharacterData::new(data.into())))
+    }
+
+    pub fn create_processing_instruction<S: Into<String>>(&mut self, data: S) -> NodeId {
+        self.push(NodeData::ProcessingInstruction(CharacterData::new(
+            data.into(),
+        )))
+    }
+
+    pub fn create_document_type(&mut self, document_type: DocumentType) -> NodeId {
+        self.push(NodeData::DocumentType(Box::new(document_type)))
+    }
+
+    /// Add `attribute` to the element `id`, unless it already has an attribute with that name
+    ///
+    /// Returns whether the attribute was added
+    ///
+    /// # Panics
+    /// If `id` isn't an element
+    pub fn add_attribute_if_missing(&mut self, id: NodeId, attribute: Attribute) -> bool {
+        let element = match self.data_mut(id) {
+            NodeData::Element(element) => element,
+            _ => panic!("add_attribute_if_missing on a node which isn't an element"),
+        };
+        if element.get_attribute(&attribute.qualified_name()).is_some() {
+            false
+        } else {
+            element.attributes.push(attribute);
+            true
+        }
+    }
+
+    /// See `Element::set_script_flags`
+    ///
+    /// # Panics
+    /// If `id` isn't an element
+    pub fn set_script_flags(&mut self, id: NodeId, flags: ScriptFlags) {
+        match self.data_mut(id) {
+            NodeData::Element(element) => element.script_flags = flags,
+            _ => panic!("set_script_flags on a node which isn't an element"),
+        }
+    }
+
+    /// Set where an element, text or comment node was in the input
+    ///
+    /// The span of a text node is extended to cover `span`, as text is merged into it.
+    ///
+    /// # Panics
+    /// If the node is another kind of node
+    pub fn set_span(&mut self, id: NodeId, span: Span) {
+        match self.data_mut(id) {
+            NodeData::Element(element) => element.span = SourceSpan(Some(span)),
+            NodeData::Text(text) => text.push_str_with_span("", span),
+            NodeData::Comment(comment) => comment.span = SourceSpan(Some(span)),
+            _ => panic!("set_span on a node which can't have a span"),
+        }
+    }
+
+    /// Make `child` the last child of `parent`, removing it from its current parent first
+    pub fn append(&mut self, parent: NodeId, child: NodeId) {
+        self.detach(child);
+        let previous = self.links[parent.index()].last_child;
+        {
+            let links = &mut self.links[child.index()];
+            links.parent = Some(parent);
+            links.previous_sibling = previous;
+        }
+        match previous {
+            Some(previous) => self.links[previous.index()].next_sibling = Some(child),
+            None => self.links[parent.index()].first_child = Some(child),
+        }
+        self.links[parent.index()].last_child = Some(child);
+    }
+
+    /// Insert `child` immediately before `sibling`, removing it from its current parent first
+    ///
+    /// # Panics
+    /// If `sibling` doesn't have a parent
+    pub fn insert_before(&mut self, sibling: NodeId, child: NodeId) {
+        self.detach(child);
+        let parent = self
+            .parent(sibling)
+            .expect("insert_before a node without a parent");
+        let previous = self.links[sibling.index()].previous_sibling;
+        {
+            let links = &mut self.links[child.index()];
+            links.parent = Some(parent);
+            links.previous_sibling = previous;
+            links.next_sibling = Some(sibling);
+        }
+        self.links[sibling.index()].previous_sibling = Some(child);
+        match previous {
+            Some(previous) => self.links[previous.index()].next_sibling = Some(child),
+            None => self.links[parent.index()].first_child = Some(child),
+        }
+    }
+
+    /// Append `data` to `parent`, adding it to the last child if that's a text node
+    pub fn append_text(&mut self, parent: NodeId, data: &str) {
+        if let Some(last) = self.last_child(parent) {
+            if let NodeData::Text(ref mut text) = self.data[last.index()] {
+                return text.push_str(data);
+            }
+        }
+        let text = self.create_text(data);
+        self.append(parent, text);
+    }
+
+    /// Insert `data` before `sibling`, adding it to the previous sibling if that's a text node
+    ///
+    /// # Panics
+    /// If `sibling` doesn't have a parent
+    pub fn insert_text_before(&mut self, sibling: NodeId, data: &str) {
+        if let Some(previous) = self.previous_sibling(sibling) {
+            if let NodeData::Text(ref mut text) = self.data[previous.index()] {
+                return text.push_str(data);
+            }
+        }
+        let text = self.create_text(data);
+        self.insert_before(sibling, text);
+    }
+
+    /// Remove the node from its parent, if it has one
+    pub fn detach(&mut self, id: NodeId) {
+        let (parent, previous, next) = {
+            let links = &mut self.links[id.index()];
+            let parent = match links.parent.take() {
+                Some(parent) => parent,
+                None => return,
+            };
+            (
+                parent,
+                links.previous_sibling.take(),
+                links.next_sibling.take(),
+            )
+        };
+        match previous {
+            Some(previous) => self.links[previous.index()].next_sibling = next,
+            None => self.links[parent.index()].first_child = next,
+        }
+        match next {
+            Some(next) => self.links[next.index()].previous_sibling = previous,
+            None => self.links[parent.index()].last_child = previous,
+        }
+    }
+
+    /// Move all the children of `id` to the end of `new_parent`'s children
+    pub fn reparent_children(&mut self, id: NodeId, new_parent: NodeId) {
+        while let Some(child) = self.first_child(id) {
+            self.append(new_parent, child);
+        }
+    }
+
+    fn append_element(&mut self, parent: NodeId, element: &Rc<RefCell<Element>>) {
+        let element = element.borrow();
+        let id = self.create_element(
+            element.name().clone(),
+            element.namespace(),
+            element.attributes().to_vec(),
+        );
+        self.set_script_flags(id, element.script_flags());
+        if let Some(span) = element.span() {
+            self.set_span(id, span);
+        }
+        self.append(parent, id);
+        if let (Some(contents), Some(contents_id)) = (
+            element.template_contents(),
+            Tree::template_contents(self, id),
+        ) {
+            self.append_children(contents_id, contents.borrow().children());
+        }
+        self.append_children(id, &element);
+    }
+
+    fn append_children(&mut self, parent: NodeId, children: &[ChildNode]) {
+        for child in children {
+            let child_id = match child {
+                ChildNode::Element(e) => {
+                    self.append_element(parent, e);
+                    continue;
+                }
+                ChildNode::Text(t) => self.push(NodeData::Text((&*t.borrow()).into())),
+                ChildNode::ProcessingInstruction(pi) => {
+                    self.push(NodeData::ProcessingInstruction((&*pi.borrow()).into()))
+                }
+                ChildNode::Comment(c) => self.push(NodeData::Comment((&*c.borrow()).into())),
+            };
+            self.append(parent, child_id);
+        }
+    }
+
+    fn append_document_children(&mut self, children: &[document::ChildNode]) {
+        for child in children {
+            let id = match child {
+                document::ChildNode::ProcessingInstruction(pi) => {
+                    self.push(NodeData::ProcessingInstruction(pi.into()))
+                }
+                document::ChildNode::Comment(c) => self.push(NodeData::Comment(c.into())),
+            };
+            self.append(self.document(), id);
+        }
+    }
+}
+
+impl From<&Document> for Tree {
+    fn from(document: &Document) -> Self {
+        let mut tree = Tree::new();
+        tree.mode = document.mode();
+        tree.append_document_children(&document.first_children);
+        if let Some(ref document_type) = document.document_type {
+            let id = tree.create_document_type(document_type.clone());
+            tree.append(tree.document(), id);
+        }
+        tree.append_document_children(&document.second_children);
+        if let Some(ref element) = document.element {
+            tree.append_element(tree.document(), element);
+        }
+        tree.append_document_children(&document.third_children);
+        tree
+    }
+}
+
+impl TreeSink for Tree {
+    type Handle = NodeId;
+    type Output = Self;
+
+    fn finish(self) -> Self {
+        self
+    }
+
+    fn restart(&mut self) {
+        *self = Tree::new();
+    }
+
+    fn document(&self) -> NodeId {
+        Tree::document(self)
+    }
+
+    fn create_element(
+        &mut self,
+        name: TagName,
+        namespace: Namespace,
+        attributes: Vec<Attribute>,
+    ) -> NodeId {
+        Tree::create_element(self, name, namespace, attributes)
+    }
+
+    fn create_comment(&mut self, data: String) -> NodeId {
+        Tree::create_comment(self, data)
+    }
+
+    fn set_source_span(&mut self, node: &NodeId, span: Span) {
+        self.set_span(*node, span);
+    }
+
+    fn append(&mut self, parent: &NodeId, child: NodeOrText<NodeId>) {
+        match child {
+            NodeOrText::Node(id) => Tree::append(self, *parent, id),
+            NodeOrText::Text(data, span) => {
+                self.append_text(*parent, &data);
+                // The text was either merged into the last child, or is the new last child
+                let text = self.last_child(*parent).unwrap();
+                self.set_span(text, span);
+            }
+        }
+    }
+
+    fn append_before_sibling(&mut self, sibling: &NodeId, child: NodeOrText<NodeId>) {
+        match child {
+            NodeOrText::Node(id) => self.insert_before(*sibling, id),
+            NodeOrText::Text(data, span) => {
+                self.insert_text_before(*sibling, &data);
+                let text = self.previous_sibling(*sibling).unwrap();
+                self.set_span(text, span);
+            }
+        }
+    }
+
+    fn append_based_on_parent_node(
+        &mut self,
+        element: &NodeId,
+        prev_element: &NodeId,
+        child: NodeOrText<NodeId>,
+    ) {
+        if self.parent(*element).is_some() {
+            self.append_before_sibling(element, child);
+        } else {
+            TreeSink::append(self, prev_element, child);
+        }
+    }
+
+    fn append_doctype_to_document(&mut self, document_type: DocumentType) {
+        let id = self.create_document_type(document_type);
+        Tree::append(self, Tree::document(self), id);
+    }
+
+    fn get_attribute(&self, element: &NodeId, qualified_name: &str) -> Option<String> {
+        self.element(*element)?
+            .get_attribute(qualified_name)
+            .map(String::from)
+    }
+
+    fn add_attributes_if_missing(&mut self, target: &NodeId, attributes: Vec<Attribute>) {
+        for attribute in attributes {
+            let _ = self.add_attribute_if_missing(*target, attribute);
+        }
+    }
+
+    fn remove_from_parent(&mut self, target: &NodeId) {
+        self.detach(*target);
+    }
+
+    fn reparent_children(&mut self, node: &NodeId, new_parent: &NodeId) {
+        Tree::reparent_children(self, *node, *new_parent);
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.set_mode(mode);
+    }
+
+    fn template_contents(&mut self, template: &NodeId) -> NodeId {
+        Tree::template_contents(self, *template).expect("Expected a template element")
+    }
+
+    fn set_script_flags(&mut self, script: &NodeId, flags: ScriptFlags) {
+        Tree::set_script_flags(self, *script, flags);
+    }
+}
+
+/// See `Tree::children`
+#[derive(Clone, Debug)]
+pub struct Children<'a> {
+    tree: &'a Tree,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+        self.next = self.tree.next_sibling(current);
+        Some(current)
+    }
+}
+
+/// See `Tree::ancestors`
+#[derive(Clone, Debug)]
+pub struct Ancestors<'a> {
+    tree: &'a Tree,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+        self.next = self.tree.parent(current);
+        Some(current)
+    }
+}
+
+/// See `Tree::descendants`
+#[derive(Clone, Debug)]
+pub struct Descendants<'a> {
+    tree: &'a Tree,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+        // Each node's links are only looked up once
+        let mut links = &self.tree.links[current.index()];
+        self.next = links.first_child.or_else(|| {
+            // Go up until there's a next sibling, stopping at the root
+            let mut node = current;
+            loop {
+                if node == self.root {
+                    return None;
+                }
+                if links.next_sibling.is_some() {
+                    return links.next_sibling;
+                }
+                node = links.parent?;
+                links = &self.tree.links[node.index()];
+            }
+        });
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{dom::ToTestTree, Parser};
+
+    fn names(tree: &Tree, ids: impl Iterator<Item = NodeId>) -> Vec<String> {
+        ids.map(|id| match tree.data(id) {
+            NodeData::Element(e) => e.local_name(),
+            NodeData::Text(t) => t.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Tree>();
+    }
+
+    #[test]
+    fn build() {
+        let mut tree = Tree::new();
+        let html = tree.create_element(TagName::Html, Namespace::HTML, Vec::new());
+        tree.append(tree.document(), html);
+        let p = tree.create_element(TagName::P, Namespace::HTML, Vec::new());
+        let b = tree.create_element(TagName::B, Namespace::HTML, Vec::new());
+        tree.append(html, p);
+        tree.append_text(p, "a");
+        tree.append_text(p, "b");
+        tree.insert_before(p, b);
+        tree.insert_text_before(p, "c");
+        tree.insert_text_before(p, "d");
+        assert_eq!(tree.document_element(), Some(html));
+        assert_eq!(names(&tree, tree.children(html)), ["b", "cd", "p"]);
+        assert_eq!(names(&tree, tree.children(p)), ["ab"]);
+
+        // Moving a node
+        tree.append(b, p);
+        assert_eq!(names(&tree, tree.children(html)), ["b", "cd"]);
+        assert_eq!(names(&tree, tree.ancestors(p)), ["b", "html", "?"]);
+        assert_eq!(
+            names(&tree, tree.descendants(tree.document())),
+            ["html", "b", "p", "ab", "cd"]
+        );
+
+        let div = tree.create_element(TagName::Div, Namespace::HTML, Vec::new());
+        tree.reparent_children(html, div);
+        assert_eq!(tree.first_child(html), None);
+        assert_eq!(names(&tree, tree.children(div)), ["b", "cd"]);
+        tree.detach(b);
+        assert_eq!(tree.parent(b), None);
+        assert_eq!(names(&tree, tree.children(div)), ["cd"]);
+        assert_eq!(names(&tree, tree.descendants(b)), ["p", "ab"]);
+
+        assert!(tree.add_attribute_if_missing(div, Attribute::new("id", "a")));
+        assert!(!tree.add_attribute_if_missing(div, Attribute::new("ID", "b")));
+        assert_eq!(tree.element(div).unwrap().get_attribute("id"), Some("a"));
+    }
+
+    #[test]
+    fn from_document() {
+        let document = Parser::new(Cursor::new(
+            "<!DOCTYPE html><html><head><title>t</title></head><body>\
+             <p id=a>x<b>y</b>z</p><svg viewBox='0 0 1 1'><foreignObject/></svg>\
+             <table><tr><td>1</td></tr></table><template><b>2</b></template></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        let tree = Tree::from(&document);
+        assert_eq!(tree.to_test_tree(), document.to_test_tree());
+    }
+
+    #[test]
+    fn parse_into_tree() {
+        let html = "<!DOCTYPE html><html><head><title>t</title></head><body><html lang=en>\
+                    <p id=a>x<b>y<i class=c>z</b>w</i></p>\
+                    <svg viewBox='0 0 1 1'><foreignObject/></svg>\
+                    <table>1<tr><td>2</td></tr><b>3</b></table>\
+                    <template>4<table>5<tr><td>6</template></body></html>";
+        let document = Parser::new(Cursor::new(html)).parse().unwrap();
+        let tree = Parser::with_sink(Cursor::new(html), Tree::new())
+            .parse()
+            .unwrap();
+        assert_eq!(tree.to_test_tree(), document.to_test_tree());
+
+        // Attributes added after the element was created are kept
+        let root = tree.document_element().unwrap();
+        assert_eq!(
+            tree.element(root).unwrap().get_attribute("lang"),
+            Some("en")
+        );
+
+        let p = tree
+            .descendants(tree.document())
+            .find(|&id| tree.element(id).is_some_and(|e| e.name() == &TagName::P))
+            .unwrap();
+        assert_eq!(
+            tree.element(p).unwrap().span().unwrap().slice(html),
+            "<p id=a>"
+        );
+        let x = tree.first_child(p).unwrap();
+        match tree.data(x) {
+            NodeData::Text(t) => assert_eq!(t.span().unwrap().slice(html), "x"),
+            _ => panic!("expected text"),
+        }
+    }
+}
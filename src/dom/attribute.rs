@@ -0,0 +1,105 @@
+use derive_more::From;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    dom::{Namespace, SourceSpan},
+    tokenizer::Span,
+};
+
+/// An attribute on an `Element`
+///
+/// Attributes parsed from HTML have no namespace or prefix, those are only
+/// set for the adjusted foreign attributes (eg. `xlink:href`).
+#[derive(Clone, Debug, Default, Deserialize, Eq, From, Hash, PartialEq, Serialize)]
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+    pub namespace: Option<Namespace>,
+    pub prefix: Option<String>,
+    #[serde(default)]
+    name_span: SourceSpan,
+    #[serde(default)]
+    value_span: SourceSpan,
+}
+
+impl Attribute {
+    #[must_use]
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        Attribute {
+            name: name.into(),
+            value: value.into(),
+            namespace: None,
+            prefix: None,
+            name_span: SourceSpan::default(),
+            value_span: SourceSpan::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn new_ns<N: Into<String>, V: Into<String>>(
+        namespace: Option<Namespace>,
+        prefix: Option<String>,
+        name: N,
+        value: V,
+    ) -> Self {
+        Attribute {
+            name: name.into(),
+            value: value.into(),
+            namespace,
+            prefix,
+            name_span: SourceSpan::default(),
+            value_span: SourceSpan::default(),
+        }
+    }
+
+    /// <https://dom.spec.whatwg.org/#concept-attribute-qualified-name>
+    #[must_use]
+    pub fn qualified_name(&self) -> String {
+        match self.prefix {
+            Some(ref prefix) => format!("{}:{}", prefix, self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    #[must_use]
+    pub fn namespace(&self) -> Option<Namespace> {
+        self.namespace
+    }
+
+    #[must_use]
+    pub fn prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Where the attribute's name was in the input, if it was parsed
+    #[must_use]
+    pub fn name_span(&self) -> Option<Span> {
+        self.name_span.0
+    }
+
+    /// Where the attribute's value was in the input, not including any quotes
+    ///
+    /// This is `None` if the attribute wasn't parsed, or was parsed without a value.
+    #[must_use]
+    pub fn value_span(&self) -> Option<Span> {
+        self.value_span.0
+    }
+
+    /// Set where the attribute's name and value were in the input
+    #[must_use]
+    pub fn with_spans(mut self, name_span: Span, value_span: Option<Span>) -> Self {
+        self.name_span = SourceSpan(Some(name_span));
+        self.value_span = SourceSpan(value_span);
+        self
+    }
+}
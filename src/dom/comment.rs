@@ -1,12 +1,37 @@
-use derive_more::{Constructor, From};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Constructor, Debug, Default, Deserialize, Eq, From, Hash, PartialEq, Serialize)]
+use crate::{
+    dom::{
+        element::{ChildNode, ParentNode},
+        ParentLink, SourceSpan,
+    },
+    tokenizer::Span,
+};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Comment {
     data: String,
+    #[serde(default)]
+    span: SourceSpan,
+    #[serde(skip)]
+    pub(crate) parent: ParentLink,
 }
 
 impl Comment {
+    #[must_use]
+    pub fn new(data: String) -> Self {
+        Comment {
+            data,
+            span: SourceSpan::default(),
+            parent: ParentLink::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
     #[must_use]
     pub fn len(&self) -> usize {
         self.data.len()
@@ -16,4 +41,44 @@ impl Comment {
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
+
+    /// Where the comment was in the input, if it was parsed
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        self.span = SourceSpan(Some(span));
+    }
+
+    /// The parent of this comment
+    #[must_use]
+    pub fn parent_node(&self) -> Option<ParentNode> {
+        self.parent.parent()
+    }
+
+    /// The node after this comment in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn next_sibling(&self) -> Option<ChildNode> {
+        self.parent.next_sibling((self as *const Self).cast())
+    }
+
+    /// The node before this comment in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn previous_sibling(&self) -> Option<ChildNode> {
+        self.parent.previous_sibling((self as *const Self).cast())
+    }
+}
+
+impl From<String> for Comment {
+    fn from(data: String) -> Self {
+        Comment::new(data)
+    }
 }
@@ -1,22 +1,31 @@
 use std::{fmt, cell::RefCell, rc::Rc};
 
 use derive_more::From;
-use log::warn;
 use serde::{Deserialize, Serialize};
 
 use crate::dom::{Comment, DocumentType, Element, ProcessingInstruction};
 
 #[derive(Clone, Default, Eq, From, PartialEq)]
 pub struct Document {
-    first_children: Vec<ChildNode>,
-    document_type: Option<DocumentType>,
-    second_children: Vec<ChildNode>,
-    element: Option<Rc<RefCell<Element>>>,
-    third_children: Vec<ChildNode>,
+    pub(super) first_children: Vec<ChildNode>,
+    pub(super) document_type: Option<DocumentType>,
+    pub(super) second_children: Vec<ChildNode>,
+    pub(super) element: Option<Rc<RefCell<Element>>>,
+    pub(super) third_children: Vec<ChildNode>,
+    pub(super) mode: QuirksMode,
+}
+
+/// <https://dom.spec.whatwg.org/#concept-document-mode>
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum QuirksMode {
+    #[default]
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, From, Hash, PartialEq, Serialize)]
-enum ChildNode {
+pub(super) enum ChildNode {
     ProcessingInstruction(ProcessingInstruction),
     Comment(Comment),
 }
@@ -63,22 +72,42 @@ impl Document {
         self.document_type = Some(document_type)
     }
 
-    pub(crate) fn set_mode(&mut self, mode: &str) {
-        let _ = self;
-        warn!("[TODO] Document::set_mode({:?})", mode)
+    /// <https://dom.spec.whatwg.org/#concept-document-mode>
+    ///
+    /// Set by the parser from the document's doctype
+    #[must_use]
+    pub fn mode(&self) -> QuirksMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: QuirksMode) {
+        self.mode = mode
     }
     pub fn push_element(&mut self, elem: Rc<RefCell<Element>>) {
         if let Some(ref element) = self.element {
-            let mut element = element.borrow_mut();
-            element.push(elem.into())
+            Element::append_child(element, elem.into())
         } else {
             self.element = Some(elem)
         }
     }
-    pub fn push_comment(&mut self, elem: Comment) {
-        let _ = self;
-        warn!("[TODO] Document::push_comment({:?})", elem);
-        drop(elem);
+    /// Append a comment to the document, after the doctype and document element if there are any
+    pub fn push_comment(&mut self, comment: Comment) {
+        self.push_child(comment.into())
+    }
+
+    /// Append a processing instruction to the document, see `Document::push_comment`
+    pub fn push_processing_instruction(&mut self, processing_instruction: ProcessingInstruction) {
+        self.push_child(processing_instruction.into())
+    }
+
+    fn push_child(&mut self, child: ChildNode) {
+        if self.element.is_some() {
+            self.third_children.push(child)
+        } else if self.document_type.is_some() {
+            self.second_children.push(child)
+        } else {
+            self.first_children.push(child)
+        }
     }
 }
 
@@ -111,6 +140,9 @@ impl fmt::Debug for Document {
         if !self.third_children.is_empty() {
             f.field("third_children", &self.third_children);
         }
+        if self.mode != QuirksMode::NoQuirks {
+            f.field("mode", &self.mode);
+        }
         f.finish()
     }
 }
@@ -1,16 +1,24 @@
-use derive_more::{Constructor, From};
+use derive_more::{Constructor, Deref, DerefMut, From};
 
-use crate::dom::Node;
+use crate::dom::element::ChildNode;
 
-#[derive(Clone, Constructor, Debug, Default, Eq, From, PartialEq)]
+/// A node which holds other nodes without being part of a document
+///
+/// The output of `Parser::parse_fragment`, and the contents of a `template` element.
+#[derive(Clone, Constructor, Debug, Default, Deref, DerefMut, Eq, From, PartialEq)]
 pub struct DocumentFragment {
-    children: Vec<Node>,
+    children: Vec<ChildNode>,
 }
 
 impl DocumentFragment {
+    #[must_use]
+    pub fn children(&self) -> &[ChildNode] {
+        &self.children
+    }
+
     #[must_use]
     pub fn len(&self) -> usize {
-        self.children.iter().map(Node::len).sum()
+        self.children.iter().map(ChildNode::len).sum()
     }
 
     #[must_use]
@@ -7,3 +7,20 @@ pub struct DocumentType {
     public_id: String,
     system_id: String,
 }
+
+impl DocumentType {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub fn public_id(&self) -> &str {
+        &self.public_id
+    }
+
+    #[must_use]
+    pub fn system_id(&self) -> &str {
+        &self.system_id
+    }
+}
@@ -1,10 +1,16 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    rc::Rc,
+};
 
 use derive_more::{Deref, DerefMut, From};
 
 use crate::{
-    dom::{Comment, Namespace, ProcessingInstruction, Text},
-    tokenizer::TagName,
+    dom::{
+        foreign, Attribute, Comment, DocumentFragment, Namespace, ParentLink,
+        ProcessingInstruction, SourceSpan, Text,
+    },
+    tokenizer::{Span, TagName},
 };
 
 #[derive(Clone, Debug, Eq, From, PartialEq)]
@@ -34,6 +40,15 @@ impl ChildNode {
         }
     }
 
+    /// The element, if this is an element
+    #[must_use]
+    pub fn element(&self) -> Option<Rc<RefCell<Element>>> {
+        match self {
+            ChildNode::Element(e) => Some(Rc::clone(e)),
+            _ => None,
+        }
+    }
+
     #[must_use]
     pub fn is_text(&self) -> bool {
         match self {
@@ -50,15 +65,203 @@ impl ChildNode {
             }
         }
     }
+
+    /// The parent of this node
+    ///
+    /// # Panics
+    /// If the node is mutably borrowed
+    #[must_use]
+    pub fn parent_node(&self) -> Option<ParentNode> {
+        self.with_link(ParentLink::parent)
+    }
+
+    /// The node after this node in its parent's children
+    ///
+    /// # Panics
+    /// If the node or its parent is mutably borrowed
+    #[must_use]
+    pub fn next_sibling(&self) -> Option<ChildNode> {
+        let node = self.as_ptr();
+        self.with_link(|link| link.next_sibling(node))
+    }
+
+    /// The node before this node in its parent's children
+    ///
+    /// # Panics
+    /// If the node or its parent is mutably borrowed
+    #[must_use]
+    pub fn previous_sibling(&self) -> Option<ChildNode> {
+        let node = self.as_ptr();
+        self.with_link(|link| link.previous_sibling(node))
+    }
+
+    /// Remove this node from its parent, returning the parent and the index it was at
+    ///
+    /// # Panics
+    /// If the node or its parent is borrowed
+    pub fn detach(&self) -> Option<(ParentNode, usize)> {
+        let node = self.as_ptr();
+        let (parent, index) = self.with_link(|link| link.locate(node))?;
+        let _ = parent.remove_child(index);
+        Some((parent, index))
+    }
+
+    /// The address of the node, which is the same for every `ChildNode` of it
+    pub(crate) fn as_ptr(&self) -> *const () {
+        match self {
+            ChildNode::Element(e) => e.as_ptr() as *const (),
+            ChildNode::Text(t) => t.as_ptr() as *const (),
+            ChildNode::ProcessingInstruction(p) => p.as_ptr() as *const (),
+            ChildNode::Comment(c) => c.as_ptr() as *const (),
+        }
+    }
+
+    fn with_link<T>(&self, f: impl FnOnce(&ParentLink) -> T) -> T {
+        match self {
+            ChildNode::Element(e) => f(&e.borrow().parent),
+            ChildNode::Text(t) => f(&t.borrow().parent),
+            ChildNode::ProcessingInstruction(p) => f(&p.borrow().parent),
+            ChildNode::Comment(c) => f(&c.borrow().parent),
+        }
+    }
+
+    fn set_link(&self, link: ParentLink) {
+        match self {
+            ChildNode::Element(e) => e.borrow_mut().parent = link,
+            ChildNode::Text(t) => t.borrow_mut().parent = link,
+            ChildNode::ProcessingInstruction(p) => p.borrow_mut().parent = link,
+            ChildNode::Comment(c) => c.borrow_mut().parent = link,
+        }
+    }
+
+    /// Update the cached index, unless the node is mutably borrowed, as it's checked anyway
+    fn set_index(&self, index: usize) {
+        match self {
+            ChildNode::Element(e) => e.try_borrow().map(|e| e.parent.set_index(index)),
+            ChildNode::Text(t) => t.try_borrow().map(|t| t.parent.set_index(index)),
+            ChildNode::ProcessingInstruction(p) => {
+                p.try_borrow().map(|p| p.parent.set_index(index))
+            }
+            ChildNode::Comment(c) => c.try_borrow().map(|c| c.parent.set_index(index)),
+        }
+        .unwrap_or_default();
+    }
+}
+
+/// A node which can have children: an element, or a document fragment such as the contents of
+/// a `template` element
+#[derive(Clone, Debug, Eq, From, PartialEq)]
+pub enum ParentNode {
+    Element(Rc<RefCell<Element>>),
+    DocumentFragment(Rc<RefCell<DocumentFragment>>),
+}
+
+impl ParentNode {
+    /// # Panics
+    /// If the node is mutably borrowed
+    #[must_use]
+    pub fn children(&self) -> Ref<'_, Vec<ChildNode>> {
+        match self {
+            ParentNode::Element(e) => Ref::map(e.borrow(), |e| &**e),
+            ParentNode::DocumentFragment(f) => Ref::map(f.borrow(), |f| &**f),
+        }
+    }
+
+    pub(crate) fn children_mut(&self) -> RefMut<'_, Vec<ChildNode>> {
+        match self {
+            ParentNode::Element(e) => RefMut::map(e.borrow_mut(), |e| &mut **e),
+            ParentNode::DocumentFragment(f) => RefMut::map(f.borrow_mut(), |f| &mut **f),
+        }
+    }
+
+    /// Whether `self` and `other` are the same node
+    #[must_use]
+    pub fn ptr_eq(&self, other: &ParentNode) -> bool {
+        match (self, other) {
+            (ParentNode::Element(a), ParentNode::Element(b)) => Rc::ptr_eq(a, b),
+            (ParentNode::DocumentFragment(a), ParentNode::DocumentFragment(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// See `Element::append_child`
+    pub fn append_child(&self, child: ChildNode) {
+        let _ = child.detach();
+        let index = self.children().len();
+        child.set_link(ParentLink::new(self, index));
+        self.children_mut().push(child);
+    }
+
+    /// See `Element::insert_child`
+    ///
+    /// # Panics
+    /// If `index` is greater than the number of children
+    pub fn insert_child(&self, index: usize, child: ChildNode) {
+        let index = match child.detach() {
+            Some((old_parent, old_index)) if old_parent.ptr_eq(self) && old_index < index => {
+                index - 1
+            }
+            _ => index,
+        };
+        child.set_link(ParentLink::new(self, index));
+        self.children_mut().insert(index, child);
+        self.update_indexes(index + 1);
+    }
+
+    /// See `Element::remove_child`
+    ///
+    /// # Panics
+    /// If `index` is out of bounds
+    pub fn remove_child(&self, index: usize) -> ChildNode {
+        let child = self.children_mut().remove(index);
+        child.set_link(ParentLink::default());
+        self.update_indexes(index);
+        child
+    }
+
+    /// Update the indexes the children from `start` on have cached, after the children before
+    /// them changed
+    fn update_indexes(&self, start: usize) {
+        for (index, child) in self.children().iter().enumerate().skip(start) {
+            child.set_index(index);
+        }
+    }
 }
 
-#[derive(Clone, Debug, Eq, From, PartialEq, Deref, DerefMut)]
+/// An element and its children
+///
+/// Each child node keeps a weak link to its parent and its index among its siblings, which are
+/// maintained by `Element::append_child`, `Element::insert_child`, `Element::remove_child` and
+/// `ChildNode::detach`, so moving to a sibling doesn't search the parent's children. Changing
+/// the children directly through `DerefMut` doesn't update the links.
+///
+/// The children of a `template` element are kept apart from its contents, see
+/// `Element::template_contents`.
+#[derive(Clone, Debug, Eq, PartialEq, Deref, DerefMut)]
 pub struct Element {
     pub name: TagName,
     pub namespace: Namespace,
+    attributes: Vec<Attribute>,
     #[deref]
     #[deref_mut]
     children: Vec<ChildNode>,
+    parent: ParentLink,
+    script_flags: ScriptFlags,
+    template_contents: Option<Rc<RefCell<DocumentFragment>>>,
+    span: SourceSpan,
+}
+
+/// The state of a `script` element which is kept by tree construction
+///
+/// <https://html.spec.whatwg.org/multipage/scripting.html#script-processing-model>
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ScriptFlags {
+    /// <https://html.spec.whatwg.org/multipage/scripting.html#already-started>
+    pub already_started: bool,
+    /// Whether the element was created by the parser, rather than by a script
+    ///
+    /// <https://html.spec.whatwg.org/multipage/scripting.html#parser-document>
+    pub parser_inserted: bool,
 }
 
 impl Element {
@@ -72,14 +275,176 @@ impl Element {
         self.namespace
     }
 
+    /// <https://dom.spec.whatwg.org/#concept-element-local-name>
+    ///
+    /// Tag names are stored lowercase, this restores the case of mixed case SVG elements
+    /// (eg. `foreignObject`)
     #[must_use]
-    pub fn new_html(name: TagName) -> Rc<RefCell<Self>> {
-        let elem = Element {
+    pub fn local_name(&self) -> String {
+        let name = self.name.to_string();
+        if self.namespace == Namespace::SVG {
+            if let Some(adjusted) = foreign::svg_tag_name(&name) {
+                return adjusted.to_string();
+            }
+        }
+        name
+    }
+
+    /// A new element, which has empty template contents if it's an HTML `template` element
+    #[must_use]
+    pub fn new(
+        name: TagName,
+        namespace: Namespace,
+        attributes: Vec<Attribute>,
+    ) -> Rc<RefCell<Self>> {
+        let mut element = Element {
             name,
-            namespace: Namespace::default(),
+            namespace,
+            attributes,
             children: Vec::new(),
+            parent: ParentLink::default(),
+            script_flags: ScriptFlags::default(),
+            template_contents: None,
+            span: SourceSpan::default(),
+        };
+        if element.is_html() && element.name == TagName::Template {
+            element.template_contents = Some(Rc::default());
+        }
+        Rc::new(RefCell::new(element))
+    }
+
+    #[must_use]
+    pub fn new_html(name: TagName) -> Rc<RefCell<Self>> {
+        Element::new(name, Namespace::default(), Vec::new())
+    }
+
+    /// The flags of a `script` element, which are all unset for other elements
+    #[must_use]
+    pub fn script_flags(&self) -> ScriptFlags {
+        self.script_flags
+    }
+
+    pub fn set_script_flags(&mut self, flags: ScriptFlags) {
+        self.script_flags = flags;
+    }
+
+    /// Where the element's start tag was in the input
+    ///
+    /// The span covers only the start tag, not the element's contents or its end tag, which may
+    /// be missing or implied. To find the extent of the whole element, combine it with the spans
+    /// of its descendants using `Span::union`.
+    ///
+    /// This is `None` for elements which weren't parsed, or which were implied by the other tags
+    /// in the input, such as a `tbody` in a table without one.
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    pub fn set_span(&mut self, span: Option<Span>) {
+        self.span = SourceSpan(span);
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/scripting.html#template-contents>
+    ///
+    /// This is only set for `template` elements created with `Element::new`. The parser puts
+    /// everything between a template's start and end tags here, rather than in its children.
+    #[must_use]
+    pub fn template_contents(&self) -> Option<&Rc<RefCell<DocumentFragment>>> {
+        self.template_contents.as_ref()
+    }
+
+    /// The attribute list, in the order the attributes were added
+    #[must_use]
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// <https://dom.spec.whatwg.org/#dom-element-getattribute>
+    #[must_use]
+    pub fn get_attribute(&self, qualified_name: &str) -> Option<&str> {
+        self.find_attribute(qualified_name)
+            .map(|i| self.attributes[i].value())
+    }
+
+    /// <https://dom.spec.whatwg.org/#dom-element-getattributens>
+    #[must_use]
+    pub fn get_attribute_ns(&self, namespace: Option<Namespace>, local_name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|a| a.namespace == namespace && a.name == local_name)
+            .map(Attribute::value)
+    }
+
+    /// <https://dom.spec.whatwg.org/#dom-element-hasattribute>
+    #[must_use]
+    pub fn has_attribute(&self, qualified_name: &str) -> bool {
+        self.find_attribute(qualified_name).is_some()
+    }
+
+    /// <https://dom.spec.whatwg.org/#dom-element-setattribute>
+    ///
+    /// Replaces the value of an existing attribute, otherwise appends a new one
+    pub fn set_attribute<V: Into<String>>(&mut self, qualified_name: &str, value: V) {
+        if let Some(i) = self.find_attribute(qualified_name) {
+            self.attributes[i].value = value.into();
+        } else {
+            let name = if self.is_html() {
+                qualified_name.to_ascii_lowercase()
+            } else {
+                qualified_name.to_string()
+            };
+            self.attributes.push(Attribute::new(name, value));
+        }
+    }
+
+    /// <https://dom.spec.whatwg.org/#dom-element-setattributens>
+    pub fn set_attribute_ns<V: Into<String>>(
+        &mut self,
+        namespace: Option<Namespace>,
+        prefix: Option<String>,
+        local_name: &str,
+        value: V,
+    ) {
+        if let Some(attr) = self
+            .attributes
+            .iter_mut()
+            .find(|a| a.namespace == namespace && a.name == local_name)
+        {
+            attr.value = value.into();
+        } else {
+            self.attributes
+                .push(Attribute::new_ns(namespace, prefix, local_name, value));
+        }
+    }
+
+    /// <https://dom.spec.whatwg.org/#dom-element-removeattribute>
+    pub fn remove_attribute(&mut self, qualified_name: &str) -> Option<Attribute> {
+        self.find_attribute(qualified_name)
+            .map(|i| self.attributes.remove(i))
+    }
+
+    /// Append `attr`, unless an attribute with the same qualified name is already present
+    ///
+    /// Returns whether the attribute was added
+    pub(crate) fn add_attribute_if_missing(&mut self, attr: Attribute) -> bool {
+        if self.has_attribute(&attr.qualified_name()) {
+            false
+        } else {
+            self.attributes.push(attr);
+            true
+        }
+    }
+
+    fn find_attribute(&self, qualified_name: &str) -> Option<usize> {
+        let qualified_name = if self.is_html() {
+            qualified_name.to_ascii_lowercase()
+        } else {
+            qualified_name.to_string()
         };
-        Rc::new(RefCell::new(elem))
+        self.attributes
+            .iter()
+            .position(|a| a.qualified_name() == qualified_name)
     }
 
     #[must_use]
@@ -89,36 +454,187 @@ impl Element {
 
     /// <https://html.spec.whatwg.org/multipage/parsing.html#mathml-text-integration-point>
     #[must_use]
-    #[allow(clippy::match_same_arms)]
     pub fn is_mathml_text_integration_point(&self) -> bool {
-        match (self.namespace, self.name()) {
-            (Namespace::MathML, TagName::Mi) => true,
-            (Namespace::MathML, TagName::Mo) => true,
-            (Namespace::MathML, TagName::Mn) => true,
-            (Namespace::MathML, TagName::Ms) => true,
-            (Namespace::MathML, TagName::Mtext) => true,
-            _ => false,
-        }
+        is_mathml_text_integration_point(&self.name, self.namespace)
     }
 
     /// <https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point>
     #[must_use]
-    #[allow(clippy::match_same_arms)]
     pub fn is_html_integration_point(&self) -> bool {
-        match (self.namespace, self.name()) {
-            (Namespace::MathML, TagName::AnnotationXml) => todo!(),
-            (Namespace::SVG, TagName::ForeignObject) => true,
-            (Namespace::SVG, TagName::Desc) => true,
-            (Namespace::SVG, TagName::Title) => true,
-            _ => false,
+        is_html_integration_point(&self.name, self.namespace, self.get_attribute("encoding"))
+    }
+
+    #[must_use]
+    pub fn category(&self) -> Category {
+        Category::of(&self.name, self.namespace)
+    }
+
+    /// The parent of this element, if it's an element
+    ///
+    /// This is `None` for the document element, for elements at the top of a document fragment
+    /// and for elements which aren't in a tree.
+    #[must_use]
+    pub fn parent(&self) -> Option<Rc<RefCell<Element>>> {
+        match self.parent_node()? {
+            ParentNode::Element(e) => Some(e),
+            ParentNode::DocumentFragment(_) => None,
+        }
+    }
+
+    /// The parent of this element, which may be a document fragment
+    #[must_use]
+    pub fn parent_node(&self) -> Option<ParentNode> {
+        self.parent.parent()
+    }
+
+    /// The node after this element in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn next_sibling(&self) -> Option<ChildNode> {
+        self.parent.next_sibling(self.as_ptr())
+    }
+
+    /// The node before this element in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn previous_sibling(&self) -> Option<ChildNode> {
+        self.parent.previous_sibling(self.as_ptr())
+    }
+
+    /// The first element after this element in its parent's children
+    ///
+    /// <https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-nextelementsibling>
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn next_element_sibling(&self) -> Option<Rc<RefCell<Element>>> {
+        let (parent, index) = self.locate()?;
+        let siblings = parent.children();
+        siblings[index + 1..].iter().find_map(ChildNode::element)
+    }
+
+    /// The last element before this element in its parent's children
+    ///
+    /// <https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-previouselementsibling>
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn previous_element_sibling(&self) -> Option<Rc<RefCell<Element>>> {
+        let (parent, index) = self.locate()?;
+        let siblings = parent.children();
+        siblings[..index].iter().rev().find_map(ChildNode::element)
+    }
+
+    /// The parent of this element, then its parent, and so on up to the document element
+    #[must_use]
+    pub fn ancestors(&self) -> Ancestors {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// Every node under this element, in tree order
+    #[must_use]
+    pub fn descendants(&self) -> Descendants {
+        Descendants {
+            stack: self.children.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// The parent of this element, and the element's index in its children
+    pub(crate) fn locate(&self) -> Option<(ParentNode, usize)> {
+        self.parent.locate(self.as_ptr())
+    }
+
+    fn as_ptr(&self) -> *const () {
+        (self as *const Self).cast()
+    }
+
+    /// Append `child` to `parent`'s children, removing it from its current parent first
+    pub fn append_child(parent: &Rc<RefCell<Element>>, child: ChildNode) {
+        ParentNode::from(Rc::clone(parent)).append_child(child);
+    }
+
+    /// Insert `child` into `parent`'s children at `index`, removing it from its current parent
+    /// first
+    ///
+    /// If `child` is already a child of `parent`, `index` is its position before it's removed.
+    ///
+    /// # Panics
+    /// If `index` is greater than the number of children
+    pub fn insert_child(parent: &Rc<RefCell<Element>>, index: usize, child: ChildNode) {
+        ParentNode::from(Rc::clone(parent)).insert_child(index, child);
+    }
+
+    /// Remove the child of `parent` at `index`
+    ///
+    /// # Panics
+    /// If `index` is out of bounds
+    pub fn remove_child(parent: &Rc<RefCell<Element>>, index: usize) -> ChildNode {
+        ParentNode::from(Rc::clone(parent)).remove_child(index)
+    }
+
+    /// Remove `element` from its parent, returning the parent and the index it was at
+    pub fn detach(element: &Rc<RefCell<Element>>) -> Option<(ParentNode, usize)> {
+        ChildNode::from(Rc::clone(element)).detach()
+    }
+}
+
+/// See `Element::ancestors`
+#[derive(Clone, Debug)]
+pub struct Ancestors {
+    next: Option<Rc<RefCell<Element>>>,
+}
+
+impl Iterator for Ancestors {
+    type Item = Rc<RefCell<Element>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+        self.next = current.borrow().parent();
+        Some(current)
+    }
+}
+
+/// See `Element::descendants`
+#[derive(Clone, Debug)]
+pub struct Descendants {
+    // The nodes still to visit, with the next one last
+    stack: Vec<ChildNode>,
+}
+
+impl Iterator for Descendants {
+    type Item = ChildNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        if let ChildNode::Element(ref e) = node {
+            self.stack.extend(e.borrow().iter().rev().cloned());
         }
+        Some(node)
     }
+}
+
+#[derive(Copy, Clone, Debug, Eq, From, PartialEq)]
+pub enum Category {
+    Special,
+    Formatting,
+    Ordinary,
+}
 
+impl Category {
+    /// The category of an element with this name and namespace
     #[must_use]
     #[allow(clippy::too_many_lines)]
-    pub fn category(&self) -> Category {
-        if self.namespace == Namespace::HTML {
-            match self.name {
+    pub(crate) fn of(name: &TagName, namespace: Namespace) -> Self {
+        if namespace == Namespace::HTML {
+            match name {
                 TagName::Address
                 | TagName::Applet
                 | TagName::Area
@@ -219,8 +735,8 @@ impl Element {
 
                 _ => Category::Ordinary,
             }
-        } else if self.namespace == Namespace::MathML {
-            match self.name {
+        } else if namespace == Namespace::MathML {
+            match name {
                 TagName::Mi
                 | TagName::Mo
                 | TagName::Mn
@@ -230,9 +746,9 @@ impl Element {
 
                 _ => Category::Ordinary,
             }
-        } else if self.namespace == Namespace::SVG {
-            match self.name {
-                TagName::ForeignObject | TagName::Desc | TagName::Time => Category::Special,
+        } else if namespace == Namespace::SVG {
+            match name {
+                TagName::ForeignObject | TagName::Desc | TagName::Title => Category::Special,
 
                 _ => Category::Ordinary,
             }
@@ -242,9 +758,225 @@ impl Element {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, From, PartialEq)]
-pub enum Category {
-    Special,
-    Formatting,
-    Ordinary,
+/// See `Element::is_mathml_text_integration_point`
+#[allow(clippy::match_same_arms)]
+pub(crate) fn is_mathml_text_integration_point(name: &TagName, namespace: Namespace) -> bool {
+    match (namespace, name) {
+        (Namespace::MathML, TagName::Mi) => true,
+        (Namespace::MathML, TagName::Mo) => true,
+        (Namespace::MathML, TagName::Mn) => true,
+        (Namespace::MathML, TagName::Ms) => true,
+        (Namespace::MathML, TagName::Mtext) => true,
+        _ => false,
+    }
+}
+
+/// See `Element::is_html_integration_point`, `encoding` is the value of the `encoding` attribute
+#[allow(clippy::match_same_arms)]
+pub(crate) fn is_html_integration_point(
+    name: &TagName,
+    namespace: Namespace,
+    encoding: Option<&str>,
+) -> bool {
+    match (namespace, name) {
+        (Namespace::MathML, TagName::AnnotationXml) => encoding.is_some_and(|encoding| {
+            encoding.eq_ignore_ascii_case("text/html")
+                || encoding.eq_ignore_ascii_case("application/xhtml+xml")
+        }),
+        (Namespace::SVG, TagName::ForeignObject) => true,
+        (Namespace::SVG, TagName::Desc) => true,
+        (Namespace::SVG, TagName::Title) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn get_attribute() {
+        let elem = Element::new(
+            TagName::A,
+            Namespace::HTML,
+            vec![
+                Attribute::new("href", "/foo"),
+                Attribute::new("class", "bar"),
+            ],
+        );
+        let elem = elem.borrow();
+        assert_eq!(elem.get_attribute("href"), Some("/foo"));
+        assert_eq!(elem.get_attribute("HREF"), Some("/foo"));
+        assert_eq!(elem.get_attribute("id"), None);
+        assert!(elem.has_attribute("class"));
+        assert!(!elem.has_attribute("id"));
+    }
+
+    #[test]
+    fn set_attribute() {
+        let elem = Element::new_html(TagName::Div);
+        let mut elem = elem.borrow_mut();
+        elem.set_attribute("ID", "foo");
+        elem.set_attribute("class", "bar");
+        elem.set_attribute("id", "baz");
+        assert_eq!(
+            elem.attributes(),
+            &[Attribute::new("id", "baz"), Attribute::new("class", "bar")]
+        );
+    }
+
+    #[test]
+    fn remove_attribute() {
+        let elem = Element::new_html(TagName::Div);
+        let mut elem = elem.borrow_mut();
+        elem.set_attribute("id", "foo");
+        assert_eq!(
+            elem.remove_attribute("id"),
+            Some(Attribute::new("id", "foo"))
+        );
+        assert_eq!(elem.remove_attribute("id"), None);
+        assert!(elem.attributes().is_empty());
+    }
+
+    #[test]
+    fn attribute_ns() {
+        let elem = Element::new(
+            TagName::Other("use".to_string()),
+            Namespace::SVG,
+            Vec::new(),
+        );
+        let mut elem = elem.borrow_mut();
+        elem.set_attribute_ns(
+            Some(Namespace::XLink),
+            Some("xlink".to_string()),
+            "href",
+            "#foo",
+        );
+        assert_eq!(
+            elem.get_attribute_ns(Some(Namespace::XLink), "href"),
+            Some("#foo")
+        );
+        assert_eq!(elem.get_attribute_ns(None, "href"), None);
+        assert_eq!(elem.get_attribute("xlink:href"), Some("#foo"));
+    }
+
+    fn names<I: Iterator<Item = Rc<RefCell<Element>>>>(elements: I) -> Vec<String> {
+        elements.map(|e| e.borrow().local_name()).collect()
+    }
+
+    #[test]
+    fn append_and_remove_children() {
+        let div = Element::new_html(TagName::Div);
+        let p = Element::new_html(TagName::P);
+        let span = Element::new_html(TagName::Span);
+        Element::append_child(&div, Rc::clone(&p).into());
+        Element::append_child(&div, Text::new("x".to_string()).into());
+        Element::insert_child(&div, 0, Rc::clone(&span).into());
+        assert!(Rc::ptr_eq(&p.borrow().parent().unwrap(), &div));
+        assert!(Rc::ptr_eq(&span.borrow().parent().unwrap(), &div));
+        assert_eq!(div.borrow().len(), 3);
+
+        // Moving a child within its parent
+        Element::insert_child(&div, 2, Rc::clone(&span).into());
+        assert_eq!(
+            names(div.borrow().iter().filter_map(ChildNode::element)),
+            ["p", "span"]
+        );
+        assert!(Rc::ptr_eq(&span.borrow().parent().unwrap(), &div));
+
+        // Moving a child to another parent
+        let section = Element::new_html(TagName::Section);
+        Element::append_child(&section, Rc::clone(&p).into());
+        assert!(Rc::ptr_eq(&p.borrow().parent().unwrap(), &section));
+        assert_eq!(div.borrow().len(), 2);
+
+        assert_eq!(Element::detach(&span).map(|(_, i)| i), Some(0));
+        assert!(span.borrow().parent().is_none());
+        assert_eq!(Element::detach(&span), None);
+        assert!(Element::remove_child(&div, 0).is_text());
+        assert!(div.borrow().is_empty());
+
+        // Clones aren't in the tree
+        assert!(p.borrow().clone().parent().is_none());
+    }
+
+    #[test]
+    fn navigation() {
+        let ul = Element::new_html(TagName::Ul);
+        let items: Vec<_> = (0..3).map(|_| Element::new_html(TagName::Li)).collect();
+        for (i, li) in items.iter().enumerate() {
+            Element::append_child(&ul, Rc::clone(li).into());
+            Element::append_child(&ul, Text::new(i.to_string()).into());
+        }
+        let b = Element::new_html(TagName::B);
+        Element::append_child(&items[1], Rc::clone(&b).into());
+
+        let middle = items[1].borrow();
+        assert!(middle.previous_sibling().unwrap().is_text());
+        assert_eq!(
+            middle.next_sibling(),
+            Some(ChildNode::Text(Text::new("1".to_string())))
+        );
+        assert!(Rc::ptr_eq(
+            &middle.next_element_sibling().unwrap(),
+            &items[2]
+        ));
+        assert!(Rc::ptr_eq(
+            &middle.previous_element_sibling().unwrap(),
+            &items[0]
+        ));
+        assert!(items[0].borrow().previous_sibling().is_none());
+        assert!(items[2].borrow().next_element_sibling().is_none());
+        assert!(ul.borrow().next_sibling().is_none());
+
+        assert_eq!(names(b.borrow().ancestors()), ["li", "ul"]);
+        let descendants: Vec<_> = ul
+            .borrow()
+            .descendants()
+            .map(|node| match node {
+                ChildNode::Element(e) => e.borrow().local_name(),
+                ChildNode::Text(t) => t.borrow().to_string(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(descendants, ["li", "0", "li", "b", "1", "li", "2"]);
+    }
+
+    #[test]
+    fn text_and_comment_links() {
+        let div = Element::new_html(TagName::Div);
+        let a = Text::new("a".to_string());
+        let comment = Rc::new(RefCell::new(Comment::new("b".to_string())));
+        let c = Text::new("c".to_string());
+        Element::append_child(&div, Rc::clone(&a).into());
+        Element::append_child(&div, Rc::clone(&c).into());
+        Element::insert_child(&div, 1, Rc::clone(&comment).into());
+
+        let parent = a.borrow().parent_node().unwrap();
+        assert!(parent.ptr_eq(&Rc::clone(&div).into()));
+        assert_eq!(a.borrow().next_sibling(), Some(Rc::clone(&comment).into()));
+        assert_eq!(
+            c.borrow().previous_sibling(),
+            Some(Rc::clone(&comment).into())
+        );
+        assert_eq!(comment.borrow().next_sibling(), Some(Rc::clone(&c).into()));
+        assert!(a.borrow().previous_sibling().is_none());
+
+        // The indexes after a removed node are updated
+        assert_eq!(
+            ChildNode::from(Rc::clone(&comment))
+                .detach()
+                .map(|(_, i)| i),
+            Some(1)
+        );
+        assert!(comment.borrow().parent_node().is_none());
+        assert_eq!(c.borrow().previous_sibling(), Some(Rc::clone(&a).into()));
+
+        // Changing the children directly leaves the indexes stale, but they're checked
+        div.borrow_mut()
+            .insert(0, Text::new("d".to_string()).into());
+        assert_eq!(c.borrow().previous_sibling(), Some(Rc::clone(&a).into()));
+        assert!(c.borrow().next_sibling().is_none());
+        assert!(c.borrow().clone().parent_node().is_none());
+    }
 }
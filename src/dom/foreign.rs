@@ -0,0 +1,154 @@
+//! Name adjustments for elements and attributes in foreign (SVG and MathML) content
+//!
+//! The tokenizer lowercases all tag and attribute names, these tables restore
+//! the mixed case names used by SVG and MathML.
+
+use crate::dom::Namespace;
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign>
+///
+/// The SVG element names which aren't all lowercase
+#[must_use]
+pub(crate) fn svg_tag_name(name: &str) -> Option<&'static str> {
+    let adjusted = match name {
+        "altglyph" => "altGlyph",
+        "altglyphdef" => "altGlyphDef",
+        "altglyphitem" => "altGlyphItem",
+        "animatecolor" => "animateColor",
+        "animatemotion" => "animateMotion",
+        "animatetransform" => "animateTransform",
+        "clippath" => "clipPath",
+        "feblend" => "feBlend",
+        "fecolormatrix" => "feColorMatrix",
+        "fecomponenttransfer" => "feComponentTransfer",
+        "fecomposite" => "feComposite",
+        "feconvolvematrix" => "feConvolveMatrix",
+        "fediffuselighting" => "feDiffuseLighting",
+        "fedisplacementmap" => "feDisplacementMap",
+        "fedistantlight" => "feDistantLight",
+        "fedropshadow" => "feDropShadow",
+        "feflood" => "feFlood",
+        "fefunca" => "feFuncA",
+        "fefuncb" => "feFuncB",
+        "fefuncg" => "feFuncG",
+        "fefuncr" => "feFuncR",
+        "fegaussianblur" => "feGaussianBlur",
+        "feimage" => "feImage",
+        "femerge" => "feMerge",
+        "femergenode" => "feMergeNode",
+        "femorphology" => "feMorphology",
+        "feoffset" => "feOffset",
+        "fepointlight" => "fePointLight",
+        "fespecularlighting" => "feSpecularLighting",
+        "fespotlight" => "feSpotLight",
+        "fetile" => "feTile",
+        "feturbulence" => "feTurbulence",
+        "foreignobject" => "foreignObject",
+        "glyphref" => "glyphRef",
+        "lineargradient" => "linearGradient",
+        "radialgradient" => "radialGradient",
+        "textpath" => "textPath",
+        _ => return None,
+    };
+    Some(adjusted)
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#adjust-svg-attributes>
+#[must_use]
+pub(crate) fn svg_attribute_name(name: &str) -> Option<&'static str> {
+    let adjusted = match name {
+        "attributename" => "attributeName",
+        "attributetype" => "attributeType",
+        "basefrequency" => "baseFrequency",
+        "baseprofile" => "baseProfile",
+        "calcmode" => "calcMode",
+        "clippathunits" => "clipPathUnits",
+        "diffuseconstant" => "diffuseConstant",
+        "edgemode" => "edgeMode",
+        "filterunits" => "filterUnits",
+        "glyphref" => "glyphRef",
+        "gradienttransform" => "gradientTransform",
+        "gradientunits" => "gradientUnits",
+        "kernelmatrix" => "kernelMatrix",
+        "kernelunitlength" => "kernelUnitLength",
+        "keypoints" => "keyPoints",
+        "keysplines" => "keySplines",
+        "keytimes" => "keyTimes",
+        "lengthadjust" => "lengthAdjust",
+        "limitingconeangle" => "limitingConeAngle",
+        "markerheight" => "markerHeight",
+        "markerunits" => "markerUnits",
+        "markerwidth" => "markerWidth",
+        "maskcontentunits" => "maskContentUnits",
+        "maskunits" => "maskUnits",
+        "numoctaves" => "numOctaves",
+        "pathlength" => "pathLength",
+        "patterncontentunits" => "patternContentUnits",
+        "patterntransform" => "patternTransform",
+        "patternunits" => "patternUnits",
+        "pointsatx" => "pointsAtX",
+        "pointsaty" => "pointsAtY",
+        "pointsatz" => "pointsAtZ",
+        "preservealpha" => "preserveAlpha",
+        "preserveaspectratio" => "preserveAspectRatio",
+        "primitiveunits" => "primitiveUnits",
+        "refx" => "refX",
+        "refy" => "refY",
+        "repeatcount" => "repeatCount",
+        "repeatdur" => "repeatDur",
+        "requiredextensions" => "requiredExtensions",
+        "requiredfeatures" => "requiredFeatures",
+        "specularconstant" => "specularConstant",
+        "specularexponent" => "specularExponent",
+        "spreadmethod" => "spreadMethod",
+        "startoffset" => "startOffset",
+        "stddeviation" => "stdDeviation",
+        "stitchtiles" => "stitchTiles",
+        "surfacescale" => "surfaceScale",
+        "systemlanguage" => "systemLanguage",
+        "tablevalues" => "tableValues",
+        "targetx" => "targetX",
+        "targety" => "targetY",
+        "textlength" => "textLength",
+        "viewbox" => "viewBox",
+        "viewtarget" => "viewTarget",
+        "xchannelselector" => "xChannelSelector",
+        "ychannelselector" => "yChannelSelector",
+        "zoomandpan" => "zoomAndPan",
+        _ => return None,
+    };
+    Some(adjusted)
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#adjust-mathml-attributes>
+#[must_use]
+pub(crate) fn mathml_attribute_name(name: &str) -> Option<&'static str> {
+    match name {
+        "definitionurl" => Some("definitionURL"),
+        _ => None,
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#adjust-foreign-attributes>
+///
+/// Returns the prefix, local name and namespace of the adjusted attribute
+#[must_use]
+pub(crate) fn foreign_attribute(
+    name: &str,
+) -> Option<(Option<&'static str>, &'static str, Namespace)> {
+    let adjusted = match name {
+        "xlink:actuate" => (Some("xlink"), "actuate", Namespace::XLink),
+        "xlink:arcrole" => (Some("xlink"), "arcrole", Namespace::XLink),
+        "xlink:href" => (Some("xlink"), "href", Namespace::XLink),
+        "xlink:role" => (Some("xlink"), "role", Namespace::XLink),
+        "xlink:show" => (Some("xlink"), "show", Namespace::XLink),
+        "xlink:title" => (Some("xlink"), "title", Namespace::XLink),
+        "xlink:type" => (Some("xlink"), "type", Namespace::XLink),
+        "xml:lang" => (Some("xml"), "lang", Namespace::XML),
+        "xml:space" => (Some("xml"), "space", Namespace::XML),
+        "xmlns" => (None, "xmlns", Namespace::XMLNS),
+        "xmlns:xlink" => (Some("xmlns"), "xlink", Namespace::XMLNS),
+        _ => return None,
+    };
+    Some(adjusted)
+}
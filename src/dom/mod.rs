@@ -1,23 +1,39 @@
 use derive_more::From;
 
+pub mod arena;
+pub mod attribute;
 pub mod comment;
 pub mod document;
 pub mod document_fragment;
 pub mod document_type;
 pub mod element;
+pub(crate) mod foreign;
 pub mod namespace;
+mod parent_link;
 pub mod processing_instruction;
+pub mod selector;
+pub mod serialize;
+mod source_span;
+pub mod test_tree;
 pub mod text;
+pub mod tree_sink;
 
+pub use attribute::Attribute;
 pub use comment::Comment;
-pub use document::Document;
+pub use document::{Document, QuirksMode};
 pub use document_fragment::DocumentFragment;
 pub use document_type::DocumentType;
-pub use element::{Category, Element};
+pub use element::{Category, Element, ScriptFlags};
 pub use namespace::Namespace;
 pub use processing_instruction::ProcessingInstruction;
+pub use selector::{SelectorError, SelectorList};
+pub use serialize::ToHtml;
+pub use test_tree::ToTestTree;
 pub use text::Text;
 
+use parent_link::ParentLink;
+use source_span::SourceSpan;
+
 #[derive(Clone, Debug, Eq, From, PartialEq)]
 pub enum Node {
     Document(Document),
@@ -0,0 +1,113 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    hash::{Hash, Hasher},
+    rc::{Rc, Weak},
+};
+
+use crate::dom::{
+    element::{ChildNode, ParentNode},
+    DocumentFragment, Element,
+};
+
+/// The link from a node to its parent, and the node's index in its parent's children
+///
+/// A clone of a node isn't in the tree, so it doesn't keep the link. The link is also ignored
+/// when comparing, hashing or serializing nodes.
+#[derive(Default)]
+pub(crate) struct ParentLink {
+    parent: Option<WeakParentNode>,
+    // Kept up to date as children are inserted and removed, but the children can also be
+    // changed directly, so it's checked before it's used
+    index: Cell<usize>,
+}
+
+enum WeakParentNode {
+    Element(Weak<RefCell<Element>>),
+    DocumentFragment(Weak<RefCell<DocumentFragment>>),
+}
+
+impl ParentLink {
+    pub(crate) fn new(parent: &ParentNode, index: usize) -> Self {
+        let parent = match parent {
+            ParentNode::Element(e) => WeakParentNode::Element(Rc::downgrade(e)),
+            ParentNode::DocumentFragment(f) => WeakParentNode::DocumentFragment(Rc::downgrade(f)),
+        };
+        ParentLink {
+            parent: Some(parent),
+            index: Cell::new(index),
+        }
+    }
+
+    pub(crate) fn parent(&self) -> Option<ParentNode> {
+        match self.parent.as_ref()? {
+            WeakParentNode::Element(e) => e.upgrade().map(ParentNode::Element),
+            WeakParentNode::DocumentFragment(f) => f.upgrade().map(ParentNode::DocumentFragment),
+        }
+    }
+
+    pub(crate) fn set_index(&self, index: usize) {
+        self.index.set(index);
+    }
+
+    /// The parent of `node`, which has this link, and the index of `node` in its children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    pub(crate) fn locate(&self, node: *const ()) -> Option<(ParentNode, usize)> {
+        let parent = self.parent()?;
+        let index = {
+            let children = parent.children();
+            let index = self.index.get();
+            if children.get(index).is_some_and(|c| c.as_ptr() == node) {
+                index
+            } else {
+                let index = children.iter().position(|c| c.as_ptr() == node)?;
+                self.index.set(index);
+                index
+            }
+        };
+        Some((parent, index))
+    }
+
+    /// See `ChildNode::next_sibling`
+    pub(crate) fn next_sibling(&self, node: *const ()) -> Option<ChildNode> {
+        let (parent, index) = self.locate(node)?;
+        let sibling = parent.children().get(index + 1).cloned();
+        sibling
+    }
+
+    /// See `ChildNode::previous_sibling`
+    pub(crate) fn previous_sibling(&self, node: *const ()) -> Option<ChildNode> {
+        let (parent, index) = self.locate(node)?;
+        let sibling = parent.children().get(index.checked_sub(1)?).cloned();
+        sibling
+    }
+}
+
+impl Clone for ParentLink {
+    fn clone(&self) -> Self {
+        ParentLink::default()
+    }
+}
+
+impl PartialEq for ParentLink {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ParentLink {}
+
+impl Hash for ParentLink {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl fmt::Debug for ParentLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.parent {
+            Some(_) => write!(f, "Some(.., {})", self.index.get()),
+            None => f.write_str("None"),
+        }
+    }
+}
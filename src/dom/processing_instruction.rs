@@ -1,12 +1,31 @@
-use derive_more::{Constructor, From};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Constructor, Debug, Default, Deserialize, Eq, From, Hash, PartialEq, Serialize)]
+use crate::dom::{
+    element::{ChildNode, ParentNode},
+    ParentLink,
+};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct ProcessingInstruction {
     data: String,
+    #[serde(skip)]
+    pub(crate) parent: ParentLink,
 }
 
 impl ProcessingInstruction {
+    #[must_use]
+    pub fn new(data: String) -> Self {
+        ProcessingInstruction {
+            data,
+            parent: ParentLink::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn data(&self) -> &str {
+        &self.data
+    }
+
     #[must_use]
     pub fn len(&self) -> usize {
         self.data.len()
@@ -16,4 +35,34 @@ impl ProcessingInstruction {
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
+
+    /// The parent of this processing instruction
+    #[must_use]
+    pub fn parent_node(&self) -> Option<ParentNode> {
+        self.parent.parent()
+    }
+
+    /// The node after this processing instruction in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn next_sibling(&self) -> Option<ChildNode> {
+        self.parent.next_sibling((self as *const Self).cast())
+    }
+
+    /// The node before this processing instruction in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn previous_sibling(&self) -> Option<ChildNode> {
+        self.parent.previous_sibling((self as *const Self).cast())
+    }
+}
+
+impl From<String> for ProcessingInstruction {
+    fn from(data: String) -> Self {
+        ProcessingInstruction::new(data)
+    }
 }
@@ -0,0 +1,232 @@
+//! Matching selectors against elements
+//!
+//! <https://drafts.csswg.org/selectors-4/#match-against-element>
+use std::{cell::RefCell, rc::Rc};
+
+use crate::dom::{
+    element::ChildNode,
+    selector::{
+        AttributeOperator, AttributeSelector, Combinator, ComplexSelector, CompoundSelector, Nth,
+        PseudoClass, SelectorList, SimpleSelector,
+    },
+    Element,
+};
+
+/// An element which is being matched
+pub(super) struct ElementRef(Rc<RefCell<Element>>);
+
+impl ElementRef {
+    fn with<T, F: FnOnce(&Element) -> T>(&self, f: F) -> T {
+        f(&self.0.borrow())
+    }
+
+    fn is_root(&self) -> bool {
+        self.parent().is_none()
+    }
+
+    fn parent(&self) -> Option<ElementRef> {
+        self.0.borrow().parent().map(ElementRef)
+    }
+
+    /// The elements sharing this element's parent, and the index of this element among them
+    ///
+    /// An element without a parent is treated as an only child.
+    fn siblings(&self) -> (Vec<ElementRef>, usize) {
+        let parent = match self.0.borrow().parent() {
+            Some(parent) => parent,
+            None => return (vec![ElementRef(Rc::clone(&self.0))], 0),
+        };
+        let siblings: Vec<ElementRef> = parent
+            .borrow()
+            .iter()
+            .filter_map(ChildNode::element)
+            .map(ElementRef)
+            .collect();
+        let index = siblings
+            .iter()
+            .position(|sibling| Rc::ptr_eq(&sibling.0, &self.0))
+            .expect("element is not a child of its parent");
+        (siblings, index)
+    }
+}
+
+/// The elements in `candidates` which match `selectors`, or only the first one
+pub(super) fn find<I>(
+    candidates: I,
+    selectors: &SelectorList,
+    first: bool,
+) -> Vec<Rc<RefCell<Element>>>
+where
+    I: IntoIterator<Item = Rc<RefCell<Element>>>,
+{
+    let matching = candidates
+        .into_iter()
+        .map(ElementRef)
+        .filter(|element| selectors.matches(element))
+        .map(|element| element.0);
+    if first {
+        matching.take(1).collect()
+    } else {
+        matching.collect()
+    }
+}
+
+impl ComplexSelector {
+    pub(super) fn matches(&self, element: &ElementRef) -> bool {
+        self.matches_from(self.compounds.len() - 1, element)
+    }
+
+    /// Match right to left, starting at the compound selector at `index`
+    fn matches_from(&self, index: usize, element: &ElementRef) -> bool {
+        if !self.compounds[index].matches(element) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+        match self.combinators[index - 1] {
+            Combinator::Child => element
+                .parent()
+                .is_some_and(|parent| self.matches_from(index - 1, &parent)),
+            Combinator::Descendant => {
+                let mut ancestor = element.parent();
+                while let Some(current) = ancestor {
+                    if self.matches_from(index - 1, &current) {
+                        return true;
+                    }
+                    ancestor = current.parent();
+                }
+                false
+            }
+            Combinator::NextSibling => {
+                let (siblings, i) = element.siblings();
+                i > 0 && self.matches_from(index - 1, &siblings[i - 1])
+            }
+            Combinator::SubsequentSibling => {
+                let (siblings, i) = element.siblings();
+                siblings[..i]
+                    .iter()
+                    .any(|sibling| self.matches_from(index - 1, sibling))
+            }
+        }
+    }
+}
+
+impl CompoundSelector {
+    fn matches(&self, element: &ElementRef) -> bool {
+        self.0.iter().all(|selector| selector.matches(element))
+    }
+}
+
+impl SimpleSelector {
+    fn matches(&self, element: &ElementRef) -> bool {
+        match self {
+            SimpleSelector::Universal => true,
+            SimpleSelector::Type(tag_name, name) => element.with(|e| {
+                // Type selectors are case-insensitive for HTML elements only
+                if e.is_html() {
+                    e.name() == tag_name
+                } else {
+                    e.local_name() == *name
+                }
+            }),
+            SimpleSelector::Id(id) => element.with(|e| e.get_attribute("id") == Some(id)),
+            SimpleSelector::Class(class) => element.with(|e| {
+                e.get_attribute("class")
+                    .is_some_and(|classes| classes.split_ascii_whitespace().any(|c| c == class))
+            }),
+            SimpleSelector::Attribute(attribute) => element.with(|e| attribute.matches(e)),
+            SimpleSelector::PseudoClass(pseudo_class) => pseudo_class.matches(element),
+        }
+    }
+}
+
+impl AttributeSelector {
+    fn matches(&self, element: &Element) -> bool {
+        let value = match element.get_attribute(&self.name) {
+            Some(value) => value,
+            None => return false,
+        };
+        let (operator, expected, case_insensitive) = match self.operator {
+            Some((operator, ref expected, case_insensitive)) => {
+                (operator, expected, case_insensitive)
+            }
+            None => return true,
+        };
+        let (value, expected) = if case_insensitive {
+            (value.to_ascii_lowercase(), expected.to_ascii_lowercase())
+        } else {
+            (value.to_string(), expected.clone())
+        };
+        match operator {
+            AttributeOperator::Equals => value == expected,
+            AttributeOperator::Includes => value.split_ascii_whitespace().any(|v| v == expected),
+            AttributeOperator::DashMatch => {
+                value == expected
+                    || value
+                        .strip_prefix(&expected)
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }
+            // These never match an empty value
+            AttributeOperator::Prefix => !expected.is_empty() && value.starts_with(&expected),
+            AttributeOperator::Suffix => !expected.is_empty() && value.ends_with(&expected),
+            AttributeOperator::Substring => !expected.is_empty() && value.contains(&expected),
+        }
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, element: &ElementRef) -> bool {
+        match self {
+            PseudoClass::Root => element.is_root(),
+            PseudoClass::Empty => element.with(|e| {
+                e.iter().all(|child| match child {
+                    ChildNode::Element(_) => false,
+                    ChildNode::Text(t) => t.borrow().is_empty(),
+                    ChildNode::ProcessingInstruction(_) | ChildNode::Comment(_) => true,
+                })
+            }),
+            PseudoClass::OnlyChild => element.siblings().0.len() == 1,
+            PseudoClass::OnlyOfType => {
+                let (siblings, _) = element.siblings();
+                siblings.iter().filter(|s| same_type(s, element)).count() == 1
+            }
+            PseudoClass::NthChild(nth, of) => nth_child(*nth, element, false, |s| {
+                of.as_ref().is_none_or(|of| of.matches(s))
+            }),
+            PseudoClass::NthLastChild(nth, of) => nth_child(*nth, element, true, |s| {
+                of.as_ref().is_none_or(|of| of.matches(s))
+            }),
+            PseudoClass::NthOfType(nth) => {
+                nth_child(*nth, element, false, |s| same_type(s, element))
+            }
+            PseudoClass::NthLastOfType(nth) => {
+                nth_child(*nth, element, true, |s| same_type(s, element))
+            }
+            PseudoClass::Not(selectors) => !selectors.matches(element),
+            PseudoClass::Is(selectors) => selectors.matches(element),
+        }
+    }
+}
+
+/// Whether the element's 1-based index among its siblings which pass `filter` is selected by
+/// `nth`. The element has to pass `filter` itself.
+fn nth_child<F>(nth: Nth, element: &ElementRef, from_end: bool, filter: F) -> bool
+where
+    F: Fn(&ElementRef) -> bool,
+{
+    if !filter(element) {
+        return false;
+    }
+    let (siblings, index) = element.siblings();
+    let counted = if from_end {
+        siblings[index + 1..].iter().filter(|s| filter(s)).count()
+    } else {
+        siblings[..index].iter().filter(|s| filter(s)).count()
+    };
+    nth.matches(counted + 1)
+}
+
+fn same_type(a: &ElementRef, b: &ElementRef) -> bool {
+    a.with(|a| b.with(|b| a.namespace() == b.namespace() && a.name() == b.name()))
+}
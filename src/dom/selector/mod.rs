@@ -0,0 +1,417 @@
+//! Selectors, for finding elements with `query_selector` and `query_selector_all`
+//!
+//! Supports the selectors of [Selectors Level 3](https://www.w3.org/TR/selectors-3/), apart
+//! from pseudo-elements, namespace prefixes and the user action and UI state pseudo-classes.
+//! From [Level 4](https://drafts.csswg.org/selectors-4/) it adds `:is()`, `:where()`,
+//! selector lists in `:not()`, `:nth-child(An+B of S)` and the `i` and `s` attribute flags.
+use std::{cell::RefCell, error, fmt, rc::Rc};
+
+use crate::{
+    dom::{Document, Element},
+    tokenizer::TagName,
+};
+
+mod matching;
+mod parser;
+
+use self::{matching::ElementRef, parser::SelectorParser};
+
+/// A parsed selector list, eg. `ul > li, .item`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectorList(Vec<ComplexSelector>);
+
+/// Compound selectors separated by combinators, eg. `ul > li.item`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ComplexSelector {
+    compounds: Vec<CompoundSelector>,
+    // The combinator between each of the compound selectors
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Combinator {
+    /// `a b`
+    Descendant,
+    /// `a > b`
+    Child,
+    /// `a + b`
+    NextSibling,
+    /// `a ~ b`
+    SubsequentSibling,
+}
+
+/// Simple selectors which all have to match an element, eg. `li.item:first-child`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CompoundSelector(Vec<SimpleSelector>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SimpleSelector {
+    /// `*`
+    Universal,
+    /// `li`, along with the name as it was written for matching foreign elements
+    Type(TagName, String),
+    /// `#id`
+    Id(String),
+    /// `.class`
+    Class(String),
+    /// `[name]` or `[name=value]`
+    Attribute(AttributeSelector),
+    /// `:first-child`, `:not(...)` etc.
+    PseudoClass(PseudoClass),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AttributeSelector {
+    name: String,
+    // The operator, the value and whether it's compared case-insensitively
+    operator: Option<(AttributeOperator, String, bool)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum AttributeOperator {
+    /// `[a=b]`
+    Equals,
+    /// `[a~=b]`, one of a whitespace separated list of words
+    Includes,
+    /// `[a|=b]`, either exactly `b` or starting with `b-`
+    DashMatch,
+    /// `[a^=b]`
+    Prefix,
+    /// `[a$=b]`
+    Suffix,
+    /// `[a*=b]`
+    Substring,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PseudoClass {
+    Root,
+    Empty,
+    OnlyChild,
+    OnlyOfType,
+    /// `:nth-child()` and `:first-child`, optionally only counting elements matching a selector
+    NthChild(Nth, Option<SelectorList>),
+    /// `:nth-last-child()` and `:last-child`
+    NthLastChild(Nth, Option<SelectorList>),
+    /// `:nth-of-type()` and `:first-of-type`
+    NthOfType(Nth),
+    /// `:nth-last-of-type()` and `:last-of-type`
+    NthLastOfType(Nth),
+    Not(SelectorList),
+    /// `:is()` and `:where()`, which only differ in specificity
+    Is(SelectorList),
+}
+
+/// The `An+B` argument of the `:nth-*` pseudo-classes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Nth {
+    a: i32,
+    b: i32,
+}
+
+impl Nth {
+    fn new(a: i32, b: i32) -> Self {
+        Nth { a, b }
+    }
+
+    /// Whether the element at the 1-based `index` is selected, ie. `index = An+B` for some `n >= 0`
+    fn matches(self, index: usize) -> bool {
+        let index = index as i64;
+        let (a, b) = (i64::from(self.a), i64::from(self.b));
+        if a == 0 {
+            return index == b;
+        }
+        let n = index - b;
+        n % a == 0 && n / a >= 0
+    }
+}
+
+impl SelectorList {
+    /// Parse a selector list
+    ///
+    /// # Errors
+    /// Fails if the selector list is invalid, or uses an unsupported selector
+    pub fn parse(selectors: &str) -> Result<Self, SelectorError> {
+        SelectorParser::new(selectors).parse()
+    }
+
+    fn matches(&self, element: &ElementRef) -> bool {
+        self.0.iter().any(|selector| selector.matches(element))
+    }
+}
+
+/// Returned when a selector can't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectorError {
+    position: usize,
+    message: &'static str,
+}
+
+impl SelectorError {
+    fn new(position: usize, message: &'static str) -> Self {
+        SelectorError { position, message }
+    }
+
+    /// The byte offset in the selector where the error was found
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl error::Error for SelectorError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SelectorError: {} at position {}",
+            self.message, self.position
+        )
+    }
+}
+
+impl Document {
+    /// The first element in the document which matches `selectors`
+    ///
+    /// <https://dom.spec.whatwg.org/#dom-parentnode-queryselector>
+    ///
+    /// # Errors
+    /// Fails if `selectors` can't be parsed
+    pub fn query_selector(
+        &self,
+        selectors: &str,
+    ) -> Result<Option<Rc<RefCell<Element>>>, SelectorError> {
+        let selectors = SelectorList::parse(selectors)?;
+        let root = match self.document_element() {
+            Some(root) => root,
+            None => return Ok(None),
+        };
+        Ok(matching::find(tree(root), &selectors, true).pop())
+    }
+
+    /// Every element in the document which matches `selectors`, in tree order
+    ///
+    /// <https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall>
+    ///
+    /// # Errors
+    /// Fails if `selectors` can't be parsed
+    pub fn query_selector_all(
+        &self,
+        selectors: &str,
+    ) -> Result<Vec<Rc<RefCell<Element>>>, SelectorError> {
+        let selectors = SelectorList::parse(selectors)?;
+        let root = match self.document_element() {
+            Some(root) => root,
+            None => return Ok(Vec::new()),
+        };
+        Ok(matching::find(tree(root), &selectors, false))
+    }
+}
+
+/// `root` and then every element under it, in tree order
+fn tree(root: Rc<RefCell<Element>>) -> impl Iterator<Item = Rc<RefCell<Element>>> {
+    let descendants = root.borrow().descendant_elements();
+    std::iter::once(root).chain(descendants)
+}
+
+impl Element {
+    fn descendant_elements(&self) -> impl Iterator<Item = Rc<RefCell<Element>>> {
+        self.descendants().filter_map(|node| node.element())
+    }
+
+    /// The first descendant of this element which matches `selectors`
+    ///
+    /// As in the DOM, the whole tree is considered when matching, so `div > p` finds a `p`
+    /// child of this element even if this element is the `div`.
+    ///
+    /// <https://dom.spec.whatwg.org/#dom-parentnode-queryselector>
+    ///
+    /// # Errors
+    /// Fails if `selectors` can't be parsed
+    pub fn query_selector(
+        &self,
+        selectors: &str,
+    ) -> Result<Option<Rc<RefCell<Element>>>, SelectorError> {
+        let selectors = SelectorList::parse(selectors)?;
+        Ok(matching::find(self.descendant_elements(), &selectors, true).pop())
+    }
+
+    /// Every descendant of this element which matches `selectors`, in tree order
+    ///
+    /// See `Element::query_selector`
+    ///
+    /// <https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall>
+    ///
+    /// # Errors
+    /// Fails if `selectors` can't be parsed
+    pub fn query_selector_all(
+        &self,
+        selectors: &str,
+    ) -> Result<Vec<Rc<RefCell<Element>>>, SelectorError> {
+        let selectors = SelectorList::parse(selectors)?;
+        Ok(matching::find(
+            self.descendant_elements(),
+            &selectors,
+            false,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{dom::Namespace, Parser};
+
+    const HTML: &str = "<!DOCTYPE html><html><head><title>t</title></head><body>\
+        <div id=main class='box wide'>\
+          <h1 lang=en-GB>Title</h1>\
+          <p class=intro>One</p>\
+          <p>Two</p>\
+          <ul><li>a</li><li class=x>b</li><li>c</li><li class=x>d</li><li>e</li></ul>\
+          <a href='https://example.com/page.html' data-Kind=Link>link</a>\
+        </div>\
+        <div id=empty></div>\
+        <svg><foreignObject><p>in svg</p></foreignObject></svg>\
+        </body></html>";
+
+    fn document() -> Document {
+        Parser::new(Cursor::new(HTML)).parse().unwrap()
+    }
+
+    /// The text of each element which matches `selectors`
+    fn select(document: &Document, selectors: &str) -> Vec<String> {
+        document
+            .query_selector_all(selectors)
+            .unwrap()
+            .iter()
+            .map(|e| {
+                let e = e.borrow();
+                let text: Vec<String> = e
+                    .iter()
+                    .filter_map(|child| match child {
+                        crate::dom::element::ChildNode::Text(t) => Some(t.borrow().to_string()),
+                        _ => None,
+                    })
+                    .collect();
+                format!("{}{}", e.local_name(), text.concat())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn type_class_and_id() {
+        let document = document();
+        assert_eq!(select(&document, "p"), vec!["pOne", "pTwo", "pin svg"]);
+        assert_eq!(select(&document, "P.intro"), vec!["pOne"]);
+        assert_eq!(select(&document, "#main.wide > h1"), vec!["h1Title"]);
+        assert_eq!(select(&document, ".box.narrow"), Vec::<String>::new());
+        assert_eq!(select(&document, "foreignObject"), vec!["foreignObject"]);
+        assert_eq!(select(&document, "foreignobject"), Vec::<String>::new());
+        assert_eq!(select(&document, "*").len(), 19);
+        assert_eq!(select(&document, "title, h1"), vec!["titlet", "h1Title"]);
+    }
+
+    #[test]
+    fn attributes() {
+        let document = document();
+        assert_eq!(select(&document, "[lang]"), vec!["h1Title"]);
+        assert_eq!(select(&document, "[lang|=en]"), vec!["h1Title"]);
+        assert_eq!(select(&document, "[lang|=e]"), Vec::<String>::new());
+        assert_eq!(select(&document, "[class~=wide]"), vec!["div"]);
+        assert_eq!(select(&document, "[href^='https:']"), vec!["alink"]);
+        assert_eq!(select(&document, "[href$=\".html\"]"), vec!["alink"]);
+        assert_eq!(select(&document, "[href*=example]"), vec!["alink"]);
+        assert_eq!(select(&document, "[href*='']"), Vec::<String>::new());
+        assert_eq!(select(&document, "[DATA-kind=Link]"), vec!["alink"]);
+        assert_eq!(select(&document, "[data-kind=link]"), Vec::<String>::new());
+        assert_eq!(select(&document, "[data-kind=link i]"), vec!["alink"]);
+    }
+
+    #[test]
+    fn combinators() {
+        let document = document();
+        assert_eq!(select(&document, "body p"), vec!["pOne", "pTwo", "pin svg"]);
+        assert_eq!(select(&document, "div > p"), vec!["pOne", "pTwo"]);
+        assert_eq!(select(&document, "h1 + p"), vec!["pOne"]);
+        assert_eq!(select(&document, "h1 ~ p"), vec!["pOne", "pTwo"]);
+        assert_eq!(select(&document, "li.x + li"), vec!["lic", "lie"]);
+        assert_eq!(
+            select(&document, "html > body > div ul li.x"),
+            vec!["lib", "lid"]
+        );
+        assert_eq!(select(&document, "div ~ svg p"), vec!["pin svg"]);
+    }
+
+    #[test]
+    fn structural_pseudo_classes() {
+        let document = document();
+        assert_eq!(select(&document, ":root"), vec!["html"]);
+        assert_eq!(select(&document, "li:first-child"), vec!["lia"]);
+        assert_eq!(select(&document, "li:last-child"), vec!["lie"]);
+        assert_eq!(
+            select(&document, "li:nth-child(odd)"),
+            vec!["lia", "lic", "lie"]
+        );
+        assert_eq!(select(&document, "li:nth-child(2n)"), vec!["lib", "lid"]);
+        assert_eq!(select(&document, "li:nth-child(-n+2)"), vec!["lia", "lib"]);
+        assert_eq!(select(&document, "li:nth-last-child(2)"), vec!["lid"]);
+        assert_eq!(select(&document, "li:nth-child(2 of .x)"), vec!["lid"]);
+        assert_eq!(select(&document, "#main > :nth-of-type(2)"), vec!["pTwo"]);
+        assert_eq!(select(&document, "#main > p:last-of-type"), vec!["pTwo"]);
+        assert_eq!(select(&document, "#main > :only-of-type").len(), 3);
+        assert_eq!(
+            select(&document, "foreignObject > :only-child"),
+            vec!["pin svg"]
+        );
+        assert_eq!(select(&document, "div:empty"), vec!["div"]);
+    }
+
+    #[test]
+    fn logical_pseudo_classes() {
+        let document = document();
+        assert_eq!(select(&document, "li:not(.x)"), vec!["lia", "lic", "lie"]);
+        assert_eq!(
+            select(&document, "li:not(.x, :first-child)"),
+            vec!["lic", "lie"]
+        );
+        assert_eq!(
+            select(&document, ":is(h1, ul > .x)"),
+            vec!["h1Title", "lib", "lid"]
+        );
+        assert_eq!(select(&document, ":where(#main) > p"), vec!["pOne", "pTwo"]);
+        assert_eq!(select(&document, "p:not(div p)"), vec!["pin svg"]);
+    }
+
+    #[test]
+    fn query_selector() {
+        let document = document();
+        let p = document.query_selector("p").unwrap().unwrap();
+        assert_eq!(p.borrow().get_attribute("class"), Some("intro"));
+        assert!(document.query_selector("table").unwrap().is_none());
+        assert!(document.query_selector("p[").is_err());
+
+        // Element queries only find descendants, and can't see outside the element
+        let main = document.query_selector("#main").unwrap().unwrap();
+        let main = main.borrow();
+        assert_eq!(main.query_selector_all("li").unwrap().len(), 5);
+        assert!(main.query_selector("#main").unwrap().is_none());
+        assert_eq!(main.query_selector_all("div > p").unwrap().len(), 2);
+        assert_eq!(main.query_selector_all("body p").unwrap().len(), 2);
+        assert_eq!(main.query_selector_all("* > p").unwrap().len(), 2);
+        assert_eq!(
+            main.query_selector("ul > :nth-child(3)")
+                .unwrap()
+                .unwrap()
+                .borrow()
+                .namespace(),
+            Namespace::HTML
+        );
+    }
+}
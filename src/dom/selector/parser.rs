@@ -0,0 +1,484 @@
+//! Parsing of selector lists
+//!
+//! <https://drafts.csswg.org/selectors-4/#grammar>
+use std::{iter::Peekable, str::CharIndices};
+
+use crate::{
+    dom::selector::{
+        AttributeOperator, AttributeSelector, Combinator, ComplexSelector, CompoundSelector, Nth,
+        PseudoClass, SelectorError, SelectorList, SimpleSelector,
+    },
+    tokenizer::TagName,
+};
+
+pub(super) struct SelectorParser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+type Result<T> = std::result::Result<T, SelectorError>;
+
+impl<'a> SelectorParser<'a> {
+    pub(super) fn new(input: &'a str) -> Self {
+        SelectorParser {
+            input,
+            chars: input.char_indices().peekable(),
+        }
+    }
+
+    /// Parse the whole input as a selector list
+    pub(super) fn parse(mut self) -> Result<SelectorList> {
+        let list = self.parse_selector_list()?;
+        match self.chars.peek() {
+            None => Ok(list),
+            Some(_) => Err(self.error("Unexpected character")),
+        }
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |&(i, _)| i)
+    }
+
+    fn error(&mut self, message: &'static str) -> SelectorError {
+        SelectorError::new(self.position(), message)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next_if(&mut self, c: char) -> bool {
+        self.chars.next_if(|&(_, next)| next == c).is_some()
+    }
+
+    fn expect(&mut self, c: char, message: &'static str) -> Result<()> {
+        if self.next_if(c) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    /// Skip any whitespace, returning whether there was any
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self
+            .chars
+            .next_if(|&(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C'))
+            .is_some()
+        {
+            skipped = true;
+        }
+        skipped
+    }
+
+    /// A comma separated list of complex selectors, up to the end of the input or a `)`
+    fn parse_selector_list(&mut self) -> Result<SelectorList> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.parse_complex_selector()?);
+            if !self.next_if(',') {
+                break;
+            }
+        }
+        Ok(SelectorList(selectors))
+    }
+
+    fn parse_complex_selector(&mut self) -> Result<ComplexSelector> {
+        let mut compounds = vec![self.parse_compound_selector()?];
+        let mut combinators = Vec::new();
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                None | Some(',') | Some(')') => break,
+                Some(_) if whitespace => Combinator::Descendant,
+                Some(_) => return Err(self.error("Expected a combinator")),
+            };
+            if combinator != Combinator::Descendant {
+                let _ = self.chars.next();
+                self.skip_whitespace();
+            }
+            combinators.push(combinator);
+            compounds.push(self.parse_compound_selector()?);
+        }
+        Ok(ComplexSelector {
+            compounds,
+            combinators,
+        })
+    }
+
+    fn parse_compound_selector(&mut self) -> Result<CompoundSelector> {
+        let mut selectors = Vec::new();
+        if self.next_if('*') {
+            selectors.push(SimpleSelector::Universal);
+        } else if self.peek().is_some_and(is_ident_start) {
+            let name = self.parse_ident()?;
+            let tag_name = name
+                .parse()
+                .unwrap_or_else(|_| TagName::Other(name.clone()));
+            selectors.push(SimpleSelector::Type(tag_name, name));
+        }
+
+        loop {
+            let selector = match self.peek() {
+                Some('#') => {
+                    let _ = self.chars.next();
+                    SimpleSelector::Id(self.parse_ident()?)
+                }
+                Some('.') => {
+                    let _ = self.chars.next();
+                    SimpleSelector::Class(self.parse_ident()?)
+                }
+                Some('[') => {
+                    let _ = self.chars.next();
+                    SimpleSelector::Attribute(self.parse_attribute_selector()?)
+                }
+                Some(':') => {
+                    let _ = self.chars.next();
+                    if self.peek() == Some(':') {
+                        return Err(self.error("Pseudo-elements are not supported"));
+                    }
+                    SimpleSelector::PseudoClass(self.parse_pseudo_class()?)
+                }
+                _ => break,
+            };
+            selectors.push(selector);
+        }
+
+        if selectors.is_empty() {
+            return Err(self.error("Expected a selector"));
+        }
+        Ok(CompoundSelector(selectors))
+    }
+
+    /// The part of an attribute selector after the `[`
+    fn parse_attribute_selector(&mut self) -> Result<AttributeSelector> {
+        self.skip_whitespace();
+        let name = self.parse_ident()?;
+        self.skip_whitespace();
+
+        let operator = match self.peek() {
+            Some(']') => {
+                let _ = self.chars.next();
+                return Ok(AttributeSelector {
+                    name,
+                    operator: None,
+                });
+            }
+            Some('=') => AttributeOperator::Equals,
+            Some('~') => AttributeOperator::Includes,
+            Some('|') => AttributeOperator::DashMatch,
+            Some('^') => AttributeOperator::Prefix,
+            Some('$') => AttributeOperator::Suffix,
+            Some('*') => AttributeOperator::Substring,
+            _ => return Err(self.error("Expected an attribute selector operator")),
+        };
+        let _ = self.chars.next();
+        if operator != AttributeOperator::Equals {
+            self.expect('=', "Expected '=' in attribute selector")?;
+        }
+        self.skip_whitespace();
+
+        let value = match self.peek() {
+            Some(quote @ '"') | Some(quote @ '\'') => {
+                let _ = self.chars.next();
+                self.parse_string(quote)?
+            }
+            _ => self.parse_ident()?,
+        };
+        self.skip_whitespace();
+
+        let case_insensitive = match self.peek() {
+            Some('i') | Some('I') => Some(true),
+            Some('s') | Some('S') => Some(false),
+            _ => None,
+        };
+        if case_insensitive.is_some() {
+            let _ = self.chars.next();
+            self.skip_whitespace();
+        }
+        self.expect(']', "Expected ']' to end the attribute selector")?;
+
+        Ok(AttributeSelector {
+            name,
+            operator: Some((operator, value, case_insensitive.unwrap_or(false))),
+        })
+    }
+
+    /// The part of a pseudo-class after the `:`
+    fn parse_pseudo_class(&mut self) -> Result<PseudoClass> {
+        let name = self.parse_ident()?.to_ascii_lowercase();
+        if !self.next_if('(') {
+            return match name.as_str() {
+                "root" => Ok(PseudoClass::Root),
+                "empty" => Ok(PseudoClass::Empty),
+                "first-child" => Ok(PseudoClass::NthChild(Nth::new(0, 1), None)),
+                "last-child" => Ok(PseudoClass::NthLastChild(Nth::new(0, 1), None)),
+                "only-child" => Ok(PseudoClass::OnlyChild),
+                "first-of-type" => Ok(PseudoClass::NthOfType(Nth::new(0, 1))),
+                "last-of-type" => Ok(PseudoClass::NthLastOfType(Nth::new(0, 1))),
+                "only-of-type" => Ok(PseudoClass::OnlyOfType),
+                _ => Err(self.error("Unsupported pseudo-class")),
+            };
+        }
+
+        self.skip_whitespace();
+        let pseudo_class = match name.as_str() {
+            "not" => PseudoClass::Not(self.parse_selector_list()?),
+            "is" | "matches" => PseudoClass::Is(self.parse_selector_list()?),
+            "where" => PseudoClass::Is(self.parse_selector_list()?),
+            "nth-child" => {
+                let nth = self.parse_nth()?;
+                PseudoClass::NthChild(nth, self.parse_of_selector()?)
+            }
+            "nth-last-child" => {
+                let nth = self.parse_nth()?;
+                PseudoClass::NthLastChild(nth, self.parse_of_selector()?)
+            }
+            "nth-of-type" => PseudoClass::NthOfType(self.parse_nth()?),
+            "nth-last-of-type" => PseudoClass::NthLastOfType(self.parse_nth()?),
+            _ => return Err(self.error("Unsupported pseudo-class")),
+        };
+        self.skip_whitespace();
+        self.expect(')', "Expected ')' to end the pseudo-class")?;
+        Ok(pseudo_class)
+    }
+
+    /// The optional `of S` part of `:nth-child` and `:nth-last-child`
+    fn parse_of_selector(&mut self) -> Result<Option<SelectorList>> {
+        self.skip_whitespace();
+        if self.peek() == Some(')') {
+            return Ok(None);
+        }
+        let of = self.parse_ident()?;
+        if !of.eq_ignore_ascii_case("of") || !self.skip_whitespace() {
+            return Err(self.error("Expected 'of' and a selector"));
+        }
+        Ok(Some(self.parse_selector_list()?))
+    }
+
+    /// <https://drafts.csswg.org/css-syntax-3/#anb-microsyntax>
+    fn parse_nth(&mut self) -> Result<Nth> {
+        let start = self.position();
+        let mut text = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|&(_, c)| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | ' '))
+            .map(|(_, c)| c)
+        {
+            text.push(c);
+        }
+        // `of` starts the selector of `:nth-child(An+B of S)`
+        let text = match text.to_ascii_lowercase().find(" of") {
+            Some(i) => {
+                self.chars = rebase(self.input, start + i);
+                text[..i].to_string()
+            }
+            None => text,
+        };
+
+        parse_an_plus_b(text.trim()).ok_or_else(|| SelectorError::new(start, "Invalid An+B"))
+    }
+
+    /// An identifier, eg. a tag, attribute or class name
+    ///
+    /// <https://drafts.csswg.org/css-syntax-3/#consume-name>
+    fn parse_ident(&mut self) -> Result<String> {
+        if !self
+            .peek()
+            .is_some_and(|c| is_ident_start(c) || c == '-' || c == '\\')
+        {
+            return Err(self.error("Expected an identifier"));
+        }
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if c == '\\' {
+                let _ = self.chars.next();
+                ident.push(self.parse_escape()?);
+            } else if is_ident_start(c) || c.is_ascii_digit() || c == '-' {
+                let _ = self.chars.next();
+                ident.push(c);
+            } else {
+                break;
+            }
+        }
+        Ok(ident)
+    }
+
+    /// A quoted string, after its opening quote
+    fn parse_string(&mut self, quote: char) -> Result<String> {
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, c)) if c == quote => return Ok(string),
+                Some((_, '\\')) => string.push(self.parse_escape()?),
+                Some((_, c)) => string.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    /// The character of an escape, after its `\`
+    ///
+    /// <https://drafts.csswg.org/css-syntax-3/#consume-escaped-code-point>
+    fn parse_escape(&mut self) -> Result<char> {
+        let mut hex = String::new();
+        while hex.len() < 6 {
+            match self.chars.next_if(|&(_, c)| c.is_ascii_hexdigit()) {
+                Some((_, c)) => hex.push(c),
+                None => break,
+            }
+        }
+        if hex.is_empty() {
+            return match self.chars.next() {
+                Some((_, c)) => Ok(c),
+                None => Ok('\u{FFFD}'),
+            };
+        }
+        // A single whitespace character after a hex escape is part of it
+        let _ = self
+            .chars
+            .next_if(|&(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0C'));
+
+        let code = u32::from_str_radix(&hex, 16).unwrap_or(0xFFFD);
+        Ok(match code {
+            0 => '\u{FFFD}',
+            code => std::char::from_u32(code).unwrap_or('\u{FFFD}'),
+        })
+    }
+}
+
+/// The characters of `input` from `position`, still indexed from the start of `input`
+fn rebase(input: &str, position: usize) -> Peekable<CharIndices<'_>> {
+    let mut chars = input.char_indices().peekable();
+    while chars.next_if(|&(i, _)| i < position).is_some() {}
+    chars
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || !c.is_ascii()
+}
+
+/// Parse the `An+B` notation, eg. `odd`, `2n+1` or `-n + 3`
+fn parse_an_plus_b(text: &str) -> Option<Nth> {
+    let text = text.to_ascii_lowercase();
+    match text.as_str() {
+        "odd" => return Some(Nth::new(2, 1)),
+        "even" => return Some(Nth::new(2, 0)),
+        _ => {}
+    }
+
+    // An optionally signed integer
+    let parse_int = |s: &str| {
+        let (sign, digits) = match s.strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse::<i32>().ok().map(|i| sign * i)
+    };
+
+    match text.find('n') {
+        None => parse_int(&text).map(|b| Nth::new(0, b)),
+        Some(i) => {
+            let a = match &text[..i] {
+                "" | "+" => 1,
+                "-" => -1,
+                a => parse_int(a)?,
+            };
+            let rest: String = text[i + 1..].chars().filter(|&c| c != ' ').collect();
+            let b = match rest.as_str() {
+                "" => 0,
+                b if b.starts_with(['+', '-']) => parse_int(b)?,
+                _ => return None,
+            };
+            Some(Nth::new(a, b))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(input: &str) -> Result<SelectorList> {
+        SelectorParser::new(input).parse()
+    }
+
+    #[test]
+    fn an_plus_b() {
+        assert_eq!(parse_an_plus_b("odd"), Some(Nth::new(2, 1)));
+        assert_eq!(parse_an_plus_b("EVEN"), Some(Nth::new(2, 0)));
+        assert_eq!(parse_an_plus_b("3"), Some(Nth::new(0, 3)));
+        assert_eq!(parse_an_plus_b("-3"), Some(Nth::new(0, -3)));
+        assert_eq!(parse_an_plus_b("n"), Some(Nth::new(1, 0)));
+        assert_eq!(parse_an_plus_b("-n+3"), Some(Nth::new(-1, 3)));
+        assert_eq!(parse_an_plus_b("2n + 1"), Some(Nth::new(2, 1)));
+        assert_eq!(parse_an_plus_b("+5n-2"), Some(Nth::new(5, -2)));
+        assert_eq!(parse_an_plus_b("2n+"), None);
+        assert_eq!(parse_an_plus_b("foo"), None);
+    }
+
+    #[test]
+    fn selector_lists() {
+        let list = parse("div > p.a#b, ul li:nth-child(2n+1 of .x) + [lang|=en i]").unwrap();
+        assert_eq!(list.0.len(), 2);
+        assert_eq!(list.0[0].combinators, vec![Combinator::Child]);
+        assert_eq!(
+            list.0[1].combinators,
+            vec![Combinator::Descendant, Combinator::NextSibling]
+        );
+        assert_eq!(
+            list.0[1].compounds[2].0,
+            vec![SimpleSelector::Attribute(AttributeSelector {
+                name: "lang".to_string(),
+                operator: Some((AttributeOperator::DashMatch, "en".to_string(), true)),
+            })]
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let list = parse(r#".a\:b, [title="x\"y"], #\31 23"#).unwrap();
+        assert_eq!(
+            list.0[0].compounds[0].0,
+            vec![SimpleSelector::Class("a:b".to_string())]
+        );
+        assert_eq!(
+            list.0[1].compounds[0].0,
+            vec![SimpleSelector::Attribute(AttributeSelector {
+                name: "title".to_string(),
+                operator: Some((AttributeOperator::Equals, "x\"y".to_string(), false)),
+            })]
+        );
+        assert_eq!(
+            list.0[2].compounds[0].0,
+            vec![SimpleSelector::Id("123".to_string())]
+        );
+    }
+
+    #[test]
+    fn invalid_selectors() {
+        for selector in &[
+            "",
+            "div,",
+            "> p",
+            "p >",
+            "[href",
+            "a[href=]",
+            ":hover",
+            "p::before",
+            ":nth-child(x)",
+            ":not(p",
+            "'p'",
+        ] {
+            assert!(parse(selector).is_err(), "{:?} should be invalid", selector);
+        }
+    }
+}
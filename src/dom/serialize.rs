@@ -0,0 +1,299 @@
+//! Serialize a DOM tree back to HTML
+//!
+//! Implements the [serializing HTML fragments](https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments)
+//! algorithm.
+
+use std::fmt::{self, Write};
+
+use crate::{
+    dom::{
+        document, element::ChildNode, Attribute, Comment, Document, DocumentFragment, DocumentType,
+        Element, Namespace, ProcessingInstruction,
+    },
+    tokenizer::TagName,
+};
+
+/// Convert a node to its HTML representation
+pub trait ToHtml {
+    /// Write the HTML serialization of this node to `w`
+    ///
+    /// # Errors
+    /// Fails if writing to `w` fails
+    fn write_html<W: Write>(&self, w: &mut W) -> fmt::Result;
+
+    #[must_use]
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        // Writing to a String never fails
+        let _ = self.write_html(&mut html);
+        html
+    }
+}
+
+/// A `Document` serializes as its children, ie. the whole document
+impl ToHtml for Document {
+    fn write_html<W: Write>(&self, w: &mut W) -> fmt::Result {
+        for child in &self.first_children {
+            write_document_child(w, child)?;
+        }
+        if let Some(ref document_type) = self.document_type {
+            write_document_type(w, document_type)?;
+        }
+        for child in &self.second_children {
+            write_document_child(w, child)?;
+        }
+        if let Some(ref element) = self.element {
+            element.borrow().write_html(w)?;
+        }
+        for child in &self.third_children {
+            write_document_child(w, child)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `DocumentFragment` serializes as its children
+impl ToHtml for DocumentFragment {
+    fn write_html<W: Write>(&self, w: &mut W) -> fmt::Result {
+        write_children(w, self.children(), false)
+    }
+}
+
+/// An `Element` serializes as itself, including its start and end tags (ie. `outerHTML`)
+impl ToHtml for Element {
+    fn write_html<W: Write>(&self, w: &mut W) -> fmt::Result {
+        let tag_name = self.local_name();
+
+        write!(w, "<{}", tag_name)?;
+        for attribute in self.attributes() {
+            write!(w, " {}=\"", serialized_attribute_name(attribute))?;
+            write_escaped(w, attribute.value(), true)?;
+            w.write_char('"')?;
+        }
+        w.write_char('>')?;
+
+        if is_void(self) {
+            return Ok(());
+        }
+
+        write_inner_html(w, self)?;
+        write!(w, "</{}>", tag_name)
+    }
+}
+
+impl Element {
+    /// The HTML serialization of this element's children (ie. `innerHTML`)
+    #[must_use]
+    pub fn inner_html(&self) -> String {
+        let mut html = String::new();
+        // Writing to a String never fails
+        let _ = write_inner_html(&mut html, self);
+        html
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments>
+fn write_inner_html<W: Write>(w: &mut W, node: &Element) -> fmt::Result {
+    if is_void(node) {
+        return Ok(());
+    }
+    if let Some(contents) = node.template_contents() {
+        return write_children(w, contents.borrow().children(), false);
+    }
+
+    write_children(w, node, is_raw_text(node))
+}
+
+fn write_children<W: Write>(w: &mut W, children: &[ChildNode], raw_text: bool) -> fmt::Result {
+    for child in children {
+        match child {
+            ChildNode::Element(element) => element.borrow().write_html(w)?,
+            ChildNode::Text(text) if raw_text => w.write_str(&text.borrow())?,
+            ChildNode::Text(text) => write_escaped(w, &text.borrow(), false)?,
+            ChildNode::ProcessingInstruction(pi) => write_processing_instruction(w, &pi.borrow())?,
+            ChildNode::Comment(comment) => write_comment(w, &comment.borrow())?,
+        }
+    }
+    Ok(())
+}
+
+fn write_document_child<W: Write>(w: &mut W, child: &document::ChildNode) -> fmt::Result {
+    match child {
+        document::ChildNode::ProcessingInstruction(pi) => write_processing_instruction(w, pi),
+        document::ChildNode::Comment(comment) => write_comment(w, comment),
+    }
+}
+
+fn write_document_type<W: Write>(w: &mut W, document_type: &DocumentType) -> fmt::Result {
+    write!(w, "<!DOCTYPE {}>", document_type.name())
+}
+
+fn write_comment<W: Write>(w: &mut W, comment: &Comment) -> fmt::Result {
+    write!(w, "<!--{}-->", comment.data())
+}
+
+fn write_processing_instruction<W: Write>(w: &mut W, pi: &ProcessingInstruction) -> fmt::Result {
+    write!(w, "<?{}>", pi.data())
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#escapingString>
+fn write_escaped<W: Write>(w: &mut W, s: &str, attribute_mode: bool) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '&' => w.write_str("&amp;")?,
+            '\u{A0}' => w.write_str("&nbsp;")?,
+            '"' if attribute_mode => w.write_str("&quot;")?,
+            '<' if !attribute_mode => w.write_str("&lt;")?,
+            '>' if !attribute_mode => w.write_str("&gt;")?,
+            c => w.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#attribute's-serialised-name>
+fn serialized_attribute_name(attribute: &Attribute) -> String {
+    match attribute.namespace() {
+        None => attribute.name().to_string(),
+        Some(Namespace::XML) => format!("xml:{}", attribute.name()),
+        Some(Namespace::XMLNS) if attribute.name() == "xmlns" => "xmlns".to_string(),
+        Some(Namespace::XMLNS) => format!("xmlns:{}", attribute.name()),
+        Some(Namespace::XLink) => format!("xlink:{}", attribute.name()),
+        Some(_) => attribute.qualified_name(),
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#serializes-as-void>
+fn is_void(element: &Element) -> bool {
+    element.is_html()
+        && matches!(
+            element.name(),
+            TagName::Area
+                | TagName::Base
+                | TagName::Basefont
+                | TagName::Bgsound
+                | TagName::Br
+                | TagName::Col
+                | TagName::Embed
+                | TagName::Frame
+                | TagName::Hr
+                | TagName::Img
+                | TagName::Input
+                | TagName::Keygen
+                | TagName::Link
+                | TagName::Meta
+                | TagName::Param
+                | TagName::Source
+                | TagName::Track
+                | TagName::Wbr
+        )
+}
+
+/// Children of these elements are serialized without escaping
+///
+/// `noscript` is only included when scripting is enabled, which the parser doesn't yet support.
+fn is_raw_text(element: &Element) -> bool {
+    element.is_html()
+        && matches!(
+            element.name(),
+            TagName::Style
+                | TagName::Script
+                | TagName::Xmp
+                | TagName::Iframe
+                | TagName::Noembed
+                | TagName::Noframes
+                | TagName::Plaintext
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{dom::Text, Parser};
+
+    #[test]
+    fn escaping() {
+        let div = Element::new(
+            TagName::Div,
+            Namespace::HTML,
+            vec![Attribute::new("title", "a \"b\" <c> & d")],
+        );
+        div.borrow_mut()
+            .push(Text::new("<b> & \u{A0}\"".to_string()).into());
+
+        assert_eq!(
+            div.borrow().to_html(),
+            "<div title=\"a &quot;b&quot; <c> &amp; d\">&lt;b&gt; &amp; &nbsp;\"</div>"
+        );
+        assert_eq!(div.borrow().inner_html(), "&lt;b&gt; &amp; &nbsp;\"");
+    }
+
+    #[test]
+    fn void_and_raw_text_elements() {
+        let body = Element::new_html(TagName::Body);
+        let br = Element::new_html(TagName::Br);
+        br.borrow_mut()
+            .push(Text::new("ignored".to_string()).into());
+        let script = Element::new_html(TagName::Script);
+        script
+            .borrow_mut()
+            .push(Text::new("if (a < b && c) {}".to_string()).into());
+        body.borrow_mut().push(br.into());
+        body.borrow_mut().push(script.into());
+
+        assert_eq!(
+            body.borrow().to_html(),
+            "<body><br><script>if (a < b && c) {}</script></body>"
+        );
+    }
+
+    #[test]
+    fn foreign_attributes() {
+        let svg = Element::new(
+            TagName::Svg,
+            Namespace::SVG,
+            vec![
+                Attribute::new("viewBox", "0 0 1 1"),
+                Attribute::new_ns(Some(Namespace::XLink), Some("xlink".into()), "href", "#a"),
+                Attribute::new_ns(
+                    Some(Namespace::XMLNS),
+                    None,
+                    "xmlns",
+                    "http://www.w3.org/2000/svg",
+                ),
+            ],
+        );
+
+        assert_eq!(
+            svg.borrow().to_html(),
+            "<svg viewBox=\"0 0 1 1\" xlink:href=\"#a\" xmlns=\"http://www.w3.org/2000/svg\"></svg>"
+        );
+    }
+
+    #[test]
+    fn document_round_trip() {
+        let document = Parser::new(Cursor::new(
+            "<!DOCTYPE html><html><head><title>a &amp; b</title></head>\
+             <body><p class=\"x\">foo<span>bar</span></p><svg><foreignObject></foreignObject></svg></body></html>",
+        ))
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            document.to_html(),
+            "<!DOCTYPE html><html><head><title>a &amp; b</title></head>\
+             <body><p class=\"x\">foo<span>bar</span></p><svg><foreignObject></foreignObject></svg></body></html>",
+        );
+    }
+
+    #[test]
+    fn comments_round_trip() {
+        let html = "<!-- license --><!DOCTYPE html><!--[if IE]><p>IE<![endif]-->\
+                    <html><head><!--a--></head><body><!--b--></body><!--c--></html><!--d-->";
+        let document = Parser::new(Cursor::new(html)).parse().unwrap();
+
+        assert_eq!(document.to_html(), html);
+    }
+}
@@ -0,0 +1,25 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::tokenizer::Span;
+
+/// Where a node was in the input it was parsed from
+///
+/// Two nodes with the same contents are equal wherever they came from, so the span is ignored
+/// when comparing or hashing nodes.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub(crate) struct SourceSpan(pub(crate) Option<Span>);
+
+impl PartialEq for SourceSpan {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SourceSpan {}
+
+impl Hash for SourceSpan {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
@@ -0,0 +1,261 @@
+//! The tree format used by the html5lib tree construction tests
+//!
+//! <https://github.com/html5lib/html5lib-tests/tree/master/tree-construction>
+use std::fmt::{self, Write};
+
+use crate::dom::{
+    arena::{NodeData, NodeId, Tree},
+    document,
+    element::ChildNode,
+    Attribute, Document, DocumentFragment, DocumentType, Element, Namespace,
+};
+
+/// Dump a node as the `#document` section of an html5lib tree construction test
+pub trait ToTestTree {
+    /// Write the children of this node to `w`, one line per node and attribute
+    ///
+    /// # Errors
+    /// Fails if writing to `w` fails
+    fn write_test_tree<W: Write>(&self, w: &mut W) -> fmt::Result;
+
+    #[must_use]
+    fn to_test_tree(&self) -> String {
+        let mut tree = String::new();
+        // Writing to a String never fails
+        let _ = self.write_test_tree(&mut tree);
+        tree
+    }
+}
+
+impl ToTestTree for Document {
+    fn write_test_tree<W: Write>(&self, w: &mut W) -> fmt::Result {
+        for child in &self.first_children {
+            write_document_child(w, child)?;
+        }
+        if let Some(ref document_type) = self.document_type {
+            write_document_type(w, document_type)?;
+        }
+        for child in &self.second_children {
+            write_document_child(w, child)?;
+        }
+        if let Some(ref element) = self.element {
+            write_element(w, &element.borrow(), 0)?;
+        }
+        for child in &self.third_children {
+            write_document_child(w, child)?;
+        }
+        Ok(())
+    }
+}
+
+impl ToTestTree for DocumentFragment {
+    fn write_test_tree<W: Write>(&self, w: &mut W) -> fmt::Result {
+        write_children(w, self.children(), 0)
+    }
+}
+
+impl ToTestTree for Tree {
+    fn write_test_tree<W: Write>(&self, w: &mut W) -> fmt::Result {
+        for child in self.children(self.document()) {
+            write_tree_node(w, self, child, 0)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_tree_node<W: Write>(w: &mut W, tree: &Tree, id: NodeId, depth: usize) -> fmt::Result {
+    match tree.data(id) {
+        NodeData::Document => {}
+        NodeData::DocumentType(document_type) => write_document_type(w, document_type)?,
+        NodeData::Element(element) => write_element_start(
+            w,
+            &element.local_name(),
+            element.namespace(),
+            element.attributes(),
+            depth,
+        )?,
+        NodeData::DocumentFragment => {}
+        NodeData::Text(text) => write_text(w, text, depth)?,
+        NodeData::ProcessingInstruction(pi) => write_processing_instruction(w, pi, depth)?,
+        NodeData::Comment(comment) => write_comment(w, comment, depth)?,
+    }
+    if let Some(contents) = tree.template_contents(id) {
+        write_content_line(w, depth + 1)?;
+        for child in tree.children(contents) {
+            write_tree_node(w, tree, child, depth + 2)?;
+        }
+    }
+    for child in tree.children(id) {
+        write_tree_node(w, tree, child, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn write_line_start<W: Write>(w: &mut W, depth: usize) -> fmt::Result {
+    write!(w, "| {:1$}", "", depth * 2)
+}
+
+fn write_document_child<W: Write>(w: &mut W, child: &document::ChildNode) -> fmt::Result {
+    match child {
+        document::ChildNode::ProcessingInstruction(pi) => {
+            write_processing_instruction(w, pi.data(), 0)
+        }
+        document::ChildNode::Comment(comment) => write_comment(w, comment.data(), 0),
+    }
+}
+
+fn write_document_type<W: Write>(w: &mut W, document_type: &DocumentType) -> fmt::Result {
+    write_line_start(w, 0)?;
+    if document_type.public_id().is_empty() && document_type.system_id().is_empty() {
+        writeln!(w, "<!DOCTYPE {}>", document_type.name())
+    } else {
+        writeln!(
+            w,
+            "<!DOCTYPE {} \"{}\" \"{}\">",
+            document_type.name(),
+            document_type.public_id(),
+            document_type.system_id()
+        )
+    }
+}
+
+fn write_element<W: Write>(w: &mut W, element: &Element, depth: usize) -> fmt::Result {
+    write_element_start(
+        w,
+        &element.local_name(),
+        element.namespace(),
+        element.attributes(),
+        depth,
+    )?;
+
+    if let Some(contents) = element.template_contents() {
+        write_content_line(w, depth + 1)?;
+        write_children(w, contents.borrow().children(), depth + 2)?;
+    }
+    write_children(w, element, depth + 1)
+}
+
+fn write_children<W: Write>(w: &mut W, children: &[ChildNode], depth: usize) -> fmt::Result {
+    for child in children {
+        match child {
+            ChildNode::Element(e) => write_element(w, &e.borrow(), depth)?,
+            ChildNode::Text(t) => write_text(w, &t.borrow(), depth)?,
+            ChildNode::ProcessingInstruction(pi) => {
+                write_processing_instruction(w, pi.borrow().data(), depth)?
+            }
+            ChildNode::Comment(c) => write_comment(w, c.borrow().data(), depth)?,
+        }
+    }
+    Ok(())
+}
+
+/// Template contents are written under a `content` line
+fn write_content_line<W: Write>(w: &mut W, depth: usize) -> fmt::Result {
+    write_line_start(w, depth)?;
+    writeln!(w, "content")
+}
+
+/// The element's name and its attributes, sorted by name
+fn write_element_start<W: Write>(
+    w: &mut W,
+    local_name: &str,
+    namespace: Namespace,
+    attributes: &[Attribute],
+    depth: usize,
+) -> fmt::Result {
+    write_line_start(w, depth)?;
+    match namespace {
+        Namespace::SVG => writeln!(w, "<svg {}>", local_name)?,
+        Namespace::MathML => writeln!(w, "<math {}>", local_name)?,
+        _ => writeln!(w, "<{}>", local_name)?,
+    }
+
+    let mut attributes: Vec<(String, &str)> = attributes
+        .iter()
+        .map(|a| (attribute_name(a), a.value()))
+        .collect();
+    attributes.sort();
+    for (name, value) in attributes {
+        write_line_start(w, depth + 1)?;
+        writeln!(w, "{}=\"{}\"", name, value)?;
+    }
+    Ok(())
+}
+
+/// Namespaced attributes are written as their prefix and local name, eg. `xlink href`
+fn attribute_name(attribute: &Attribute) -> String {
+    match attribute.namespace() {
+        Some(Namespace::XML) => format!("xml {}", attribute.name()),
+        Some(Namespace::XMLNS) => format!("xmlns {}", attribute.name()),
+        Some(Namespace::XLink) => format!("xlink {}", attribute.name()),
+        _ => attribute.name().to_string(),
+    }
+}
+
+fn write_text<W: Write>(w: &mut W, text: &str, depth: usize) -> fmt::Result {
+    write_line_start(w, depth)?;
+    writeln!(w, "\"{}\"", text)
+}
+
+fn write_comment<W: Write>(w: &mut W, data: &str, depth: usize) -> fmt::Result {
+    write_line_start(w, depth)?;
+    writeln!(w, "<!-- {} -->", data)
+}
+
+fn write_processing_instruction<W: Write>(w: &mut W, data: &str, depth: usize) -> fmt::Result {
+    write_line_start(w, depth)?;
+    writeln!(w, "<?{}>", data)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::Parser;
+
+    #[test]
+    fn document_tree() {
+        let document = Parser::new(Cursor::new(
+            "<!DOCTYPE html><html><head></head><body><p class=b id=a>x</p>\
+             <svg viewbox=\"0 0 1 1\" xlink:href=y><foreignObject/></svg></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        assert_eq!(
+            document.to_test_tree(),
+            "| <!DOCTYPE html>\n\
+             | <html>\n\
+             |   <head>\n\
+             |   <body>\n\
+             |     <p>\n\
+             |       class=\"b\"\n\
+             |       id=\"a\"\n\
+             |       \"x\"\n\
+             |     <svg svg>\n\
+             |       viewBox=\"0 0 1 1\"\n\
+             |       xlink href=\"y\"\n\
+             |       <svg foreignObject>\n"
+        );
+    }
+
+    #[test]
+    fn template_contents() {
+        let document = Parser::new(Cursor::new(
+            "<html><head><template id=t><p>x</template></head><body></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        assert_eq!(
+            document.to_test_tree(),
+            "| <html>\n\
+             |   <head>\n\
+             |     <template>\n\
+             |       id=\"t\"\n\
+             |       content\n\
+             |         <p>\n\
+             |           \"x\"\n\
+             |   <body>\n"
+        );
+    }
+}
@@ -1,19 +1,90 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use derive_more::{Deref, DerefMut, From};
+use derive_more::{Deref, DerefMut};
 use serde::{Deserialize, Serialize};
 
-#[derive(
-    Clone, Debug, Default, Deserialize, Eq, From, Hash, PartialEq, Serialize, Deref, DerefMut,
-)]
+use crate::{
+    dom::{
+        element::{ChildNode, ParentNode},
+        ParentLink, SourceSpan,
+    },
+    tokenizer::Span,
+};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize, Deref, DerefMut)]
 pub struct Text {
+    #[deref]
+    #[deref_mut]
     data: String,
+    #[serde(default)]
+    span: SourceSpan,
+    #[serde(skip)]
+    pub(crate) parent: ParentLink,
 }
 
 impl Text {
     #[must_use]
     pub fn new(data: String) -> Rc<RefCell<Self>> {
-        Rc::new(RefCell::new(Text { data }))
+        Rc::new(RefCell::new(Text::from(data)))
+    }
+
+    /// Text parsed from the part of the input covered by `span`
+    #[must_use]
+    pub fn new_with_span(data: String, span: Span) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Text {
+            data,
+            span: SourceSpan(Some(span)),
+            parent: ParentLink::default(),
+        }))
+    }
+
+    /// Where the text was in the input, if it was parsed
+    ///
+    /// Text from character references or from separate parts of the input is merged into one
+    /// node, and the span covers all of it.
+    #[must_use]
+    pub fn span(&self) -> Option<Span> {
+        self.span.0
+    }
+
+    /// Append `data`, which was parsed from `span`
+    pub fn push_str_with_span(&mut self, data: &str, span: Span) {
+        self.data.push_str(data);
+        self.span.0 = Some(self.span.0.map_or(span, |s| s.union(span)));
+    }
+
+    /// The parent of this text
+    #[must_use]
+    pub fn parent_node(&self) -> Option<ParentNode> {
+        self.parent.parent()
+    }
+
+    /// The node after this text in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn next_sibling(&self) -> Option<ChildNode> {
+        self.parent.next_sibling((self as *const Self).cast())
+    }
+
+    /// The node before this text in its parent's children
+    ///
+    /// # Panics
+    /// If the parent is mutably borrowed
+    #[must_use]
+    pub fn previous_sibling(&self) -> Option<ChildNode> {
+        self.parent.previous_sibling((self as *const Self).cast())
+    }
+}
+
+impl From<String> for Text {
+    fn from(data: String) -> Self {
+        Text {
+            data,
+            span: SourceSpan::default(),
+            parent: ParentLink::default(),
+        }
     }
 }
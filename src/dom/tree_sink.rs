@@ -0,0 +1,204 @@
+//! Building a `Document` with the parser
+use std::{cell::RefCell, mem, rc::Rc};
+
+use crate::{
+    dom::{
+        element::{ChildNode, ParentNode},
+        Attribute, Comment, Document, DocumentFragment, DocumentType, Element, Namespace,
+        QuirksMode, ScriptFlags, Text,
+    },
+    parser::{NodeOrText, TreeSink},
+    tokenizer::{Span, TagName},
+};
+
+/// A node of a `Document` which is being built by the parser
+#[derive(Clone, Debug)]
+pub enum Handle {
+    Document,
+    Element(Rc<RefCell<Element>>),
+    Comment(Rc<RefCell<Comment>>),
+    /// The contents of a `template` element
+    DocumentFragment(Rc<RefCell<DocumentFragment>>),
+}
+
+impl Handle {
+    /// The node as a child of an element
+    ///
+    /// # Panics
+    /// If this is the document
+    fn into_child_node(self) -> ChildNode {
+        match self {
+            Handle::Document | Handle::DocumentFragment(_) => {
+                panic!("{:?} can't be the child of an element", self)
+            }
+            Handle::Element(e) => e.into(),
+            Handle::Comment(c) => c.into(),
+        }
+    }
+
+    /// # Panics
+    /// If this isn't an element
+    fn element(&self) -> &Rc<RefCell<Element>> {
+        match self {
+            Handle::Element(e) => e,
+            _ => panic!("Expected an element, got {:?}", self),
+        }
+    }
+
+    /// # Panics
+    /// If this isn't an element or a document fragment
+    fn parent_node(&self) -> ParentNode {
+        match self {
+            Handle::Element(e) => Rc::clone(e).into(),
+            Handle::DocumentFragment(f) => Rc::clone(f).into(),
+            _ => panic!("Expected an element or a document fragment, got {:?}", self),
+        }
+    }
+}
+
+impl TreeSink for Document {
+    type Handle = Handle;
+    type Output = Self;
+
+    fn finish(self) -> Self {
+        self
+    }
+
+    fn restart(&mut self) {
+        *self = Document::default();
+    }
+
+    fn document(&self) -> Handle {
+        Handle::Document
+    }
+
+    fn create_element(
+        &mut self,
+        name: TagName,
+        namespace: Namespace,
+        attributes: Vec<Attribute>,
+    ) -> Handle {
+        Handle::Element(Element::new(name, namespace, attributes))
+    }
+
+    fn create_comment(&mut self, data: String) -> Handle {
+        Handle::Comment(Rc::new(RefCell::new(Comment::new(data))))
+    }
+
+    fn set_source_span(&mut self, node: &Handle, span: Span) {
+        match node {
+            Handle::Element(e) => e.borrow_mut().set_span(Some(span)),
+            Handle::Comment(c) => c.borrow_mut().set_span(span),
+            Handle::Document | Handle::DocumentFragment(_) => {}
+        }
+    }
+
+    fn append(&mut self, parent: &Handle, child: NodeOrText<Handle>) {
+        let parent = match parent {
+            Handle::Document => {
+                return match child {
+                    NodeOrText::Node(Handle::Element(e)) => self.push_element(e),
+                    NodeOrText::Node(Handle::Comment(c)) => self.push_comment(c.borrow().clone()),
+                    NodeOrText::Node(Handle::Document)
+                    | NodeOrText::Node(Handle::DocumentFragment(_))
+                    | NodeOrText::Text(..) => {
+                        panic!("Only elements and comments can be children of the document")
+                    }
+                };
+            }
+            parent => parent.parent_node(),
+        };
+
+        match child {
+            NodeOrText::Node(node) => parent.append_child(node.into_child_node()),
+            NodeOrText::Text(data, span) => {
+                if let Some(ChildNode::Text(text)) = parent.children().last() {
+                    return text.borrow_mut().push_str_with_span(&data, span);
+                }
+                parent.append_child(Text::new_with_span(data, span).into());
+            }
+        }
+    }
+
+    fn append_before_sibling(&mut self, sibling: &Handle, child: NodeOrText<Handle>) {
+        let (parent, index) = match sibling.element().borrow().locate() {
+            Some(location) => location,
+            None => return,
+        };
+
+        match child {
+            NodeOrText::Node(node) => parent.insert_child(index, node.into_child_node()),
+            NodeOrText::Text(data, span) => {
+                if let Some(ChildNode::Text(text)) = index
+                    .checked_sub(1)
+                    .and_then(|i| parent.children().get(i).cloned())
+                {
+                    return text.borrow_mut().push_str_with_span(&data, span);
+                }
+                parent.insert_child(index, Text::new_with_span(data, span).into());
+            }
+        }
+    }
+
+    fn append_based_on_parent_node(
+        &mut self,
+        element: &Handle,
+        prev_element: &Handle,
+        child: NodeOrText<Handle>,
+    ) {
+        if element.element().borrow().parent_node().is_some() {
+            self.append_before_sibling(element, child);
+        } else {
+            self.append(prev_element, child);
+        }
+    }
+
+    fn append_doctype_to_document(&mut self, document_type: DocumentType) {
+        self.add_document_type(document_type);
+    }
+
+    fn get_attribute(&self, element: &Handle, qualified_name: &str) -> Option<String> {
+        element
+            .element()
+            .borrow()
+            .get_attribute(qualified_name)
+            .map(String::from)
+    }
+
+    fn add_attributes_if_missing(&mut self, target: &Handle, attributes: Vec<Attribute>) {
+        let mut target = target.element().borrow_mut();
+        for attribute in attributes {
+            let _ = target.add_attribute_if_missing(attribute);
+        }
+    }
+
+    fn remove_from_parent(&mut self, target: &Handle) {
+        match target {
+            Handle::Element(_) | Handle::Comment(_) => {
+                let _ = target.clone().into_child_node().detach();
+            }
+            Handle::Document | Handle::DocumentFragment(_) => {}
+        }
+    }
+
+    fn reparent_children(&mut self, node: &Handle, new_parent: &Handle) {
+        let new_parent = new_parent.parent_node();
+        let children = mem::take(&mut *node.parent_node().children_mut());
+        for child in children {
+            new_parent.append_child(child);
+        }
+    }
+
+    fn template_contents(&mut self, template: &Handle) -> Handle {
+        let contents = template.element().borrow().template_contents().cloned();
+        Handle::DocumentFragment(contents.expect("Expected a template element"))
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.set_mode(mode);
+    }
+
+    fn set_script_flags(&mut self, script: &Handle, flags: ScriptFlags) {
+        script.element().borrow_mut().set_script_flags(flags);
+    }
+}
@@ -60,6 +60,17 @@ pub fn get() -> &'static Encodings {
     })
 }
 
+/// <https://encoding.spec.whatwg.org/#concept-encoding-get>
+///
+/// Look up the `encoding_rs` encoding for a label, to decode input with
+#[must_use]
+pub fn get_decoder(label: &str) -> Option<&'static encoding_rs::Encoding> {
+    get_encoding(label).map(|e| {
+        // The replacement encoding's name isn't one of its labels
+        encoding_rs::Encoding::for_label(e.name.as_bytes()).unwrap_or(encoding_rs::REPLACEMENT)
+    })
+}
+
 #[must_use]
 pub fn get_encoding(name: &str) -> Option<&'static Encoding> {
     let encodings = get();
@@ -69,3 +80,18 @@ pub fn get_encoding(name: &str) -> Option<&'static Encoding> {
             .any(|l| l.eq_ignore_ascii_case(&name.trim()))
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn every_encoding_has_a_decoder() {
+        for encoding in get() {
+            for label in &encoding.labels {
+                let decoder = get_decoder(label).unwrap();
+                assert_eq!(decoder.name(), encoding.name);
+            }
+        }
+    }
+}
@@ -3,13 +3,18 @@ use std::{error, fmt};
 use auto_enums::enum_derive;
 use derive_more::From;
 
-use crate::parser::States;
+use crate::{parser::States, tokenizer};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[enum_derive(Error, From)]
 pub enum Error {
+    IO(std::io::Error),
+    // Boxed, as a tokenizer error can hold a tokenizer state
+    Tokenizer(Box<tokenizer::errors::Error>),
     StateTransition(StateTransitionError),
+    Unimplemented(UnimplementedError),
+    Invariant(InvariantError),
 }
 
 #[derive(Debug)]
@@ -36,3 +41,48 @@ impl fmt::Display for StateTransitionError {
         )
     }
 }
+
+/// Returned when something the specification guarantees about the parser's state doesn't hold,
+/// which is a bug in the parser
+#[derive(Debug)]
+pub struct InvariantError(&'static str);
+
+impl InvariantError {
+    pub(super) fn new(what: &'static str) -> Self {
+        InvariantError(what)
+    }
+}
+
+impl error::Error for InvariantError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InvariantError: {}", self.0)
+    }
+}
+
+/// Returned when the parser reaches a part of the specification which hasn't been implemented yet
+#[derive(Debug)]
+pub struct UnimplementedError(&'static str);
+
+impl UnimplementedError {
+    pub(super) fn new(what: &'static str) -> Self {
+        UnimplementedError(what)
+    }
+}
+
+impl error::Error for UnimplementedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+impl fmt::Display for UnimplementedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UnimplementedError: '{}' is not yet implemented", self.0)
+    }
+}
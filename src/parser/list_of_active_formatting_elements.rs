@@ -1,21 +1,35 @@
-use std::{cell::RefCell, rc::Rc};
+use std::rc::Rc;
 
-use derive_more::{Deref, DerefMut, From};
+use derive_more::{Deref, DerefMut};
 
-use crate::{dom, tokenizer::TagName};
+use crate::{
+    dom,
+    parser::tree_element::TreeElement,
+    tokenizer::{Span, TagName},
+};
 
-#[derive(Debug, Default, PartialEq, Eq, Deref, DerefMut)]
-pub(super) struct ListOfActiveFormattingElements {
-    list: Vec<ActiveFormattingElementOrMarker>,
+#[derive(Debug, Deref, DerefMut)]
+pub(super) struct ListOfActiveFormattingElements<H> {
+    list: Vec<ActiveFormattingElementOrMarker<H>>,
 }
 
-#[derive(Debug, PartialEq, Eq, From)]
-pub(super) enum ActiveFormattingElementOrMarker {
-    ActiveFormattingElement(Rc<RefCell<dom::Element>>),
+#[derive(Debug)]
+pub(super) enum ActiveFormattingElementOrMarker<H> {
+    ActiveFormattingElement(Rc<TreeElement<H>>, Rc<FormattingToken>),
     Marker,
 }
 
-impl ListOfActiveFormattingElements {
+/// What's needed from the start tag token an active formatting element was created for, to
+/// create another element for the same token
+///
+/// The name and namespace are the element's own.
+#[derive(Debug)]
+pub(super) struct FormattingToken {
+    pub(super) attributes: Vec<dom::Attribute>,
+    pub(super) span: Option<Span>,
+}
+
+impl<H> ListOfActiveFormattingElements<H> {
     pub(crate) fn new() -> Self {
         ListOfActiveFormattingElements { list: Vec::new() }
     }
@@ -24,6 +38,75 @@ impl ListOfActiveFormattingElements {
         self.list.push(ActiveFormattingElementOrMarker::Marker)
     }
 
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker>
+    pub(crate) fn clear_up_to_last_marker(&mut self) {
+        while let Some(entry) = self.list.pop() {
+            if entry.is_marker() {
+                break;
+            }
+        }
+    }
+
+    /// Push an element onto the list, applying the Noah's Ark clause
+    ///
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#push-onto-the-list-of-active-formatting-elements>
+    pub(crate) fn push_element(&mut self, elem: Rc<TreeElement<H>>, token: FormattingToken) {
+        // If there are already three elements after the last marker with the same tag name,
+        // namespace and attributes, remove the earliest of them
+        let same_elements: Vec<usize> = self
+            .list
+            .iter()
+            .enumerate()
+            .rev()
+            .take_while(|(_, e)| !e.is_marker())
+            .filter_map(|(i, e)| match e {
+                ActiveFormattingElementOrMarker::ActiveFormattingElement(e, t)
+                    if is_same_element((e, t), (&elem, &token)) =>
+                {
+                    Some(i)
+                }
+                _ => None,
+            })
+            .collect();
+        if same_elements.len() >= 3 {
+            let _ = self.list.remove(same_elements[same_elements.len() - 1]);
+        }
+
+        self.list
+            .push(ActiveFormattingElementOrMarker::ActiveFormattingElement(
+                elem,
+                Rc::new(token),
+            ))
+    }
+
+    /// The last element with the given `name` after the last marker
+    pub(crate) fn last_element_before_marker(&self, name: &TagName) -> Option<Rc<TreeElement<H>>> {
+        self.list
+            .iter()
+            .rev()
+            .take_while(|e| !e.is_marker())
+            .find_map(|e| match e {
+                ActiveFormattingElementOrMarker::ActiveFormattingElement(e, _)
+                    if e.is_html() && e.name() == name =>
+                {
+                    Some(Rc::clone(e))
+                }
+                _ => None,
+            })
+    }
+
+    /// The index of `elem` in the list
+    pub(crate) fn position_of(&self, elem: &Rc<TreeElement<H>>) -> Option<usize> {
+        self.list.iter().position(|e| e.is(elem))
+    }
+
+    /// Remove `elem` from the list, if it's there
+    pub(crate) fn remove_element(&mut self, elem: &Rc<TreeElement<H>>) {
+        if let Some(i) = self.position_of(elem) {
+            let _ = self.list.remove(i);
+        }
+    }
+
     pub(crate) fn contains_element(&self, name: &TagName) -> bool {
         self.list
             .iter()
@@ -34,20 +117,40 @@ impl ListOfActiveFormattingElements {
     }
 }
 
-impl ActiveFormattingElementOrMarker {
+impl<H> ActiveFormattingElementOrMarker<H> {
     pub(crate) fn is_element(&self, name: &TagName) -> bool {
         match self {
             ActiveFormattingElementOrMarker::Marker => false,
-            ActiveFormattingElementOrMarker::ActiveFormattingElement(e) => {
-                e.borrow().name() == name
-            }
+            ActiveFormattingElementOrMarker::ActiveFormattingElement(e, _) => e.name() == name,
+        }
+    }
+
+    /// Whether this entry is `elem` itself, rather than an equal element
+    pub(crate) fn is(&self, elem: &Rc<TreeElement<H>>) -> bool {
+        match self {
+            ActiveFormattingElementOrMarker::Marker => false,
+            ActiveFormattingElementOrMarker::ActiveFormattingElement(e, _) => Rc::ptr_eq(e, elem),
         }
     }
 
     pub(crate) fn is_marker(&self) -> bool {
         match self {
             ActiveFormattingElementOrMarker::Marker => true,
-            ActiveFormattingElementOrMarker::ActiveFormattingElement(_) => false,
+            ActiveFormattingElementOrMarker::ActiveFormattingElement(..) => false,
         }
     }
 }
+
+/// Elements which are the same for the Noah's Ark clause, ignoring the order of their attributes
+fn is_same_element<H>(
+    (a, a_token): (&TreeElement<H>, &FormattingToken),
+    (b, b_token): (&TreeElement<H>, &FormattingToken),
+) -> bool {
+    a.name() == b.name()
+        && a.namespace() == b.namespace()
+        && a_token.attributes.len() == b_token.attributes.len()
+        && a_token
+            .attributes
+            .iter()
+            .all(|attr| b_token.attributes.contains(attr))
+}
@@ -1,19 +1,22 @@
-use log::error;
-
 pub mod errors;
 mod list_of_active_formatting_elements;
 mod open_elements_stack;
 mod parser_struct;
+pub mod script_handler;
 mod states;
 mod transition_result;
 mod transitions;
+mod tree_element;
 pub mod encodings;
+pub mod tree_sink;
 
 use list_of_active_formatting_elements::ListOfActiveFormattingElements;
 use open_elements_stack::OpenElementsStack;
 pub use parser_struct::Parser;
+pub use script_handler::{DocumentWrite, ScriptHandler};
 use states::States;
 use transition_result::TransitionResult;
+pub use tree_sink::{NodeOrText, TreeSink};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 enum ScriptingFlag {
@@ -27,7 +30,8 @@ enum FramesetOkFlag {
     NotOk,
 }
 
-// TODO How to report parser errors?
-fn parse_error(msg: &str) {
-    error!("Parse Error: {}", msg);
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum FosterParentingFlag {
+    Enabled,
+    Disabled,
 }
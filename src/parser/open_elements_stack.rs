@@ -1,16 +1,16 @@
-use std::{cell::RefCell, rc::Rc};
+use std::rc::Rc;
 
 use derive_more::{Deref, DerefMut};
 use log::trace;
 
-use crate::{dom, tokenizer::TagName};
+use crate::{dom, parser::tree_element::TreeElement, tokenizer::TagName};
 
-#[derive(Debug, Default, PartialEq, Eq, Deref, DerefMut)]
-pub(super) struct OpenElementsStack {
-    stack: Vec<Rc<RefCell<dom::Element>>>,
+#[derive(Debug, Deref, DerefMut)]
+pub(super) struct OpenElementsStack<H> {
+    stack: Vec<Rc<TreeElement<H>>>,
 }
 
-impl OpenElementsStack {
+impl<H> OpenElementsStack<H> {
     pub(crate) fn new() -> Self {
         OpenElementsStack { stack: Vec::new() }
     }
@@ -27,29 +27,65 @@ impl OpenElementsStack {
         self.stack
             .iter()
             .by_ref()
-            .any(|e| names.iter().any(|name| &e.borrow().name() == name))
+            .any(|e| names.iter().any(|name| &e.name() == name))
+    }
+
+    /// The index of `elem` in the stack
+    #[must_use]
+    pub(crate) fn position_of(&self, elem: &Rc<TreeElement<H>>) -> Option<usize> {
+        self.stack.iter().position(|e| Rc::ptr_eq(e, elem))
+    }
+
+    /// Remove `elem` from the stack, if it's there
+    pub(crate) fn remove_element(&mut self, elem: &Rc<TreeElement<H>>) {
+        if let Some(i) = self.position_of(elem) {
+            let _ = self.stack.remove(i);
+        }
     }
 
     /// Pop elements off the stack until one of `names` has been popped
     pub(crate) fn pop_until(&mut self, names: &[&TagName]) {
         while let Some(e) = self.pop() {
-            let elem = e.borrow();
-            if names.iter().any(|name| &&elem.name == name) {
+            if names.iter().any(|name| &&e.name == name) {
+                break;
+            }
+            trace!("InBody: Popped {:?} off stack", e.name());
+        }
+    }
+
+    /// Pop elements off the stack until the current node is an HTML element in `names`
+    ///
+    /// Used to clear the stack back to a table, table body or table row context
+    pub(crate) fn clear_back_to(&mut self, names: &[&TagName]) {
+        while let Some(e) = self.last() {
+            if e.is_html() && names.iter().any(|name| &&e.name == name) {
                 break;
             }
-            trace!("InBody: Popped {:?} off stack", elem);
+            trace!(
+                "Clearing back to {:?}: Popped {:?} off stack",
+                names,
+                e.name()
+            );
+            let _ = self.pop();
         }
     }
 
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope>
+    ///
+    /// `list` contains HTML elements, when `foreign` is set the MathML and SVG elements
+    /// of the default scope are also included.
     pub(crate) fn has_element_in_a_specific_scope(
         &self,
         target_node: &TagName,
         list: &[&TagName],
+        foreign: bool,
     ) -> bool {
         for node in self.iter().rev() {
-            if &node.borrow().name == target_node {
+            if node.is_html() && &node.name == target_node {
                 return true;
-            } else if list.iter().any(|name| &&node.borrow().name == name) {
+            } else if (node.is_html() && list.iter().any(|name| &&node.name == name))
+                || (foreign && is_foreign_scope_element(node))
+            {
                 return false;
             }
         }
@@ -69,18 +105,8 @@ impl OpenElementsStack {
                 &TagName::Marquee,
                 &TagName::Object,
                 &TagName::Template,
-                /* TODO: None HTML Elements:
-                MathML mi
-                MathML mo
-                MathML mn
-                MathML ms
-                MathML mtext
-                MathML annotation-xml
-                SVG foreignObject
-                SVG desc
-                SVG title
-                */
             ],
+            true,
         )
     }
 
@@ -104,20 +130,10 @@ impl OpenElementsStack {
                 &TagName::Marquee,
                 &TagName::Object,
                 &TagName::Template,
-                /* TODO: None HTML Elements:
-                MathML mi
-                MathML mo
-                MathML mn
-                MathML ms
-                MathML mtext
-                MathML annotation-xml
-                SVG foreignObject
-                SVG desc
-                SVG title
-                */
                 &TagName::Ol,
                 &TagName::Ul,
             ],
+            true,
         )
     }
 
@@ -134,19 +150,9 @@ impl OpenElementsStack {
                 &TagName::Marquee,
                 &TagName::Object,
                 &TagName::Template,
-                /* TODO: None HTML Elements:
-                MathML mi
-                MathML mo
-                MathML mn
-                MathML ms
-                MathML mtext
-                MathML annotation-xml
-                SVG foreignObject
-                SVG desc
-                SVG title
-                */
                 &TagName::Button,
             ],
+            true,
         )
     }
 
@@ -154,10 +160,37 @@ impl OpenElementsStack {
         self.has_element_in_a_specific_scope(
             target_node,
             &[&TagName::Html, &TagName::Table, &TagName::Template],
+            false,
         )
     }
 
+    /// Unlike the other scopes, every element except `optgroup` and `option` is a boundary of the
+    /// select scope
     pub(crate) fn has_a_particular_element_in_select_scope(&self, target_node: &TagName) -> bool {
-        self.has_element_in_a_specific_scope(target_node, &[&TagName::Optgroup, &TagName::Option])
+        for node in self.iter().rev() {
+            if node.is_html() && &node.name == target_node {
+                return true;
+            } else if !(node.is_html() && matches!(node.name, TagName::Optgroup | TagName::Option))
+            {
+                return false;
+            }
+        }
+        false
     }
 }
+
+/// The MathML and SVG elements which are part of the default scope
+fn is_foreign_scope_element<H>(elem: &TreeElement<H>) -> bool {
+    matches!(
+        (elem.namespace(), elem.name()),
+        (dom::Namespace::MathML, TagName::Mi)
+            | (dom::Namespace::MathML, TagName::Mo)
+            | (dom::Namespace::MathML, TagName::Mn)
+            | (dom::Namespace::MathML, TagName::Ms)
+            | (dom::Namespace::MathML, TagName::Mtext)
+            | (dom::Namespace::MathML, TagName::AnnotationXml)
+            | (dom::Namespace::SVG, TagName::ForeignObject)
+            | (dom::Namespace::SVG, TagName::Desc)
+            | (dom::Namespace::SVG, TagName::Title)
+    )
+}
@@ -1,66 +1,297 @@
-use std::{cell::RefCell, fmt, io::prelude::*, rc::Rc, str};
+use std::{
+    fmt,
+    io::{self, prelude::*},
+    rc::Rc,
+    str,
+};
 
-use log::{debug, trace};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use log::{debug, error, trace};
 
 use crate::{
-    dom::{self, Document},
+    dom::{self, Document, DocumentFragment},
     parser::{
-        states::States, FramesetOkFlag, ListOfActiveFormattingElements, OpenElementsStack,
-        ScriptingFlag, TransitionResult,
+        errors::Result,
+        list_of_active_formatting_elements::{ActiveFormattingElementOrMarker, FormattingToken},
+        states::States,
+        transitions::foreign_content,
+        tree_element::TreeElement,
+        DocumentWrite, FosterParentingFlag, FramesetOkFlag, ListOfActiveFormattingElements,
+        NodeOrText, OpenElementsStack, ScriptHandler, ScriptingFlag, TransitionResult, TreeSink,
+    },
+    tokenizer::{
+        errors::{self as tokenizer_errors, ParseError, PositionedParseError},
+        Confidence, Span, StartTag, TagName, Token, Tokenizer,
     },
-    tokenizer::{TagName, Token, Tokenizer},
 };
 
-pub struct Parser<R>
+/// An HTML parser, which builds its output with a `TreeSink`
+///
+/// By default the output is a `dom::Document`, see `Parser::with_sink` to build something else.
+pub struct Parser<R, S = Document>
 where
-    R: Read + Seek,
+    R: Read,
+    S: TreeSink,
 {
-    pub document: Document,
+    sink: S,
 
     tokenizer: Tokenizer<R>,
 
     insertion_mode: Option<States>,
     reprocess: bool,
     last_token: Option<Token>,
+    // Where the current token is in the input, and so where the nodes created for it are
+    pub(super) token_span: Span,
 
-    pub(super) open_elements: OpenElementsStack,
-    pub(super) list_of_active_formatting_elements: ListOfActiveFormattingElements,
+    pub(super) open_elements: OpenElementsStack<S::Handle>,
+    pub(super) list_of_active_formatting_elements: ListOfActiveFormattingElements<S::Handle>,
+    // The current template insertion mode is the last one
+    pub(super) template_insertion_modes: Vec<States>,
 
     // Element pointsers
-    head_element_pointer: Option<Rc<RefCell<dom::Element>>>,
+    pub(super) head_element_pointer: Option<Rc<TreeElement<S::Handle>>>,
+    pub(super) form_element_pointer: Option<Rc<TreeElement<S::Handle>>>,
+    // Set when parsing a fragment, see `Parser::parse_fragment`
+    context_element: Option<Rc<TreeElement<S::Handle>>>,
 
     // Other Parsing state flags
     pub(super) scripting: ScriptingFlag,
     pub(super) frameset_ok: FramesetOkFlag,
+    pub(super) foster_parenting: FosterParentingFlag,
+    // The sink is told about changes with `TreeSink::set_quirks_mode`
+    quirks_mode: dom::QuirksMode,
+
+    // Set when a <meta> element changes the encoding, and the input has to be parsed again
+    restart_with_encoding: Option<&'static Encoding>,
+
+    script_handler: Option<Box<dyn ScriptHandler>>,
+    // The text of the script element being parsed in the "text" insertion mode
+    pub(super) script_text: Option<String>,
+
+    errors: Vec<PositionedParseError>,
 }
 
 impl<R> Parser<R>
 where
-    R: Read + Seek,
+    R: Read,
 {
+    /// Create a parser, sniffing the encoding of the input
     pub fn new(r: R) -> Self {
-        let document = Document::default();
-        let tokenizer = Tokenizer::new(r, false);
+        Parser::with_sink(r, Document::default())
+    }
+
+    /// Create a parser for input whose encoding was given by the transport layer
+    ///
+    /// See `Tokenizer::with_transport_encoding`
+    pub fn with_transport_encoding(r: R, label: &str) -> Self {
+        Parser::with_tokenizer(
+            Tokenizer::with_transport_encoding(r, true, label),
+            Document::default(),
+        )
+    }
+
+    /// The document built so far
+    #[must_use]
+    pub fn document(&self) -> &Document {
+        &self.sink
+    }
+
+    /// Parse the input as the contents of `context`, as when setting an element's `innerHTML`
+    ///
+    /// The input is always decoded as UTF-8.
+    ///
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments>
+    ///
+    /// # Errors
+    /// See `Parser::run`
+    pub fn parse_fragment(input: R, context: &dom::Element) -> Result<DocumentFragment> {
+        let mut parser =
+            Parser::with_tokenizer(Tokenizer::new_utf8(input, true), Document::default());
+        // TODO: Use the quirks mode of the context element's node document
+
+        if context.is_html() {
+            match context.name() {
+                TagName::Title | TagName::Textarea => parser.tokenizer.switch_to_rcdata_state(),
+                TagName::Style
+                | TagName::Xmp
+                | TagName::Iframe
+                | TagName::Noembed
+                | TagName::Noframes => parser.tokenizer.switch_to_rawtext_state(),
+                TagName::Script => parser.tokenizer.switch_to_script_data_state(),
+                TagName::Noscript if parser.scripting == ScriptingFlag::Enabled => {
+                    parser.tokenizer.switch_to_rawtext_state()
+                }
+                TagName::Plaintext => parser.tokenizer.switch_to_plaintext_state(),
+                _ => {}
+            }
+        }
+
+        let root = parser.create_html_element(TagName::Html);
+        parser.append_element_to_document(&root);
+        parser.open_elements.push(root);
+        if context.is_html() && context.name() == &TagName::Template {
+            parser.template_insertion_modes.push(States::in_template());
+        }
+
+        // The context element isn't part of the fragment, so a detached copy of it is used
+        let context_copy = parser.create_element(
+            context.name().clone(),
+            context.namespace(),
+            context.attributes().to_vec(),
+            context.span(),
+        );
+        parser.context_element = Some(Rc::clone(&context_copy));
+        parser.insertion_mode = Some(parser.reset_the_insertion_mode_appropriately());
+
+        // The form element pointer is set to the nearest form element which is an inclusive
+        // ancestor of the context element, a detached copy of it too
+        let is_form = |e: &dom::Element| e.is_html() && e.name() == &TagName::Form;
+        parser.form_element_pointer = if is_form(context) {
+            Some(context_copy)
+        } else {
+            context
+                .ancestors()
+                .find(|ancestor| is_form(&ancestor.borrow()))
+                .map(|form| {
+                    let form = form.borrow();
+                    parser.create_element(
+                        TagName::Form,
+                        form.namespace(),
+                        form.attributes().to_vec(),
+                        form.span(),
+                    )
+                })
+        };
+
+        parser.run()?;
+
+        // The children are moved out of the root, which unlinks them from it
+        let root = parser.document().document_element().unwrap();
+        let len = root.borrow().len();
+        let mut children: Vec<_> = (0..len)
+            .rev()
+            .map(|i| dom::Element::remove_child(&root, i))
+            .collect();
+        children.reverse();
+        Ok(DocumentFragment::new(children))
+    }
+}
+
+impl Parser<io::Empty> {
+    /// Create a parser whose input is fed in with `Parser::feed`
+    ///
+    /// The document is built as the input arrives, after the last chunk of input call
+    /// `Parser::finish` to complete it.
+    #[must_use]
+    pub fn new_push() -> Self {
+        Parser::with_tokenizer(Tokenizer::new_push(true), Document::default())
+    }
+}
+
+impl<S> Parser<io::Empty, S>
+where
+    S: TreeSink,
+{
+    /// Add the next chunk of input, and parse as much of it as possible
+    ///
+    /// # Errors
+    /// See `Parser::run`
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<()> {
+        self.tokenizer.feed(bytes);
+        self.run()
+    }
+
+    /// Mark the end of the input, and finish parsing the document
+    ///
+    /// # Errors
+    /// See `Parser::run`
+    pub fn finish(&mut self) -> Result<()> {
+        self.tokenizer.finish();
+        self.run()
+    }
+}
 
+impl<R, S> Parser<R, S>
+where
+    R: Read,
+    S: TreeSink,
+{
+    /// Create a parser which builds its output with `sink`, sniffing the encoding of the input
+    pub fn with_sink(r: R, sink: S) -> Self {
+        Parser::with_tokenizer(Tokenizer::new(r, true), sink)
+    }
+
+    /// Create a parser which reads its tokens from `tokenizer`, and builds its output with `sink`
+    ///
+    /// The other constructors collapse character tokens, a tree is built from a run of text
+    /// much faster than from each of its characters. Either way the output is the same.
+    pub fn with_tokenizer(tokenizer: Tokenizer<R>, sink: S) -> Self {
         Parser {
-            document,
+            sink,
             tokenizer,
 
             insertion_mode: Some(States::new()),
             reprocess: false,
             last_token: None,
+            token_span: Span::default(),
 
             open_elements: OpenElementsStack::new(),
             list_of_active_formatting_elements: ListOfActiveFormattingElements::new(),
+            template_insertion_modes: Vec::new(),
 
             head_element_pointer: None,
+            form_element_pointer: None,
+            context_element: None,
 
             scripting: ScriptingFlag::Disabled,
             frameset_ok: FramesetOkFlag::Ok,
+            foster_parenting: FosterParentingFlag::Disabled,
+            quirks_mode: dom::QuirksMode::NoQuirks,
+
+            restart_with_encoding: None,
+
+            script_handler: None,
+            script_text: None,
+
+            errors: Vec::new(),
         }
     }
 
-    pub fn run(&mut self) {
+    /// Parse the input, consuming the parser and returning the sink's output
+    ///
+    /// # Errors
+    /// See `Parser::run`
+    pub fn parse(mut self) -> Result<S::Output> {
+        self.run()?;
+        Ok(self.sink.finish())
+    }
+
+    /// Run the scripts in the input with `handler`, as they're parsed
+    ///
+    /// This also sets the scripting flag, so `<noscript>` elements are parsed as raw text.
+    pub fn set_script_handler<H>(&mut self, handler: H)
+    where
+        H: ScriptHandler + 'static,
+    {
+        self.script_handler = Some(Box::new(handler));
+        self.scripting = ScriptingFlag::Enabled;
+    }
+
+    /// The sink the output is being built with
+    #[must_use]
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Run the parser until the end of the input has been reached
+    ///
+    /// The output built so far is then available in `Parser::sink`, or `Parser::document`.
+    /// A parser created with `Parser::new_push` instead stops when it needs more input.
+    ///
+    /// # Errors
+    /// Fails if the parser reaches an invalid state transition,
+    /// or a part of the specification which isn't yet implemented.
+    pub fn run(&mut self) -> Result<()> {
         loop {
             let insertion_mode = self.insertion_mode.take().unwrap();
 
@@ -75,63 +306,400 @@ where
                 insertion_mode
             );
             let res = if let States::Term(_) = insertion_mode {
-                return;
+                self.insertion_mode = Some(insertion_mode);
+                return Ok(());
             } else {
                 let token = if self.reprocess {
                     self.last_token.take().unwrap()
                 } else {
-                    self.tokenizer.next().unwrap()
+                    let in_foreign_content = self
+                        .adjusted_current_node()
+                        .is_some_and(|node| !node.is_html());
+                    self.tokenizer.set_in_foreign_content(in_foreign_content);
+                    self.tokenizer
+                        .set_whitespace_runs(insertion_mode.handles_whitespace());
+
+                    let token = self.tokenizer.next();
+                    for error in self.tokenizer.take_errors() {
+                        self.sink.parse_error(&error);
+                        self.errors.push(error);
+                    }
+                    if let Some(e) = self.tokenizer.take_error() {
+                        self.insertion_mode = Some(insertion_mode);
+                        return Err(match e {
+                            tokenizer_errors::Error::IO(e) => e.into(),
+                            e => Box::new(e).into(),
+                        });
+                    }
+                    let (token, span) = match token {
+                        Some(token) => token,
+                        None => {
+                            // The tokenizer is waiting for more input to be fed in
+                            debug_assert!(self.tokenizer.is_starved());
+                            self.insertion_mode = Some(insertion_mode);
+                            return Ok(());
+                        }
+                    };
+                    self.token_span = span;
+                    // The insertion modes handle runs of characters, which this is one of
+                    match token {
+                        Token::Character(c) => Token::Characters(c.to_string()),
+                        token => token,
+                    }
                 };
 
                 // tree construction dispatcher
-                if self.is_tree_construction_first_case(&token) {
-                    trace!(target: "html_parser::parser", "Received token {:?}", token);
-                    let ret = insertion_mode.on_token(self, &token);
-                    self.last_token = Some(token);
-                    ret
+                trace!(target: "html_parser::parser", "Received token {:?}", token);
+                let ret = if self.is_tree_construction_first_case(&token) {
+                    insertion_mode.on_token(self, &token)
                 } else {
-                    todo!("Parser: Process the token according to the rules given in the section for parsing tokens in foreign content.");
-                }
+                    foreign_content::on_token(insertion_mode, self, &token)
+                };
+                self.last_token = Some(token);
+                ret
             };
 
-            if res.is_err() {
-                let next_state_error = res.state().unwrap_err();
-                // TODO return err?
-                panic!("Parser error: {}", next_state_error);
+            self.reprocess = res.reprocess();
+            self.insertion_mode = Some(res.state()?);
+
+            if let Some(encoding) = self.restart_with_encoding.take() {
+                self.restart(encoding)?;
             }
+        }
+    }
 
-            trace!(target: "html_parser::parser", "Document: {:#?}", self.document);
-            self.reprocess = res.reprocess();
-            self.insertion_mode = Some(res.state().unwrap());
+    /// The encoding the input is being decoded with
+    #[must_use]
+    pub fn encoding(&self) -> &'static Encoding {
+        self.tokenizer.encoding()
+    }
+
+    /// How sure we are of the input's encoding
+    #[must_use]
+    pub fn encoding_confidence(&self) -> Confidence {
+        self.tokenizer.confidence()
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#changing-the-encoding-while-parsing>
+    ///
+    /// We never change the decoder on the fly, the input is instead parsed again from the start.
+    pub(super) fn change_the_encoding(&mut self, encoding: &'static Encoding) {
+        let current = self.tokenizer.encoding();
+        if current == UTF_16BE || current == UTF_16LE {
+            self.tokenizer.set_confidence(Confidence::Certain);
+            return;
+        }
+
+        let encoding = if encoding == UTF_16BE || encoding == UTF_16LE {
+            UTF_8
+        } else if encoding == X_USER_DEFINED {
+            WINDOWS_1252
+        } else {
+            encoding
+        };
+
+        if encoding == current {
+            self.tokenizer.set_confidence(Confidence::Certain);
+        } else {
+            debug!(
+                target: "html_parser::parser",
+                "Changing encoding from {} to {}, restarting",
+                current.name(),
+                encoding.name()
+            );
+            self.restart_with_encoding = Some(encoding);
         }
     }
 
-    pub(super) fn set_head(&mut self, head_elem: Rc<RefCell<dom::Element>>) {
+    /// Throw away everything parsed so far, and parse the input again as `encoding`
+    fn restart(&mut self, encoding: &'static Encoding) -> Result<()> {
+        self.tokenizer.restart_with_encoding(encoding)?;
+
+        self.sink.restart();
+        self.insertion_mode = Some(States::new());
+        self.reprocess = false;
+        self.last_token = None;
+        self.token_span = Span::default();
+        self.open_elements = OpenElementsStack::new();
+        self.list_of_active_formatting_elements = ListOfActiveFormattingElements::new();
+        self.template_insertion_modes.clear();
+        self.head_element_pointer = None;
+        self.form_element_pointer = None;
+        self.frameset_ok = FramesetOkFlag::Ok;
+        self.foster_parenting = FosterParentingFlag::Disabled;
+        self.quirks_mode = dom::QuirksMode::NoQuirks;
+        self.script_text = None;
+        self.errors.clear();
+        Ok(())
+    }
+
+    /// The document's mode, which is set from its doctype
+    #[must_use]
+    pub fn quirks_mode(&self) -> dom::QuirksMode {
+        self.quirks_mode
+    }
+
+    /// The parse errors found so far, from both the tokenizer and tree construction
+    #[must_use]
+    pub fn errors(&self) -> &[PositionedParseError] {
+        &self.errors
+    }
+
+    pub(super) fn parse_error(&mut self, msg: &'static str) {
+        error!("Parse Error: {}", msg);
+        let error =
+            PositionedParseError::new(ParseError::TreeConstruction(msg), self.tokenizer.position());
+        self.sink.parse_error(&error);
+        self.errors.push(error);
+    }
+
+    pub(super) fn set_head(&mut self, head_elem: Rc<TreeElement<S::Handle>>) {
         self.head_element_pointer = Some(head_elem);
     }
 
-    pub(super) fn insert_html_element(&mut self, elem: Rc<RefCell<dom::Element>>) {
-        let (target, pos) = self.appropriate_place_for_inserting_a_node(None).unwrap();
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#create-an-element-for-the-token>
+    ///
+    /// Attributes of MathML and SVG elements are adjusted as per the
+    /// "adjust MathML attributes", "adjust SVG attributes" and "adjust foreign attributes" steps.
+    pub(super) fn create_element_for_token(
+        &mut self,
+        tag: &StartTag,
+        namespace: dom::Namespace,
+    ) -> Rc<TreeElement<S::Handle>> {
+        let attributes = self.attributes_for_token(tag, namespace);
+        let span = self.token_span;
+        self.create_element(tag.name.clone(), namespace, attributes, Some(span))
+    }
+
+    /// The attributes of an element in `namespace` created for `tag`, see
+    /// `Parser::create_element_for_token`
+    fn attributes_for_token(
+        &self,
+        tag: &StartTag,
+        namespace: dom::Namespace,
+    ) -> Vec<dom::Attribute> {
+        tag.attributes_iter()
+            .map(|a| {
+                let spans = (a.name_span, a.value_span);
+                let name = match namespace {
+                    dom::Namespace::MathML => dom::foreign::mathml_attribute_name(&a.name),
+                    dom::Namespace::SVG => dom::foreign::svg_attribute_name(&a.name),
+                    _ => None,
+                };
+                let attribute = if let Some(name) = name {
+                    dom::Attribute::new(name, a.value.clone())
+                } else if let (false, Some((prefix, name, ns))) = (
+                    namespace == dom::Namespace::HTML,
+                    dom::foreign::foreign_attribute(&a.name),
+                ) {
+                    dom::Attribute::new_ns(
+                        Some(ns),
+                        prefix.map(String::from),
+                        name,
+                        a.value.clone(),
+                    )
+                } else {
+                    dom::Attribute::new(a.name.clone(), a.value.clone())
+                };
+                attribute.with_spans(spans.0, spans.1)
+            })
+            .collect()
+    }
+
+    /// Create an HTML element without attributes, for an element which is implied by the tokens
+    pub(super) fn create_html_element(&mut self, name: TagName) -> Rc<TreeElement<S::Handle>> {
+        self.create_element(name, dom::Namespace::HTML, Vec::new(), None)
+    }
+
+    /// Create an element for the token the entry at `index` in the list of active formatting
+    /// elements was created for, and replace the entry's element with it
+    pub(super) fn recreate_active_formatting_element(
+        &mut self,
+        index: usize,
+    ) -> Rc<TreeElement<S::Handle>> {
+        let (elem, token) = match self.list_of_active_formatting_elements[index] {
+            ActiveFormattingElementOrMarker::ActiveFormattingElement(ref e, ref t) => {
+                (Rc::clone(e), Rc::clone(t))
+            }
+            ActiveFormattingElementOrMarker::Marker => panic!("Can't recreate a marker"),
+        };
+        let new_element = self.create_element(
+            elem.name().clone(),
+            elem.namespace(),
+            token.attributes.clone(),
+            token.span,
+        );
+        self.list_of_active_formatting_elements[index] =
+            ActiveFormattingElementOrMarker::ActiveFormattingElement(
+                Rc::clone(&new_element),
+                token,
+            );
+        new_element
+    }
+
+    /// Insert an HTML element for `tag`, and push it onto the list of active formatting elements
+    pub(super) fn insert_formatting_element_for_token(&mut self, tag: &StartTag) {
+        let token = FormattingToken {
+            attributes: self.attributes_for_token(tag, dom::Namespace::HTML),
+            span: Some(self.token_span),
+        };
+        let elem = self.create_element(
+            tag.name.clone(),
+            dom::Namespace::HTML,
+            token.attributes.clone(),
+            token.span,
+        );
+        self.insert_html_element(Rc::clone(&elem));
+        self.list_of_active_formatting_elements
+            .push_element(elem, token);
+    }
+
+    /// Create an element with the sink, and keep its name and namespace for the parser
+    fn create_element(
+        &mut self,
+        name: TagName,
+        namespace: dom::Namespace,
+        attributes: Vec<dom::Attribute>,
+        span: Option<Span>,
+    ) -> Rc<TreeElement<S::Handle>> {
+        // This is the only thing the parser needs to know about the attributes
+        let encoding = attributes
+            .iter()
+            .find(|a| a.namespace().is_none() && a.name() == "encoding")
+            .map(dom::Attribute::value);
+        let html_integration_point =
+            dom::element::is_html_integration_point(&name, namespace, encoding);
+
+        let handle = self
+            .sink
+            .create_element(name.clone(), namespace, attributes);
+        if let Some(span) = span {
+            self.sink.set_source_span(&handle, span);
+        }
+        Rc::new(TreeElement::new(
+            handle,
+            name,
+            namespace,
+            html_integration_point,
+        ))
+    }
+
+    pub(super) fn insert_html_element(&mut self, elem: Rc<TreeElement<S::Handle>>) {
+        self.insert_foreign_element(elem)
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#insert-a-foreign-element>
+    ///
+    /// The element's namespace is set when it's created by `create_element_for_token`
+    pub(super) fn insert_foreign_element(&mut self, elem: Rc<TreeElement<S::Handle>>) {
+        let place = self.appropriate_place_for_inserting_a_node(None).unwrap();
         // TODO: If it is possible to insert element at the adjusted insertion location
         // TODO: custom element stuff
-        let mut target = target.borrow_mut();
-        target.insert(pos, elem.clone().into());
+        self.insert_at(place, NodeOrText::Node(elem.handle.clone()));
         self.open_elements.push(elem);
     }
 
     pub(super) fn insert_character<C: AsRef<str>>(&mut self, data: C) {
-        let (target, pos) = self.appropriate_place_for_inserting_a_node(None).unwrap();
-        let mut target = RefCell::borrow_mut(&target);
-        if pos > 0 {
-            if let Some(dom::element::ChildNode::Text(text)) = target.get_mut(pos - 1) {
-                trace!(target: "html_parser::parser", "Appending char at position {}", pos - 1);
-                let mut text = text.borrow_mut();
-                return text.push_str(data.as_ref());
-            }
+        let place = self.appropriate_place_for_inserting_a_node(None).unwrap();
+        trace!(target: "html_parser::parser", "Inserting text {:?} at {:?}", data.as_ref(), place);
+        let text = NodeOrText::Text(data.as_ref().to_string(), self.token_span);
+        self.insert_at(place, text);
+    }
+
+    /// Create a comment with the sink, for the current token
+    fn create_comment(&mut self, data: &str) -> S::Handle {
+        let comment = self.sink.create_comment(data.to_string());
+        self.sink.set_source_span(&comment, self.token_span);
+        comment
+    }
+
+    /// Append a comment to the document
+    pub(super) fn append_comment_to_document(&mut self, data: &str) {
+        let comment = self.create_comment(data);
+        let document = self.sink.document();
+        self.sink.append(&document, NodeOrText::Node(comment));
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment>
+    pub(super) fn insert_comment(&mut self, data: &str) {
+        let place = self.appropriate_place_for_inserting_a_node(None).unwrap();
+        let comment = self.create_comment(data);
+        self.insert_at(place, NodeOrText::Node(comment));
+    }
+
+    /// Append a comment to `elem`, rather than at the appropriate place for inserting a node
+    pub(super) fn append_comment_to(&mut self, elem: &TreeElement<S::Handle>, data: &str) {
+        let comment = self.create_comment(data);
+        self.sink.append(&elem.handle, NodeOrText::Node(comment));
+    }
+
+    /// Append a doctype to the document
+    pub(super) fn append_doctype_to_document(&mut self, document_type: dom::DocumentType) {
+        self.sink.append_doctype_to_document(document_type);
+    }
+
+    /// Append an element which isn't on the stack of open elements to the document
+    pub(super) fn append_element_to_document(&mut self, elem: &TreeElement<S::Handle>) {
+        let document = self.sink.document();
+        self.sink
+            .append(&document, NodeOrText::Node(elem.handle.clone()));
+    }
+
+    pub(super) fn set_quirks_mode(&mut self, mode: dom::QuirksMode) {
+        self.quirks_mode = mode;
+        self.sink.set_quirks_mode(mode);
+    }
+
+    /// Add each of `attributes` to `elem`, unless it already has an attribute with that name
+    pub(super) fn add_attributes_if_missing(
+        &mut self,
+        elem: &TreeElement<S::Handle>,
+        attributes: Vec<dom::Attribute>,
+    ) {
+        self.sink
+            .add_attributes_if_missing(&elem.handle, attributes);
+    }
+
+    /// Insert `node` at `place`, see `Parser::appropriate_place_for_inserting_a_node`
+    pub(super) fn insert_at(
+        &mut self,
+        place: InsertionPoint<S::Handle>,
+        node: NodeOrText<S::Handle>,
+    ) {
+        match place {
+            InsertionPoint::LastChild(parent) => self.sink.append(&parent, node),
+            InsertionPoint::BeforeTable {
+                table,
+                previous_element,
+            } => self
+                .sink
+                .append_based_on_parent_node(&table, &previous_element, node),
         }
-        let node = dom::Text::new(data.as_ref().to_string());
-        trace!(target: "html_parser::parser", "Inserting char {:?} at position {}", node, pos);
-        target.insert(pos, node.into());
+    }
+
+    /// Remove `elem` from its parent
+    pub(super) fn remove_from_parent(&mut self, elem: &TreeElement<S::Handle>) {
+        self.sink.remove_from_parent(&elem.handle);
+    }
+
+    /// Move all the children of `elem` to the end of `new_parent`'s children
+    pub(super) fn reparent_children(
+        &mut self,
+        elem: &TreeElement<S::Handle>,
+        new_parent: &TreeElement<S::Handle>,
+    ) {
+        self.sink
+            .reparent_children(&elem.handle, &new_parent.handle);
+    }
+
+    /// Append `child` to `parent`'s children, removing it from its current parent first
+    pub(super) fn append_child(
+        &mut self,
+        parent: &TreeElement<S::Handle>,
+        child: &TreeElement<S::Handle>,
+    ) {
+        self.sink
+            .append(&parent.handle, NodeOrText::Node(child.handle.clone()));
     }
 
     pub(super) fn generic_raw_text_element_parse(
@@ -139,7 +707,7 @@ where
         current_state: States,
         token: &Token,
     ) -> TransitionResult {
-        let node = dom::Element::new_html(token.tag_name().unwrap().clone());
+        let node = self.create_element_for_token(token.start_tag().unwrap(), dom::Namespace::HTML);
         self.insert_html_element(node);
 
         self.tokenizer.switch_to_rawtext_state();
@@ -147,12 +715,22 @@ where
         States::text(Box::new(current_state)).into_transition_result()
     }
 
+    pub(super) fn switch_the_tokenizer_to_plaintext_state(&mut self) {
+        self.tokenizer.switch_to_plaintext_state();
+    }
+
+    /// Drop the next token if it's a line feed, as the start of a `pre`, `listing` or `textarea`
+    /// element is
+    pub(super) fn ignore_next_line_feed(&mut self) {
+        self.tokenizer.ignore_next_line_feed();
+    }
+
     pub(super) fn generic_rcdata_element_parse(
         &mut self,
         current_state: States,
         token: &Token,
     ) -> TransitionResult {
-        let node = dom::Element::new_html(token.tag_name().unwrap().clone());
+        let node = self.create_element_for_token(token.start_tag().unwrap(), dom::Namespace::HTML);
         self.insert_html_element(node);
 
         self.tokenizer.switch_to_rcdata_state();
@@ -160,9 +738,108 @@ where
         States::text(Box::new(current_state)).into_transition_result()
     }
 
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#scriptTag>
+    ///
+    /// Insert a `script` element for a start tag in the "in head" insertion mode, and switch the
+    /// tokenizer to the script data state
+    pub(super) fn insert_script_element(
+        &mut self,
+        current_state: States,
+        tag: &StartTag,
+    ) -> TransitionResult {
+        let place = self.appropriate_place_for_inserting_a_node(None).unwrap();
+        let script = self.create_element_for_token(tag, dom::Namespace::HTML);
+        // A script in a fragment is never run
+        self.set_script_flags(
+            &script,
+            dom::ScriptFlags {
+                already_started: self.context_element.is_some(),
+                parser_inserted: true,
+            },
+        );
+        self.insert_at(place, NodeOrText::Node(script.handle.clone()));
+        self.open_elements.push(script);
+        self.script_text = Some(String::new());
+
+        self.tokenizer.switch_to_script_data_state();
+
+        States::text(Box::new(current_state)).into_transition_result()
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element>
+    ///
+    /// Called once the end tag of `script` has been parsed, with the script's text. Classic
+    /// inline scripts are run with the `ScriptHandler`, and anything they write is inserted at
+    /// the insertion point. External scripts aren't fetched, so they never run.
+    pub(super) fn prepare_the_script_element(
+        &mut self,
+        script: &TreeElement<S::Handle>,
+        text: &str,
+    ) {
+        self.set_script_flags(
+            script,
+            dom::ScriptFlags {
+                already_started: true,
+                parser_inserted: true,
+            },
+        );
+
+        let handler = match self.script_handler {
+            Some(ref mut handler) => handler,
+            None => return,
+        };
+        // TODO: Fetch external scripts, and run them when they've loaded
+        if text.is_empty()
+            || self.sink.get_attribute(&script.handle, "src").is_some()
+            || !is_classic_script(self.sink.get_attribute(&script.handle, "type").as_deref())
+        {
+            return;
+        }
+
+        let mut document = DocumentWrite::default();
+        handler.run_script(text, &mut document);
+        self.tokenizer.insert_input(&document.into_written());
+    }
+
+    pub(super) fn set_script_flags(
+        &mut self,
+        script: &TreeElement<S::Handle>,
+        flags: dom::ScriptFlags,
+    ) {
+        self.sink.set_script_flags(&script.handle, flags);
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements>
+    pub(super) fn reconstruct_the_active_formatting_elements(&mut self) {
+        let is_open = |entry: &ActiveFormattingElementOrMarker<S::Handle>| match entry {
+            ActiveFormattingElementOrMarker::Marker => true,
+            ActiveFormattingElementOrMarker::ActiveFormattingElement(e, _) => {
+                self.open_elements.position_of(e).is_some()
+            }
+        };
+
+        // Nothing to do if the last entry is a marker or an element which is still open
+        match self.list_of_active_formatting_elements.last() {
+            Some(entry) if !is_open(entry) => {}
+            _ => return,
+        }
+
+        // Rewind to the entry after the last marker or open element
+        let mut i = self.list_of_active_formatting_elements.len() - 1;
+        while i > 0 && !is_open(&self.list_of_active_formatting_elements[i - 1]) {
+            i -= 1;
+        }
+
+        // Advance, creating an element for each entry
+        for i in i..self.list_of_active_formatting_elements.len() {
+            let node = self.recreate_active_formatting_element(i);
+            trace!(target: "html_parser::parser", "Reconstructing {:?}", node.name());
+            self.insert_html_element(node);
+        }
+    }
+
     pub(super) fn generate_implied_end_tags(&mut self, except: Option<&TagName>) {
         while let Some(node) = self.current_node() {
-            let node = node.borrow();
             let name = node.name();
             if !matches!(
                 name,
@@ -185,25 +862,163 @@ where
         }
     }
 
-    // Returning the parent element and the index to insert at
-    //
-    // ie. You cancall this then call `ret.0.insert(ret.1, new_elem)`
-    fn appropriate_place_for_inserting_a_node(
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#generate-all-implied-end-tags-thoroughly>
+    pub(super) fn generate_all_implied_end_tags_thoroughly(&mut self) {
+        while let Some(node) = self.current_node() {
+            if !node.is_html()
+                || !matches!(
+                    node.name(),
+                    TagName::Caption
+                        | TagName::Colgroup
+                        | TagName::Dd
+                        | TagName::Dt
+                        | TagName::Li
+                        | TagName::Optgroup
+                        | TagName::Option
+                        | TagName::P
+                        | TagName::Rb
+                        | TagName::Rp
+                        | TagName::Rt
+                        | TagName::Rtc
+                        | TagName::Tbody
+                        | TagName::Td
+                        | TagName::Tfoot
+                        | TagName::Th
+                        | TagName::Thead
+                        | TagName::Tr
+                )
+            {
+                break;
+            }
+            let _ = self.open_elements.pop();
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#appropriate-place-for-inserting-a-node>
+    ///
+    /// Insert a node there with `Parser::insert_at`
+    pub(super) fn appropriate_place_for_inserting_a_node(
         &mut self,
-        r#override: Option<()>,
-    ) -> Option<(Rc<RefCell<dom::Element>>, usize)> {
-        if r#override.is_some() {
-            todo!("Parser::appropriate_place_for_inserting_a_node with override")
+        r#override: Option<Rc<TreeElement<S::Handle>>>,
+    ) -> Option<InsertionPoint<S::Handle>> {
+        let target = match r#override {
+            Some(target) => target,
+            None => self.current_node()?,
+        };
+
+        let target_is_table_element = target.is_html()
+            && matches!(
+                target.name(),
+                TagName::Table | TagName::Tbody | TagName::Tfoot | TagName::Thead | TagName::Tr
+            );
+        if self.foster_parenting == FosterParentingFlag::Enabled && target_is_table_element {
+            return self.foster_parent_location();
         }
-        let target = self.current_node()?;
 
-        // TODO: foster parenting
-        if target.borrow().name() == &TagName::Template {
-            todo!("Parser::appropriate_place_for_inserting_a_node in template")
+        Some(InsertionPoint::LastChild(self.inside(&target)))
+    }
+
+    /// The node to append to when inserting inside `elem`, which is its template contents if
+    /// it's a `template` element
+    fn inside(&mut self, elem: &TreeElement<S::Handle>) -> S::Handle {
+        if elem.is_html() && elem.name() == &TagName::Template {
+            self.sink.template_contents(&elem.handle)
+        } else {
+            elem.handle.clone()
         }
-        let pos = target.borrow().len();
+    }
 
-        Some((target, pos))
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#foster-parent>
+    ///
+    /// The location for a node which is inserted while foster parenting is enabled, this is
+    /// immediately before the last table in the stack of open elements.
+    fn foster_parent_location(&mut self) -> Option<InsertionPoint<S::Handle>> {
+        let last_index_of = |name: &TagName| {
+            self.open_elements
+                .iter()
+                .rposition(|e| e.is_html() && e.name() == name)
+        };
+        let last_template = last_index_of(&TagName::Template);
+        let last_table = last_index_of(&TagName::Table);
+
+        match (last_template, last_table) {
+            (Some(template), table) if table.is_none_or(|table| template > table) => {
+                let template = Rc::clone(&self.open_elements[template]);
+                Some(InsertionPoint::LastChild(self.inside(&template)))
+            }
+            (_, None) => {
+                // fragment case
+                let html = self.open_elements.first()?;
+                Some(InsertionPoint::LastChild(html.handle.clone()))
+            }
+            (_, Some(table_index)) => {
+                let table = &self.open_elements[table_index];
+                let previous_element = &self.open_elements[table_index.checked_sub(1)?];
+                Some(InsertionPoint::BeforeTable {
+                    table: table.handle.clone(),
+                    previous_element: previous_element.handle.clone(),
+                })
+            }
+        }
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately>
+    pub(super) fn reset_the_insertion_mode_appropriately(&self) -> States {
+        for (i, node) in self.open_elements.iter().enumerate().rev() {
+            let last = i == 0;
+            // In the fragment case, the context element stands in for the root html element
+            let node = match self.context_element {
+                Some(ref context) if last => Rc::clone(context),
+                _ => Rc::clone(node),
+            };
+            if !node.is_html() {
+                continue;
+            }
+
+            match node.name() {
+                TagName::Select => {
+                    let ancestors = if last {
+                        &[][..]
+                    } else {
+                        &self.open_elements[..i]
+                    };
+                    for ancestor in ancestors.iter().rev() {
+                        if ancestor.is_html() && ancestor.name() == &TagName::Template {
+                            break;
+                        }
+                        if ancestor.is_html() && ancestor.name() == &TagName::Table {
+                            return States::in_select_in_table();
+                        }
+                    }
+                    return States::in_select();
+                }
+                TagName::Td | TagName::Th if !last => return States::in_cell(),
+                TagName::Tr => return States::in_row(),
+                TagName::Tbody | TagName::Thead | TagName::Tfoot => return States::in_table_body(),
+                TagName::Caption => return States::in_caption(),
+                TagName::Colgroup => return States::in_column_group(),
+                TagName::Table => return States::in_table(),
+                TagName::Template => {
+                    return self
+                        .template_insertion_modes
+                        .last()
+                        .cloned()
+                        .unwrap_or_else(States::in_template);
+                }
+                TagName::Head if !last => return States::in_head(),
+                TagName::Body => return States::in_body(),
+                TagName::Frameset => return States::in_frameset(),
+                TagName::Html => {
+                    return if self.head_element_pointer.is_none() {
+                        States::before_head()
+                    } else {
+                        States::after_head()
+                    };
+                }
+                _ => {}
+            }
+        }
+        States::in_body()
     }
 
     // https://html.spec.whatwg.org/multipage/parsing.html#tree-construction
@@ -213,12 +1028,11 @@ where
             return true;
         }
 
-        let adjusted_current_node = self.adjusted_current_node().expect(
+        let node = self.adjusted_current_node().expect(
             "No adjusted_current_node when there are elements on the stack of open elements",
         );
-        let node = adjusted_current_node.borrow();
         let is_token_start_tag = token.is_start_tag();
-        let is_token_character = token.is_start_tag();
+        let is_token_character = token.is_character();
         let is_token_eof = token.is_eof();
         let token_tag_name = token.tag_name();
 
@@ -272,40 +1086,1032 @@ where
         false
     }
 
-    pub fn adjusted_current_node(&self) -> Option<Rc<RefCell<dom::Element>>> {
-        if
-        /* self.parsing_algorithm == HTMLFragmentParsing && self.open_elements.len() == 1 */
-        false {
-            todo!("Parser::adjusted_current_node with HTMLFragmentParsing");
-        } else {
-            self.current_node()
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#adjusted-current-node>
+    pub(super) fn adjusted_current_node(&self) -> Option<Rc<TreeElement<S::Handle>>> {
+        match self.context_element {
+            Some(ref context) if self.open_elements.len() == 1 => Some(Rc::clone(context)),
+            _ => self.current_node(),
         }
     }
 
-    pub fn current_node(&self) -> Option<Rc<RefCell<dom::Element>>> {
-        self.open_elements.last().map(|e| Rc::clone(e))
+    pub(super) fn current_node(&self) -> Option<Rc<TreeElement<S::Handle>>> {
+        self.open_elements.last().map(Rc::clone)
+    }
+}
+
+/// Where to insert a node, see `Parser::appropriate_place_for_inserting_a_node`
+#[derive(Debug)]
+pub(super) enum InsertionPoint<H> {
+    /// After the last child of the element
+    LastChild(H),
+    /// Immediately before `table` if it has a parent, otherwise after the last child of
+    /// `previous_element`
+    BeforeTable { table: H, previous_element: H },
+}
+
+/// Whether the `type` attribute of `script` makes it a classic script, rather than a module or
+/// a data block
+///
+/// <https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element> (step 8)
+fn is_classic_script(script_type: Option<&str>) -> bool {
+    match script_type {
+        None => true,
+        Some(script_type) => {
+            let script_type = script_type.trim_matches(|c: char| c.is_ascii_whitespace());
+            script_type.is_empty() || is_javascript_mime_type(script_type)
+        }
     }
 }
 
-impl<R> fmt::Debug for Parser<R>
+/// <https://mimesniff.spec.whatwg.org/#javascript-mime-type-essence-match>
+fn is_javascript_mime_type(mime_type: &str) -> bool {
+    const JAVASCRIPT_MIME_TYPES: &[&str] = &[
+        "application/ecmascript",
+        "application/javascript",
+        "application/x-ecmascript",
+        "application/x-javascript",
+        "text/ecmascript",
+        "text/javascript",
+        "text/javascript1.0",
+        "text/javascript1.1",
+        "text/javascript1.2",
+        "text/javascript1.3",
+        "text/javascript1.4",
+        "text/javascript1.5",
+        "text/jscript",
+        "text/livescript",
+        "text/x-ecmascript",
+        "text/x-javascript",
+    ];
+    JAVASCRIPT_MIME_TYPES
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(mime_type))
+}
+
+impl<R, S> fmt::Debug for Parser<R, S>
 where
-    R: Read + Seek + fmt::Debug,
+    R: Read + fmt::Debug,
+    S: TreeSink + fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Parser")
-            .field("document", &self.document)
+            .field("sink", &self.sink)
             .field("tokenizer", &self.tokenizer)
             .field("insertion_mode", &self.insertion_mode)
             .field("reprocess", &self.reprocess)
             .field("last_token", &self.last_token)
+            .field("token_span", &self.token_span)
             .field("open_elements", &self.open_elements)
             .field(
                 "list_of_active_formatting_elements",
                 &self.list_of_active_formatting_elements,
             )
+            .field("template_insertion_modes", &self.template_insertion_modes)
             .field("head_element_pointer", &self.head_element_pointer)
+            .field("form_element_pointer", &self.form_element_pointer)
+            .field("context_element", &self.context_element)
             .field("scripting", &self.scripting)
             .field("frameset_ok", &self.frameset_ok)
+            .field("foster_parenting", &self.foster_parenting)
+            .field("quirks_mode", &self.quirks_mode)
+            .field("script_handler", &self.script_handler.is_some())
+            .field("script_text", &self.script_text)
+            .field("errors", &self.errors)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dom::ToHtml;
+    use std::{cell::RefCell, io::Cursor};
+
+    fn parse(html: &str) -> Document {
+        let _ = pretty_env_logger::formatted_builder()
+            .is_test(true)
+            .try_init();
+        Parser::new(Cursor::new(html)).parse().unwrap()
+    }
+
+    fn find_element(
+        elem: &Rc<RefCell<dom::Element>>,
+        name: &TagName,
+    ) -> Option<Rc<RefCell<dom::Element>>> {
+        if elem.borrow().name() == name {
+            return Some(Rc::clone(elem));
+        }
+        elem.borrow().iter().find_map(|child| match child {
+            dom::element::ChildNode::Element(e) => find_element(e, name),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn element_attributes() {
+        let document = parse("<html lang=\"en\"><head></head><body><a href=\"/foo\" class=\"bar\">x</a></body></html>");
+        let html = document.document_element().unwrap();
+        assert_eq!(html.borrow().get_attribute("lang"), Some("en"));
+
+        let a = find_element(&html, &TagName::A).unwrap();
+        let a = a.borrow();
+        assert_eq!(
+            a.attributes(),
+            &[
+                dom::Attribute::new("href", "/foo"),
+                dom::Attribute::new("class", "bar")
+            ]
+        );
+    }
+
+    #[test]
+    fn end_tag_with_attributes() {
+        let document = parse("<b>x</b y><h></h a='b' a>");
+        let html = document.document_element().unwrap();
+        assert!(find_element(&html, &TagName::B).is_some());
+    }
+
+    #[test]
+    fn eof_in_text() {
+        let document = parse("<html><head><title>foo");
+        let html = document.document_element().unwrap();
+        let title = find_element(&html, &TagName::Title).unwrap();
+        assert_eq!(title.borrow().len(), 1);
+    }
+
+    #[test]
+    fn svg_foreign_content() {
+        let document = parse(
+            "<html><body><svg viewbox=\"0 0 10 10\"><clipPath><use xlink:href=\"#a\"/></clipPath>\
+             <foreignObject><p>foo</p></foreignObject><![CDATA[x<y]]></svg></body></html>",
+        );
+        let html = document.document_element().unwrap();
+
+        let svg = find_element(&html, &TagName::Svg).unwrap();
+        let svg = svg.borrow();
+        assert_eq!(svg.namespace(), dom::Namespace::SVG);
+        assert_eq!(svg.get_attribute("viewBox"), Some("0 0 10 10"));
+        assert_eq!(svg.len(), 3);
+        assert_eq!(
+            svg.last(),
+            Some(&dom::element::ChildNode::Text(dom::Text::new(
+                "x<y".to_string()
+            )))
+        );
+
+        let clip_path = find_element(&html, &TagName::Other("clippath".to_string())).unwrap();
+        assert_eq!(clip_path.borrow().local_name(), "clipPath");
+        let r#use = find_element(&html, &TagName::Other("use".to_string())).unwrap();
+        assert_eq!(
+            r#use
+                .borrow()
+                .get_attribute_ns(Some(dom::Namespace::XLink), "href"),
+            Some("#a")
+        );
+        assert!(r#use.borrow().is_empty());
+
+        let foreign_object = find_element(&html, &TagName::ForeignObject).unwrap();
+        assert_eq!(foreign_object.borrow().local_name(), "foreignObject");
+        let p = find_element(&foreign_object, &TagName::P).unwrap();
+        assert_eq!(p.borrow().namespace(), dom::Namespace::HTML);
+    }
+
+    #[test]
+    fn mathml_foreign_content() {
+        let document =
+            parse("<html><body><math definitionurl=\"foo\"><mi>x</mi><div>bar</div></body></html>");
+        let html = document.document_element().unwrap();
+
+        let math = find_element(&html, &TagName::Math).unwrap();
+        assert_eq!(math.borrow().namespace(), dom::Namespace::MathML);
+        assert_eq!(math.borrow().get_attribute("definitionURL"), Some("foo"));
+        let mi = find_element(&math, &TagName::Mi).unwrap();
+        assert_eq!(mi.borrow().namespace(), dom::Namespace::MathML);
+
+        // The div breaks out of foreign content
+        assert!(find_element(&math, &TagName::Div).is_none());
+        let body = find_element(&html, &TagName::Body).unwrap();
+        let div = find_element(&body, &TagName::Div).unwrap();
+        assert_eq!(div.borrow().namespace(), dom::Namespace::HTML);
+    }
+
+    fn child_names(elem: &Rc<RefCell<dom::Element>>) -> Vec<String> {
+        elem.borrow()
+            .iter()
+            .map(|child| match child {
+                dom::element::ChildNode::Element(e) => e.borrow().name().to_string(),
+                dom::element::ChildNode::Text(t) => format!("#text {}", **t.borrow()),
+                _ => "#other".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn table_structure() {
+        let document = parse(
+            "<html><body><table><caption>c</caption><colgroup><col></colgroup>\
+             <tr><th>a</th><td>b</td></tr></table></body></html>",
+        );
+        let html = document.document_element().unwrap();
+
+        let table = find_element(&html, &TagName::Table).unwrap();
+        assert_eq!(child_names(&table), vec!["caption", "colgroup", "tbody"]);
+        let tbody = find_element(&table, &TagName::Tbody).unwrap();
+        assert_eq!(child_names(&tbody), vec!["tr"]);
+        let tr = find_element(&tbody, &TagName::Tr).unwrap();
+        assert_eq!(child_names(&tr), vec!["th", "td"]);
+        let td = find_element(&tr, &TagName::Td).unwrap();
+        assert_eq!(child_names(&td), vec!["#text b"]);
+    }
+
+    #[test]
+    fn table_foster_parenting() {
+        let mut parser = Parser::new(Cursor::new(
+            "<html><body><table>foo<tr><td>bar</td></tr><div>baz</div></table></body></html>",
+        ));
+        parser.run().unwrap();
+        assert!(!parser.errors().is_empty());
+        let html = parser.document().document_element().unwrap();
+
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(child_names(&body), vec!["#text foo", "div", "table"]);
+        let table = find_element(&body, &TagName::Table).unwrap();
+        assert!(find_element(&table, &TagName::Div).is_none());
+        let td = find_element(&table, &TagName::Td).unwrap();
+        assert_eq!(child_names(&td), vec!["#text bar"]);
+    }
+
+    fn text_of(elem: &Rc<RefCell<dom::Element>>) -> String {
+        elem.borrow()
+            .iter()
+            .filter_map(|child| match child {
+                dom::element::ChildNode::Text(t) => Some(t.borrow().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn meta_charset_changes_encoding() {
+        let mut parser = Parser::new(Cursor::new(
+            b"<html><head><meta charset=\"windows-1252\"></head><body><p>caf\xE9</p></body></html>"
+                .to_vec(),
+        ));
+        parser.run().unwrap();
+        // The prescan finds the <meta>, so the input is only parsed once
+        assert_eq!(parser.encoding(), encoding_rs::WINDOWS_1252);
+        assert_eq!(parser.encoding_confidence(), Confidence::Certain);
+
+        let html = parser.document().document_element().unwrap();
+        let p = find_element(&html, &TagName::P).unwrap();
+        assert_eq!(text_of(&p), "caf\u{E9}");
+    }
+
+    #[test]
+    fn meta_after_prescan_restarts_parsing() {
+        let mut input = b"<html><head><title>".to_vec();
+        input.extend(std::iter::repeat_n(b'x', 1024));
+        input.extend(
+            b"</title><meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift_JIS\">\
+              </head><body><p>\x93\xfa\x96\x7b</p></body></html>"
+                .iter(),
+        );
+        let mut parser = Parser::new(Cursor::new(input));
+        parser.run().unwrap();
+        assert_eq!(parser.encoding(), encoding_rs::SHIFT_JIS);
+        assert_eq!(parser.encoding_confidence(), Confidence::Certain);
+
+        let html = parser.document().document_element().unwrap();
+        let head = find_element(&html, &TagName::Head).unwrap();
+        assert_eq!(child_names(&head), vec!["title", "meta"]);
+        let p = find_element(&html, &TagName::P).unwrap();
+        assert_eq!(text_of(&p), "\u{65E5}\u{672C}");
+    }
+
+    #[test]
+    fn transport_encoding() {
+        let input = b"<html><head><meta charset=utf-8></head><body><p>\xA1</p></body></html>";
+        let mut parser = Parser::with_transport_encoding(Cursor::new(input.to_vec()), "latin2");
+        parser.run().unwrap();
+        assert_eq!(parser.encoding(), encoding_rs::ISO_8859_2);
+
+        let html = parser.document().document_element().unwrap();
+        let p = find_element(&html, &TagName::P).unwrap();
+        assert_eq!(text_of(&p), "\u{104}");
+    }
+
+    #[test]
+    fn utf_16_bom() {
+        let input: Vec<u8> = "\u{FEFF}<html><body><p>\u{263A}</p></body></html>"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        let mut parser = Parser::new(Cursor::new(input));
+        parser.run().unwrap();
+        assert_eq!(parser.encoding(), encoding_rs::UTF_16BE);
+
+        let html = parser.document().document_element().unwrap();
+        let p = find_element(&html, &TagName::P).unwrap();
+        assert_eq!(text_of(&p), "\u{263A}");
+    }
+
+    /// Reads a byte at a time, and can't seek
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&b, rest)), Some(out)) => {
+                    *out = b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    const STREAMED_HTML: &str = "<!DOCTYPE html><html><head><title>x &amp; y</title></head>\
+                                 <body><p id=\"a\">caf\u{E9}\r\n&notin;</p><!-- c --></body></html>";
+
+    #[test]
+    fn non_seekable_reader() {
+        let expected = parse(STREAMED_HTML).to_html();
+
+        let document = Parser::new(Trickle(STREAMED_HTML.as_bytes()))
+            .parse()
+            .unwrap();
+        assert_eq!(document.to_html(), expected);
+    }
+
+    #[test]
+    fn feed_chunks() {
+        let expected = parse(STREAMED_HTML).to_html();
+
+        for chunk_size in &[1, 3, 7, 1000] {
+            let mut parser = Parser::new_push();
+            for chunk in STREAMED_HTML.as_bytes().chunks(*chunk_size) {
+                parser.feed(chunk).unwrap();
+            }
+            parser.finish().unwrap();
+            assert_eq!(parser.document().to_html(), expected);
+        }
+    }
+
+    #[test]
+    fn feed_parses_as_input_arrives() {
+        let mut parser = Parser::new_push();
+        let mut input = b"<html><head></head><body><p>".to_vec();
+        input.extend(std::iter::repeat_n(b'x', 2000));
+        parser.feed(&input).unwrap();
+
+        // Most of the text has been parsed before the end of the input
+        let html = parser.document().document_element().unwrap();
+        let p = find_element(&html, &TagName::P).unwrap();
+        assert!(text_of(&p).len() > 1000);
+
+        parser.feed(b"</p></body></html>").unwrap();
+        parser.finish().unwrap();
+        assert_eq!(text_of(&p).len(), 2000);
+    }
+
+    #[test]
+    fn quirks_mode() {
+        let mode = |html: &str| parse(html).mode();
+        assert_eq!(mode("<!DOCTYPE html><html>"), dom::QuirksMode::NoQuirks);
+        assert_eq!(mode("<html>"), dom::QuirksMode::Quirks);
+        assert_eq!(
+            mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"><html>"),
+            dom::QuirksMode::Quirks
+        );
+        assert_eq!(
+            mode(
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \
+                 \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\"><html>"
+            ),
+            dom::QuirksMode::LimitedQuirks
+        );
+
+        // A table only closes a p element when not in quirks mode
+        let p_children = |html: &str| {
+            let document = parse(html);
+            let p = find_element(&document.document_element().unwrap(), &TagName::P).unwrap();
+            child_names(&p)
+        };
+        assert!(p_children("<!DOCTYPE html><html><body><p><table></table>").is_empty());
+        assert_eq!(p_children("<html><body><p><table></table>"), ["table"]);
+    }
+
+    #[test]
+    fn downlevel_revealed_conditional_comments() {
+        let document = parse("<html><body><![if !IE]><p>x</p><![endif]>");
+        let html = document.document_element().unwrap();
+        let body = find_element(&html, &TagName::Body).unwrap();
+        let comments: Vec<_> = body
+            .borrow()
+            .iter()
+            .filter_map(|child| match child {
+                dom::element::ChildNode::Comment(c) => Some(c.borrow().data().to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(comments, ["[if !IE]", "[endif]"]);
+        assert_eq!(child_names(&body), ["#other", "p", "#other"]);
+    }
+
+    #[test]
+    fn comments_in_elements() {
+        let document = parse("<html><body><table><!--a--><tr><td>x</td></tr></table><!--b-->");
+        let html = document.document_element().unwrap();
+        let table = find_element(&html, &TagName::Table).unwrap();
+        assert_eq!(child_names(&table), ["#other", "tbody"]);
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(child_names(&body), ["table", "#other"]);
+    }
+
+    #[test]
+    fn script_elements() {
+        let document = parse(
+            "<html><head><script>if (a < b) { document.write('<p>') }</script></head>\
+             <body><script><!--<script>x</script>-->y</script><p>after</p>",
+        );
+        let html = document.document_element().unwrap();
+        let head = find_element(&html, &TagName::Head).unwrap();
+        assert_eq!(child_names(&head), ["script"]);
+        let script = find_element(&head, &TagName::Script).unwrap();
+        assert_eq!(text_of(&script), "if (a < b) { document.write('<p>') }");
+        assert_eq!(
+            script.borrow().script_flags(),
+            dom::ScriptFlags {
+                already_started: true,
+                parser_inserted: true,
+            }
+        );
+
+        // A <script> inside an escaped comment doesn't end the script
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(child_names(&body), ["script", "p"]);
+        let script = find_element(&body, &TagName::Script).unwrap();
+        assert_eq!(text_of(&script), "<!--<script>x</script>-->y");
+    }
+
+    fn parse_with_scripts(html: &str) -> Document {
+        let mut parser = Parser::new(Cursor::new(html));
+        parser.set_script_handler(|text: &str, document: &mut DocumentWrite| {
+            if let Some(written) = text.strip_prefix("write:") {
+                document.write(&written.replace("<\\/", "</"));
+            }
+        });
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn document_write() {
+        let document = parse_with_scripts(
+            "<html><body><script>write:<p>written</script><p>static</p>\
+             <script type=module>write:<i></script><script src=a.js>write:<i></script>",
+        );
+        let html = document.document_element().unwrap();
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(child_names(&body), ["script", "p", "p", "script", "script"]);
+        let p = find_element(&body, &TagName::P).unwrap();
+        assert_eq!(text_of(&p), "written");
+    }
+
+    #[test]
+    fn document_write_nested_script() {
+        // The written script runs after the script which wrote it
+        let document = parse_with_scripts(
+            "<html><body><script>write:<script>write:<b><\\/script><i></script>",
+        );
+        let html = document.document_element().unwrap();
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(child_names(&body), ["script", "script", "b"]);
+        let b = find_element(&body, &TagName::B).unwrap();
+        assert_eq!(child_names(&b), ["i"]);
+    }
+
+    #[test]
+    fn noscript_with_scripting_enabled() {
+        let document = parse_with_scripts("<html><body><noscript><p>x</p></noscript>");
+        let html = document.document_element().unwrap();
+        let noscript = find_element(&html, &TagName::Noscript).unwrap();
+        assert_eq!(text_of(&noscript), "<p>x</p>");
+    }
+
+    #[test]
+    fn template_contents() {
+        let document = parse(
+            "<html><head><template><p>a</template></head>\
+             <body><template><tr><td>b</td></tr><template><col></template></template><p>c",
+        );
+        let html = document.document_element().unwrap();
+        let head = find_element(&html, &TagName::Head).unwrap();
+        let template = find_element(&head, &TagName::Template).unwrap();
+        // The template's children go in its contents, rather than in the element
+        assert!(template.borrow().is_empty());
+        let contents = template.borrow().template_contents().cloned().unwrap();
+        let p = contents.borrow().children()[0].element().unwrap();
+        assert_eq!(text_of(&p), "a");
+        assert!(p.borrow().parent().is_none());
+        assert!(matches!(
+            p.borrow().parent_node(),
+            Some(dom::element::ParentNode::DocumentFragment(ref f)) if Rc::ptr_eq(f, &contents)
+        ));
+
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(child_names(&body), ["template", "p"]);
+        assert_eq!(
+            body.borrow().inner_html(),
+            "<template><tr><td>b</td></tr><template><col></template></template><p>c</p>"
+        );
+    }
+
+    #[test]
+    fn template_eof() {
+        let document = parse("<html><body><template><table>x<tr><td>1");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><template>x<table><tbody><tr><td>1</td></tr></tbody>\
+             </table></template></body></html>"
+        );
+    }
+
+    #[test]
+    fn fragment_in_template() {
+        let template = dom::Element::new_html(TagName::Template);
+        let fragment = parse_fragment("<td>a</td><p>b", &template);
+        assert_eq!(fragment.to_html(), "<td>a</td><p>b</p>");
+    }
+
+    #[test]
+    fn missing_html_element() {
+        let document = parse("<!DOCTYPE html><p>a");
+        assert_eq!(
+            document.to_html(),
+            "<!DOCTYPE html><html><head></head><body><p>a</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn noscript_in_head() {
+        let document = parse("<html><head><noscript><link></noscript><noscript>a");
+        assert_eq!(
+            document.to_html(),
+            "<html><head><noscript><link></noscript><noscript></noscript></head>\
+             <body>a</body></html>"
+        );
+    }
+
+    #[test]
+    fn select() {
+        let document = parse(
+            "<html><body><select><option>a<optgroup><option>b<hr></optgroup><p>c</select>d\
+             <table><tr><td><select><option>e</td><td>f",
+        );
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><select><option>a</option><optgroup><option>b</option>\
+             </optgroup><hr>c</select>d<table><tbody><tr><td><select><option>e</option>\
+             </select></td><td>f</td></tr></tbody></table></body></html>"
+        );
+    }
+
+    #[test]
+    fn frameset() {
+        let document = parse(
+            "<html><head></head><frameset><frameset><frame></frameset><frame>x\
+             </frameset><noframes>y</noframes></html><!-- z -->",
+        );
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><frameset><frameset><frame></frameset><frame></frameset>\
+             <noframes>y</noframes></html><!-- z -->"
+        );
+
+        // A frameset replaces the body, unless it already has content
+        let document = parse("<html><p><frameset><frame>");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><frameset><frame></frameset></html>"
+        );
+        let document = parse("<html><body>x<frameset><frame>");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body>x</body></html>"
+        );
+
+        // Only the whitespace of any text is kept
+        let document = parse("<html><frameset> a <frame></frameset> b\n</html>c ");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><frameset>  <frame></frameset> \n </html>"
+        );
+    }
+
+    #[test]
+    fn body_start_tag_adds_attributes() {
+        let document = parse("<html><body class=a><div><body class=b id=c>");
+        let html = document.document_element().unwrap();
+        let body = find_element(&html, &TagName::Body).unwrap();
+        assert_eq!(
+            body.borrow().attributes(),
+            &[
+                dom::Attribute::new("class", "a"),
+                dom::Attribute::new("id", "c")
+            ]
+        );
+    }
+
+    #[test]
+    fn leading_newline_is_ignored() {
+        let document =
+            parse("<html><pre>\na</pre><listing>\n\nb</listing><textarea>\nc</textarea>");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><pre>a</pre><listing>\nb</listing>\
+             <textarea>c</textarea></body></html>"
+        );
+
+        // However the line feed was written
+        let html = "<html><pre>\r\nd</pre><pre>&#10;e</pre><pre><!---->\nf</pre>";
+        let document = parse(html);
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><pre>d</pre><pre>e</pre><pre><!---->\nf</pre></body></html>"
+        );
+        let pre = find_element(&document.document_element().unwrap(), &TagName::Pre).unwrap();
+        let pre = pre.borrow();
+        let children: Vec<_> = pre.iter().collect();
+        match &children[0] {
+            dom::element::ChildNode::Text(t) => {
+                assert_eq!(t.borrow().span().unwrap().slice(html), "d")
+            }
+            _ => panic!("expected text"),
+        }
+    }
+
+    #[test]
+    fn uncollapsed_characters() {
+        // Each character token is parsed as a run of one, with the same result
+        let html = "<html> <head> <title>a&amp;b</title> </head> x<table> y <tr><td>z</table>\
+                    <pre>\nw</pre><svg>\0v</svg>";
+        let tokenizer = Tokenizer::new(html.as_bytes(), false);
+        let document = Parser::with_tokenizer(tokenizer, Document::default())
+            .parse()
+            .unwrap();
+        assert_eq!(document.to_html(), parse(html).to_html());
+    }
+
+    #[test]
+    fn whitespace_before_body() {
+        let html = "\u{C}<!DOCTYPE html>\r\n<html> <head>\r\n <title>t</title>\r\n x y</html>";
+        let document = parse(html);
+        assert_eq!(
+            document.to_html(),
+            "<!DOCTYPE html><html><head>\n <title>t</title>\n </head><body>x y</body></html>"
+        );
+
+        // The whitespace is split from the text after it, which starts the body
+        let spans = |elem: &Rc<RefCell<dom::Element>>| -> Vec<&str> {
+            elem.borrow()
+                .iter()
+                .filter_map(|child| match child {
+                    dom::element::ChildNode::Text(t) => {
+                        Some(t.borrow().span().unwrap().slice(html))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        let root = document.document_element().unwrap();
+        let head = find_element(&root, &TagName::Head).unwrap();
+        assert_eq!(spans(&head), ["\r\n ", "\r\n "]);
+        let body = find_element(&root, &TagName::Body).unwrap();
+        assert_eq!(spans(&body), ["x y"]);
+    }
+
+    #[test]
+    fn forms() {
+        let document = parse(
+            "<html><form><input type=hidden><button>a<button>b</button></form>\
+             <form><form><select><option>c<input></form><br/></br>",
+        );
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><form><input type=\"hidden\"><button>a</button>\
+             <button>b</button></form><form><select><option>c</option></select><input>\
+             </form><br><br></body></html>"
+        );
+    }
+
+    #[test]
+    fn definition_lists() {
+        let document = parse("<html><dl><dt>a<dd>b<div><dt>c</div></dd><dt>d</dt></dd></dl>");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><dl><dt>a</dt><dd>b<div></div></dd><dt>c</dt>\
+             <dt>d</dt></dl></body></html>"
+        );
+    }
+
+    #[test]
+    fn void_and_raw_text_elements() {
+        let document = parse(
+            "<html><img src=x><image><hr><wbr><embed><p><hr>a<xmp><b>b</xmp>\
+             <iframe><i>c</iframe><plaintext></plaintext>",
+        );
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><img src=\"x\"><img><hr><wbr><embed><p></p><hr>a\
+             <xmp><b>b</xmp><iframe><i>c</iframe><plaintext></plaintext></plaintext>\
+             </body></html>"
+        );
+    }
+
+    #[test]
+    fn object_and_ruby() {
+        let document =
+            parse("<html><b><object><i>a</b></object>b<ruby>c<rb>d<rt>e<rtc>f<rp>g</ruby>");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><b><object><i>a</i></object>b<ruby>c<rb>d</rb>\
+             <rt>e</rt><rtc>f<rp>g</rp></rtc></ruby></b></body></html>"
+        );
+    }
+
+    #[test]
+    fn source_spans() {
+        let html = "<html><body>\n<p class=\"a\" id=b>x &amp; y<!--c--></p>\
+                    <table>z<tr></table>";
+        let document = parse(html);
+        let root = document.document_element().unwrap();
+        assert_eq!(root.borrow().span().unwrap().slice(html), "<html>");
+        let body = find_element(&root, &TagName::Body).unwrap();
+        assert_eq!(body.borrow().span().unwrap().slice(html), "<body>");
+        // Implied elements weren't in the input
+        let tbody = find_element(&root, &TagName::Tbody).unwrap();
+        assert_eq!(tbody.borrow().span(), None);
+
+        let p = find_element(&root, &TagName::P).unwrap();
+        let p = p.borrow();
+        let span = p.span().unwrap();
+        assert_eq!(span.slice(html), "<p class=\"a\" id=b>");
+        assert_eq!((span.start.line, span.start.column), (2, 1));
+        let spans: Vec<_> = p
+            .attributes()
+            .iter()
+            .map(|a| {
+                (
+                    a.name_span().unwrap().slice(html),
+                    a.value_span().unwrap().slice(html),
+                )
+            })
+            .collect();
+        assert_eq!(spans, [("class", "a"), ("id", "b")]);
+
+        let children: Vec<_> = p.iter().collect();
+        match &children[0] {
+            dom::element::ChildNode::Text(t) => {
+                assert_eq!(t.borrow().span().unwrap().slice(html), "x &amp; y")
+            }
+            _ => panic!("expected text"),
+        }
+        match &children[1] {
+            dom::element::ChildNode::Comment(c) => {
+                assert_eq!(c.borrow().span().unwrap().slice(html), "<!--c-->")
+            }
+            _ => panic!("expected a comment"),
+        }
+
+        // Foster parented text keeps the span of the characters
+        let body = body.borrow();
+        let children: Vec<_> = body.iter().collect();
+        match &children[2] {
+            dom::element::ChildNode::Text(t) => {
+                assert_eq!(t.borrow().span().unwrap().slice(html), "z")
+            }
+            _ => panic!("expected text"),
+        }
+    }
+
+    #[test]
+    fn unrecognised_markup_declarations() {
+        let mut parser = Parser::new(Cursor::new("<!x><html><!><p><!-><!--a-->&b"));
+        parser.run().unwrap();
+        assert_eq!(
+            parser.errors()[0].error,
+            ParseError::IncorrectlyOpenedComment
+        );
+        assert_eq!(
+            parser.parse().unwrap().to_html(),
+            "<!--x--><html><!----><head></head><body><p><!-----><!--a-->&amp;b</p></body></html>"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut parser = Parser::new(Cursor::new("<html>\n<body a=1 a=2>"));
+        parser.run().unwrap();
+
+        let errors: Vec<_> = parser
+            .errors()
+            .iter()
+            .map(|e| (e.error, e.position.line, e.position.column))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (ParseError::TreeConstruction("Initial::on_token(_)"), 1, 6),
+                (ParseError::DuplicateAttribute, 2, 12),
+            ]
+        );
+    }
+
+    fn parse_fragment(html: &str, context: &Rc<RefCell<dom::Element>>) -> DocumentFragment {
+        let _ = pretty_env_logger::formatted_builder()
+            .is_test(true)
+            .try_init();
+        Parser::parse_fragment(Cursor::new(html), &context.borrow()).unwrap()
+    }
+
+    #[test]
+    fn fragment_in_div() {
+        let div = dom::Element::new_html(TagName::Div);
+        let fragment = parse_fragment("<p>a<b>b</b></p>c", &div);
+        assert_eq!(fragment.to_html(), "<p>a<b>b</b></p>c");
+        // The context element isn't changed
+        assert!(div.borrow().is_empty());
+    }
+
+    #[test]
+    fn fragment_in_table_body() {
+        let tbody = dom::Element::new_html(TagName::Tbody);
+        let fragment = parse_fragment("<tr><td>x</td></tr><td>y", &tbody);
+        assert_eq!(fragment.to_html(), "<tr><td>x</td></tr><tr><td>y</td></tr>");
+    }
+
+    #[test]
+    fn fragment_in_select() {
+        let select = dom::Element::new_html(TagName::Select);
+        let fragment = parse_fragment("<option>a<p>b", &select);
+        assert_eq!(fragment.to_html(), "<option>ab</option>");
+    }
+
+    #[test]
+    fn fragment_in_form() {
+        let form = dom::Element::new_html(TagName::Form);
+        let div = dom::Element::new_html(TagName::Div);
+        dom::Element::append_child(&form, Rc::clone(&div).into());
+        // A form can't be nested in the form the context element is in
+        let fragment = parse_fragment("<form><input></form>a", &div);
+        assert_eq!(fragment.to_html(), "<input>a");
+
+        let fragment = parse_fragment(
+            "<form><input></form>a",
+            &dom::Element::new_html(TagName::Div),
+        );
+        assert_eq!(fragment.to_html(), "<form><input></form>a");
+    }
+
+    #[test]
+    fn fragment_in_raw_text_elements() {
+        let text_of_fragment = |fragment: &DocumentFragment| match fragment.children() {
+            [dom::element::ChildNode::Text(text)] => text.borrow().to_string(),
+            children => panic!("Expected a single text node, got {:?}", children),
+        };
+
+        let title = dom::Element::new_html(TagName::Title);
+        let fragment = parse_fragment("<b>a &amp; b</b>", &title);
+        assert_eq!(text_of_fragment(&fragment), "<b>a & b</b>");
+
+        let style = dom::Element::new_html(TagName::Style);
+        let fragment = parse_fragment("<b>a &amp; b</b>", &style);
+        assert_eq!(text_of_fragment(&fragment), "<b>a &amp; b</b>");
+    }
+
+    #[test]
+    fn fragment_in_svg() {
+        let svg = dom::Element::new(TagName::Svg, dom::Namespace::SVG, Vec::new());
+        let fragment = parse_fragment("<circle/><foreignObject><p>x</p></foreignObject>", &svg);
+        assert_eq!(
+            fragment.to_html(),
+            "<circle></circle><foreignObject><p>x</p></foreignObject>"
+        );
+        match &fragment.children()[0] {
+            dom::element::ChildNode::Element(circle) => {
+                assert_eq!(circle.borrow().namespace(), dom::Namespace::SVG)
+            }
+            node => panic!("Expected an element, got {:?}", node),
+        }
+    }
+
+    fn body_html(html: &str) -> String {
+        parse_fragment(html, &dom::Element::new_html(TagName::Body)).to_html()
+    }
+
+    #[test]
+    fn misnested_formatting_elements() {
+        assert_eq!(
+            body_html("<p><b><i>x</b>y</i></p>"),
+            "<p><b><i>x</i></b><i>y</i></p>"
+        );
+        assert_eq!(body_html("<b>1<p>2</b>3</p>"), "<b>1</b><p><b>2</b>3</p>");
+        assert_eq!(
+            body_html("<a>1<div>2<a>3</a></div>"),
+            "<a>1</a><div><a>2</a><a>3</a></div>"
+        );
+        assert_eq!(
+            body_html("<a><p>X<a>Y</a>Z</p></a>"),
+            "<a></a><p><a>X</a><a>Y</a>Z</p>"
+        );
+        // Only the three nearest formatting elements are reopened around the furthest block
+        assert_eq!(
+            body_html("<b><em><i><s><u><div>x</b>y"),
+            "<b><em><i><s><u></u></s></i></em></b><i><s><u><div><b>x</b>y</div></u></s></i>"
+        );
+    }
+
+    #[test]
+    fn block_elements_respect_scope() {
+        // In quirks mode a table doesn't close the `<p>`, and the `<div>` in the cell can't
+        let document = parse("<p><table><tr><td><div>");
+        assert_eq!(
+            document.to_html(),
+            "<html><head></head><body><p><table><tbody><tr><td><div></div></td></tr></tbody>\
+             </table></p></body></html>"
+        );
+        // The `<div>` outside the table isn't in scope for the end tag in the cell
+        assert_eq!(
+            body_html("<div><table><tr><td></div>x</td></tr></table>y</div>"),
+            "<div><table><tbody><tr><td>x</td></tr></tbody></table>y</div>"
+        );
+    }
+
+    #[test]
+    fn p_inside_button() {
+        // The button is a scope boundary, so the inner `<p>` doesn't close the outer one
+        assert_eq!(
+            body_html("<p><button><p></button>x"),
+            "<p><button><p></p></button>x</p>"
+        );
+    }
+
+    /// Check every node under `elem` links to its parent and its siblings
+    fn assert_parent_links(elem: &Rc<RefCell<dom::Element>>) {
+        let children = elem.borrow().to_vec();
+        for (i, child) in children.iter().enumerate() {
+            let parent = child.parent_node().expect("node without a parent link");
+            assert!(parent.ptr_eq(&Rc::clone(elem).into()), "{:?}", child);
+            assert_eq!(
+                child.next_sibling().map(|n| n.as_ptr()),
+                children.get(i + 1).map(dom::element::ChildNode::as_ptr)
+            );
+            if let dom::element::ChildNode::Element(e) = child {
+                assert_parent_links(e);
+            }
+        }
+    }
+
+    #[test]
+    fn parent_links() {
+        let document = parse(
+            "<html><head><title>x</title></head><body>\
+             <p><b><i>1</b>2</i></p><a>3<div>4<a>5</a></div>\
+             <table><tr><td>6</td></tr>7<span>8</span></table></body></html>",
+        );
+        let html = document.document_element().unwrap();
+        assert!(html.borrow().parent().is_none());
+        assert_parent_links(&html);
+
+        let span = find_element(&html, &TagName::Span).unwrap();
+        let table = span.borrow().next_element_sibling().unwrap();
+        assert_eq!(table.borrow().name(), &TagName::Table);
+        assert_eq!(
+            span.borrow()
+                .ancestors()
+                .map(|e| e.borrow().name().clone())
+                .collect::<Vec<_>>(),
+            [TagName::Body, TagName::Html]
+        );
+    }
+
+    #[test]
+    fn reconstruct_active_formatting_elements() {
+        assert_eq!(
+            body_html("<p><b class=x><i>1</p>2"),
+            "<p><b class=\"x\"><i>1</i></b></p><b class=\"x\"><i>2</i></b>"
+        );
+        // Noah's Ark clause: only the last three identical elements are reopened
+        assert_eq!(
+            body_html("<p><b><b><b><b>x</p>y"),
+            "<p><b><b><b><b>x</b></b></b></b></p><b><b><b>y</b></b></b>"
+        );
+        // Elements with different attributes aren't identical
+        assert_eq!(
+            body_html("<p><b a=1><b a=2><b a=3><b a=4>x</p>y"),
+            "<p><b a=\"1\"><b a=\"2\"><b a=\"3\"><b a=\"4\">x</b></b></b></b></p>\
+             <b a=\"1\"><b a=\"2\"><b a=\"3\"><b a=\"4\">y</b></b></b></b>"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+//! Running scripts while parsing
+//!
+//! The parser doesn't run scripts itself. When it reaches the end tag of a `script` element it
+//! pauses and gives the script's text to a `ScriptHandler`. Anything the script writes with
+//! `document.write()` is inserted at the insertion point, just after the end tag, and is parsed
+//! before the rest of the input. A script in the written markup runs once the parser reaches
+//! its end tag, after the script which wrote it has finished.
+//!
+//! <https://html.spec.whatwg.org/multipage/parsing.html#scriptEndTag>
+
+/// Runs the scripts found by `Parser`
+///
+/// Closures taking the script's text and a `DocumentWrite` are also handlers.
+pub trait ScriptHandler {
+    /// Run the text of a `script` element whose end tag has just been parsed
+    fn run_script(&mut self, text: &str, document: &mut DocumentWrite);
+}
+
+impl<F> ScriptHandler for F
+where
+    F: FnMut(&str, &mut DocumentWrite),
+{
+    fn run_script(&mut self, text: &str, document: &mut DocumentWrite) {
+        self(text, document)
+    }
+}
+
+/// The markup written by a script, which is inserted into the input once the script has run
+#[derive(Debug, Default)]
+pub struct DocumentWrite {
+    written: String,
+}
+
+impl DocumentWrite {
+    /// <https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-document-write>
+    pub fn write(&mut self, text: &str) {
+        self.written.push_str(text);
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-document-writeln>
+    pub fn writeln(&mut self, text: &str) {
+        self.written.push_str(text);
+        self.written.push('\n');
+    }
+
+    pub(super) fn into_written(self) -> String {
+        self.written
+    }
+}
@@ -3,14 +3,14 @@ use std::io;
 use derive_more::{Display, From};
 
 use crate::{
-    parser::{errors, Parser, TransitionResult},
-    tokenizer::Token,
+    parser::{errors, Parser, TransitionResult, TreeSink},
+    tokenizer::{Span, Token},
 };
 
 macro_rules! create_states {
     ($($s:ident,)+) => {
         // #[derive(Copy, Clone, Debug, PartialEq, Eq, Display)]
-         #[derive(Debug, PartialEq, Eq, Display)]
+         #[derive(Clone, Debug, PartialEq, Eq, Display)]
         pub(super) enum States {
             $(
                 $s($s),
@@ -61,78 +61,83 @@ create_states! {
     Term,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct Initial {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct BeforeHtml {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct BeforeHead {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InHead {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InHeadNoscript {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct AfterHead {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InBody {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct Text {
     pub(super) original_insertion_mode: Box<States>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InTable {}
 
-#[derive(Debug, PartialEq, Eq)]
-pub(super) struct InTableText {}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct InTableText {
+    pub(super) original_insertion_mode: Box<States>,
+    pub(super) pending_table_character_tokens: String,
+    // Where the pending table character tokens were in the input
+    pub(super) pending_table_character_span: Option<Span>,
+}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InCaption {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InColumnGroup {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InTableBody {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InRow {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InCell {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InSelect {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InSelectInTable {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InTemplate {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct AfterBody {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct InFrameset {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct AfterFrameset {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct AfterAfterBody {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct AfterAfterFrameset {}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct Term {}
 
 impl States {
@@ -178,8 +183,12 @@ impl States {
         States::InTable(InTable {})
     }
 
-    pub(super) fn in_table_text() -> Self {
-        States::InTableText(InTableText {})
+    pub(super) fn in_table_text(original_insertion_mode: Box<States>) -> Self {
+        States::InTableText(InTableText {
+            original_insertion_mode,
+            pending_table_character_tokens: String::new(),
+            pending_table_character_span: None,
+        })
     }
 
     pub(super) fn in_caption() -> Self {
@@ -238,47 +247,68 @@ impl States {
         States::Term(Term {})
     }
 
+    /// Whether the insertion mode handles whitespace differently from other text, so the
+    /// tokenizer returns a run of whitespace on its own
+    ///
+    /// The frameset insertion modes pick the whitespace out of any text themselves.
+    pub(super) fn handles_whitespace(&self) -> bool {
+        matches!(
+            self,
+            States::Initial(_)
+                | States::BeforeHtml(_)
+                | States::BeforeHead(_)
+                | States::InHead(_)
+                | States::InHeadNoscript(_)
+                | States::AfterHead(_)
+                | States::InColumnGroup(_)
+                | States::AfterBody(_)
+                | States::AfterAfterBody(_)
+        )
+    }
+
     // Transitions
 
-    pub(super) fn on_token<R>(self, parser: &mut Parser<R>, input: &Token) -> TransitionResult
+    pub(super) fn on_token<R, S>(self, parser: &mut Parser<R, S>, input: &Token) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         match self {
             States::Initial(state) => state.on_token(parser, input),
             States::BeforeHtml(state) => state.on_token(parser, input),
             States::BeforeHead(state) => state.on_token(parser, input),
             States::InHead(state) => state.on_token(parser, input),
-            // States::InHeadNoscript(state) => state.on_token(parser, input),
+            States::InHeadNoscript(state) => state.on_token(parser, input),
             States::AfterHead(state) => state.on_token(parser, input),
             States::InBody(state) => state.on_token(parser, input),
             States::Text(state) => state.on_token(parser, input),
-            // States::InTable(state) => state.on_token(parser, input),
-            // States::InTableText(state) => state.on_token(parser, input),
-            // States::InCaption(state) => state.on_token(parser, input),
-            // States::InColumnGroup(state) => state.on_token(parser, input),
-            // States::InTableBody(state) => state.on_token(parser, input),
-            // States::InRow(state) => state.on_token(parser, input),
-            // States::InCell(state) => state.on_token(parser, input),
-            // States::InSelect(state) => state.on_token(parser, input),
-            // States::InSelectInTable(state) => state.on_token(parser, input),
-            // States::InTemplate(state) => state.on_token(parser, input),
+            States::InTable(state) => state.on_token(parser, input),
+            States::InTableText(state) => state.on_token(parser, input),
+            States::InCaption(state) => state.on_token(parser, input),
+            States::InColumnGroup(state) => state.on_token(parser, input),
+            States::InTableBody(state) => state.on_token(parser, input),
+            States::InRow(state) => state.on_token(parser, input),
+            States::InCell(state) => state.on_token(parser, input),
+            States::InSelect(state) => state.on_token(parser, input),
+            States::InSelectInTable(state) => state.on_token(parser, input),
+            States::InTemplate(state) => state.on_token(parser, input),
             States::AfterBody(state) => state.on_token(parser, input),
-            // States::InFrameset(state) => state.on_token(parser, input),
-            // States::AfterFrameset(state) => state.on_token(parser, input),
+            States::InFrameset(state) => state.on_token(parser, input),
+            States::AfterFrameset(state) => state.on_token(parser, input),
             States::AfterAfterBody(state) => state.on_token(parser, input),
-            // States::AfterAfterFrameset(state) => state.on_token(parser, input),
+            States::AfterAfterFrameset(state) => state.on_token(parser, input),
             _ => Err(errors::StateTransitionError::new(self, "Token")).into(),
         }
     }
 
-    pub(super) fn execute<R>(
+    pub(super) fn execute<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         input: &StateMachineMessages<'_>,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         match input {
             StateMachineMessages::Token(token) => self.on_token(parser, token),
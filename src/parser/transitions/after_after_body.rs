@@ -1,48 +1,46 @@
 use std::io;
 
 use crate::{
-    dom,
-    parser::{parse_error, states::{self, States}, transitions::in_body, Parser, TransitionResult},
+    parser::{states::{self, States}, transitions::in_body, Parser, TransitionResult, TreeSink},
     tokenizer::{TagName, Token},
 };
 
 impl states::AfterAfterBody {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
         Token::Comment(comment) => {
-            let node = dom::Comment::new(comment.clone());
-            parser.document.push_comment(node);
+            parser.append_comment_to_document(comment);
             current_state.into_transition_result()
         }
-        Token::Doctype(_)
-        | Token::Character('\t')
-        | Token::Character('\n')
-        | Token::Character(' ') => in_body::transition(current_state, parser, t),
+        Token::Doctype(_) => in_body::transition(current_state, parser, t),
+        Token::Characters(_) if t.is_whitespace() => in_body::transition(current_state, parser, t),
         Token::StartTag(tag) if tag.name == TagName::Html => {
-            todo!("AfterAfterBody::on_token('html')");
+            in_body::transition(current_state, parser, t)
         }
         Token::Eof => States::term().into_transition_result(),
         _ => {
-            parse_error("AfterAfterBody::on_token(_)");
+            parser.parse_error("AfterAfterBody::on_token(_)");
 
             let mut ret = States::in_body().into_transition_result();
             ret.set_reprocess();
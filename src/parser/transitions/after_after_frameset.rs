@@ -0,0 +1,66 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::AfterAfterFrameset {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-frameset-insertion-mode>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Comment(comment) => {
+            parser.append_comment_to_document(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => in_body::transition(current_state, parser, t),
+        Token::Characters(s) => {
+            // Only the whitespace is processed, anything else is a parse error and ignored
+            let whitespace: String = s.chars().filter(char::is_ascii_whitespace).collect();
+            if whitespace.len() != s.len() {
+                parser.parse_error("AfterAfterFrameset::on_token(Characters)");
+            }
+            if whitespace.is_empty() {
+                current_state.into_transition_result()
+            } else {
+                in_body::transition(current_state, parser, &Token::Characters(whitespace))
+            }
+        }
+        Token::StartTag(tag) if tag.name == TagName::Html => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::Eof => States::term().into_transition_result(),
+        Token::StartTag(tag) if tag.name == TagName::Noframes => {
+            in_head::transition(current_state, parser, t)
+        }
+        _ => {
+            parser.parse_error("AfterAfterFrameset::on_token(_)");
+            current_state.into_transition_result()
+        }
+    }
+}
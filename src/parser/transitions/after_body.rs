@@ -1,46 +1,52 @@
-use std::io;
+use std::{io, rc::Rc};
 
 use log::warn;
 
 use crate::{
-    parser::{states::{self, States}, transitions, Parser, TransitionResult, parse_error},
+    parser::{states::{self, States}, transitions, Parser, TransitionResult, TreeSink},
     tokenizer::{TagName, Token},
 };
 
 impl states::AfterBody {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
-        Token::Character('\t') | Token::Character('\n') | Token::Character(' ') => {
+        Token::Characters(_) if t.is_whitespace() => {
             transitions::in_body::transition(current_state, parser, t)
         }
-        Token::Comment(_comment) => {
-            todo!("AfterBody::on_token(Comment)");
+        Token::Comment(comment) => {
+            // Insert the comment as the last child of the html element
+            if let Some(html) = parser.open_elements.first().map(Rc::clone) {
+                parser.append_comment_to(&html, comment);
+            }
+            current_state.into_transition_result()
         }
         Token::Doctype(_) => {
-            parse_error("AfterBody::on_token(Doctype)");
+            parser.parse_error("AfterBody::on_token(Doctype)");
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Html => {
-            todo!("AfterBody::on_token('html')");
+            transitions::in_body::transition(current_state, parser, t)
         }
         Token::EndTag(tag) if tag.name == TagName::Html => {
             warn!("TODO: ...");
@@ -48,7 +54,7 @@ where
         }
         Token::Eof => States::term().into_transition_result(),
         _ => {
-            parse_error("AfterBody::on_token(_)");
+            parser.parse_error("AfterBody::on_token(_)");
 
             let mut ret = States::in_body().into_transition_result();
             ret.set_reprocess();
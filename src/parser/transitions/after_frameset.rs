@@ -0,0 +1,71 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::AfterFrameset {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterframeset>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Characters(s) => {
+            // Only the whitespace is inserted, anything else is a parse error and ignored
+            let whitespace: String = s.chars().filter(char::is_ascii_whitespace).collect();
+            if whitespace.len() != s.len() {
+                parser.parse_error("AfterFrameset::on_token(Characters)");
+            }
+            if !whitespace.is_empty() {
+                parser.insert_character(whitespace);
+            }
+            current_state.into_transition_result()
+        }
+        Token::Comment(comment) => {
+            parser.insert_comment(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => {
+            parser.parse_error("AfterFrameset::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Html => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Html => {
+            States::after_after_frameset().into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Noframes => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::Eof => States::term().into_transition_result(),
+        _ => {
+            parser.parse_error("AfterFrameset::on_token(_)");
+            current_state.into_transition_result()
+        }
+    }
+}
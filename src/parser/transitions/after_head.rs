@@ -1,58 +1,67 @@
-use std::io;
+use std::{io, rc::Rc};
 
 use crate::{
     dom,
-    parser::{parse_error, self, states::{self, States}, Parser, TransitionResult},
+    parser::{
+        self,
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
     tokenizer::{TagName, Token},
 };
 
 impl states::AfterHead {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
         match t {
-            Token::Character(ch @ '\t') | Token::Character(ch @ '\n') | Token::Character(ch @ ' ') => {
-                parser.insert_character(ch.to_string());
+            Token::Characters(s) if t.is_whitespace() => {
+                parser.insert_character(s);
                 current_state.into_transition_result()
             }
             Token::Comment(comment) => {
-                let node = dom::Comment::new(comment.clone());
-                parser.document.push_comment(node);
+                parser.insert_comment(comment);
                 current_state.into_transition_result()
             }
             Token::Doctype(_) => {
-                parse_error("AfterHead::on_token(Doctype)");
+                parser.parse_error("AfterHead::on_token(Doctype)");
                 current_state.into_transition_result()
             }
             Token::StartTag(tag) if tag.name == TagName::Html => {
-                todo!("AfterHead::on_token('html')");
+                in_body::transition(current_state, parser, t)
             }
             Token::StartTag(tag) if tag.name == TagName::Body => {
-                let node = dom::Element::new_html(TagName::Body);
+                let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
                 parser.insert_html_element(node);
                 parser.frameset_ok = parser::FramesetOkFlag::NotOk;
 
                 States::in_body().into_transition_result()
             }
             Token::StartTag(tag) if tag.name == TagName::Frameset => {
-                todo!("AfterHead::on_token('frameset')");
+                let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
+                parser.insert_html_element(node);
+
+                States::in_frameset().into_transition_result()
             }
             Token::StartTag(tag)
                 if (tag.name == TagName::Base
@@ -66,31 +75,47 @@ where
                     || tag.name == TagName::Template
                     || tag.name == TagName::Title) =>
             {
-                parse_error("AfterHead::on_token(StartTag('base|basefont|bgsound|link|meta|noframes|script|style|template|title'))");
-                todo!("AfterHead::on_token('base|basefont|bgsound|link|meta|noframes|script|style|template|title')");
+                parser.parse_error("AfterHead::on_token(StartTag('base|basefont|bgsound|link|meta|noframes|script|style|template|title'))");
+                let head = match parser.head_element_pointer {
+                    Some(ref head) => Rc::clone(head),
+                    None => return in_head::transition(current_state, parser, t),
+                };
+                parser.open_elements.push(Rc::clone(&head));
+                let ret = in_head::transition(current_state, parser, t);
+                // The head element might not be the current node any more
+                parser.open_elements.remove_element(&head);
+                ret
             }
             Token::EndTag(tag) if tag.name == TagName::Template => {
-                todo!("AfterHead::on_token('template')");
+                in_head::transition(current_state, parser, t)
             }
             Token::EndTag(tag)
                 if (tag.name == TagName::Body || tag.name == TagName::Html || tag.name == TagName::Br) =>
             {
                 // Insert an HTML element for a "body" start tag token with no attributes.
-                let node = dom::Element::new_html(TagName::Body);
-                parser.document.push_element(node);
+                let node = parser.create_html_element(TagName::Body);
+                parser.insert_html_element(node);
 
                 let mut ret = States::in_body().into_transition_result();
                 ret.set_reprocess();
                 ret
             }
             Token::StartTag(tag) if tag.name == TagName::Head => {
-                parse_error("AfterHead::on_token(StartTag('head'))");
+                parser.parse_error("AfterHead::on_token(StartTag('head'))");
                 current_state.into_transition_result()
             }
             Token::EndTag(_) => {
-                parse_error("AfterHead::on_token(EndTag(_))");
+                parser.parse_error("AfterHead::on_token(EndTag(_))");
                 current_state.into_transition_result()
             }
-            _ => todo!("AfterHead::on_token(_)"),
+            _ => {
+                // Insert an HTML element for a "body" start tag token with no attributes.
+                let node = parser.create_html_element(TagName::Body);
+                parser.insert_html_element(node);
+
+                let mut ret = States::in_body().into_transition_result();
+                ret.set_reprocess();
+                ret
+            }
         }
     }
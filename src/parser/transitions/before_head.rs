@@ -2,49 +2,50 @@ use std::io;
 
 use crate::{
     dom,
-    parser::{states::{self, States}, Parser, TransitionResult, parse_error},
+    parser::{states::{self, States}, transitions::in_body, Parser, TransitionResult, TreeSink},
     tokenizer::{TagName, Token},
 };
 
 impl states::BeforeHead {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
-        Token::Character('\t') | Token::Character('\n') | Token::Character(' ') => {
+        Token::Characters(_) if t.is_whitespace() => {
             current_state.into_transition_result()
         }
         Token::Comment(comment) => {
-            let node = dom::Comment::new(comment.clone());
-            parser.document.push_comment(node);
+            parser.insert_comment(comment);
             current_state.into_transition_result()
         }
         Token::Doctype(_) => {
-            parse_error("BeforeHead::on_token(Doctype)");
+            parser.parse_error("BeforeHead::on_token(Doctype)");
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Html => {
-            todo!("Process the token using the rules for the \"in body\" insertion mode.")
+            in_body::transition(current_state, parser, t)
         }
         Token::StartTag(tag) if tag.name == TagName::Head => {
-            let node = dom::Element::new_html(TagName::Head);
+            let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
             parser.insert_html_element(node.clone());
             parser.set_head(node);
             States::in_head().into_transition_result()
@@ -56,7 +57,7 @@ where
                 && tag.name != TagName::Br) =>
         {
             // Parse error. Ignore the token.
-            parse_error("BeforeHead::on_token(EndTag(_))");
+            parser.parse_error("BeforeHead::on_token(EndTag(_))");
             current_state.into_transition_result()
         }
         _ => {
@@ -65,9 +66,8 @@ where
             // Switch the insertion mode to "in head".
             // Reprocess the current token.
 
-            let node = dom::Element::new_html(TagName::Head);
-            parser.document.push_element(node.clone());
-            parser.open_elements.push(node.clone());
+            let node = parser.create_html_element(TagName::Head);
+            parser.insert_html_element(node.clone());
             parser.set_head(node);
 
             let mut ret = States::in_head().into_transition_result();
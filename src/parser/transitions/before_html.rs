@@ -2,44 +2,48 @@ use std::io;
 
 use crate::{
     dom,
-    parser::{states::{self, States}, Parser, TransitionResult, parse_error},
+    parser::{states::{self, States}, Parser, TransitionResult, TreeSink},
     tokenizer::{TagName, Token},
 };
 
 impl states::BeforeHtml {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
         Token::Doctype(_) => {
-            parse_error("BeforeHtml::on_token(Doctype)");
+            parser.parse_error("BeforeHtml::on_token(Doctype)");
             current_state.into_transition_result()
         }
-        Token::Character('\t') | Token::Character('\n') | Token::Character(' ') => {
+        Token::Comment(comment) => {
+            parser.append_comment_to_document(comment);
+            current_state.into_transition_result()
+        }
+        Token::Characters(_) if t.is_whitespace() => {
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Html => {
-            let elem = dom::Element::new_html(tag.name.clone());
-            // TODO: We shouldn't be cloning here, I'm guessing we'll need RC,
-            // but then how to mutate? When do we need to mutate?
-            parser.document.push_element(elem.clone());
+            let elem = parser.create_element_for_token(tag, dom::Namespace::HTML);
+            parser.append_element_to_document(&elem);
             parser.open_elements.push(elem);
 
             // TODO: If the Document is being loaded as part of navigation of a browsing context and the result of executing Is environment settings object a secure context? on the Document's relevant settings object is true, then:
@@ -53,9 +57,17 @@ where
                 && tag.name != TagName::Br) =>
         {
             // Parse error. Ignore the token.
-            parse_error("BeforeHtml::on_token(EndTag(_))");
+            parser.parse_error("BeforeHtml::on_token(EndTag(_))");
             current_state.into_transition_result()
         }
-        _ => todo!("BeforeHtml::on_token({:?})", t),
+        _ => {
+            let elem = parser.create_html_element(TagName::Html);
+            parser.append_element_to_document(&elem);
+            parser.open_elements.push(elem);
+
+            let mut ret = States::before_head().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
     }
 }
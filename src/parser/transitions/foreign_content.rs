@@ -0,0 +1,189 @@
+use std::io;
+
+use log::trace;
+
+use crate::{
+    dom::Namespace,
+    parser::{states::States, FramesetOkFlag, Parser, TransitionResult, TreeSink},
+    tokenizer::{TagName, Token},
+};
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign>
+///
+/// The rules for parsing tokens in foreign content, `current_state` is the current
+/// insertion mode which is used when the token breaks out of foreign content.
+pub(in crate::parser) fn on_token<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Character(_) => unreachable!("Character tokens are parsed as runs of one"),
+        Token::Characters(chs) => {
+            if chs.contains('\0') {
+                parser.parse_error("ForeignContent::on_token(\\0)");
+            }
+            parser.insert_character(chs.replace('\0', "\u{FFFD}"));
+            if chs
+                .chars()
+                .any(|c| !matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ' | '\0'))
+            {
+                parser.frameset_ok = FramesetOkFlag::NotOk;
+            }
+            current_state.into_transition_result()
+        }
+        Token::Comment(comment) => {
+            parser.insert_comment(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => {
+            parser.parse_error("ForeignContent::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag)
+            if is_breakout_tag(&tag.name)
+                || (tag.name == TagName::Font
+                    && tag
+                        .attributes_iter()
+                        .any(|a| matches!(a.name.as_str(), "color" | "face" | "size"))) =>
+        {
+            parser.parse_error("ForeignContent::on_token(StartTag(_)) - HTML start tag");
+            pop_until_html_content(parser);
+            current_state.on_token(parser, t)
+        }
+        Token::EndTag(tag) if matches!(tag.name, TagName::Br | TagName::P) => {
+            parser.parse_error("ForeignContent::on_token(EndTag('br|p'))");
+            pop_until_html_content(parser);
+            current_state.on_token(parser, t)
+        }
+        Token::StartTag(tag) => {
+            let namespace = parser
+                .adjusted_current_node()
+                .map_or(Namespace::HTML, |node| node.namespace());
+
+            let node = parser.create_element_for_token(tag, namespace);
+            parser.insert_foreign_element(node);
+
+            if tag.is_self_closing() {
+                // Acknowledge the token's self-closing flag, and pop the current node
+                // off the stack of open elements.
+                // TODO: If the token's tag name is "script", and the new current node is in the
+                // SVG namespace, then act as described in the steps for a "script" end tag.
+                let _ = parser.open_elements.pop();
+            }
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag)
+            if tag.name == TagName::Script
+                && parser.current_node().is_some_and(|node| {
+                    node.namespace() == Namespace::SVG && node.name() == &TagName::Script
+                }) =>
+        {
+            // Pop the current node off the stack of open elements.
+            // TODO: Process the SVG script element.
+            let _ = parser.open_elements.pop();
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) => {
+            let mut i = parser.open_elements.len() - 1;
+            if parser.open_elements[i].name() != &tag.name {
+                parser.parse_error("ForeignContent::on_token(EndTag(_)) - Unexpected end tag");
+            }
+
+            loop {
+                // If node is the topmost element in the stack of open elements, return.
+                if i == 0 {
+                    return current_state.into_transition_result();
+                }
+
+                let node = &parser.open_elements[i];
+                if node.name() == &tag.name {
+                    trace!(
+                        "ForeignContent::on_token(EndTag(_)) - Popping {} element(s)",
+                        parser.open_elements.len() - i
+                    );
+                    parser.open_elements.truncate(i);
+                    return current_state.into_transition_result();
+                }
+
+                i -= 1;
+                if parser.open_elements[i].is_html() {
+                    return current_state.on_token(parser, t);
+                }
+            }
+        }
+        Token::Eof => current_state.on_token(parser, t),
+    }
+}
+
+/// While the current node is not a MathML text integration point, an HTML integration point,
+/// or an element in the HTML namespace, pop elements from the stack of open elements.
+fn pop_until_html_content<R, S>(parser: &mut Parser<R, S>)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    while let Some(node) = parser.current_node() {
+        if node.is_mathml_text_integration_point()
+            || node.is_html_integration_point()
+            || node.is_html()
+        {
+            break;
+        }
+        let _ = parser.open_elements.pop();
+    }
+}
+
+fn is_breakout_tag(name: &TagName) -> bool {
+    matches!(
+        name,
+        TagName::B
+            | TagName::Big
+            | TagName::Blockquote
+            | TagName::Body
+            | TagName::Br
+            | TagName::Center
+            | TagName::Code
+            | TagName::Dd
+            | TagName::Div
+            | TagName::Dl
+            | TagName::Dt
+            | TagName::Em
+            | TagName::Embed
+            | TagName::H1
+            | TagName::H2
+            | TagName::H3
+            | TagName::H4
+            | TagName::H5
+            | TagName::H6
+            | TagName::Head
+            | TagName::Hr
+            | TagName::I
+            | TagName::Img
+            | TagName::Li
+            | TagName::Listing
+            | TagName::Menu
+            | TagName::Meta
+            | TagName::Nobr
+            | TagName::Ol
+            | TagName::P
+            | TagName::Pre
+            | TagName::Ruby
+            | TagName::S
+            | TagName::Small
+            | TagName::Span
+            | TagName::Strong
+            | TagName::Strike
+            | TagName::Sub
+            | TagName::Sup
+            | TagName::Table
+            | TagName::Tt
+            | TagName::U
+            | TagName::Ul
+            | TagName::Var
+    )
+}
@@ -1,77 +1,86 @@
-use std::{io, rc::Rc};
+use std::{borrow::Cow, io, rc::Rc};
 
-use log::{trace, warn};
+use log::trace;
 
 use crate::{
-    dom::{Category, Comment, Element, Namespace},
+    dom::{Attribute, Category, Namespace, QuirksMode},
     parser::{
-        parse_error,
         states::{self, States},
-        FramesetOkFlag, Parser, ScriptingFlag, TransitionResult,
+        transitions::{in_head, in_template},
+        FramesetOkFlag, NodeOrText, Parser, ScriptingFlag, TransitionResult, TreeSink,
     },
-    tokenizer::{TagName, Token},
+    tokenizer::{EndTag, TagName, Token},
 };
 
 impl states::InBody {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
 #[allow(clippy::cognitive_complexity, clippy::too_many_lines)]
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
-        Token::Character('\0') => {
-            parse_error("InBody::on_token(\\0)");
-            current_state.into_transition_result()
-        }
-        Token::Character(ch @ '\t') | Token::Character(ch @ '\n') | Token::Character(ch @ ' ') => {
-            warn!(
-                "[TODO] InBody: '\\t|\\n| ' - Reconstruct the active formatting elements, if any."
-            );
-            parser.insert_character(ch.to_string());
-            current_state.into_transition_result()
-        }
-        Token::Characters(ch) => {
-            warn!("[TODO] InBody: _  - Reconstruct the active formatting elements, if any.");
-            parser.insert_character(ch.to_string());
-            parser.frameset_ok = FramesetOkFlag::NotOk;
-
-            current_state.into_transition_result()
-        }
-        Token::Character(ch) => {
-            warn!("[TODO] InBody: _  - Reconstruct the active formatting elements, if any.");
-            parser.insert_character(ch.to_string());
-            parser.frameset_ok = FramesetOkFlag::NotOk;
+        Token::Character(_) => unreachable!("Character tokens are parsed as runs of one"),
+        Token::Characters(s) => {
+            // NUL characters are a parse error and ignored
+            let text = if s.contains('\0') {
+                parser.parse_error("InBody::on_token(\\0)");
+                Cow::Owned(s.replace('\0', ""))
+            } else {
+                Cow::Borrowed(s.as_str())
+            };
+            if text.is_empty() {
+                return current_state.into_transition_result();
+            }
+            parser.reconstruct_the_active_formatting_elements();
+            parser.insert_character(&text);
+            if !text.bytes().all(|b| b.is_ascii_whitespace()) {
+                parser.frameset_ok = FramesetOkFlag::NotOk;
+            }
 
             current_state.into_transition_result()
         }
         Token::Comment(comment) => {
-            let node = Comment::new(comment.to_owned());
-            parser.document.push_comment(node);
+            parser.insert_comment(comment);
             current_state.into_transition_result()
         }
         Token::Doctype(_) => {
-            parse_error("InBody::on_token(Doctype)");
+            parser.parse_error("InBody::on_token(Doctype)");
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Html => {
-            parse_error("InBody::on_token(StartTag('html'))");
-            todo!("InBody::on_token('html')");
+            parser.parse_error("InBody::on_token(StartTag('html'))");
+            if parser.open_elements.contains_element(&TagName::Template) {
+                return current_state.into_transition_result();
+            }
+            // For each attribute on the token, check to see if the attribute is already present
+            // on the top element of the stack of open elements.
+            // If it is not, add the attribute and its corresponding value to that element.
+            if let Some(top) = parser.open_elements.first().map(Rc::clone) {
+                let attributes = tag
+                    .attributes_iter()
+                    .map(|attr| Attribute::new(attr.name.clone(), attr.value.clone()))
+                    .collect();
+                parser.add_attributes_if_missing(&top, attributes);
+            }
+            current_state.into_transition_result()
         }
         Token::StartTag(tag)
             if matches!(
@@ -88,26 +97,60 @@ where
                     | TagName::Title
             ) =>
         {
-            todo!("InBody::on_token('base|basefont|bgsound|link|meta|noframes|script|style|template|title')");
+            in_head::transition(current_state, parser, t)
         }
         Token::EndTag(tag) if tag.name == TagName::Template => {
-            todo!("InBody::on_token('template')");
+            in_head::transition(current_state, parser, t)
         }
         Token::StartTag(tag) if tag.name == TagName::Body => {
-            todo!("InBody::on_token('body')");
+            parser.parse_error("InBody::on_token(StartTag('body'))");
+            let body = match parser.open_elements.get(1) {
+                Some(body) if body.is_html() && body.name == TagName::Body => Rc::clone(body),
+                // fragment case
+                _ => return current_state.into_transition_result(),
+            };
+            if parser.open_elements.contains_element(&TagName::Template) {
+                return current_state.into_transition_result();
+            }
+
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+            let attributes = tag
+                .attributes_iter()
+                .map(|attr| Attribute::new(attr.name.clone(), attr.value.clone()))
+                .collect();
+            parser.add_attributes_if_missing(&body, attributes);
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Frameset => {
-            todo!("InBody::on_token('frameset')");
+            parser.parse_error("InBody::on_token(StartTag('frameset'))");
+            let body = match parser.open_elements.get(1) {
+                Some(body) if body.is_html() && body.name == TagName::Body => Rc::clone(body),
+                // fragment case
+                _ => return current_state.into_transition_result(),
+            };
+            if parser.frameset_ok == FramesetOkFlag::NotOk {
+                return current_state.into_transition_result();
+            }
+
+            parser.remove_from_parent(&body);
+            parser.open_elements.truncate(1);
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            States::in_frameset().into_transition_result()
         }
         Token::Eof => {
-            // TODO: If the stack of template insertion modes is not empty, then process the token using the rules for the "in template" insertion mode.
+            if !parser.template_insertion_modes.is_empty() {
+                return in_template::transition(current_state, parser, t);
+            }
 
             let has_unexpected_elem = parser
                 .open_elements
                 .iter()
                 .filter(|e| {
-                    let elem = e.borrow();
-                    let name = elem.name();
+                    let name = e.name();
 
                     name != &TagName::Dd
                         && name != &TagName::Dt
@@ -131,14 +174,14 @@ where
                 .count()
                 > 0;
             if has_unexpected_elem {
-                parse_error("Unexpected element(s) in stack of open elements");
+                parser.parse_error("Unexpected element(s) in stack of open elements");
             }
 
             States::term().into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Body => {
             if !parser.open_elements.contains_element(&TagName::Body) {
-                parse_error("No Body in stack of open elements");
+                parser.parse_error("No Body in stack of open elements");
                 // ignore the token.
                 return current_state.into_transition_result();
             }
@@ -146,8 +189,7 @@ where
                 .open_elements
                 .iter()
                 .filter(|e| {
-                    let elem = e.borrow();
-                    let name = elem.name();
+                    let name = e.name();
 
                     name != &TagName::Dd
                         && name != &TagName::Dt
@@ -171,14 +213,14 @@ where
                 .count()
                 > 0;
             if has_unexpected_elem {
-                parse_error("Unexpected element(s) in stack of open elements");
+                parser.parse_error("Unexpected element(s) in stack of open elements");
             }
 
             States::after_body().into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Html => {
             if !parser.open_elements.contains_element(&TagName::Body) {
-                parse_error("No Body in stack of open elements");
+                parser.parse_error("No Body in stack of open elements");
                 // ignore the token.
                 return current_state.into_transition_result();
             }
@@ -186,8 +228,7 @@ where
                 .open_elements
                 .iter()
                 .filter(|e| {
-                    let elem = e.borrow();
-                    let name = elem.name();
+                    let name = e.name();
 
                     name != &TagName::Dd
                         && name != &TagName::Dt
@@ -211,7 +252,7 @@ where
                 .count()
                 > 0;
             if has_unexpected_elem {
-                parse_error("Unexpected element(s) in stack of open elements");
+                parser.parse_error("Unexpected element(s) in stack of open elements");
             }
 
             let mut ret = States::after_body().into_transition_result();
@@ -247,11 +288,14 @@ where
                     | TagName::Ul
             ) =>
         {
-            if parser.open_elements.contains_element(&TagName::P) {
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
                 close_a_p_element(parser)
             }
 
-            let node = Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
             parser.insert_html_element(node);
 
             current_state.into_transition_result()
@@ -270,10 +314,10 @@ where
             }
 
             let current_node = parser.current_node().unwrap();
-            let is_html = current_node.borrow().is_html();
+            let is_html = current_node.is_html();
             if is_html
                 && matches!(
-                    current_node.borrow().name(),
+                    current_node.name(),
                     TagName::H1
                         | TagName::H2
                         | TagName::H3
@@ -282,38 +326,68 @@ where
                         | TagName::H6
                 )
             {
-                parse_error("<hN>");
+                parser.parse_error("<hN>");
                 let _ = parser.open_elements.pop();
             }
-            let node = Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
             parser.insert_html_element(node);
 
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if (tag.name == TagName::Pre || tag.name == TagName::Listing) => {
-            todo!("InBody::on_token('pre|listing')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            // A newline at the start of pre blocks is ignored as an authoring convenience
+            parser.ignore_next_line_feed();
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Form => {
-            todo!("InBody::on_token('form')");
+            let has_template = parser.open_elements.contains_element(&TagName::Template);
+            if parser.form_element_pointer.is_some() && !has_template {
+                parser.parse_error("InBody::on_token(StartTag('form'))");
+                return current_state.into_transition_result();
+            }
+
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(Rc::clone(&node));
+            if !has_template {
+                parser.form_element_pointer = Some(node);
+            }
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Li => {
             parser.frameset_ok = FramesetOkFlag::NotOk;
             for node in parser.open_elements.iter().rev() {
                 trace!("InBody:: <li>: Examining node: {:?}", node);
-                if node.borrow().name == TagName::Li {
+                if node.name == TagName::Li {
                     parser.generate_implied_end_tags(Some(&TagName::Li));
-                    if parser.current_node().unwrap().borrow().name != TagName::Li {
-                        parse_error("<li>");
+                    if parser.current_node().unwrap().name != TagName::Li {
+                        parser.parse_error("<li>");
                     }
                     parser.open_elements.pop_until(&[&TagName::Li]);
                     break;
                 }
 
-                if node.borrow().category() == Category::Special
-                    && !matches!(
-                        node.borrow().name,
-                        TagName::Address | TagName::Div | TagName::P
-                    )
+                if node.category() == Category::Special
+                    && !matches!(node.name, TagName::Address | TagName::Div | TagName::P)
                 {
                     break;
                 }
@@ -326,19 +400,77 @@ where
                 close_a_p_element(parser)
             }
 
-            let node = Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
             parser.insert_html_element(Rc::clone(&node));
 
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if (tag.name == TagName::Dd || tag.name == TagName::Dt) => {
-            todo!("InBody::on_token('dd|dt')");
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+            for node in parser.open_elements.iter().rev() {
+                trace!("InBody:: <dd|dt>: Examining node: {:?}", node);
+                if matches!(node.name, TagName::Dd | TagName::Dt) {
+                    let name = node.name.clone();
+                    parser.generate_implied_end_tags(Some(&name));
+                    if parser.current_node().unwrap().name != name {
+                        parser.parse_error("<dd|dt>");
+                    }
+                    parser.open_elements.pop_until(&[&name]);
+                    break;
+                }
+
+                if node.category() == Category::Special
+                    && !matches!(node.name, TagName::Address | TagName::Div | TagName::P)
+                {
+                    break;
+                }
+            }
+
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Plaintext => {
-            todo!("InBody::on_token('plaintext')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            // Once a plaintext start tag has been seen, that will be the last token ever seen
+            // other than character tokens (and the end-of-file token)
+            parser.switch_the_tokenizer_to_plaintext_state();
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Button => {
-            todo!("InBody::on_token('button')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_scope(&TagName::Button)
+            {
+                parser.parse_error("InBody::on_token('button') - button already in scope");
+                parser.generate_implied_end_tags(None);
+                parser.open_elements.pop_until(&[&TagName::Button]);
+            }
+
+            parser.reconstruct_the_active_formatting_elements();
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            current_state.into_transition_result()
         }
         Token::EndTag(tag)
             if matches!(
@@ -371,33 +503,73 @@ where
                     | TagName::Ul
             ) =>
         {
-            if !parser.open_elements.contains_element(&tag.name) {
-                parse_error(&format!("No {} in stack of open elements", &tag.name));
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_scope(&tag.name)
+            {
+                parser.parse_error("No matching element in scope");
                 return current_state.into_transition_result();
             }
 
             parser.generate_implied_end_tags(None);
             let current_node = parser.current_node().unwrap();
-            let current_node = current_node.borrow();
             if !(current_node.namespace == Namespace::HTML && current_node.name == tag.name) {
-                parse_error("Unexpected tag")
+                parser.parse_error("Unexpected tag")
             }
             parser.open_elements.pop_until(&[&tag.name]);
 
             current_state.into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Form => {
-            todo!("InBody::on_token(EndTag('form'))");
+            if parser.open_elements.contains_element(&TagName::Template) {
+                if !parser
+                    .open_elements
+                    .has_a_particular_element_in_scope(&TagName::Form)
+                {
+                    parser.parse_error("InBody::on_token(EndTag('form')) - form isn't in scope");
+                    return current_state.into_transition_result();
+                }
+                parser.generate_implied_end_tags(None);
+                let current_node = parser.current_node().unwrap();
+                if !(current_node.is_html() && current_node.name == TagName::Form) {
+                    parser.parse_error("InBody::on_token(EndTag('form')) - unclosed elements");
+                }
+                parser.open_elements.pop_until(&[&TagName::Form]);
+
+                return current_state.into_transition_result();
+            }
+
+            let node = match parser.form_element_pointer.take() {
+                Some(node) if parser.open_elements.position_of(&node).is_some() => node,
+                _ => {
+                    parser.parse_error("InBody::on_token(EndTag('form')) - no open form");
+                    return current_state.into_transition_result();
+                }
+            };
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_scope(&TagName::Form)
+            {
+                parser.parse_error("InBody::on_token(EndTag('form')) - form isn't in scope");
+                return current_state.into_transition_result();
+            }
+            parser.generate_implied_end_tags(None);
+            if !Rc::ptr_eq(&parser.current_node().unwrap(), &node) {
+                parser.parse_error("InBody::on_token(EndTag('form')) - unclosed elements");
+            }
+            parser.open_elements.remove_element(&node);
+
+            current_state.into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::P => {
-            if parser
+            if !parser
                 .open_elements
                 .has_a_particular_element_in_button_scope(&TagName::P)
             {
-                parse_error("</p>");
+                parser.parse_error("</p>");
 
                 // insert an HTML element for a "p" start tag token with no attributes.
-                let node = Element::new_html(TagName::P);
+                let node = parser.create_html_element(TagName::P);
                 parser.insert_html_element(node);
             }
 
@@ -406,24 +578,38 @@ where
             current_state.into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Li => {
-            if parser
+            if !parser
                 .open_elements
                 .has_a_particular_element_in_list_item_scope(&TagName::Li)
             {
-                parse_error("</li>");
+                parser.parse_error("</li>");
                 return current_state.into_transition_result();
             }
 
             parser.generate_implied_end_tags(Some(&TagName::Li));
-            if parser.current_node().unwrap().borrow().name != TagName::Li {
-                parse_error("</li>");
+            if parser.current_node().unwrap().name != TagName::Li {
+                parser.parse_error("</li>");
             }
             parser.open_elements.pop_until(&[&TagName::Li]);
 
             current_state.into_transition_result()
         }
         Token::EndTag(tag) if (tag.name == TagName::Dd || tag.name == TagName::Dt) => {
-            todo!("InBody::on_token(EndTag('dd|dt'))");
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_scope(&tag.name)
+            {
+                parser.parse_error("</dd|dt>");
+                return current_state.into_transition_result();
+            }
+
+            parser.generate_implied_end_tags(Some(&tag.name));
+            if parser.current_node().unwrap().name != tag.name {
+                parser.parse_error("</dd|dt>");
+            }
+            parser.open_elements.pop_until(&[&tag.name]);
+
+            current_state.into_transition_result()
         }
         Token::EndTag(tag)
             if matches!(
@@ -439,14 +625,13 @@ where
                 &TagName::H5,
                 &TagName::H6,
             ]) {
-                parse_error("</hN>");
+                parser.parse_error("</hN>");
                 return current_state.into_transition_result();
             }
             parser.generate_implied_end_tags(None);
             let current_node = parser.current_node().unwrap();
-            let current_node = current_node.borrow();
             if !(current_node.namespace == Namespace::HTML && current_node.name == tag.name) {
-                parse_error("Unexpected tag")
+                parser.parse_error("Unexpected tag")
             }
             parser.open_elements.pop_until(&[
                 &TagName::H1,
@@ -459,31 +644,27 @@ where
             current_state.into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Other("sarcasm".to_string()) => {
-            panic!("This parser is very serious")
+            // Take a deep breath, then act as described in the "any other end tag" entry below.
+            any_other_end_tag(parser, tag);
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::A => {
-            if let Some((i, _)) = parser
+            if let Some(a) = parser
                 .list_of_active_formatting_elements
-                .iter()
-                .enumerate()
-                .rev()
-                .take_while(|(_, e)| !e.is_marker())
-                .find(|(_, e)| e.is_element(&TagName::A))
+                .last_element_before_marker(&TagName::A)
             {
-                parse_error("Existing A in active formatting elements");
-                // run the adoption agency algorithm for the token,
-                // then remove that element from the list of active formatting elements and
-                // the stack of open elements if the adoption agency algorithm
-                // didn't already remove it (it might not have if the element is not in table scope).
+                parser.parse_error(
+                    "InBody::on_token('a') - Existing a in active formatting elements",
+                );
                 adoption_agency_algorithm(parser, t);
-                parser.list_of_active_formatting_elements.remove(i);
-                todo!("InBody::on_token('a')");
+                // The adoption agency algorithm might not have removed it,
+                // if it wasn't in table scope
+                parser.list_of_active_formatting_elements.remove_element(&a);
+                parser.open_elements.remove_element(&a);
             }
-            warn!("[TODO] InBody: 'A' - Reconstruct the active formatting elements, if any.");
-            let node = Element::new_html(tag.name.clone());
-            parser.insert_html_element(Rc::clone(&node));
-
-            parser.list_of_active_formatting_elements.push(node.into());
+            parser.reconstruct_the_active_formatting_elements();
+            parser.insert_formatting_element_for_token(tag);
 
             current_state.into_transition_result()
         }
@@ -504,17 +685,26 @@ where
                     | TagName::U
             ) =>
         {
-            warn!("[TODO] InBody: 'input' - Reconstruct the active formatting elements, if any.");
-
-            let node = Element::new_html(tag.name.clone());
-            parser.insert_html_element(Rc::clone(&node));
+            parser.reconstruct_the_active_formatting_elements();
 
-            parser.list_of_active_formatting_elements.push(node.into());
+            parser.insert_formatting_element_for_token(tag);
 
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Nobr => {
-            todo!("InBody::on_token('nobr')");
+            parser.reconstruct_the_active_formatting_elements();
+            if parser
+                .open_elements
+                .has_a_particular_element_in_scope(&TagName::Nobr)
+            {
+                parser.parse_error("InBody::on_token('nobr') - nobr already in scope");
+                adoption_agency_algorithm(parser, t);
+                parser.reconstruct_the_active_formatting_elements();
+            }
+
+            parser.insert_formatting_element_for_token(tag);
+
+            current_state.into_transition_result()
         }
         Token::EndTag(tag)
             if matches!(
@@ -545,7 +735,13 @@ where
                 TagName::Applet | TagName::Marquee | TagName::Object
             ) =>
         {
-            todo!("InBody::on_token('applet|...')");
+            parser.reconstruct_the_active_formatting_elements();
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            parser.list_of_active_formatting_elements.push_marker();
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            current_state.into_transition_result()
         }
         Token::EndTag(tag)
             if matches!(
@@ -553,13 +749,51 @@ where
                 TagName::Applet | TagName::Marquee | TagName::Object
             ) =>
         {
-            todo!("InBody::on_token(endTag('applet|...'))");
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_scope(&tag.name)
+            {
+                parser.parse_error("InBody::on_token(EndTag('applet|...'))");
+                return current_state.into_transition_result();
+            }
+
+            parser.generate_implied_end_tags(None);
+            let current_node = parser.current_node().unwrap();
+            if !(current_node.is_html() && current_node.name == tag.name) {
+                parser.parse_error("InBody::on_token(EndTag('applet|...')) - unclosed elements");
+            }
+            parser.open_elements.pop_until(&[&tag.name]);
+            parser
+                .list_of_active_formatting_elements
+                .clear_up_to_last_marker();
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Table => {
-            todo!("InBody::on_token('table')");
+            if parser.quirks_mode() != QuirksMode::Quirks
+                && parser
+                    .open_elements
+                    .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            States::in_table().into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Br => {
-            todo!("InBody::on_token('br')");
+            parser.parse_error("InBody::on_token(EndTag('br'))");
+
+            // Drop the attributes from the token, and act as described in the next entry
+            parser.reconstruct_the_active_formatting_elements();
+            let node = parser.create_html_element(TagName::Br);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag)
             if matches!(
@@ -572,23 +806,26 @@ where
                     | TagName::Wbr
             ) =>
         {
-            todo!("InBody::on_token('area|...')");
+            parser.reconstruct_the_active_formatting_elements();
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Input => {
-            warn!("[TODO] InBody: 'input' - Reconstruct the active formatting elements, if any.");
+            parser.reconstruct_the_active_formatting_elements();
 
-            let node = Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
             parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
 
-            if tag.is_self_closing() {
-                warn!(
-                    "[TODO] InBody: 'input' - Acknowledge the token's self-closing flag, if it is set."
-                );
-            }
-
-            if tag
+            if !tag
                 .attributes_iter()
-                .any(|a| a.name == "type" && a.value.to_lowercase() == "hidden")
+                .any(|a| a.name == "type" && a.value.eq_ignore_ascii_case("hidden"))
             {
                 parser.frameset_ok = FramesetOkFlag::NotOk;
             }
@@ -598,57 +835,151 @@ where
         Token::StartTag(tag)
             if matches!(tag.name, TagName::Param | TagName::Source | TagName::Track) =>
         {
-            todo!("InBody::on_token('param|...')");
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Hr => {
-            todo!("InBody::on_token('hr')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Other("image".to_string()) => {
-            /*
-            // Don't ask, apparently
-            tag.name = TagName::Img;
-
-            let mut ret = current_state.into_transition_result();
-            ret.set_reprocess();
-            ret
-            */
+            parser.parse_error("InBody::on_token(StartTag('image'))");
 
-            todo!("InBody::on_token('image')");
+            // Don't ask, apparently
+            let mut img = tag.clone();
+            img.name = TagName::Img;
+            transition(current_state, parser, &Token::StartTag(img))
         }
         Token::StartTag(tag) if tag.name == TagName::Textarea => {
-            todo!("InBody::on_token('textarea')");
+            let ret = parser.generic_rcdata_element_parse(current_state, t);
+
+            // A newline at the start of textarea elements is ignored as an authoring convenience
+            parser.ignore_next_line_feed();
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            ret
         }
         Token::StartTag(tag) if tag.name == TagName::Xmp => {
-            todo!("InBody::on_token('xmp')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_button_scope(&TagName::P)
+            {
+                close_a_p_element(parser)
+            }
+            parser.reconstruct_the_active_formatting_elements();
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            parser.generic_raw_text_element_parse(current_state, t)
         }
         Token::StartTag(tag) if tag.name == TagName::Iframe => {
-            todo!("InBody::on_token('iframe')");
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            parser.generic_raw_text_element_parse(current_state, t)
         }
         Token::StartTag(tag) if tag.name == TagName::Noembed => {
-            todo!("InBody::on_token('noembed')");
+            parser.generic_raw_text_element_parse(current_state, t)
         }
         Token::StartTag(tag)
             if tag.name == TagName::Noscript && parser.scripting == ScriptingFlag::Enabled =>
         {
-            todo!("InBody::on_token('noscript')");
+            parser.generic_raw_text_element_parse(current_state, t)
         }
         Token::StartTag(tag) if tag.name == TagName::Select => {
-            todo!("InBody::on_token('select')");
+            parser.reconstruct_the_active_formatting_elements();
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+
+            match current_state {
+                States::InTable(_)
+                | States::InCaption(_)
+                | States::InTableBody(_)
+                | States::InRow(_)
+                | States::InCell(_) => States::in_select_in_table().into_transition_result(),
+                _ => States::in_select().into_transition_result(),
+            }
         }
         Token::StartTag(tag) if matches!(tag.name, TagName::Optgroup | TagName::Option) => {
-            todo!("InBody::on_token('Optgroup|option')");
+            if parser
+                .current_node()
+                .is_some_and(|node| node.is_html() && node.name == TagName::Option)
+            {
+                let _ = parser.open_elements.pop();
+            }
+            parser.reconstruct_the_active_formatting_elements();
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if matches!(tag.name, TagName::Rb | TagName::Rtc) => {
-            todo!("InBody::on_token('rb|rtc')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_scope(&TagName::Ruby)
+            {
+                parser.generate_implied_end_tags(None);
+                if !parser
+                    .current_node()
+                    .is_some_and(|node| node.is_html() && node.name == TagName::Ruby)
+                {
+                    parser.parse_error("InBody::on_token('rb|rtc') - expected ruby");
+                }
+            }
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if matches!(tag.name, TagName::Rp | TagName::Rt) => {
-            todo!("InBody::on_token('rp|rt')");
-        }
-        Token::StartTag(tag) if tag.name == TagName::Math => {
-            todo!("InBody::on_token('math')");
+            if parser
+                .open_elements
+                .has_a_particular_element_in_scope(&TagName::Ruby)
+            {
+                parser.generate_implied_end_tags(Some(&TagName::Rtc));
+                if !parser.current_node().is_some_and(|node| {
+                    node.is_html() && matches!(node.name, TagName::Ruby | TagName::Rtc)
+                }) {
+                    parser.parse_error("InBody::on_token('rp|rt') - expected ruby or rtc");
+                }
+            }
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
         }
-        Token::StartTag(tag) if tag.name == TagName::Svg => {
-            todo!("InBody::on_token('svg')");
+        Token::StartTag(tag) if matches!(tag.name, TagName::Math | TagName::Svg) => {
+            parser.reconstruct_the_active_formatting_elements();
+
+            let namespace = if tag.name == TagName::Math {
+                Namespace::MathML
+            } else {
+                Namespace::SVG
+            };
+            let node = parser.create_element_for_token(tag, namespace);
+            parser.insert_foreign_element(node);
+
+            if tag.is_self_closing() {
+                // Acknowledge the token's self-closing flag
+                let _ = parser.open_elements.pop();
+            }
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag)
             if matches!(
@@ -666,113 +997,234 @@ where
                     | TagName::Tr
             ) =>
         {
-            todo!("InBody::on_token('caption|...')");
+            parser.parse_error("InBody::on_token('caption|...')");
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) => {
-            warn!("[TODO] InBody: '_' - Reconstruct the active formatting elements, if any.");
+            parser.reconstruct_the_active_formatting_elements();
 
-            let node = Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
             parser.insert_html_element(node);
 
             current_state.into_transition_result()
         }
         Token::EndTag(tag) => {
-            let open_elements_len = parser.open_elements.len();
-            let mut i = parser.open_elements.len() - 1;
-            let mut node = parser.open_elements.get(i).unwrap();
-            let mut node_is_current_node = true;
-
-            trace!(
-                "InBody::on_token(EndTag(_)) - Finding matching Node for {:?}",
-                tag
-            );
-            loop {
-                trace!("InBody::on_token(EndTag(_)) - Node: {:?}", node);
-                let tag_name = &tag.name;
-                if node.borrow().is_html() && node.borrow().name() == tag_name {
-                    parser.generate_implied_end_tags(Some(tag_name));
-                    if !node_is_current_node {
-                        parse_error("</_>");
-                    }
-
-                    // Pop all the nodes from the current node up to node, including node, then stop these steps.
-                    trace!(
-                        "InBody::on_token(EndTag(_)) - Popping {} element(s)",
-                        open_elements_len - i
-                    );
-                    while i != open_elements_len {
-                        let e = parser.open_elements.pop();
-                        trace!("InBody::on_token(EndTag(_)) - Popped {:?}", e);
-                        i += 1;
-                    }
-
-                    break;
-                } else if node.borrow().category() == Category::Special {
-                    parse_error("Special Node found in body");
-                    return current_state.into_transition_result();
-                }
-
-                node_is_current_node = false;
-                i -= 1;
-                node = parser.open_elements.get(i).unwrap();
-            }
+            any_other_end_tag(parser, tag);
 
             current_state.into_transition_result()
         }
     }
 }
 
-fn adoption_agency_algorithm<R>(parser: &mut Parser<R>, token: &Token)
+/// <https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm>
+#[allow(clippy::too_many_lines)]
+fn adoption_agency_algorithm<R, S>(parser: &mut Parser<R, S>, token: &Token)
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     let subject = token.tag_name().unwrap();
     let current_node = parser.current_node().unwrap();
-    if current_node.borrow().is_html()
-        && current_node.borrow().name() == subject
+    if current_node.is_html()
+        && current_node.name() == subject
         && parser
             .list_of_active_formatting_elements
-            .contains(&Rc::clone(&current_node).into())
+            .position_of(&current_node)
+            .is_none()
     {
         let _ = parser.open_elements.pop();
         return;
     }
 
-    let mut outer_loop_counter = 0;
-    '_outer: loop {
-        if outer_loop_counter >= 8 {
-            return;
-        }
-        outer_loop_counter += 1;
-        let _formatting_element = match parser
+    for _ in 0..8 {
+        let formatting_element = match parser
             .list_of_active_formatting_elements
-            .iter()
-            .rev()
-            .take_while(|e| !e.is_marker())
-            .find(|e| e.is_element(subject))
+            .last_element_before_marker(subject)
         {
+            Some(e) => e,
+            None => {
+                // return and instead act as described in the "any other end tag" entry above.
+                if let Token::EndTag(tag) = token {
+                    any_other_end_tag(parser, tag);
+                }
+                return;
+            }
+        };
+
+        let formatting_element_index = match parser.open_elements.position_of(&formatting_element) {
+            Some(i) => i,
             None => {
-                todo!("adoption_agency_algorithm return and instead act as described in the \"any other end tag\" entry above.");
+                parser.parse_error("adoption_agency_algorithm - Formatting element isn't open");
+                parser
+                    .list_of_active_formatting_elements
+                    .remove_element(&formatting_element);
+                return;
             }
+        };
+        if !parser
+            .open_elements
+            .has_a_particular_element_in_scope(subject)
+        {
+            parser.parse_error("adoption_agency_algorithm - Formatting element isn't in scope");
+            return;
+        }
+        if !Rc::ptr_eq(&formatting_element, &parser.current_node().unwrap()) {
+            parser.parse_error("adoption_agency_algorithm - Formatting element isn't current node");
+        }
+
+        // The topmost special element below the formatting element in the stack of open elements
+        let furthest_block = parser.open_elements[formatting_element_index + 1..]
+            .iter()
+            .find(|e| e.category() == Category::Special)
+            .map(Rc::clone);
+        let furthest_block = match furthest_block {
             Some(e) => e,
+            None => {
+                while let Some(e) = parser.open_elements.pop() {
+                    if Rc::ptr_eq(&e, &formatting_element) {
+                        break;
+                    }
+                }
+                parser
+                    .list_of_active_formatting_elements
+                    .remove_element(&formatting_element);
+                return;
+            }
         };
 
-        todo!(
-            "adoption_agency_algorithm for {:?} {}",
-            token,
-            outer_loop_counter
-        )
+        let common_ancestor = Rc::clone(&parser.open_elements[formatting_element_index - 1]);
+        let mut bookmark = parser
+            .list_of_active_formatting_elements
+            .position_of(&formatting_element)
+            .unwrap();
+
+        let mut node_index = parser.open_elements.position_of(&furthest_block).unwrap();
+        let mut last_node = Rc::clone(&furthest_block);
+        let mut inner_loop_counter = 0;
+        loop {
+            inner_loop_counter += 1;
+            node_index -= 1;
+            let node = Rc::clone(&parser.open_elements[node_index]);
+            if Rc::ptr_eq(&node, &formatting_element) {
+                break;
+            }
+
+            let mut list_index = parser.list_of_active_formatting_elements.position_of(&node);
+            if let (true, Some(i)) = (inner_loop_counter > 3, list_index) {
+                let _ = parser.list_of_active_formatting_elements.remove(i);
+                if i < bookmark {
+                    bookmark -= 1;
+                }
+                list_index = None;
+            }
+            let list_index = match list_index {
+                Some(i) => i,
+                None => {
+                    let _ = parser.open_elements.remove(node_index);
+                    continue;
+                }
+            };
+
+            let node = parser.recreate_active_formatting_element(list_index);
+            parser.open_elements[node_index] = Rc::clone(&node);
+
+            if Rc::ptr_eq(&last_node, &furthest_block) {
+                bookmark = list_index + 1;
+            }
+
+            parser.append_child(&node, &last_node);
+            last_node = node;
+        }
+
+        parser.remove_from_parent(&last_node);
+        if let Some(place) = parser.appropriate_place_for_inserting_a_node(Some(common_ancestor)) {
+            parser.insert_at(place, NodeOrText::Node(last_node.handle.clone()));
+        }
+
+        // Move the furthest block's children into a new formatting element, which replaces the
+        // formatting element's entry and moves it to the bookmark
+        let formatting_element_entry = parser
+            .list_of_active_formatting_elements
+            .position_of(&formatting_element)
+            .unwrap();
+        let new_element = parser.recreate_active_formatting_element(formatting_element_entry);
+        parser.reparent_children(&furthest_block, &new_element);
+        parser.append_child(&furthest_block, &new_element);
+
+        if formatting_element_entry < bookmark {
+            bookmark -= 1;
+        }
+        let entry = parser
+            .list_of_active_formatting_elements
+            .remove(formatting_element_entry);
+        parser
+            .list_of_active_formatting_elements
+            .insert(bookmark, entry);
+
+        parser.open_elements.remove_element(&formatting_element);
+        let furthest_block_index = parser.open_elements.position_of(&furthest_block).unwrap();
+        parser
+            .open_elements
+            .insert(furthest_block_index + 1, new_element);
+    }
+}
+
+/// The "Any other end tag" steps of the "in body" insertion mode
+fn any_other_end_tag<R, S>(parser: &mut Parser<R, S>, tag: &EndTag)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    let open_elements_len = parser.open_elements.len();
+    let mut i = parser.open_elements.len() - 1;
+    let mut node = parser.open_elements.get(i).unwrap();
+    let mut node_is_current_node = true;
+
+    trace!(
+        "InBody::on_token(EndTag(_)) - Finding matching Node for {:?}",
+        tag
+    );
+    loop {
+        trace!("InBody::on_token(EndTag(_)) - Node: {:?}", node);
+        let tag_name = &tag.name;
+        if node.is_html() && node.name() == tag_name {
+            parser.generate_implied_end_tags(Some(tag_name));
+            if !node_is_current_node {
+                parser.parse_error("</_>");
+            }
+
+            // Pop all the nodes from the current node up to node, including node, then stop these steps.
+            trace!(
+                "InBody::on_token(EndTag(_)) - Popping {} element(s)",
+                open_elements_len - i
+            );
+            while i != open_elements_len {
+                let e = parser.open_elements.pop();
+                trace!("InBody::on_token(EndTag(_)) - Popped {:?}", e);
+                i += 1;
+            }
+
+            break;
+        } else if node.category() == Category::Special {
+            parser.parse_error("Special Node found in body");
+            return;
+        }
+
+        node_is_current_node = false;
+        i -= 1;
+        node = parser.open_elements.get(i).unwrap();
     }
 }
 
-fn close_a_p_element<R>(parser: &mut Parser<R>)
+fn close_a_p_element<R, S>(parser: &mut Parser<R, S>)
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     parser.generate_implied_end_tags(Some(&TagName::P));
     let current_node = parser.current_node().unwrap();
-    if !(current_node.borrow().name == TagName::P) {
-        parse_error("Unexpected tag - expected 'p'");
+    if !(current_node.name == TagName::P) {
+        parser.parse_error("Unexpected tag - expected 'p'");
     }
     parser.open_elements.pop_until(&[&TagName::P]);
 }
@@ -0,0 +1,127 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::in_body,
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InCaption {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::EndTag(tag) if tag.name == TagName::Caption => {
+            if !close_the_caption(parser) {
+                return current_state.into_transition_result();
+            }
+
+            States::in_table().into_transition_result()
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Tbody
+                    | TagName::Td
+                    | TagName::Tfoot
+                    | TagName::Th
+                    | TagName::Thead
+                    | TagName::Tr
+            ) =>
+        {
+            if !close_the_caption(parser) {
+                return current_state.into_transition_result();
+            }
+
+            let mut ret = States::in_table().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag) if tag.name == TagName::Table => {
+            if !close_the_caption(parser) {
+                return current_state.into_transition_result();
+            }
+
+            let mut ret = States::in_table().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Body
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Html
+                    | TagName::Tbody
+                    | TagName::Td
+                    | TagName::Tfoot
+                    | TagName::Th
+                    | TagName::Thead
+                    | TagName::Tr
+            ) =>
+        {
+            parser.parse_error("InCaption::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        _ => in_body::transition(current_state, parser, t),
+    }
+}
+
+/// Pop the caption element and the elements above it off the stack of open elements
+///
+/// Returns false if there is no caption element in table scope, in which case the token should
+/// be ignored.
+fn close_the_caption<R, S>(parser: &mut Parser<R, S>) -> bool
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    if !parser
+        .open_elements
+        .has_a_particular_element_in_table_scope(&TagName::Caption)
+    {
+        // fragment case
+        parser.parse_error("InCaption::on_token(_) - No caption in table scope");
+        return false;
+    }
+
+    parser.generate_implied_end_tags(None);
+    if parser
+        .current_node()
+        .is_some_and(|node| node.name() != &TagName::Caption)
+    {
+        parser.parse_error("InCaption::on_token(_) - Current node isn't a caption");
+    }
+    parser.open_elements.pop_until(&[&TagName::Caption]);
+    parser
+        .list_of_active_formatting_elements
+        .clear_up_to_last_marker();
+
+    true
+}
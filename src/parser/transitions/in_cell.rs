@@ -0,0 +1,139 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::in_body,
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InCell {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::EndTag(tag) if matches!(tag.name, TagName::Td | TagName::Th) => {
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&tag.name)
+            {
+                parser.parse_error("InCell::on_token(EndTag('td|th'))");
+                return current_state.into_transition_result();
+            }
+
+            parser.generate_implied_end_tags(None);
+            if parser.current_node().is_some_and(|node| {
+                !(node.is_html() && node.name() == &tag.name)
+            }) {
+                parser.parse_error("InCell::on_token(EndTag('td|th')) - Unexpected current node");
+            }
+            parser.open_elements.pop_until(&[&tag.name]);
+            parser
+                .list_of_active_formatting_elements
+                .clear_up_to_last_marker();
+
+            States::in_row().into_transition_result()
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Tbody
+                    | TagName::Td
+                    | TagName::Tfoot
+                    | TagName::Th
+                    | TagName::Thead
+                    | TagName::Tr
+            ) =>
+        {
+            if !(parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&TagName::Td)
+                || parser
+                    .open_elements
+                    .has_a_particular_element_in_table_scope(&TagName::Th))
+            {
+                // fragment case
+                parser.parse_error("InCell::on_token(StartTag(_)) - No td or th in table scope");
+                return current_state.into_transition_result();
+            }
+            close_the_cell(parser);
+
+            let mut ret = States::in_row().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Body | TagName::Caption | TagName::Col | TagName::Colgroup | TagName::Html
+            ) =>
+        {
+            parser.parse_error("InCell::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Table | TagName::Tbody | TagName::Tfoot | TagName::Thead | TagName::Tr
+            ) =>
+        {
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&tag.name)
+            {
+                parser.parse_error("InCell::on_token(EndTag('table|tbody|tfoot|thead|tr'))");
+                return current_state.into_transition_result();
+            }
+            close_the_cell(parser);
+
+            let mut ret = States::in_row().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        _ => in_body::transition(current_state, parser, t),
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell>
+fn close_the_cell<R, S>(parser: &mut Parser<R, S>)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser.generate_implied_end_tags(None);
+    if parser.current_node().is_some_and(|node| {
+        !(node.is_html() && matches!(node.name(), TagName::Td | TagName::Th))
+    }) {
+        parser.parse_error("InCell - close the cell: Current node isn't a td or th");
+    }
+    parser
+        .open_elements
+        .pop_until(&[&TagName::Td, &TagName::Th]);
+    parser
+        .list_of_active_formatting_elements
+        .clear_up_to_last_marker();
+}
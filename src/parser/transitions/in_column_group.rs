@@ -0,0 +1,102 @@
+use std::io;
+
+use crate::{
+    dom::Namespace,
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InColumnGroup {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Characters(s) if t.is_whitespace() => {
+            parser.insert_character(s);
+            current_state.into_transition_result()
+        }
+        Token::Comment(comment) => {
+            parser.insert_comment(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => {
+            parser.parse_error("InColumnGroup::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Html => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag) if tag.name == TagName::Col => {
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Colgroup => {
+            if !current_node_is_colgroup(parser) {
+                parser.parse_error("InColumnGroup::on_token(EndTag('colgroup'))");
+                return current_state.into_transition_result();
+            }
+            let _ = parser.open_elements.pop();
+
+            States::in_table().into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Col => {
+            parser.parse_error("InColumnGroup::on_token(EndTag('col'))");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Template => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Template => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::Eof => in_body::transition(current_state, parser, t),
+        _ => {
+            if !current_node_is_colgroup(parser) {
+                parser.parse_error("InColumnGroup::on_token(_)");
+                return current_state.into_transition_result();
+            }
+            let _ = parser.open_elements.pop();
+
+            let mut ret = States::in_table().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+    }
+}
+
+fn current_node_is_colgroup<R, S>(parser: &Parser<R, S>) -> bool
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser.current_node().is_some_and(|node| {
+        node.is_html() && node.name() == &TagName::Colgroup
+    })
+}
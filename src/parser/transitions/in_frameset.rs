@@ -0,0 +1,105 @@
+use std::io;
+
+use crate::{
+    dom::Namespace,
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InFrameset {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inframeset>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Characters(s) => {
+            // Only the whitespace is inserted, anything else is a parse error and ignored
+            let whitespace: String = s.chars().filter(char::is_ascii_whitespace).collect();
+            if whitespace.len() != s.len() {
+                parser.parse_error("InFrameset::on_token(Characters)");
+            }
+            if !whitespace.is_empty() {
+                parser.insert_character(whitespace);
+            }
+            current_state.into_transition_result()
+        }
+        Token::Comment(comment) => {
+            parser.insert_comment(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => {
+            parser.parse_error("InFrameset::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Html => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag) if tag.name == TagName::Frameset => {
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Frameset => {
+            if parser.open_elements.len() == 1 {
+                // fragment case, the current node is the root html element
+                parser.parse_error("InFrameset::on_token(EndTag('frameset'))");
+                return current_state.into_transition_result();
+            }
+            let _ = parser.open_elements.pop();
+
+            if parser
+                .current_node()
+                .is_some_and(|node| node.is_html() && node.name == TagName::Frameset)
+            {
+                current_state.into_transition_result()
+            } else {
+                States::after_frameset().into_transition_result()
+            }
+        }
+        Token::StartTag(tag) if tag.name == TagName::Frame => {
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Noframes => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::Eof => {
+            if parser.open_elements.len() != 1 {
+                parser.parse_error("InFrameset::on_token(Eof)");
+            }
+            States::term().into_transition_result()
+        }
+        _ => {
+            parser.parse_error("InFrameset::on_token(_)");
+            current_state.into_transition_result()
+        }
+    }
+}
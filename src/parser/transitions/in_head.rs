@@ -2,48 +2,55 @@ use std::io;
 
 use crate::{
     dom,
-    parser::{encodings, parse_error, states::{self, States}, Parser, ScriptingFlag, TransitionResult},
-    tokenizer::{TagName, Token},
+    parser::{
+        encodings,
+        errors::InvariantError,
+        states::{self, States},
+        transitions::in_body,
+        FramesetOkFlag, Parser, ScriptingFlag, TransitionResult, TreeSink,
+    },
+    tokenizer::{extract_character_encoding_from_meta, Confidence, TagName, Token},
 };
 
 impl states::InHead {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
 #[allow(clippy::too_many_lines)]
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
-        Token::Character(ch @ '\t') | Token::Character(ch @ '\n') | Token::Character(ch @ ' ') => {
-            parser.insert_character(ch.to_string());
+        Token::Characters(s) if t.is_whitespace() => {
+            parser.insert_character(s);
             current_state.into_transition_result()
         }
         Token::Comment(comment) => {
-            let node = dom::Comment::new(comment.clone());
-            parser.document.push_comment(node);
+            parser.insert_comment(comment);
             current_state.into_transition_result()
         }
         Token::Doctype(_) => {
-            parse_error("InHead::on_token(Doctype)");
+            parser.parse_error("InHead::on_token(Doctype)");
             current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Html => {
-            todo!("InHead::on_token('html')");
+            in_body::transition(current_state, parser, t)
         }
         Token::StartTag(tag)
             if (tag.name == TagName::Base
@@ -51,29 +58,38 @@ where
                 || tag.name == TagName::Bgsound
                 || tag.name == TagName::Link) =>
         {
-            todo!("InHead::on_token('base|basefont|bgsound|link')");
+            let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+
+            // Acknowledge the token's self-closing flag, if it is set.
+
+            current_state.into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Meta => {
-            let node = dom::Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
             parser.insert_html_element(node);
             let _ = parser.open_elements.pop();
 
-            if tag.is_self_closing() {
-                todo!("InHead:on_token(Self closing 'meta')");
-            }
+            // Acknowledge the token's self-closing flag, if it is set.
 
-            if let Some(attr) = tag.attributes_iter().find(|a| a.name == "charset") {
-                if let Some(encoding) = encodings::get_encoding(&attr.value) {
-                    if encoding.name != "UTF-8" {
-                        panic!("Unsupported encoding - {:?}", encoding);
-                    }
-                };
-            }
-            if let Some(attr) = tag.attributes_iter().find(|a| a.name == "http-equiv") {
-                if attr.value.eq_ignore_ascii_case("content-type") {
-                    if let Some(_attr) = tag.attributes_iter().find(|a| a.name == "content") {
-                        todo!("InHead(StartTag('meta')) Unsupported <meta http-equiv='Content-Type' content='...'>")
-                    }
+            if parser.encoding_confidence() == Confidence::Tentative {
+                let charset = tag
+                    .attributes_iter()
+                    .find(|a| a.name == "charset")
+                    .and_then(|a| encodings::get_decoder(&a.value));
+                let is_content_type = tag.attributes_iter().any(|a| {
+                    a.name == "http-equiv" && a.value.eq_ignore_ascii_case("content-type")
+                });
+                let content = tag
+                    .attributes_iter()
+                    .find(|a| a.name == "content")
+                    .and_then(|a| extract_character_encoding_from_meta(&a.value));
+
+                if let Some(encoding) = charset {
+                    parser.change_the_encoding(encoding);
+                } else if let (true, Some(encoding)) = (is_content_type, content) {
+                    parser.change_the_encoding(encoding);
                 }
             }
 
@@ -94,25 +110,17 @@ where
         Token::StartTag(tag)
             if tag.name == TagName::Noscript && parser.scripting == ScriptingFlag::Disabled =>
         {
-            let node = dom::Element::new_html(tag.name.clone());
+            let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
             parser.insert_html_element(node);
 
             States::in_head_noscript().into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Script => {
-            todo!("InHead::on_token('script')");
+            parser.insert_script_element(current_state, tag)
         }
         Token::EndTag(tag) if tag.name == TagName::Head => {
-            // Pop the current node (which will be the head element) off the stack of open elements.
-            let elem = parser
-                .open_elements
-                .pop()
-                .expect("Expected element on the stack of open elements");
-            if elem.borrow().name() != &TagName::Head {
-                panic!(
-                    "Unexpected element on the stack of open elements: {:?} (Expected 'head')",
-                    elem
-                );
+            if let Err(e) = pop_the_head_element(parser) {
+                return e.into();
             }
             States::after_head().into_transition_result()
         }
@@ -121,16 +129,8 @@ where
                 || tag.name == TagName::Html
                 || tag.name == TagName::Br) =>
         {
-            // Pop the current node (which will be the head element) off the stack of open elements.
-            let elem = parser
-                .open_elements
-                .pop()
-                .expect("Expected element on the stack of open elements");
-            if elem.borrow().name() != &TagName::Head {
-                panic!(
-                    "Unexpected element on the stack of open elements: {:?} (Expected 'head')",
-                    elem
-                );
+            if let Err(e) = pop_the_head_element(parser) {
+                return e.into();
             }
 
             let mut ret = States::after_head().into_transition_result();
@@ -138,29 +138,48 @@ where
             ret
         }
         Token::StartTag(tag) if tag.name == TagName::Template => {
-            todo!("InHead::on_token('template')");
+            let node = parser.create_element_for_token(tag, dom::Namespace::HTML);
+            parser.insert_html_element(node);
+            parser.list_of_active_formatting_elements.push_marker();
+            parser.frameset_ok = FramesetOkFlag::NotOk;
+            parser.template_insertion_modes.push(States::in_template());
+
+            States::in_template().into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Template => {
-            todo!("InHead::on_token('template')");
+            if !parser.open_elements.contains_element(&TagName::Template) {
+                parser.parse_error("InHead::on_token(EndTag('template'))");
+                return current_state.into_transition_result();
+            }
+
+            parser.generate_all_implied_end_tags_thoroughly();
+            if !parser
+                .current_node()
+                .is_some_and(|node| node.is_html() && node.name() == &TagName::Template)
+            {
+                parser.parse_error("InHead::on_token(EndTag('template'))");
+            }
+            parser.open_elements.pop_until(&[&TagName::Template]);
+            parser
+                .list_of_active_formatting_elements
+                .clear_up_to_last_marker();
+            let _ = parser.template_insertion_modes.pop();
+
+            parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result()
         }
         Token::StartTag(tag) if tag.name == TagName::Head => {
-            parse_error("InHead::on_token(StartTag('head'))");
+            parser.parse_error("InHead::on_token(StartTag('head'))");
             current_state.into_transition_result()
         }
         Token::EndTag(_) => {
-            parse_error("InHead::on_token(EndTag(_))");
+            parser.parse_error("InHead::on_token(EndTag(_))");
             current_state.into_transition_result()
         }
         _ => {
-            let elem = parser
-                .open_elements
-                .pop()
-                .expect("Expected element on the stack of open elements");
-            if elem.borrow().name() != &TagName::Head {
-                panic!(
-                    "Unexpected element on the stack of open elements: {:?} (Expected 'head')",
-                    elem
-                );
+            if let Err(e) = pop_the_head_element(parser) {
+                return e.into();
             }
 
             let mut ret = States::after_head().into_transition_result();
@@ -169,3 +188,17 @@ where
         }
     }
 }
+
+/// Pop the current node, which will be the head element, off the stack of open elements
+fn pop_the_head_element<R, S>(parser: &mut Parser<R, S>) -> Result<(), InvariantError>
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match parser.open_elements.pop() {
+        Some(elem) if elem.name() == &TagName::Head => Ok(()),
+        _ => Err(InvariantError::new(
+            "InHead: the current node isn't the head element",
+        )),
+    }
+}
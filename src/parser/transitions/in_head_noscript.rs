@@ -0,0 +1,82 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InHeadNoscript {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inheadnoscript>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Doctype(_) => {
+            parser.parse_error("InHeadNoscript::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Html => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Noscript => {
+            // Pop the current node (which will be the noscript element) off the stack
+            let _ = parser.open_elements.pop();
+            States::in_head().into_transition_result()
+        }
+        Token::Characters(_) if t.is_whitespace() => in_head::transition(current_state, parser, t),
+        Token::Comment(_) => in_head::transition(current_state, parser, t),
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Basefont
+                    | TagName::Bgsound
+                    | TagName::Link
+                    | TagName::Meta
+                    | TagName::Noframes
+                    | TagName::Style
+            ) =>
+        {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag) if matches!(tag.name, TagName::Head | TagName::Noscript) => {
+            parser.parse_error("InHeadNoscript::on_token(StartTag('head|noscript'))");
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name != TagName::Br => {
+            parser.parse_error("InHeadNoscript::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        _ => {
+            parser.parse_error("InHeadNoscript::on_token(_)");
+            // Pop the current node (which will be the noscript element) off the stack
+            let _ = parser.open_elements.pop();
+
+            let mut ret = States::in_head().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::io;
+
+use crate::{
+    dom::Namespace,
+    parser::{
+        states::{self, States},
+        transitions::in_table,
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InRow {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::StartTag(tag) if matches!(tag.name, TagName::Th | TagName::Td) => {
+            clear_the_stack_back_to_a_table_row_context(parser);
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            parser.list_of_active_formatting_elements.push_marker();
+
+            States::in_cell().into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Tr => {
+            if !close_the_row(parser) {
+                return current_state.into_transition_result();
+            }
+
+            States::in_table_body().into_transition_result()
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Tbody
+                    | TagName::Tfoot
+                    | TagName::Thead
+                    | TagName::Tr
+            ) =>
+        {
+            if !close_the_row(parser) {
+                return current_state.into_transition_result();
+            }
+
+            let mut ret = States::in_table_body().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag) if tag.name == TagName::Table => {
+            if !close_the_row(parser) {
+                return current_state.into_transition_result();
+            }
+
+            let mut ret = States::in_table_body().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag)
+            if matches!(tag.name, TagName::Tbody | TagName::Tfoot | TagName::Thead) =>
+        {
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&tag.name)
+            {
+                parser.parse_error("InRow::on_token(EndTag('tbody|tfoot|thead'))");
+                return current_state.into_transition_result();
+            }
+            if !close_the_row(parser) {
+                return current_state.into_transition_result();
+            }
+
+            let mut ret = States::in_table_body().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Body
+                    | TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Html
+                    | TagName::Td
+                    | TagName::Th
+            ) =>
+        {
+            parser.parse_error("InRow::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        _ => in_table::transition(current_state, parser, t),
+    }
+}
+
+/// Pop the current tr element off the stack of open elements
+///
+/// Returns false if there is no tr element in table scope, in which case the token should be
+/// ignored.
+fn close_the_row<R, S>(parser: &mut Parser<R, S>) -> bool
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    if !parser
+        .open_elements
+        .has_a_particular_element_in_table_scope(&TagName::Tr)
+    {
+        parser.parse_error("InRow::on_token(_) - No tr in table scope");
+        return false;
+    }
+    clear_the_stack_back_to_a_table_row_context(parser);
+    let _ = parser.open_elements.pop();
+
+    true
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-row-context>
+fn clear_the_stack_back_to_a_table_row_context<R, S>(parser: &mut Parser<R, S>)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser
+        .open_elements
+        .clear_back_to(&[&TagName::Tr, &TagName::Template, &TagName::Html]);
+}
@@ -0,0 +1,199 @@
+use std::io;
+
+use crate::{
+    dom::Namespace,
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InSelect {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inselect>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Characters(s) => {
+            // NUL characters are a parse error and ignored
+            if s.contains('\0') {
+                parser.parse_error("InSelect::on_token(\\0)");
+                let s = s.replace('\0', "");
+                if !s.is_empty() {
+                    parser.insert_character(s);
+                }
+            } else {
+                parser.insert_character(s);
+            }
+            current_state.into_transition_result()
+        }
+        Token::Comment(comment) => {
+            parser.insert_comment(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => {
+            parser.parse_error("InSelect::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Html => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag) if tag.name == TagName::Option => {
+            pop_current_node_if(parser, &TagName::Option);
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Optgroup => {
+            pop_current_node_if(parser, &TagName::Option);
+            pop_current_node_if(parser, &TagName::Optgroup);
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Hr => {
+            pop_current_node_if(parser, &TagName::Option);
+            pop_current_node_if(parser, &TagName::Optgroup);
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Optgroup => {
+            let len = parser.open_elements.len();
+            if current_node_is(parser, &TagName::Option)
+                && len > 1
+                && parser.open_elements[len - 2].is_html()
+                && parser.open_elements[len - 2].name == TagName::Optgroup
+            {
+                let _ = parser.open_elements.pop();
+            }
+
+            if current_node_is(parser, &TagName::Optgroup) {
+                let _ = parser.open_elements.pop();
+            } else {
+                parser.parse_error("InSelect::on_token(EndTag('optgroup'))");
+            }
+
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Option => {
+            if current_node_is(parser, &TagName::Option) {
+                let _ = parser.open_elements.pop();
+            } else {
+                parser.parse_error("InSelect::on_token(EndTag('option'))");
+            }
+
+            current_state.into_transition_result()
+        }
+        Token::EndTag(tag) if tag.name == TagName::Select => {
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_select_scope(&TagName::Select)
+            {
+                // fragment case
+                parser.parse_error("InSelect::on_token(EndTag('select'))");
+                return current_state.into_transition_result();
+            }
+            parser.open_elements.pop_until(&[&TagName::Select]);
+
+            parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Select => {
+            parser.parse_error("InSelect::on_token(StartTag('select'))");
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_select_scope(&TagName::Select)
+            {
+                return current_state.into_transition_result();
+            }
+            parser.open_elements.pop_until(&[&TagName::Select]);
+
+            parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result()
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Input | TagName::Keygen | TagName::Textarea
+            ) =>
+        {
+            parser.parse_error("InSelect::on_token(StartTag('input|keygen|textarea'))");
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_select_scope(&TagName::Select)
+            {
+                return current_state.into_transition_result();
+            }
+            parser.open_elements.pop_until(&[&TagName::Select]);
+
+            let mut ret = parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::StartTag(tag) if matches!(tag.name, TagName::Script | TagName::Template) => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Template => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::Eof => in_body::transition(current_state, parser, t),
+        _ => {
+            parser.parse_error("InSelect::on_token(_)");
+            current_state.into_transition_result()
+        }
+    }
+}
+
+fn current_node_is<R, S>(parser: &Parser<R, S>, name: &TagName) -> bool
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser
+        .current_node()
+        .is_some_and(|node| node.is_html() && node.name() == name)
+}
+
+fn pop_current_node_if<R, S>(parser: &mut Parser<R, S>, name: &TagName)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    if current_node_is(parser, name) {
+        let _ = parser.open_elements.pop();
+    }
+}
@@ -0,0 +1,79 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::in_select,
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InSelectInTable {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inselectintable>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::StartTag(tag) if is_table_element(&tag.name) => {
+            parser.parse_error("InSelectInTable::on_token(StartTag('caption|table|...'))");
+            parser.open_elements.pop_until(&[&TagName::Select]);
+
+            let mut ret = parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag) if is_table_element(&tag.name) => {
+            parser.parse_error("InSelectInTable::on_token(EndTag('caption|table|...'))");
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&tag.name)
+            {
+                return current_state.into_transition_result();
+            }
+            parser.open_elements.pop_until(&[&TagName::Select]);
+
+            let mut ret = parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        _ => in_select::transition(current_state, parser, t),
+    }
+}
+
+fn is_table_element(name: &TagName) -> bool {
+    matches!(
+        name,
+        TagName::Caption
+            | TagName::Table
+            | TagName::Tbody
+            | TagName::Tfoot
+            | TagName::Thead
+            | TagName::Tr
+            | TagName::Td
+            | TagName::Th
+    )
+}
@@ -0,0 +1,233 @@
+use std::{io, rc::Rc};
+
+use crate::{
+    dom::Namespace,
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        FosterParentingFlag, Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InTable {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Characters(_)
+            if parser.current_node().is_some_and(|node| {
+                node.is_html()
+                    && matches!(
+                        node.name(),
+                        TagName::Table
+                            | TagName::Tbody
+                            | TagName::Template
+                            | TagName::Tfoot
+                            | TagName::Thead
+                            | TagName::Tr
+                    )
+            }) =>
+        {
+            let mut ret = States::in_table_text(Box::new(current_state)).into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::Comment(comment) => {
+            parser.insert_comment(comment);
+            current_state.into_transition_result()
+        }
+        Token::Doctype(_) => {
+            parser.parse_error("InTable::on_token(Doctype)");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Caption => {
+            clear_the_stack_back_to_a_table_context(parser);
+            parser.list_of_active_formatting_elements.push_marker();
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            States::in_caption().into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Colgroup => {
+            clear_the_stack_back_to_a_table_context(parser);
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            States::in_column_group().into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Col => {
+            clear_the_stack_back_to_a_table_context(parser);
+            // Insert an HTML element for a "colgroup" start tag token with no attributes
+            let node = parser.create_html_element(TagName::Colgroup);
+            parser.insert_html_element(node);
+
+            let mut ret = States::in_column_group().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::StartTag(tag)
+            if matches!(tag.name, TagName::Tbody | TagName::Tfoot | TagName::Thead) =>
+        {
+            clear_the_stack_back_to_a_table_context(parser);
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            States::in_table_body().into_transition_result()
+        }
+        Token::StartTag(tag) if matches!(tag.name, TagName::Td | TagName::Th | TagName::Tr) => {
+            clear_the_stack_back_to_a_table_context(parser);
+            // Insert an HTML element for a "tbody" start tag token with no attributes
+            let node = parser.create_html_element(TagName::Tbody);
+            parser.insert_html_element(node);
+
+            let mut ret = States::in_table_body().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::StartTag(tag) if tag.name == TagName::Table => {
+            parser.parse_error("InTable::on_token(StartTag('table'))");
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&TagName::Table)
+            {
+                return current_state.into_transition_result();
+            }
+
+            parser.open_elements.pop_until(&[&TagName::Table]);
+
+            let mut ret = parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag) if tag.name == TagName::Table => {
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&TagName::Table)
+            {
+                parser.parse_error("InTable::on_token(EndTag('table'))");
+                return current_state.into_transition_result();
+            }
+
+            parser.open_elements.pop_until(&[&TagName::Table]);
+
+            parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result()
+        }
+        Token::EndTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Body
+                    | TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Html
+                    | TagName::Tbody
+                    | TagName::Td
+                    | TagName::Tfoot
+                    | TagName::Th
+                    | TagName::Thead
+                    | TagName::Tr
+            ) =>
+        {
+            parser.parse_error("InTable::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Style | TagName::Script | TagName::Template
+            ) =>
+        {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Template => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag)
+            if tag.name == TagName::Input
+                && tag
+                    .attributes_iter()
+                    .any(|a| a.name == "type" && a.value.eq_ignore_ascii_case("hidden")) =>
+        {
+            parser.parse_error("InTable::on_token(StartTag('input'))");
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+            let _ = parser.open_elements.pop();
+            // Acknowledge the token's self-closing flag, if it is set.
+
+            current_state.into_transition_result()
+        }
+        Token::StartTag(tag) if tag.name == TagName::Form => {
+            parser.parse_error("InTable::on_token(StartTag('form'))");
+            if parser.open_elements.contains_element(&TagName::Template)
+                || parser.form_element_pointer.is_some()
+            {
+                return current_state.into_transition_result();
+            }
+
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(Rc::clone(&node));
+            parser.form_element_pointer = Some(node);
+            let _ = parser.open_elements.pop();
+
+            current_state.into_transition_result()
+        }
+        Token::Eof => in_body::transition(current_state, parser, t),
+        _ => {
+            parser.parse_error("InTable::on_token(_)");
+            anything_else(current_state, parser, t)
+        }
+    }
+}
+
+/// Process the token using the rules for the "in body" insertion mode,
+/// with foster parenting enabled
+pub(super) fn anything_else<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser.foster_parenting = FosterParentingFlag::Enabled;
+    let ret = in_body::transition(current_state, parser, t);
+    parser.foster_parenting = FosterParentingFlag::Disabled;
+    ret
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context>
+fn clear_the_stack_back_to_a_table_context<R, S>(parser: &mut Parser<R, S>)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser
+        .open_elements
+        .clear_back_to(&[&TagName::Table, &TagName::Template, &TagName::Html]);
+}
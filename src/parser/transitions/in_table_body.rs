@@ -0,0 +1,146 @@
+use std::io;
+
+use crate::{
+    dom::Namespace,
+    parser::{
+        states::{self, States},
+        transitions::in_table,
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InTableBody {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::StartTag(tag) if tag.name == TagName::Tr => {
+            clear_the_stack_back_to_a_table_body_context(parser);
+            let node = parser.create_element_for_token(tag, Namespace::HTML);
+            parser.insert_html_element(node);
+
+            States::in_row().into_transition_result()
+        }
+        Token::StartTag(tag) if matches!(tag.name, TagName::Th | TagName::Td) => {
+            parser.parse_error("InTableBody::on_token(StartTag('th|td'))");
+            clear_the_stack_back_to_a_table_body_context(parser);
+            // Insert an HTML element for a "tr" start tag token with no attributes
+            let node = parser.create_html_element(TagName::Tr);
+            parser.insert_html_element(node);
+
+            let mut ret = States::in_row().into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+        Token::EndTag(tag)
+            if matches!(tag.name, TagName::Tbody | TagName::Tfoot | TagName::Thead) =>
+        {
+            if !parser
+                .open_elements
+                .has_a_particular_element_in_table_scope(&tag.name)
+            {
+                parser.parse_error("InTableBody::on_token(EndTag('tbody|tfoot|thead'))");
+                return current_state.into_transition_result();
+            }
+            clear_the_stack_back_to_a_table_body_context(parser);
+            let _ = parser.open_elements.pop();
+
+            States::in_table().into_transition_result()
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Tbody
+                    | TagName::Tfoot
+                    | TagName::Thead
+            ) =>
+        {
+            close_the_table_body(current_state, parser)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Table => {
+            close_the_table_body(current_state, parser)
+        }
+        Token::EndTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Body
+                    | TagName::Caption
+                    | TagName::Col
+                    | TagName::Colgroup
+                    | TagName::Html
+                    | TagName::Td
+                    | TagName::Th
+                    | TagName::Tr
+            ) =>
+        {
+            parser.parse_error("InTableBody::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        _ => in_table::transition(current_state, parser, t),
+    }
+}
+
+/// Pop the current table body element, and reprocess the token in the "in table" insertion mode
+fn close_the_table_body<R, S>(current_state: States, parser: &mut Parser<R, S>) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    if !(parser
+        .open_elements
+        .has_a_particular_element_in_table_scope(&TagName::Tbody)
+        || parser
+            .open_elements
+            .has_a_particular_element_in_table_scope(&TagName::Thead)
+        || parser
+            .open_elements
+            .has_a_particular_element_in_table_scope(&TagName::Tfoot))
+    {
+        parser.parse_error("InTableBody::on_token(_) - No tbody, thead or tfoot in table scope");
+        return current_state.into_transition_result();
+    }
+    clear_the_stack_back_to_a_table_body_context(parser);
+    let _ = parser.open_elements.pop();
+
+    let mut ret = States::in_table().into_transition_result();
+    ret.set_reprocess();
+    ret
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-body-context>
+fn clear_the_stack_back_to_a_table_body_context<R, S>(parser: &mut Parser<R, S>)
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    parser.open_elements.clear_back_to(&[
+        &TagName::Tbody,
+        &TagName::Tfoot,
+        &TagName::Thead,
+        &TagName::Template,
+        &TagName::Html,
+    ]);
+}
@@ -0,0 +1,79 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::in_table,
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::Token,
+};
+
+impl states::InTableText {
+    /// Remember where the character tokens being added to the pending list were
+    fn extend_pending_span<R, S>(&mut self, parser: &Parser<R, S>)
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        let span = parser.token_span;
+        self.pending_table_character_span = Some(
+            self.pending_table_character_span
+                .map_or(span, |pending| pending.union(span)),
+        );
+    }
+}
+
+impl states::InTableText {
+    pub(in crate::parser) fn on_token<R, S>(
+        mut self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        match t {
+            Token::Characters(chs) => {
+                if chs.contains('\0') {
+                    parser.parse_error("InTableText::on_token(\\0)");
+                }
+                self.pending_table_character_tokens
+                    .extend(chs.chars().filter(|&c| c != '\0'));
+                self.extend_pending_span(parser);
+                States::from(self).into_transition_result()
+            }
+            _ => {
+                let pending = self.pending_table_character_tokens;
+                // The nodes for the pending characters are created for the character tokens
+                let token_span = parser.token_span;
+                if let Some(span) = self.pending_table_character_span {
+                    parser.token_span = span;
+                }
+                if !pending.bytes().all(|b| b.is_ascii_whitespace()) {
+                    // Reprocess the character tokens in the pending table character tokens list
+                    // using the rules given in the "anything else" entry in the "in table"
+                    // insertion mode.
+                    parser.parse_error("InTableText::on_token(_) - Non-whitespace characters");
+                    let res = in_table::anything_else(
+                        States::in_table(),
+                        parser,
+                        &Token::Characters(pending),
+                    );
+                    if res.is_err() {
+                        parser.token_span = token_span;
+                        return res;
+                    }
+                } else if !pending.is_empty() {
+                    parser.insert_character(pending);
+                }
+                parser.token_span = token_span;
+
+                let mut ret = self.original_insertion_mode.into_transition_result();
+                ret.set_reprocess();
+                ret
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::io;
+
+use crate::{
+    parser::{
+        states::{self, States},
+        transitions::{in_body, in_head},
+        Parser, TransitionResult, TreeSink,
+    },
+    tokenizer::{TagName, Token},
+};
+
+impl states::InTemplate {
+    pub(in crate::parser) fn on_token<R, S>(
+        self,
+        parser: &mut Parser<R, S>,
+        t: &Token,
+    ) -> TransitionResult
+    where
+        R: io::Read,
+        S: TreeSink,
+    {
+        transition(States::from(self), parser, t)
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intemplate>
+pub(super) fn transition<R, S>(
+    current_state: States,
+    parser: &mut Parser<R, S>,
+    t: &Token,
+) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    match t {
+        Token::Character(_) | Token::Characters(_) | Token::Comment(_) | Token::Doctype(_) => {
+            in_body::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Base
+                    | TagName::Basefont
+                    | TagName::Bgsound
+                    | TagName::Link
+                    | TagName::Meta
+                    | TagName::Noframes
+                    | TagName::Script
+                    | TagName::Style
+                    | TagName::Template
+                    | TagName::Title
+            ) =>
+        {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::EndTag(tag) if tag.name == TagName::Template => {
+            in_head::transition(current_state, parser, t)
+        }
+        Token::StartTag(tag)
+            if matches!(
+                tag.name,
+                TagName::Caption
+                    | TagName::Colgroup
+                    | TagName::Tbody
+                    | TagName::Tfoot
+                    | TagName::Thead
+            ) =>
+        {
+            switch_template_insertion_mode(parser, States::in_table())
+        }
+        Token::StartTag(tag) if tag.name == TagName::Col => {
+            switch_template_insertion_mode(parser, States::in_column_group())
+        }
+        Token::StartTag(tag) if tag.name == TagName::Tr => {
+            switch_template_insertion_mode(parser, States::in_table_body())
+        }
+        Token::StartTag(tag) if matches!(tag.name, TagName::Td | TagName::Th) => {
+            switch_template_insertion_mode(parser, States::in_row())
+        }
+        Token::StartTag(_) => switch_template_insertion_mode(parser, States::in_body()),
+        Token::EndTag(_) => {
+            parser.parse_error("InTemplate::on_token(EndTag(_))");
+            current_state.into_transition_result()
+        }
+        Token::Eof => {
+            if !parser.open_elements.contains_element(&TagName::Template) {
+                // fragment case
+                return States::term().into_transition_result();
+            }
+            parser.parse_error("InTemplate::on_token(Eof)");
+
+            parser.open_elements.pop_until(&[&TagName::Template]);
+            parser
+                .list_of_active_formatting_elements
+                .clear_up_to_last_marker();
+            let _ = parser.template_insertion_modes.pop();
+
+            let mut ret = parser
+                .reset_the_insertion_mode_appropriately()
+                .into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
+    }
+}
+
+/// Replace the current template insertion mode with `mode`, and reprocess the token in it
+fn switch_template_insertion_mode<R, S>(parser: &mut Parser<R, S>, mode: States) -> TransitionResult
+where
+    R: io::Read,
+    S: TreeSink,
+{
+    let _ = parser.template_insertion_modes.pop();
+    parser.template_insertion_modes.push(mode.clone());
+
+    let mut ret = mode.into_transition_result();
+    ret.set_reprocess();
+    ret
+}
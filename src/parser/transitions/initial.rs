@@ -2,38 +2,39 @@ use std::io;
 
 use crate::{
     dom,
-    parser::{parse_error, states::{self, States}, Parser, TransitionResult, transitions::force_quirks_check},
+    parser::{states::{self, States}, Parser, TransitionResult, TreeSink, transitions::force_quirks_check},
     tokenizer::Token,
 };
 
 impl states::Initial {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
         transition(States::from(self), parser, t)
     }
 }
 
-pub(super) fn transition<R>(
+pub(super) fn transition<R, S>(
     current_state: States,
-    parser: &mut Parser<R>,
+    parser: &mut Parser<R, S>,
     t: &Token,
 ) -> TransitionResult
 where
-    R: io::Read + io::Seek,
+    R: io::Read,
+    S: TreeSink,
 {
     match t {
-        Token::Character('\t') | Token::Character('\n') | Token::Character(' ') => {
+        Token::Characters(_) if t.is_whitespace() => {
             current_state.into_transition_result()
         }
         Token::Comment(comment) => {
-            let node = dom::Comment::new(comment.clone());
-            parser.document.push_comment(node);
+            parser.append_comment_to_document(comment);
             current_state.into_transition_result()
         }
         Token::Doctype(d) => {
@@ -54,7 +55,7 @@ where
                 || public_id_present
                 || system_id_present && system_id == "about:legacy-compat"
             {
-                parse_error("Initial::on_token")
+                parser.parse_error("Initial::on_token")
             }
 
             // Append a DocumentType node to the Document node,
@@ -71,19 +72,21 @@ where
                 is_force_quirks,
                 system_id_present,
             ) {
-                parser.document.set_mode("quirks");
+                parser.set_quirks_mode(dom::QuirksMode::Quirks);
             } else if force_quirks_check::limited_quirks_check(&public_id, system_id_present)
             {
-                parser.document.set_mode("limited-quirks");
+                parser.set_quirks_mode(dom::QuirksMode::LimitedQuirks);
             }
 
             let document_type = dom::DocumentType::new(name, public_id, system_id);
-            parser.document.add_document_type(document_type);
+            parser.append_doctype_to_document(document_type);
 
             States::before_html().into_transition_result()
         }
         _ => {
             // If the document is not an iframe srcdoc document, then this is a parse error; set the Document to quirks mode.
+            parser.parse_error("Initial::on_token(_)");
+            parser.set_quirks_mode(dom::QuirksMode::Quirks);
             let mut ret = States::before_html().into_transition_result();
             ret.set_reprocess();
             ret
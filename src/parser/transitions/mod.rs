@@ -5,12 +5,27 @@ mod force_quirks_check;
  */
 
 pub(super) mod after_after_body;
+pub(super) mod after_after_frameset;
 pub(super) mod after_body;
+pub(super) mod after_frameset;
 pub(super) mod after_head;
 pub(super) mod before_head;
 pub(super) mod before_html;
+pub(super) mod foreign_content;
 pub(super) mod in_body;
+pub(super) mod in_caption;
+pub(super) mod in_cell;
+pub(super) mod in_column_group;
+pub(super) mod in_frameset;
 pub(super) mod in_head;
+pub(super) mod in_head_noscript;
+pub(super) mod in_row;
+pub(super) mod in_select;
+pub(super) mod in_select_in_table;
+pub(super) mod in_table;
+pub(super) mod in_table_body;
+pub(super) mod in_table_text;
+pub(super) mod in_template;
 pub(super) mod initial;
 pub(super) mod text;
 
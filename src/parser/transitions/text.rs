@@ -1,32 +1,66 @@
 use std::io;
 
 use crate::{
-    parser::{states::{self, States}, Parser, TransitionResult},
+    dom,
+    parser::{states::{self, States}, Parser, TransitionResult, TreeSink},
     tokenizer::{TagName, Token},
 };
 
 impl states::Text {
-    pub(in crate::parser) fn on_token<R>(
+    pub(in crate::parser) fn on_token<R, S>(
         self,
-        parser: &mut Parser<R>,
+        parser: &mut Parser<R, S>,
         t: &Token,
     ) -> TransitionResult
     where
-        R: io::Read + io::Seek,
+        R: io::Read,
+        S: TreeSink,
     {
     match t {
-        Token::Character('\0') => unreachable!(),
-        Token::Character(ch) => {
-            parser.insert_character(ch.to_string());
+        Token::Characters(s) => {
+            if let Some(ref mut text) = parser.script_text {
+                text.push_str(s);
+            }
+            parser.insert_character(s);
             States::from(self).into_transition_result()
         }
         Token::EndTag(tag) if tag.name == TagName::Script => {
-            todo!("Text::on_token(</script>)")
+            // TODO: If the active speculative HTML parser is null and the JavaScript execution context stack is empty, then perform a microtask checkpoint.
+            let script = parser
+                .open_elements
+                .pop()
+                .expect("Expected the script element on the stack of open elements");
+            let text = parser.script_text.take().unwrap_or_default();
+            let ret = self.original_insertion_mode.into_transition_result();
+            parser.prepare_the_script_element(&script, &text);
+            ret
         }
         Token::EndTag(_) => {
             let _ = parser.open_elements.pop();
             self.original_insertion_mode.into_transition_result()
         }
+        Token::Eof => {
+            parser.parse_error("Text::on_token(EOF)");
+            // If the current node is a script element, then set its already started to true.
+            if let Some(script) = parser
+                .current_node()
+                .filter(|node| node.is_html() && node.name() == &TagName::Script)
+            {
+                parser.script_text = None;
+                parser.set_script_flags(
+                    &script,
+                    dom::ScriptFlags {
+                        already_started: true,
+                        parser_inserted: true,
+                    },
+                );
+            }
+            let _ = parser.open_elements.pop();
+
+            let mut ret = self.original_insertion_mode.into_transition_result();
+            ret.set_reprocess();
+            ret
+        }
         _ => unreachable!("Parser - Text State"),
     }
 }
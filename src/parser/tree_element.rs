@@ -0,0 +1,81 @@
+use std::fmt;
+
+use crate::{
+    dom::{
+        self,
+        element::{self, Category},
+    },
+    tokenizer::TagName,
+};
+
+/// An element created by the parser, as kept in the stack of open elements and the list of
+/// active formatting elements
+///
+/// `handle` refers to the element in the `TreeSink`, which holds everything about the element.
+/// Only the name and namespace are kept here, as tree construction looks at them for almost
+/// every token.
+///
+/// Elements are compared by identity, with `Rc::ptr_eq`.
+pub(super) struct TreeElement<H> {
+    pub(super) handle: H,
+    pub(super) name: TagName,
+    pub(super) namespace: dom::Namespace,
+    // Whether this is an HTML integration point depends on the `encoding` attribute of the
+    // start tag, so it's decided when the element is created
+    html_integration_point: bool,
+}
+
+impl<H> TreeElement<H> {
+    pub(super) fn new(
+        handle: H,
+        name: TagName,
+        namespace: dom::Namespace,
+        html_integration_point: bool,
+    ) -> Self {
+        TreeElement {
+            handle,
+            name,
+            namespace,
+            html_integration_point,
+        }
+    }
+
+    pub(super) fn name(&self) -> &TagName {
+        &self.name
+    }
+
+    pub(super) fn namespace(&self) -> dom::Namespace {
+        self.namespace
+    }
+
+    pub(super) fn is_html(&self) -> bool {
+        self.namespace == dom::Namespace::HTML
+    }
+
+    pub(super) fn category(&self) -> Category {
+        Category::of(&self.name, self.namespace)
+    }
+
+    /// See `dom::Element::is_mathml_text_integration_point`
+    pub(super) fn is_mathml_text_integration_point(&self) -> bool {
+        element::is_mathml_text_integration_point(&self.name, self.namespace)
+    }
+
+    /// See `dom::Element::is_html_integration_point`
+    pub(super) fn is_html_integration_point(&self) -> bool {
+        self.html_integration_point
+    }
+}
+
+impl<H> fmt::Debug for TreeElement<H>
+where
+    H: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreeElement")
+            .field("handle", &self.handle)
+            .field("name", &self.name)
+            .field("namespace", &self.namespace)
+            .finish()
+    }
+}
@@ -0,0 +1,117 @@
+//! The interface tree construction uses to build the document
+//!
+//! The parser decides where each node goes, and a `TreeSink` creates the nodes and links them
+//! together. `dom::Document` is the default sink, and `dom::arena::Tree` can be built directly
+//! without an intermediate `Document`.
+use std::fmt;
+
+use crate::{
+    dom::{Attribute, DocumentType, Namespace, QuirksMode, ScriptFlags},
+    tokenizer::{errors::PositionedParseError, Span, TagName},
+};
+
+/// A node to insert, or text to be merged into an adjacent text node
+///
+/// Text comes with the span of the input it was parsed from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NodeOrText<H> {
+    Node(H),
+    Text(String, Span),
+}
+
+/// Builds a tree for `Parser`
+///
+/// The parser only keeps the name and namespace of each element it creates, which is all tree
+/// construction needs for almost every element. Anything else it needs, like the attributes of
+/// a script, it asks the sink for, so the sink's nodes are the only record of the tree.
+pub trait TreeSink {
+    /// Refers to a node created by the sink
+    type Handle: Clone + fmt::Debug;
+
+    /// What `Parser::parse` returns
+    type Output;
+
+    /// Consume the sink once parsing has finished
+    fn finish(self) -> Self::Output;
+
+    /// Throw away everything built so far, as the input is going to be parsed again
+    ///
+    /// This happens when a `<meta>` element changes the encoding.
+    fn restart(&mut self);
+
+    /// Called for each parse error from the tokenizer or tree construction
+    ///
+    /// The errors are also collected in `Parser::errors`.
+    fn parse_error(&mut self, _error: &PositionedParseError) {}
+
+    /// The document node
+    fn document(&self) -> Self::Handle;
+
+    /// Create an element which isn't in the tree yet
+    fn create_element(
+        &mut self,
+        name: TagName,
+        namespace: Namespace,
+        attributes: Vec<Attribute>,
+    ) -> Self::Handle;
+
+    /// Create a comment which isn't in the tree yet
+    fn create_comment(&mut self, data: String) -> Self::Handle;
+
+    /// Record where `node`, an element or a comment, was in the input
+    ///
+    /// This is called just after the node is created, for an element the span is its start tag.
+    /// Elements which are implied by the other tags, rather than being in the input, have no
+    /// span.
+    fn set_source_span(&mut self, _node: &Self::Handle, _span: Span) {}
+
+    /// Append `child` to `parent`, removing it from its current parent first
+    ///
+    /// Text is appended to the last child of `parent` if that's a text node.
+    fn append(&mut self, parent: &Self::Handle, child: NodeOrText<Self::Handle>);
+
+    /// Insert `child` immediately before `sibling`, removing it from its current parent first
+    ///
+    /// Text is appended to the previous sibling of `sibling` if that's a text node.
+    fn append_before_sibling(&mut self, sibling: &Self::Handle, child: NodeOrText<Self::Handle>);
+
+    /// Insert `child` before `element` if it has a parent, otherwise append it to `prev_element`
+    ///
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#foster-parent>
+    fn append_based_on_parent_node(
+        &mut self,
+        element: &Self::Handle,
+        prev_element: &Self::Handle,
+        child: NodeOrText<Self::Handle>,
+    );
+
+    /// Append a doctype to the document
+    fn append_doctype_to_document(&mut self, document_type: DocumentType);
+
+    /// The value of the attribute of `element` with the given qualified name
+    ///
+    /// See `dom::Element::get_attribute`.
+    fn get_attribute(&self, element: &Self::Handle, qualified_name: &str) -> Option<String>;
+
+    /// Add each of `attributes` to `target`, unless it already has an attribute with that name
+    fn add_attributes_if_missing(&mut self, target: &Self::Handle, attributes: Vec<Attribute>);
+
+    /// Remove `target` from its parent, if it has one
+    fn remove_from_parent(&mut self, target: &Self::Handle);
+
+    /// Move all the children of `node` to the end of `new_parent`'s children
+    fn reparent_children(&mut self, node: &Self::Handle, new_parent: &Self::Handle);
+
+    /// The document fragment holding the contents of `template`, an HTML `template` element
+    ///
+    /// <https://html.spec.whatwg.org/multipage/scripting.html#template-contents>
+    fn template_contents(&mut self, template: &Self::Handle) -> Self::Handle;
+
+    /// <https://dom.spec.whatwg.org/#concept-document-mode>
+    fn set_quirks_mode(&mut self, mode: QuirksMode);
+
+    /// Record the flags of a `script` element, when it's created and when it's prepared
+    ///
+    /// Scripts aren't run by the parser, so a sink can ignore these.
+    fn set_script_flags(&mut self, _script: &Self::Handle, _flags: ScriptFlags) {}
+}
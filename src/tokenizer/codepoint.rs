@@ -70,9 +70,8 @@ pub(crate) fn is_ascii_whitespace(c: Codepoint) -> bool {
 }
 
 /// A C0 control is a code point in the range U+0000 NULL to U+001F INFORMATION SEPARATOR ONE, inclusive.
-#[allow(clippy::absurd_extreme_comparisons)]
 pub(crate) fn is_c0_control(c: Codepoint) -> bool {
-    c <= 0x0000 && c <= 0x001F
+    c <= 0x001F
 }
 
 /// A C0 control or space is a C0 control or U+0020 SPACE.
@@ -82,7 +81,7 @@ pub(crate) fn is_c0_control_or_space(c: Codepoint) -> bool {
 
 /// A control is a C0 control or a code point in the range U+007F DELETE to U+009F APPLICATION PROGRAM COMMAND, inclusive.
 pub(crate) fn is_control(c: Codepoint) -> bool {
-    0x007F <= c && c <= 0x009F
+    is_c0_control(c) || (0x007F..=0x009F).contains(&c)
 }
 
 /// An ASCII digit is a code point in the range U+0030 (0) to U+0039 (9), inclusive.
@@ -3,7 +3,7 @@ use std::{error, fmt, result};
 use auto_enums::enum_derive;
 use derive_more::From;
 
-use crate::tokenizer::States;
+use crate::tokenizer::{Position, States};
 
 mod parse_error;
 
@@ -46,3 +46,29 @@ impl fmt::Display for StateTransitionError {
         )
     }
 }
+
+/// A `ParseError` along with where in the input it occurred
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PositionedParseError {
+    pub error: ParseError,
+    pub position: Position,
+}
+
+impl PositionedParseError {
+    #[must_use]
+    pub fn new(error: ParseError, position: Position) -> Self {
+        PositionedParseError { error, position }
+    }
+}
+
+impl error::Error for PositionedParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for PositionedParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.error)
+    }
+}
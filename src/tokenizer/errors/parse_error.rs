@@ -187,6 +187,9 @@ pub enum ParseError {
     UnexpectedSolidusInTag,
     /// This error occurs if the parser encounters an ambiguous ampersand. In this case the parser doesn't resolve the character reference.
     UnknownNamedCharacterReference,
+
+    /// This error occurs during tree construction. The specification doesn't give these errors a code, instead the message describes the insertion mode and token which caused it.
+    TreeConstruction(&'static str),
 }
 
 impl error::Error for ParseError {
@@ -294,6 +297,7 @@ impl fmt::Display for ParseError {
             ParseError::UnknownNamedCharacterReference => {
                 write!(f, "UnknownNamedCharacterReference")
             }
+            ParseError::TreeConstruction(msg) => write!(f, "TreeConstruction({})", msg),
         }
     }
 }
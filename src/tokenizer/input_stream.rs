@@ -0,0 +1,882 @@
+//! The input byte stream
+//!
+//! <https://html.spec.whatwg.org/multipage/parsing.html#the-input-byte-stream>
+//!
+//! Bytes are either read from a `Read`er as the tokenizer needs them, or fed in by the user.
+//! The encoding of the input is determined with the
+//! [encoding sniffing algorithm](https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm),
+//! then the bytes are decoded into a buffer of characters for the tokenizer to consume.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, prelude::*},
+};
+
+use encoding_rs::{
+    Decoder, DecoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED,
+};
+use log::debug;
+
+use crate::parser::encodings;
+
+/// The number of bytes the prescan looks at
+const PRESCAN_LENGTH: usize = 1024;
+
+/// How many bytes to read from the reader at once
+const READ_SIZE: usize = 8 * 1024;
+
+/// How sure we are that the input's encoding is correct
+///
+/// <https://html.spec.whatwg.org/multipage/parsing.html#concept-encoding-confidence>
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Confidence {
+    /// The encoding was guessed, and may be changed by a `<meta>` element
+    Tentative,
+    /// The encoding was given by a BOM or the transport layer
+    Certain,
+    /// The encoding is irrelevant, eg. the input isn't a byte stream
+    Irrelevant,
+}
+
+pub(crate) struct InputStream<R> {
+    // None if the input is fed in with `push_bytes`
+    reader: Option<R>,
+    // No more bytes will be added to the stream
+    finished: bool,
+
+    // The transport layer's encoding, used if there is no BOM
+    hint: Option<&'static Encoding>,
+    encoding: &'static Encoding,
+    confidence: Confidence,
+    // None until enough of the input has been seen to sniff the encoding
+    decoder: Option<Decoder>,
+
+    // The start of the input, buffered until the encoding has been sniffed
+    prefix: Vec<u8>,
+    // Every byte after any BOM, kept while the encoding may still change
+    raw: Option<Vec<u8>>,
+
+    // The length of a BOM which hasn't been counted in the tokenizer's position yet
+    bom_length: usize,
+    // Bytes the decoder has read which haven't been output as a character yet
+    undecoded_length: usize,
+    // The length of a '\r' at the end of the decoded input, which may be followed by a '\n'
+    pending_cr: Option<usize>,
+    // Decoded characters, along with how many bytes of the input they were decoded from, and
+    // whether they're the same as those bytes of UTF-8. Characters inserted with `insert`
+    // weren't decoded from the input.
+    chars: VecDeque<(char, Option<usize>, bool)>,
+}
+
+impl<R> InputStream<R>
+where
+    R: Read,
+{
+    /// Read the input from `reader`, preferring the transport layer's `hint` if there is no BOM
+    pub(crate) fn new(reader: R, hint: Option<&'static Encoding>) -> Self {
+        let mut input_stream = InputStream::new_push(hint);
+        input_stream.reader = Some(reader);
+        input_stream
+    }
+
+    /// Read input which is known to be UTF-8, eg. from a string, without sniffing its encoding
+    ///
+    /// A leading UTF-8 byte order mark is still ignored.
+    pub(crate) fn new_utf8(reader: R) -> Self {
+        let mut input_stream = InputStream::new(reader, Some(UTF_8));
+        input_stream.confidence = Confidence::Irrelevant;
+        input_stream
+    }
+
+    /// Read more from the reader until at least `wanted` characters are buffered,
+    /// or the end of the input is reached
+    ///
+    /// Does nothing for input which is fed in with `push_bytes`.
+    pub(crate) fn fill(&mut self, wanted: usize) -> io::Result<()> {
+        let mut buf = [0; READ_SIZE];
+        while self.chars.len() < wanted && !self.finished {
+            let reader = match self.reader {
+                Some(ref mut reader) => reader,
+                None => return Ok(()),
+            };
+            match reader.read(&mut buf) {
+                Ok(0) => self.finish(),
+                Ok(n) => self.push_bytes(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.finish();
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R> InputStream<R> {
+    /// Input which will be fed in with `push_bytes`
+    pub(crate) fn new_push(hint: Option<&'static Encoding>) -> Self {
+        InputStream {
+            reader: None,
+            finished: false,
+
+            hint,
+            encoding: UTF_8,
+            confidence: Confidence::Tentative,
+            decoder: None,
+
+            prefix: Vec::new(),
+            raw: None,
+
+            bom_length: 0,
+            undecoded_length: 0,
+            pending_cr: None,
+            chars: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    pub(crate) fn confidence(&self) -> Confidence {
+        self.confidence
+    }
+
+    pub(crate) fn set_confidence(&mut self, confidence: Confidence) {
+        self.confidence = confidence;
+        if confidence != Confidence::Tentative {
+            self.raw = None;
+        }
+    }
+
+    pub(crate) fn push_bytes(&mut self, bytes: &[u8]) {
+        if self.finished {
+            debug!(
+                "Ignoring {} bytes pushed after the end of the input",
+                bytes.len()
+            );
+            return;
+        }
+
+        if self.decoder.is_none() {
+            self.prefix.extend_from_slice(bytes);
+            // Input known to be UTF-8 only needs to be checked for a BOM
+            let wanted = if self.confidence == Confidence::Irrelevant {
+                3
+            } else {
+                PRESCAN_LENGTH
+            };
+            if self.prefix.len() >= wanted {
+                self.sniff();
+            }
+            return;
+        }
+
+        if let Some(ref mut raw) = self.raw {
+            raw.extend_from_slice(bytes);
+        }
+        self.decode(bytes, false);
+    }
+
+    /// Mark the end of the input
+    pub(crate) fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        if self.decoder.is_none() {
+            self.sniff();
+        }
+        self.finished = true;
+        self.decode(&[], true);
+    }
+
+    /// Insert characters before the rest of the input, normalizing their newlines
+    ///
+    /// <https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#document-write-steps>
+    pub(crate) fn insert(&mut self, input: &str) {
+        let normalized = input.replace("\r\n", "\n").replace('\r', "\n");
+        for c in normalized.chars().rev() {
+            self.chars.push_front((c, None, false));
+        }
+    }
+
+    /// Whether more input is needed to have `wanted` characters buffered
+    pub(crate) fn is_starved(&self, wanted: usize) -> bool {
+        self.chars.len() < wanted && !self.finished
+    }
+
+    /// The next character, how many bytes of the input it was decoded from, or `None` if it was
+    /// inserted with `insert`, and whether it's the same as those bytes
+    ///
+    /// A character isn't the same as its bytes if the input isn't UTF-8, or it replaced a
+    /// malformed sequence or a CR.
+    pub(crate) fn next_char(&mut self) -> Option<(char, Option<usize>, bool)> {
+        self.chars.pop_front()
+    }
+
+    /// The length of the BOM at the start of the input, which comes before the first character
+    ///
+    /// Returns 0 after the first call, so the BOM is only counted once.
+    pub(crate) fn take_bom_length(&mut self) -> usize {
+        std::mem::take(&mut self.bom_length)
+    }
+
+    /// Look at the character `n` characters ahead, without consuming it
+    pub(crate) fn peek(&self, n: usize) -> Option<char> {
+        self.chars.get(n).map(|&(c, _, _)| c)
+    }
+
+    /// Go back to the start of the input, and decode it as `encoding` from now on
+    ///
+    /// # Errors
+    /// Fails if the encoding was already certain, so the start of the input wasn't kept
+    pub(crate) fn restart_with_encoding(&mut self, encoding: &'static Encoding) -> io::Result<()> {
+        let raw = self.raw.take().ok_or_else(|| {
+            io::Error::other("The input can't be restarted once its encoding is certain")
+        })?;
+
+        self.encoding = encoding;
+        self.confidence = Confidence::Certain;
+        self.decoder = Some(encoding.new_decoder_without_bom_handling());
+        self.undecoded_length = 0;
+        self.pending_cr = None;
+        self.chars.clear();
+
+        self.decode(&raw, self.finished);
+        Ok(())
+    }
+
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm>
+    fn sniff(&mut self) {
+        let prefix = std::mem::take(&mut self.prefix);
+
+        let (encoding, confidence, bom_length) = if self.confidence == Confidence::Irrelevant {
+            let bom_length = if prefix.starts_with(b"\xEF\xBB\xBF") {
+                3
+            } else {
+                0
+            };
+            (UTF_8, Confidence::Irrelevant, bom_length)
+        } else if let Some((encoding, bom_length)) = Encoding::for_bom(&prefix) {
+            (encoding, Confidence::Certain, bom_length)
+        } else if let Some(encoding) = self.hint {
+            (encoding, Confidence::Certain, 0)
+        } else if let Some(encoding) = prescan(&prefix) {
+            (encoding, Confidence::Tentative, 0)
+        } else {
+            // The implementation-defined default
+            (UTF_8, Confidence::Tentative, 0)
+        };
+        debug!("Input encoding: {} ({:?})", encoding.name(), confidence);
+
+        let bytes = &prefix[bom_length..];
+        self.encoding = encoding;
+        self.confidence = confidence;
+        self.bom_length = bom_length;
+        self.decoder = Some(encoding.new_decoder_without_bom_handling());
+        if confidence == Confidence::Tentative {
+            self.raw = Some(bytes.to_vec());
+        }
+        self.decode(bytes, false);
+    }
+
+    /// Decode `bytes`, normalizing newlines as they're added to the buffer
+    ///
+    /// <https://html.spec.whatwg.org/multipage/parsing.html#preprocessing-the-input-stream>
+    fn decode(&mut self, bytes: &[u8], last: bool) {
+        let decoder = match self.decoder {
+            Some(ref mut decoder) => decoder,
+            None => return,
+        };
+
+        let (decoded, had_errors) = decode_with_lengths(
+            decoder,
+            self.encoding,
+            &mut self.undecoded_length,
+            bytes,
+            last,
+        );
+        if had_errors {
+            debug!("Input contained invalid {} sequences", self.encoding.name());
+        }
+
+        // U+FFFD may have replaced a malformed sequence, so it's never counted as the same
+        let is_utf8 = self.encoding == UTF_8;
+        let verbatim = |c: char, len: usize| {
+            is_utf8 && len == c.len_utf8() && c != char::REPLACEMENT_CHARACTER
+        };
+
+        for (c, len) in decoded {
+            // To normalize newlines in a string, replace every
+            // U+000D CR U+000A LF (\r\n) code point pair with a single
+            // U+000A LF (\n) code point, and then replace every remaining
+            // U+000D CR (\r) code point with a U+000A LF (\n) code point.
+            if let Some(cr_len) = self.pending_cr.take() {
+                if c == '\n' {
+                    self.chars.push_back(('\n', Some(cr_len + len), false));
+                    continue;
+                }
+                self.chars.push_back(('\n', Some(cr_len), false));
+            }
+            if c == '\r' {
+                self.pending_cr = Some(len);
+            } else {
+                self.chars.push_back((c, Some(len), verbatim(c, len)));
+            }
+        }
+        if last {
+            if let Some(cr_len) = self.pending_cr.take() {
+                self.chars.push_back(('\n', Some(cr_len), false));
+            }
+        }
+    }
+}
+
+/// Decode `bytes` as `encoding`, along with how many bytes each character was decoded from, and
+/// whether any were malformed
+///
+/// Malformed sequences are replaced with U+FFFD, which has the length of the sequence.
+/// `undecoded_length` carries the bytes of a character split between calls.
+fn decode_with_lengths(
+    decoder: &mut Decoder,
+    encoding: &'static Encoding,
+    undecoded_length: &mut usize,
+    bytes: &[u8],
+    last: bool,
+) -> (Vec<(char, usize)>, bool) {
+    let mut chars = Vec::with_capacity(bytes.len());
+    let mut had_errors = false;
+
+    if encoding == UTF_8 {
+        // Every well-formed character is as long as it is in the output
+        let mut decoded = String::with_capacity(
+            decoder
+                .max_utf8_buffer_length_without_replacement(bytes.len())
+                .unwrap_or(bytes.len()),
+        );
+        let mut bytes = bytes;
+        loop {
+            let (result, read) =
+                decoder.decode_to_string_without_replacement(bytes, &mut decoded, last);
+            chars.extend(decoded.chars().map(|c| (c, c.len_utf8())));
+            decoded.clear();
+            bytes = &bytes[read..];
+            match result {
+                DecoderResult::InputEmpty => break,
+                DecoderResult::OutputFull => decoded.reserve(bytes.len() + 4),
+                DecoderResult::Malformed(len, _) => {
+                    had_errors = true;
+                    chars.push((char::REPLACEMENT_CHARACTER, usize::from(len)));
+                }
+            }
+        }
+    } else if encoding.is_single_byte() {
+        let mut decoded = String::with_capacity(
+            decoder
+                .max_utf8_buffer_length(bytes.len())
+                .unwrap_or(bytes.len()),
+        );
+        let (_, _, errors) = decoder.decode_to_string(bytes, &mut decoded, last);
+        had_errors = errors;
+        chars.extend(decoded.chars().map(|c| (c, 1)));
+    } else {
+        // Characters of other encodings vary in length, so decode a byte at a time and count
+        // the bytes read since the last character was output
+        let mut decoded = String::new();
+        let mut decode_byte = |byte: &[u8], last: bool| {
+            decoded.reserve(decoder.max_utf8_buffer_length(byte.len()).unwrap_or(16));
+            let (_, _, errors) = decoder.decode_to_string(byte, &mut decoded, last);
+            had_errors |= errors;
+            *undecoded_length += byte.len();
+            for c in decoded.chars() {
+                // Any further characters decoded from the same bytes have no length
+                chars.push((c, std::mem::take(undecoded_length)));
+            }
+            decoded.clear();
+        };
+        for byte in bytes.chunks(1) {
+            decode_byte(byte, false);
+        }
+        if last {
+            decode_byte(&[], true);
+        }
+    }
+
+    (chars, had_errors)
+}
+
+impl<R> fmt::Debug for InputStream<R>
+where
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InputStream")
+            .field("reader", &self.reader)
+            .field("finished", &self.finished)
+            .field("encoding", &self.encoding)
+            .field("confidence", &self.confidence)
+            .field("chars", &self.chars.len())
+            .finish()
+    }
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding>
+///
+/// Look for a `<meta>` element declaring the encoding in the start of the input.
+#[must_use]
+pub(crate) fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let starts_with = |position: usize, s: &[u8]| {
+        bytes
+            .get(position..position + s.len())
+            .is_some_and(|b| b.eq_ignore_ascii_case(s))
+    };
+    let find = |position: usize, s: &[u8]| {
+        bytes[position..]
+            .windows(s.len())
+            .position(|w| w == s)
+            .map(|i| position + i)
+    };
+
+    let mut position = 0;
+    while position < bytes.len() {
+        if starts_with(position, b"<!--") {
+            // The '>' of "-->" may overlap the "<!--"
+            position = find(position + 2, b"-->")? + 2;
+        } else if starts_with(position, b"<meta")
+            && bytes
+                .get(position + 5)
+                .is_some_and(|&b| is_whitespace(b) || b == b'/')
+        {
+            position += 5;
+            if let Some(encoding) = prescan_meta(bytes, &mut position)? {
+                return Some(encoding);
+            }
+        } else if (starts_with(position, b"<")
+            && bytes.get(position + 1).is_some_and(u8::is_ascii_alphabetic))
+            || (starts_with(position, b"</")
+                && bytes.get(position + 2).is_some_and(u8::is_ascii_alphabetic))
+        {
+            while !bytes
+                .get(position)
+                .is_some_and(|&b| is_whitespace(b) || b == b'>')
+            {
+                position += 1;
+                if position >= bytes.len() {
+                    return None;
+                }
+            }
+            while get_an_attribute(bytes, &mut position)?.is_some() {}
+        } else if starts_with(position, b"<!")
+            || starts_with(position, b"</")
+            || starts_with(position, b"<?")
+        {
+            position = find(position, b">")?;
+        }
+
+        position += 1;
+    }
+
+    None
+}
+
+/// The attributes of a `<meta>` element found by the prescan
+///
+/// Returns `None` if the end of the input was reached, otherwise the declared encoding, if any.
+fn prescan_meta(bytes: &[u8], position: &mut usize) -> Option<Option<&'static Encoding>> {
+    let mut attribute_list = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+
+    while let Some((name, value)) = get_an_attribute(bytes, position)? {
+        if attribute_list.contains(&name) {
+            continue;
+        }
+
+        match name.as_str() {
+            "http-equiv" if value == "content-type" => got_pragma = true,
+            "content" if charset.is_none() => {
+                if let Some(encoding) = extract_character_encoding_from_meta(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            "charset" => {
+                charset = encodings::get_decoder(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        attribute_list.push(name);
+    }
+
+    match need_pragma {
+        None => return Some(None),
+        Some(true) if !got_pragma => return Some(None),
+        _ => {}
+    }
+    match charset {
+        None => Some(None),
+        Some(encoding) if encoding == UTF_16BE || encoding == UTF_16LE => Some(Some(UTF_8)),
+        Some(encoding) if encoding == X_USER_DEFINED => Some(Some(WINDOWS_1252)),
+        Some(encoding) => Some(Some(encoding)),
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing>
+///
+/// Returns `None` if the end of the input was reached, otherwise the next attribute, if any.
+/// Names and values are lowercased.
+fn get_an_attribute(bytes: &[u8], position: &mut usize) -> Option<Option<(String, String)>> {
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    let next = |position: &mut usize| {
+        let b = bytes.get(*position).copied();
+        *position += 1;
+        b
+    };
+    let attribute = |name: Vec<u8>, value: Vec<u8>| {
+        Some(Some((
+            String::from_utf8_lossy(&name).into_owned(),
+            String::from_utf8_lossy(&value).into_owned(),
+        )))
+    };
+
+    let mut b = next(position)?;
+    while is_whitespace(b) || b == b'/' {
+        b = next(position)?;
+    }
+    if b == b'>' {
+        *position -= 1;
+        return Some(None);
+    }
+
+    // Attribute name
+    loop {
+        match b {
+            b'=' if !name.is_empty() => break,
+            _ if is_whitespace(b) => {
+                // Spaces
+                while is_whitespace(b) {
+                    b = next(position)?;
+                }
+                if b != b'=' {
+                    *position -= 1;
+                    return attribute(name, value);
+                }
+                break;
+            }
+            b'/' | b'>' => {
+                *position -= 1;
+                return attribute(name, value);
+            }
+            b => name.push(b.to_ascii_lowercase()),
+        }
+        b = next(position)?;
+    }
+
+    // Attribute value
+    b = next(position)?;
+    while is_whitespace(b) {
+        b = next(position)?;
+    }
+    match b {
+        quote @ b'"' | quote @ b'\'' => loop {
+            b = next(position)?;
+            if b == quote {
+                return attribute(name, value);
+            }
+            value.push(b.to_ascii_lowercase());
+        },
+        b'>' => {
+            *position -= 1;
+            return attribute(name, value);
+        }
+        b => value.push(b.to_ascii_lowercase()),
+    }
+    loop {
+        b = next(position)?;
+        if is_whitespace(b) || b == b'>' {
+            *position -= 1;
+            return attribute(name, value);
+        }
+        value.push(b.to_ascii_lowercase());
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element>
+///
+/// Find the encoding in the `content` attribute of a `<meta http-equiv="Content-Type">` element
+#[must_use]
+pub(crate) fn extract_character_encoding_from_meta(content: &str) -> Option<&'static Encoding> {
+    let content = content.as_bytes();
+    let mut position = 0;
+
+    loop {
+        let charset = content[position..]
+            .windows(b"charset".len())
+            .position(|w| w.eq_ignore_ascii_case(b"charset"))?;
+        position += charset + b"charset".len();
+
+        while content.get(position).copied().is_some_and(is_whitespace) {
+            position += 1;
+        }
+        if content.get(position) == Some(&b'=') {
+            position += 1;
+            break;
+        }
+    }
+
+    while content.get(position).copied().is_some_and(is_whitespace) {
+        position += 1;
+    }
+
+    let value = match content.get(position)? {
+        quote @ b'"' | quote @ b'\'' => {
+            let len = content[position + 1..].iter().position(|b| b == quote)?;
+            &content[position + 1..position + 1 + len]
+        }
+        _ => {
+            let len = content[position..]
+                .iter()
+                .position(|&b| is_whitespace(b) || b == b';')
+                .unwrap_or(content.len() - position);
+            &content[position..position + len]
+        }
+    };
+
+    encodings::get_decoder(&String::from_utf8_lossy(value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use encoding_rs::{EUC_KR, ISO_8859_2, SHIFT_JIS};
+
+    fn sniffed(input: &[u8], hint: Option<&'static Encoding>) -> (&'static Encoding, Confidence) {
+        let mut stream = InputStream::new(input, hint);
+        stream.fill(1).unwrap();
+        (stream.encoding(), stream.confidence())
+    }
+
+    fn read_to_string<R: Read>(stream: &mut InputStream<R>) -> String {
+        stream.fill(usize::MAX).unwrap();
+        std::iter::from_fn(|| stream.next_char().map(|(c, _, _)| c)).collect()
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!(
+            sniffed(b"\xEF\xBB\xBF<p>", Some(SHIFT_JIS)),
+            (UTF_8, Confidence::Certain)
+        );
+        assert_eq!(
+            sniffed(b"\xFE\xFF\x00<", None),
+            (UTF_16BE, Confidence::Certain)
+        );
+        assert_eq!(
+            sniffed(b"\xFF\xFE<\x00", None),
+            (UTF_16LE, Confidence::Certain)
+        );
+    }
+
+    #[test]
+    fn transport_layer_hint() {
+        assert_eq!(
+            sniffed(b"<meta charset=euc-kr>", Some(SHIFT_JIS)),
+            (SHIFT_JIS, Confidence::Certain)
+        );
+        assert_eq!(sniffed(b"<p>", None), (UTF_8, Confidence::Tentative));
+    }
+
+    #[test]
+    fn prescan_meta_charset() {
+        assert_eq!(prescan(b"<meta charset=\"EUC-KR\">"), Some(EUC_KR));
+        assert_eq!(prescan(b"<META CHARSET=latin2 />"), Some(ISO_8859_2));
+        assert_eq!(
+            prescan(
+                b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset='shift_jis'\">"
+            ),
+            Some(SHIFT_JIS)
+        );
+        // A content attribute without the pragma is ignored
+        assert_eq!(
+            prescan(b"<meta content=\"text/html; charset=shift_jis\">"),
+            None
+        );
+        // UTF-16 can't be declared in the document
+        assert_eq!(prescan(b"<meta charset=utf-16le>"), Some(UTF_8));
+        assert_eq!(
+            prescan(b"<meta charset=x-user-defined>"),
+            Some(WINDOWS_1252)
+        );
+    }
+
+    #[test]
+    fn prescan_skips_comments_and_tags() {
+        assert_eq!(prescan(b"<!-- <meta charset=euc-kr> --><p>"), None);
+        assert_eq!(
+            prescan(b"<!--><title data-x=\"<meta charset=euc-kr>\"><meta charset=latin2>"),
+            Some(ISO_8859_2)
+        );
+        assert_eq!(
+            prescan(b"<?xml version=\"1.0\"?><meta charset=euc-kr>"),
+            Some(EUC_KR)
+        );
+        assert_eq!(prescan(b"<meta charset=euc-kr"), None);
+    }
+
+    #[test]
+    fn extract_from_meta_content() {
+        assert_eq!(
+            extract_character_encoding_from_meta("text/html; charset=Shift_JIS"),
+            Some(SHIFT_JIS)
+        );
+        assert_eq!(
+            extract_character_encoding_from_meta("text/html; charset = \"euc-kr\""),
+            Some(EUC_KR)
+        );
+        assert_eq!(
+            extract_character_encoding_from_meta("charsetcharset=latin2;foo"),
+            Some(ISO_8859_2)
+        );
+        assert_eq!(
+            extract_character_encoding_from_meta("charset='euc-kr"),
+            None
+        );
+        assert_eq!(extract_character_encoding_from_meta("text/html"), None);
+    }
+
+    #[test]
+    fn decodes_input() {
+        let mut stream = InputStream::new(&b"\xFF\xFEa\x00\xE9\x00"[..], None);
+        assert_eq!(read_to_string(&mut stream), "a\u{E9}");
+
+        let mut stream = InputStream::new(&b"caf\xE9"[..], Some(WINDOWS_1252));
+        assert_eq!(read_to_string(&mut stream), "caf\u{E9}");
+    }
+
+    #[test]
+    fn replaces_malformed_input() {
+        // Truncated, overlong, surrogate and too long sequences each become U+FFFD
+        let mut stream = InputStream::new_utf8(&b"a\xF0\x9F\x98b\xC0\xAFc\xED\xA0\x80d"[..]);
+        assert_eq!(
+            read_to_string(&mut stream),
+            "a\u{FFFD}b\u{FFFD}\u{FFFD}c\u{FFFD}\u{FFFD}\u{FFFD}d"
+        );
+        let mut stream = InputStream::new_utf8(&b"\xF8\x88\x80\x80\x80e"[..]);
+        assert_eq!(read_to_string(&mut stream), "\u{FFFD}".repeat(5) + "e");
+    }
+
+    #[test]
+    fn strips_byte_order_mark() {
+        let mut stream = InputStream::new(&b"\xEF\xBB\xBFa\xEF\xBB\xBF"[..], None);
+        assert_eq!(read_to_string(&mut stream), "a\u{FEFF}");
+        let mut stream = InputStream::new_utf8(&b"\xEF\xBB\xBFa"[..]);
+        assert_eq!(read_to_string(&mut stream), "a");
+    }
+
+    #[test]
+    fn normalizes_newlines() {
+        let mut stream = InputStream::new(&b"a\r\nb\rc\r"[..], None);
+        stream.fill(usize::MAX).unwrap();
+        let chars: Vec<_> = std::iter::from_fn(|| stream.next_char()).collect();
+        assert_eq!(
+            chars,
+            vec![
+                ('a', Some(1), true),
+                ('\n', Some(2), false),
+                ('b', Some(1), true),
+                ('\n', Some(1), false),
+                ('c', Some(1), true),
+                ('\n', Some(1), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn source_lengths() {
+        let lengths = |stream: &mut InputStream<&[u8]>| {
+            stream.fill(usize::MAX).unwrap();
+            let bom_length = stream.take_bom_length();
+            let chars: Vec<_> = std::iter::from_fn(|| stream.next_char())
+                .map(|(c, len, _)| (c, len))
+                .collect();
+            (bom_length, chars)
+        };
+
+        // A BOM and CRLF in UTF-16
+        let mut stream = InputStream::new(&b"\xFF\xFEa\x00\r\x00\n\x00\x00\xD8\x00\xDF"[..], None);
+        assert_eq!(
+            lengths(&mut stream),
+            (
+                2,
+                vec![('a', Some(2)), ('\n', Some(4)), ('\u{10300}', Some(4))]
+            )
+        );
+
+        let mut stream = InputStream::new(&b"\x80\r\n"[..], Some(WINDOWS_1252));
+        assert_eq!(
+            lengths(&mut stream),
+            (0, vec![('\u{20AC}', Some(1)), ('\n', Some(2))])
+        );
+
+        let mut stream = InputStream::new(&b"a\x82\xA0\xB1"[..], Some(SHIFT_JIS));
+        assert_eq!(
+            lengths(&mut stream),
+            (
+                0,
+                vec![('a', Some(1)), ('\u{3042}', Some(2)), ('\u{FF71}', Some(1))]
+            )
+        );
+
+        // Malformed sequences have the length of the bytes they replace
+        let mut stream = InputStream::new_utf8(&b"\xEF\xBB\xBF\xF0\x9F\x98b\xC3\xA9"[..]);
+        assert_eq!(
+            lengths(&mut stream),
+            (
+                3,
+                vec![('\u{FFFD}', Some(3)), ('b', Some(1)), ('\u{E9}', Some(2))]
+            )
+        );
+    }
+
+    #[test]
+    fn inserted_characters() {
+        let mut stream = InputStream::new_utf8(&b"<p>a"[..]);
+        stream.fill(2).unwrap();
+        assert_eq!(stream.next_char(), Some(('<', Some(1), true)));
+        stream.insert("b\r\nc\r");
+        assert_eq!(read_to_string(&mut stream), "b\nc\np>a");
+    }
+
+    #[test]
+    fn pushed_bytes() {
+        let mut stream = InputStream::<io::Empty>::new_push(None);
+        // A multi-byte character and a CRLF split across chunks
+        for chunk in &[&b"caf\xC3"[..], b"\xA9\r", b"\n!"] {
+            stream.push_bytes(chunk);
+        }
+        // The encoding can't be sniffed until the end of the input, or the prescan length
+        assert!(stream.is_starved(1));
+        stream.finish();
+        assert!(!stream.is_starved(1));
+        assert_eq!(read_to_string(&mut stream), "caf\u{E9}\n!");
+    }
+
+    #[test]
+    fn restart_with_encoding() {
+        let mut stream = InputStream::new(&b"caf\xE9"[..], None);
+        stream.fill(usize::MAX).unwrap();
+        assert_eq!(stream.confidence(), Confidence::Tentative);
+        stream.restart_with_encoding(WINDOWS_1252).unwrap();
+        assert_eq!(read_to_string(&mut stream), "caf\u{E9}");
+        assert!(stream.restart_with_encoding(UTF_8).is_err());
+    }
+}
@@ -1,15 +1,20 @@
 use std::{
     cell::{Cell, RefCell},
+    collections::VecDeque,
     fmt,
-    io::{prelude::*, BufReader, SeekFrom},
-    str,
+    io::{self, prelude::*},
 };
 
-use log::{debug, trace};
+use encoding_rs::Encoding;
+use log::{debug, error, trace};
+
+use crate::parser::encodings;
 
 mod codepoint;
 pub mod errors;
+mod input_stream;
 mod named_character_references;
+mod position;
 mod states;
 mod tagname;
 mod token;
@@ -18,22 +23,30 @@ mod transitions;
 
 use self::{
     codepoint::Codepoint,
-    errors::Result,
+    errors::{Error, ParseError, PositionedParseError},
+    input_stream::InputStream,
+    named_character_references::get_entity_trie,
     states::{Character, NamedCharacterReference, States},
 };
 
+pub(crate) use input_stream::extract_character_encoding_from_meta;
+pub use input_stream::Confidence;
 pub use named_character_references::{get_entities, Entity};
-pub(crate) use tagname::TagName;
-pub(crate) use token::Token;
+pub use position::{Position, Span};
+pub use tagname::TagName;
+pub use token::{Attribute, Doctype, EndTag, ForceQuirksFlag, SelfClosingFlag, StartTag, Token};
 pub(crate) use transition_result::TransitionResult;
 
 type Emit = Vec<Token>;
 
-pub struct Tokenizer<R>
-where
-    R: Read + Seek,
-{
-    reader: BufReader<R>,
+/// How many characters of lookahead the tokenizer needs,
+/// enough for the longest named character reference
+const LOOKAHEAD: usize = 64;
+
+pub struct Tokenizer<R> {
+    input: InputStream<R>,
+    // An error reading the input or in the state machine, which ended tokenizing early
+    error: Option<Error>,
     collapse_chars: bool,
 
     state: Option<States>,
@@ -41,21 +54,88 @@ where
     last_char: Option<Character>,
     last_start_tag_emitted: Option<token::StartTag>,
 
+    // The position of the next character to be read,
+    // and the position of the last character which was read
+    next_position: Position,
+    char_position: Position,
+    errors: Vec<PositionedParseError>,
+
+    // Set by the parser when the adjusted current node is not in the HTML namespace
+    in_foreign_content: bool,
+    // Set by the parser in the insertion modes which treat whitespace differently from other
+    // text, so a collapsed run of whitespace is returned on its own
+    whitespace_runs: bool,
+    // Set by the parser after a `pre`, `listing` or `textarea` start tag
+    ignore_line_feed: bool,
+
+    // Where the next token to be emitted starts
+    token_start: Position,
+    // Whether a character was replaced since the start of the current step, so the characters
+    // emitted may not be the same as the input, and whether the last token returned was the same
+    replaced: bool,
+    token_verbatim: bool,
+
     // We collapse multiple Token::Character into Token::Characters
-    characters_emit_cache: Cell<Option<Token>>,
-    token_emit_cache: RefCell<Vec<Token>>,
+    characters_emit_cache: Cell<Option<(Token, Span, bool)>>,
+    cached_whitespace: bool,
+    token_emit_cache: RefCell<VecDeque<(Token, Span, bool)>>,
 }
 
 impl<R> Tokenizer<R>
 where
-    R: Read + Seek,
+    R: Read,
 {
+    /// Create a tokenizer which reads its input from `reader` as it's needed,
+    /// sniffing the encoding of the input
     pub fn new(reader: R, collapse_chars: bool) -> Self {
+        Tokenizer::from_input_stream(InputStream::new(reader, None), collapse_chars)
+    }
+
+    /// Create a tokenizer for input whose encoding was given by the transport layer,
+    /// eg. the charset parameter of a Content-Type header
+    ///
+    /// A byte order mark in the input takes precedence over the given encoding,
+    /// an unknown encoding label is ignored.
+    pub fn with_transport_encoding(reader: R, collapse_chars: bool, label: &str) -> Self {
+        let hint = encodings::get_decoder(label);
+        Tokenizer::from_input_stream(InputStream::new(reader, hint), collapse_chars)
+    }
+
+    /// Create a tokenizer for input which is known to be UTF-8, eg. from a string,
+    /// so the encoding is irrelevant
+    pub(crate) fn new_utf8(reader: R, collapse_chars: bool) -> Self {
+        Tokenizer::from_input_stream(InputStream::new_utf8(reader), collapse_chars)
+    }
+}
+
+impl Tokenizer<io::Empty> {
+    /// Create a tokenizer whose input is fed in with `Tokenizer::feed`
+    ///
+    /// Tokens are emitted once enough input is available to be sure of them,
+    /// after the last chunk of input call `Tokenizer::finish` to get the rest.
+    /// Collapsed characters are returned when more input is needed, so a run of text may be
+    /// split across several `Token::Characters`.
+    #[must_use]
+    pub fn new_push(collapse_chars: bool) -> Self {
+        Tokenizer::from_input_stream(InputStream::new_push(None), collapse_chars)
+    }
+
+    /// Add the next chunk of input
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.input.push_bytes(bytes);
+    }
+
+    /// Mark the end of the input
+    pub fn finish(&mut self) {
+        self.input.finish();
+    }
+}
+
+impl<R> Tokenizer<R> {
+    fn from_input_stream(input_stream: InputStream<R>, collapse_chars: bool) -> Self {
         Tokenizer {
-            // TODO, we assume this is UTF-8
-            // To be standard compliant we should use the
-            // [encoding sniffing algorithm](https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm)
-            reader: BufReader::new(reader),
+            input: input_stream,
+            error: None,
             collapse_chars,
 
             state: Some(States::new()),
@@ -63,106 +143,230 @@ where
             last_char: None,
             last_start_tag_emitted: None,
 
+            next_position: Position::new(),
+            char_position: Position::new(),
+            errors: Vec::new(),
+
+            in_foreign_content: false,
+            whitespace_runs: false,
+            ignore_line_feed: false,
+
+            token_start: Position::new(),
+            replaced: false,
+            token_verbatim: false,
+
             characters_emit_cache: Cell::new(None),
-            token_emit_cache: RefCell::new(Vec::new()),
+            cached_whitespace: false,
+            token_emit_cache: RefCell::new(VecDeque::new()),
         }
     }
 
-    pub fn run(&mut self) {
+    pub fn run(&mut self)
+    where
+        R: Read,
+    {
         // IDEAS:
         //
         // TODO:
         // '<' in Script tag...
         // StartTag(StartTag { name: "t.length;r++)console.log(\"actionqueue\",c(t[r]))}function&&&&&&&&&&&&&&&",
 
-        for token in self {
+        for (token, _) in self {
             println!("[EMIT]: {}", token);
         }
     }
 
-    pub(crate) fn switch_to_rawtext_state(&mut self) {
+    /// The encoding the input is being decoded with
+    #[must_use]
+    pub fn encoding(&self) -> &'static Encoding {
+        self.input.encoding()
+    }
+
+    /// How sure we are of the input's encoding
+    #[must_use]
+    pub fn confidence(&self) -> Confidence {
+        self.input.confidence()
+    }
+
+    pub(crate) fn set_confidence(&mut self, confidence: Confidence) {
+        self.input.set_confidence(confidence);
+    }
+
+    /// Start tokenizing again from the start of the input, decoding it as `encoding`
+    ///
+    /// # Errors
+    /// Fails if the encoding is already certain, so the start of the input wasn't kept
+    pub(crate) fn restart_with_encoding(&mut self, encoding: &'static Encoding) -> io::Result<()> {
+        self.input.restart_with_encoding(encoding)?;
+
+        self.state = Some(States::new());
+        self.reconsume = false;
+        self.last_char = None;
+        self.last_start_tag_emitted = None;
+        self.next_position = Position::new();
+        self.char_position = Position::new();
+        self.errors.clear();
+        self.in_foreign_content = false;
+        self.whitespace_runs = false;
+        self.ignore_line_feed = false;
+        self.token_start = Position::new();
+        self.replaced = false;
+        self.token_verbatim = false;
+        self.characters_emit_cache.set(None);
+        self.token_emit_cache.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// Whether the tokenizer is waiting for more input to be fed in
+    pub(crate) fn is_starved(&self) -> bool {
+        self.input.is_starved(LOOKAHEAD)
+    }
+
+    /// An error which stopped tokenizing, if there was one
+    ///
+    /// This is either an error reading the input, after which the input was treated as having
+    /// ended, or a bug in the tokenizer, after which no more tokens are returned.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// The parse errors found so far
+    #[must_use]
+    pub fn errors(&self) -> &[PositionedParseError] {
+        &self.errors
+    }
+
+    /// The position of the last character consumed from the input
+    #[must_use]
+    pub fn position(&self) -> Position {
+        self.char_position
+    }
+
+    /// Whether the text of the `Token::Character` or `Token::Characters` last returned by
+    /// `Tokenizer::next` is exactly the input covered by its span
+    ///
+    /// When it is, the text can be borrowed from in-memory input with `Span::slice` instead of
+    /// using the token's own `String`. It isn't when a character reference, CR or NUL was
+    /// replaced, or when the input isn't UTF-8. This is always false for other tokens.
+    #[must_use]
+    pub fn token_is_verbatim(&self) -> bool {
+        self.token_verbatim
+    }
+
+    pub(crate) fn take_errors(&mut self) -> Vec<PositionedParseError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// CDATA sections are only allowed when the adjusted current node is a foreign element
+    pub(crate) fn set_in_foreign_content(&mut self, in_foreign_content: bool) {
+        self.in_foreign_content = in_foreign_content;
+    }
+
+    /// Return collapsed whitespace separately from the text after it, for the insertion modes
+    /// which only handle whitespace themselves
+    ///
+    /// A run is only split where whitespace is followed by other text, so a run which starts
+    /// with anything else is left whole.
+    pub(crate) fn set_whitespace_runs(&mut self, whitespace_runs: bool) {
+        self.whitespace_runs = whitespace_runs;
+    }
+
+    /// Drop the next token if it's a line feed, as the start of a `pre`, `listing` or
+    /// `textarea` element is
+    pub(crate) fn ignore_next_line_feed(&mut self) {
+        self.ignore_line_feed = true;
+    }
+
+    // The tree construction stage switches the tokenizer's state for some elements,
+    // these are also used to start tokenizing in a given state (eg. for the html5lib tests)
+
+    pub fn switch_to_rawtext_state(&mut self) {
         trace!("External switch to States::RawText");
         self.state = Some(States::raw_text(String::new()))
     }
 
-    pub(crate) fn switch_to_rcdata_state(&mut self) {
+    pub fn switch_to_rcdata_state(&mut self) {
         trace!("External switch to States::RcData");
         self.state = Some(States::rc_data(String::new()))
     }
 
-    fn peek_next_character(&mut self) -> Result<Character> {
-        let pos = self.reader.seek(SeekFrom::Current(0))?;
-        let ret = self.next_character()?;
-        trace!("Peeked char: {:?}", ret);
-        let _ = self.reader.seek(SeekFrom::Start(pos))?;
-        Ok(ret)
+    pub fn switch_to_script_data_state(&mut self) {
+        trace!("External switch to States::ScriptData");
+        self.state = Some(States::script_data())
     }
 
-    fn next_character(&mut self) -> Result<Character> {
-        // TODO: https://html.spec.whatwg.org/multipage/parsing.html#preprocessing-the-input-stream
-        // Any occurrences of surrogates are surrogate-in-input-stream parse errors.
-        // Any occurrences of noncharacters are noncharacter-in-input-stream parse errors
-        // and any occurrences of controls other than ASCII whitespace and U+0000 NULL
-        // characters are control-character-in-input-stream parse errors.
+    pub fn switch_to_plaintext_state(&mut self) {
+        trace!("External switch to States::PlainText");
+        self.state = Some(States::plain_text())
+    }
 
-        let mut potential_char = Vec::with_capacity(4);
-        loop {
-            let mut b = [0; 1];
-            match self.reader.read_exact(&mut b) {
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
-                    return Ok(Character::Eof)
-                }
-                r => r,
-            }?;
-            potential_char.push(b[0]);
-            let c = match str::from_utf8(&potential_char) {
-                // To normalize newlines in a string, replace every
-                // U+000D CR U+000A LF (\r\n) code point pair with a single
-                // U+000A LF (\n) code point, and then replace every remaining
-                // U+000D CR (\r) code point with a U+000A LF (\n) code point.
-                Ok("\r") => {
-                    if let Character::Char('\n') = self.peek_next_character()? {
-                        // Remove the '\r' from potential_char, the '\n' found next will be handled
-                        let _ = potential_char.pop();
-                        continue;
-                    } else {
-                        // Replace the '\r' in potential_char with '\n'
-                        trace!("Replacing lone \\r with \\n");
-                        Ok('\n'.into())
-                    }
+    /// Insert characters at the insertion point, so they're tokenized before the rest of the input
+    ///
+    /// This is how `document.write()` adds to the input while the parser is paused for a
+    /// script. Parse errors in the characters are reported at the insertion point.
+    pub fn insert_input(&mut self, input: &str) {
+        trace!("Inserting {:?} at the insertion point", input);
+        self.input.insert(input);
+    }
+
+    /// Set the name of the last start tag, as if it had been emitted
+    ///
+    /// This decides whether an end tag is appropriate in the RCDATA, RAWTEXT and script data
+    /// states.
+    pub fn set_last_start_tag(&mut self, name: &str) {
+        let mut tag = StartTag::default();
+        tag.push_str(name);
+        tag.name = tag.name.finalize();
+        self.last_start_tag_emitted = Some(tag);
+    }
+
+    fn peek_next_character(&self) -> Character {
+        let ret = self.input.peek(0).map_or(Character::Eof, Character::Char);
+        trace!("Peeked char: {:?}", ret);
+        ret
+    }
+
+    fn next_character(&mut self) -> Character {
+        self.char_position = self.next_position;
+        match self.input.next_char() {
+            Some((c, len, verbatim)) => {
+                trace!("Read character: {:?}", c);
+                // A NUL may be replaced with U+FFFD by the state
+                self.replaced |= !verbatim || c == '\0';
+                if let Some(error) = input_stream_error(c) {
+                    self.errors
+                        .push(PositionedParseError::new(error, self.char_position));
                 }
-                Ok(c) => Ok(c.chars().next().unwrap().into()),
-                e @ Err(_) if potential_char.len() == 4 => {
-                    debug!("Invalid UTF8: {:x?}", potential_char);
-                    e.map(|_| unreachable!())?
+                // Characters inserted by a script aren't part of the input, so the position stays
+                // at the insertion point
+                if let Some(len) = len {
+                    self.next_position.advance(c, len);
                 }
-                Err(_) => continue,
-            };
-            trace!("Read character: {:?}", c);
-            return c;
+                Character::Char(c)
+            }
+            None => Character::Eof,
         }
     }
 
-    fn next_few_characters_are(&mut self, other: &str, case_insesitive: bool) -> Result<bool> {
-        let pos = self.reader.seek(SeekFrom::Current(0))?;
-        let mut buffer = vec![0_u8; other.len()];
-        if self.reader.read_exact(&mut buffer).is_err() {
-            return Ok(false);
-        }
-        let read = str::from_utf8(&buffer);
-        trace!("next_few_characters_are:: Read {:?}", read);
-        match read {
-            Ok(s)
-                if (!case_insesitive && (s == other))
-                    || (case_insesitive && (s.eq_ignore_ascii_case(other))) =>
-            {
-                Ok(true)
-            }
-            _ => {
-                let _ = self.reader.seek(SeekFrom::Start(pos))?;
-                Ok(false)
+    fn next_few_characters_are(&mut self, other: &str, case_insesitive: bool) -> bool {
+        let matches = other
+            .chars()
+            .enumerate()
+            .all(|(i, c)| match self.input.peek(i) {
+                Some(next) if case_insesitive => next.eq_ignore_ascii_case(&c),
+                Some(next) => next == c,
+                None => false,
+            });
+        trace!("next_few_characters_are({:?}): {}", other, matches);
+
+        if matches {
+            let char_position = self.next_position;
+            for _ in other.chars() {
+                let _ = self.next_character();
             }
+            self.char_position = char_position;
         }
+        matches
     }
 
     // Consume the maximum number of characters possible,
@@ -172,122 +376,371 @@ where
         &mut self,
         original_character: Character,
         tmp: &mut String,
-    ) -> Result<Option<String>> {
-        let entities = get_entities();
-        let identifiers = entities.keys().collect::<std::collections::HashSet<_>>();
-        trace!(
-            "Searching for named character referece ({} names)",
-            identifiers.len()
-        );
+    ) -> Option<String> {
+        let trie = get_entity_trie();
+        trace!("Searching for named character reference");
         let original_char = match original_character {
             Character::Char(c) => c,
-            Character::Eof => {
-                todo!("find_named_character_reference: How to handle EOF/NULL?");
-            }
+            // Nothing can match, the characters consumed so far are flushed
+            Character::Eof => return None,
         };
         tmp.push(original_char);
+        let tmp_start = tmp.len();
 
+        // Characters are only peeked at here, then those which are part of the match are consumed
         let mut found_ident = None;
-        let mut last_valid_reader_pos = self.reader.seek(SeekFrom::Current(0))?;
+        let mut peeked = 0;
+        let mut matched = 0;
 
-        loop {
-            trace!("Checking {:?} against idents", tmp);
-            // ownership woes
-            let r_tmp = tmp.clone();
-            if let Some(&&ident) = identifiers.iter().find(|&&ident| ident.starts_with(&r_tmp)) {
-                if ident == tmp {
-                    found_ident = Some(ident.to_string());
-                    last_valid_reader_pos = self.reader.seek(SeekFrom::Current(0))?;
-                    trace!(
-                        "Exact character reference match found at pos: {:?}",
-                        last_valid_reader_pos
-                    );
+        // Walk down the trie as the characters are peeked, so each is only looked at once
+        let mut node = trie.find(tmp);
+        while let Some(current) = node {
+            if let Some(ident) = trie.name(current) {
+                found_ident = Some(ident.to_string());
+                matched = peeked;
+                trace!("Exact character reference match found: {:?}", tmp);
+            }
+            // We have an initial match, check again with the next char
+            match self.input.peek(peeked) {
+                Some(c) => {
+                    tmp.push(c);
+                    node = trie.child(current, c);
                 }
-                // We have an initial match, read next char from file and check again
-                let next_char = match self.next_character()? {
-                    Character::Eof => {
-                        todo!("find_named_character_reference: How to handle EOF/NULL?")
-                    }
-                    Character::Char(c) => c,
-                };
-                tmp.push(next_char);
-            } else {
-                trace!("{:?} didn't match the start of any idents", tmp);
+                None => break,
+            }
+            peeked += 1;
+        }
+        if node.is_none() {
+            trace!("{:?} didn't match the start of any idents", tmp);
+        }
 
-                // If we ever found anything, we should seek back 1 char
-                if found_ident.is_some() {
-                    // Remove the excess char we read in (remember, not only a single byte!)
-                    let _ = tmp.pop();
-                    let _ = self.reader.seek(SeekFrom::Start(last_valid_reader_pos))?;
-                }
-                break;
+        // If we ever found anything, only the characters of the match are consumed
+        if found_ident.is_some() {
+            let len = tmp[tmp_start..]
+                .char_indices()
+                .nth(matched)
+                .map_or(tmp.len(), |(i, _)| tmp_start + i);
+            tmp.truncate(len);
+        } else {
+            // The character which didn't continue any ident isn't consumed, it may end an
+            // attribute value. If the original character didn't start one, nothing was peeked.
+            if node.is_none() && peeked > 0 {
+                tmp.pop();
+                peeked -= 1;
             }
+            matched = peeked;
+        }
+        for _ in 0..matched {
+            let _ = self.next_character();
         }
 
-        Ok(found_ident)
+        found_ident
     }
 
-    fn handle_transition_result(&mut self, mut res: TransitionResult) -> Option<Token> {
+    fn handle_transition_result(&mut self, mut res: TransitionResult) -> Option<(Token, Span)> {
+        for err in res.parse_errors() {
+            self.errors
+                .push(PositionedParseError::new(err, self.char_position));
+        }
+
+        // A reconsumed character is part of whatever comes next
+        let end = if res.reconsume() {
+            self.char_position
+        } else {
+            self.next_position
+        };
+        let span = Span::new(self.token_start, end);
         for token in res.emits() {
-            if self.collapse_chars {
-                if !token.is_character() {
-                    if let Some(cached_token) = self.characters_emit_cache.take() {
-                        self.token_emit_cache.borrow_mut().push(cached_token);
-                    }
-                    self.token_emit_cache.borrow_mut().push(token);
-                } else if let Some(mut cached_token) = self.characters_emit_cache.take() {
-                    // Take the cached_token, and add the current char to it
-                    cached_token.push_token(&token);
-                    self.characters_emit_cache.set(Some(cached_token));
-                } else {
-                    // Make a new Token::Characters, from the current char
-                    let mut cached_token = Token::Characters(String::new());
-                    cached_token.push_token(&token);
-                    self.characters_emit_cache.set(Some(cached_token));
+            self.emit(token, span);
+        }
+
+        self.reconsume = res.reconsume();
+        match res.state() {
+            Ok(state) => self.state = Some(state),
+            Err(e) => {
+                // Tokenizing can't continue, the error is returned by `Tokenizer::take_error`
+                error!("Tokenizer error: {}", e);
+                self.error = Some(e);
+                self.state = Some(States::term());
+            }
+        }
+
+        self.pop_emitted()
+    }
+
+    /// Queue `token` to be returned, collapsing character tokens if `collapse_chars` is set
+    fn emit(&mut self, token: Token, span: Span) {
+        if std::mem::take(&mut self.ignore_line_feed) && token == Token::Character('\n') {
+            trace!("Ignoring line feed");
+            return;
+        }
+        let is_character = matches!(token, Token::Character(_) | Token::Characters(_));
+        // Characters inserted by a script have an empty span, so they're never the same as it
+        let verbatim = is_character && !self.replaced && span.start != span.end;
+        if !self.collapse_chars {
+            self.token_emit_cache
+                .borrow_mut()
+                .push_back((token, span, verbatim));
+        } else if !is_character {
+            if let Some(cached_token) = self.characters_emit_cache.take() {
+                self.token_emit_cache.borrow_mut().push_back(cached_token);
+            }
+            self.token_emit_cache
+                .borrow_mut()
+                .push_back((token, span, verbatim));
+        } else if let Some((mut cached_token, cached_span, cached_verbatim)) =
+            self.characters_emit_cache.take()
+        {
+            if self.whitespace_runs && self.cached_whitespace && !token.is_whitespace() {
+                // The whitespace is returned first, and the text after it starts a new run
+                self.token_emit_cache.borrow_mut().push_back((
+                    cached_token,
+                    cached_span,
+                    cached_verbatim,
+                ));
+                self.start_characters_run(&token, span, verbatim);
+                return;
+            }
+            // Take the cached_token, and add the current chars to it
+            cached_token.push_token(&token);
+            self.cached_whitespace &= token.is_whitespace();
+            self.characters_emit_cache.set(Some((
+                cached_token,
+                cached_span.union(span),
+                cached_verbatim && verbatim,
+            )));
+        } else {
+            self.start_characters_run(&token, span, verbatim);
+        }
+    }
+
+    /// Make a new `Token::Characters` from the characters of `token`, which more can be added to
+    fn start_characters_run(&mut self, token: &Token, span: Span, verbatim: bool) {
+        let mut cached_token = Token::Characters(String::new());
+        cached_token.push_token(token);
+        self.cached_whitespace = token.is_whitespace();
+        self.characters_emit_cache
+            .set(Some((cached_token, span, verbatim)));
+    }
+
+    /// The next queued token and its span, if there is one
+    fn pop_emitted(&mut self) -> Option<(Token, Span)> {
+        let (token, span, verbatim) = self.token_emit_cache.borrow_mut().pop_front()?;
+        // Several characters emitted at once share a span, so each covers more than its own text
+        let len = match token {
+            Token::Character(c) => c.len_utf8(),
+            Token::Characters(ref s) => s.len(),
+            _ => 0,
+        };
+        self.token_verbatim = verbatim && len == span.end.offset - span.start.offset;
+        if let Token::StartTag(ref tag) = token {
+            self.last_start_tag_emitted = Some(tag.clone());
+        }
+        Some((token, span))
+    }
+
+    /// Consume a run of characters which the current state would emit, or append to the
+    /// current attribute's value, unchanged
+    ///
+    /// Most of a document is plain text and attribute values, so this skips the state machine
+    /// for most characters. In collapse mode the run is emitted as one `Token::Characters`,
+    /// whose span covers exactly the characters, as none of them were replaced.
+    ///
+    /// Returns whether any characters were consumed.
+    fn consume_text_run(&mut self) -> bool {
+        let state = match self.state {
+            Some(ref state) => state,
+            None => return false,
+        };
+        let special = match state.special_characters() {
+            Some(special) => special,
+            None => return false,
+        };
+        let is_attribute_value = state.is_attribute_value_state();
+        // Each character is emitted as it's consumed, unless they're being collapsed
+        let emit_each = !is_attribute_value && !self.collapse_chars;
+
+        if self.ignore_line_feed && !is_attribute_value && self.input.peek(0) == Some('\n') {
+            trace!("Ignoring line feed");
+            self.ignore_line_feed = false;
+            let c = self.next_character();
+            self.last_char = Some(c);
+            return true;
+        }
+        // Whitespace at the start of a run is emitted before the text after it, when it's
+        // returned on its own
+        let split_whitespace = self.whitespace_runs && !emit_each && !is_attribute_value;
+        let mut only_whitespace = true;
+
+        let start = self.next_position;
+        let mut consumed = false;
+        let mut run = String::new();
+        while let Some(c) = self.input.peek(0) {
+            if special.contains(&c) {
+                break;
+            }
+            if split_whitespace {
+                if only_whitespace && !run.is_empty() && !c.is_ascii_whitespace() {
+                    break;
                 }
+                only_whitespace &= c.is_ascii_whitespace();
+            }
+            consumed = true;
+            let _ = self.next_character();
+            self.last_char = Some(Character::Char(c));
+            if emit_each {
+                let span = Span::new(self.char_position, self.next_position);
+                self.emit(Token::Character(c), span);
             } else {
-                self.token_emit_cache.borrow_mut().push(token);
+                run.push(c);
             }
         }
-
-        if res.is_err() {
-            let next_state_error = res.state().unwrap_err();
-            // TODO return err?
-            panic!("Tokenizer error: {}", next_state_error);
+        if !consumed {
+            return false;
         }
+        let span = Span::new(start, self.next_position);
 
-        self.reconsume = res.reconsume();
-        self.state = Some(res.state().unwrap());
+        if is_attribute_value {
+            let next_position = self.next_position;
+            let attribute = self
+                .state
+                .as_mut()
+                .and_then(States::current_attribute_mut);
+            if let Some(attribute) = attribute {
+                attribute.push_str_value(&run);
+                if self.replaced {
+                    attribute.set_value_replaced();
+                }
+                if let Some(ref mut value_span) = attribute.value_span {
+                    value_span.end = next_position;
+                }
+            }
+        } else if !run.is_empty() {
+            self.emit(Token::Characters(run), span);
+        }
+        true
+    }
 
-        if self.token_emit_cache.borrow().is_empty() {
-            None
+    /// Extend the span of the attribute being built, after a character has been consumed in
+    /// the attribute name state or before the attribute value, or in some other state
+    fn update_attribute_span(
+        &mut self,
+        was_attribute_name: bool,
+        was_before_attribute_value: bool,
+    ) {
+        let end = if self.reconsume {
+            self.char_position
         } else {
-            let mut token_emit_cache = self.token_emit_cache.borrow_mut();
-            let to_emit = Some(token_emit_cache.remove(0));
+            self.next_position
+        };
+        let char_position = self.char_position;
+        let state = match self.state.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        let is_attribute_name = matches!(state, States::AttributeName(_));
+        let is_attribute_value = state.is_attribute_value_state();
+        let attribute = match state.current_attribute_mut() {
+            Some(attribute) => attribute,
+            None => return,
+        };
 
-            if let Some(Token::StartTag(ref tag)) = to_emit {
-                self.last_start_tag_emitted = Some(tag.clone());
+        if is_attribute_name {
+            // The character which started the attribute is always part of its name
+            if !was_attribute_name {
+                attribute.name_span.start = char_position;
+            }
+            attribute.name_span.end = end;
+        } else if is_attribute_value {
+            if self.replaced {
+                attribute.set_value_replaced();
+            }
+            // The value starts after the opening quote, if there is one
+            match attribute.value_span {
+                Some(ref mut span) if !was_before_attribute_value => span.end = end,
+                _ => attribute.value_span = Some(Span::new(end, end)),
             }
-
-            to_emit
         }
     }
 }
 
+/// <https://html.spec.whatwg.org/multipage/parsing.html#preprocessing-the-input-stream>
+///
+/// Any occurrences of surrogates are surrogate-in-input-stream parse errors.
+/// Any occurrences of noncharacters are noncharacter-in-input-stream parse errors
+/// and any occurrences of controls other than ASCII whitespace and U+0000 NULL
+/// characters are control-character-in-input-stream parse errors.
+///
+/// A `char` can't be a surrogate, the decoder replaces them with U+FFFD like any other
+/// malformed input, so `SurrogateInInputStream` is never reported.
+fn input_stream_error(c: char) -> Option<ParseError> {
+    let c = c as Codepoint;
+    if codepoint::is_noncharacter(c) {
+        Some(ParseError::NoncharacterInInputStream)
+    } else if codepoint::is_control(c) && !codepoint::is_ascii_whitespace(c) && c != 0x00 {
+        Some(ParseError::ControlCharacterInInputStream)
+    } else {
+        None
+    }
+}
+
+/// Tokens are produced as the input is read, each with where it is in the input
+///
+/// A tag's span covers it from the `<` to the `>`, and the spans of its attributes are on
+/// each `Attribute`. A character which was part of a character reference has the span of
+/// the whole reference. A collapsed `Token::Characters` covers all of its characters, so for
+/// in-memory input the text can be borrowed from the input with `Span::slice` when
+/// `Tokenizer::token_is_verbatim`.
+///
+/// A tokenizer created with `Tokenizer::new_push` returns `None` when it needs more input,
+/// until `Tokenizer::finish` has been called.
 impl<R> Iterator for Tokenizer<R>
 where
-    R: Read + Seek,
+    R: Read,
 {
-    type Item = Token;
+    type Item = (Token, Span);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            if !self.token_emit_cache.borrow().is_empty() {
-                let mut token_emit_cache = self.token_emit_cache.borrow_mut();
-                return Some(token_emit_cache.remove(0));
+            if let Some(token) = self.pop_emitted() {
+                return Some(token);
+            }
+
+            if let Err(e) = self.input.fill(LOOKAHEAD) {
+                error!("Error reading input: {}", e);
+                self.error = Some(e.into());
+            }
+            if self.input.is_starved(LOOKAHEAD) {
+                // The text so far is returned rather than held until the rest of the run arrives
+                if let Some(cached_token) = self.characters_emit_cache.take() {
+                    self.token_emit_cache.borrow_mut().push_back(cached_token);
+                    continue;
+                }
+                trace!("Waiting for more input");
+                return None;
+            }
+            // Offsets count the BOM, which comes before the first character
+            self.next_position.offset += self.input.take_bom_length();
+            self.replaced = false;
+
+            // The input may need filling again after a run of characters
+            if !self.reconsume && self.consume_text_run() {
+                continue;
             }
 
             let state = self.state.take().unwrap();
+            if let States::MarkupDeclarationOpen(_) = state {
+                // Whether a CDATA section is allowed depends on the tree, which the text before
+                // it may change, so that's returned first
+                if let Some(cached_token) = self.characters_emit_cache.take() {
+                    self.token_emit_cache.borrow_mut().push_back(cached_token);
+                    self.state = Some(state);
+                    continue;
+                }
+            }
+            let was_data_state = state.is_data_state();
+            let was_attribute_name = matches!(state, States::AttributeName(_));
+            let was_before_attribute_value = matches!(state, States::BeforeAttributeValue(_));
             debug!(
                 "State ({}): {:?}",
                 if self.reconsume { "R" } else { "-" },
@@ -295,36 +748,61 @@ where
             );
             let res = match state {
                 States::Term(_) => return None,
-                States::MarkupDeclarationOpen(ref m) => {
-                    if self.next_few_characters_are("--", false).unwrap() {
-                        state.on_next_few_characters(&Some("--".to_string()).into())
-                    } else if self.next_few_characters_are("DOCTYPE", true).unwrap() {
-                        state.on_next_few_characters(&Some("DOCTYPE".to_string()).into())
-                    } else if self.next_few_characters_are("[CDATA[", false).unwrap() {
-                        state.on_next_few_characters(&Some("[CDATA[".to_string()).into())
+                States::MarkupDeclarationOpen(_) => {
+                    let in_foreign_content = self.in_foreign_content;
+                    if self.next_few_characters_are("--", false) {
+                        state.on_next_few_characters(
+                            &Some("--".to_string()).into(),
+                            in_foreign_content,
+                        )
+                    } else if self.next_few_characters_are("DOCTYPE", true) {
+                        state.on_next_few_characters(
+                            &Some("DOCTYPE".to_string()).into(),
+                            in_foreign_content,
+                        )
+                    } else if self.next_few_characters_are("[CDATA[", false) {
+                        state.on_next_few_characters(
+                            &Some("[CDATA[".to_string()).into(),
+                            in_foreign_content,
+                        )
                     } else {
-                        todo!("MarkupDeclarationOpen::{:?}", m);
+                        // Anything else starts a bogus comment
+                        state.on_next_few_characters(&None.into(), in_foreign_content)
                     }
                 }
+                // The `PUBLIC` and `SYSTEM` keywords are looked for before consuming a character
+                States::AfterDoctypeName(_)
+                    if !self.reconsume && self.next_few_characters_are("PUBLIC", true) =>
+                {
+                    state.on_next_few_characters(&Some("PUBLIC".to_string()).into(), false)
+                }
+                States::AfterDoctypeName(_)
+                    if !self.reconsume && self.next_few_characters_are("SYSTEM", true) =>
+                {
+                    state.on_next_few_characters(&Some("SYSTEM".to_string()).into(), false)
+                }
                 States::NamedCharacterReference(NamedCharacterReference {
                     mut tmp,
                     return_state,
                 }) => {
-                    let possible_char_ref = self
-                        .find_named_character_reference(self.last_char.unwrap(), &mut tmp)
-                        .unwrap();
+                    let possible_char_ref =
+                        self.find_named_character_reference(self.last_char.unwrap(), &mut tmp);
+                    self.replaced |= possible_char_ref.is_some();
                     let reconstructed_state =
                         States::NamedCharacterReference(NamedCharacterReference {
                             tmp,
                             return_state,
                         });
-                    let next_char = self.peek_next_character().unwrap();
+                    let next_char = self.peek_next_character();
                     reconstructed_state.on_possible_character_reference_with_next_char(
                         (possible_char_ref, next_char).into(),
                     )
                 }
 
-                States::NumericCharacterReferenceEnd(_) => state.on_advance(),
+                States::NumericCharacterReferenceEnd(_) => {
+                    self.replaced = true;
+                    state.on_advance()
+                }
 
                 States::RcDataEndTagName(_)
                 | States::RawTextEndTagName(_)
@@ -333,7 +811,7 @@ where
                     let c = if self.reconsume {
                         self.last_char.unwrap()
                     } else {
-                        self.next_character().unwrap()
+                        self.next_character()
                     };
                     self.last_char = Some(c);
                     state.on_character_and_last_start_tag(
@@ -345,13 +823,19 @@ where
                     let c = if self.reconsume {
                         self.last_char.unwrap()
                     } else {
-                        self.next_character().unwrap()
+                        self.next_character()
                     };
                     self.last_char = Some(c);
                     state.on_character(c)
                 }
             };
-            if let Some(token) = self.handle_transition_result(res) {
+            // Each character in a data state may be the start of a token
+            if was_data_state {
+                self.token_start = self.char_position;
+            }
+            let token = self.handle_transition_result(res);
+            self.update_attribute_span(was_attribute_name, was_before_attribute_value);
+            if let Some(token) = token {
                 return Some(token);
             }
         }
@@ -360,16 +844,20 @@ where
 
 impl<R> fmt::Debug for Tokenizer<R>
 where
-    R: Read + Seek + fmt::Debug,
+    R: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Tokenizer")
-            .field("reader", &self.reader)
+            .field("input", &self.input)
             .field("collapse_chars", &self.collapse_chars)
             .field("state", &self.state)
             .field("reconsume", &self.reconsume)
             .field("last_char", &self.last_char)
             .field("last_start_tag_emitted", &self.last_start_tag_emitted)
+            .field("next_position", &self.next_position)
+            .field("errors", &self.errors)
+            .field("in_foreign_content", &self.in_foreign_content)
+            .field("token_start", &self.token_start)
             .field("token_emit_cache", &self.token_emit_cache)
             .finish()
     }
@@ -378,6 +866,7 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use errors::ParseError;
     use std::io::Cursor;
     use token::*;
 
@@ -389,7 +878,7 @@ mod test {
                 let mut f = Cursor::new($html);
                 let tokenizer = Tokenizer::new(&mut f, false);
 
-                itertools::assert_equal(tokenizer,
+                itertools::assert_equal(tokenizer.map(|(token, _)| token),
                     vec![
                         $(
                             $token,
@@ -428,6 +917,39 @@ mod test {
         })
     }
 
+    token_test! {
+        doctype_public_and_system_identifiers,
+        "<!DOCTYPE html public '-//W3C//DTD HTML 4.01//EN' \"http://www.w3.org/TR/html4/strict.dtd\">",
+        Token::Doctype(Doctype {
+            name: Some("html".to_string()),
+            public_identifier: Some("-//W3C//DTD HTML 4.01//EN".to_string()),
+            system_identifier: Some("http://www.w3.org/TR/html4/strict.dtd".to_string()),
+            ..Default::default()
+        })
+    }
+
+    token_test! {
+        doctype_bogus_after_name,
+        "<!DOCTYPE html foo>",
+        Token::Doctype(Doctype {
+            name: Some("html".to_string()),
+            force_quirks: ForceQuirksFlag::On,
+            ..Default::default()
+        })
+    }
+
+    token_test! {
+        comment_with_less_than_sign,
+        "<!--[if IE]><p><![endif]-->",
+        Token::Comment("[if IE]><p><![endif]".to_string())
+    }
+
+    token_test! {
+        nested_comment,
+        "<!--a<!--b-->",
+        Token::Comment("a<!--b".to_string())
+    }
+
     token_test! {
         simple_tag,
         "<html>",
@@ -437,6 +959,20 @@ mod test {
         })
     }
 
+    token_test! {
+        end_tag_with_attributes,
+        "</b x y='1' x>",
+        Token::EndTag(EndTag {
+            name: TagName::B,
+            attributes: vec![
+                Attribute::new("x", "", false),
+                Attribute::new("y", "1", false),
+                Attribute::new("x", "", true),
+            ],
+            ..Default::default()
+        })
+    }
+
     token_test! {
         simple_tag_bare_attr,
         "<html foo>",
@@ -548,6 +1084,18 @@ mod test {
         })
     }
 
+    token_test! {
+        numeric_char_ref_overflow,
+        "&#99999999999;",
+        Token::Character('\u{FFFD}')
+    }
+
+    token_test! {
+        numeric_char_ref_outside_unicode_range,
+        "&#x110000;",
+        Token::Character('\u{FFFD}')
+    }
+
     token_test! {
         amp_string_named_char_ref,
         "<html>&euro;</html>",
@@ -589,4 +1137,372 @@ mod test {
             ..Default::default()
         })
     }
+
+    // The longest name is matched, even without a semicolon
+    token_test! {
+        named_char_ref_longest_match,
+        "&notin;&noti",
+        Token::Character('∉')
+        Token::Character('¬')
+        Token::Character('i')
+    }
+
+    // For historical reasons a reference without a semicolon in an attribute value isn't
+    // decoded if it's followed by `=` or an alphanumeric, but one with a semicolon always is
+    token_test! {
+        named_char_ref_in_attribute_values,
+        "<a b='&notx' c='&not=' d='&amp;b=' e='&not;x'>",
+        Token::StartTag(StartTag {
+            name: TagName::A,
+            attributes: vec![
+                Attribute::new("b", "&notx", false),
+                Attribute::new("c", "&not=", false),
+                Attribute::new("d", "&b=", false),
+                Attribute::new("e", "¬x", false),
+            ],
+            ..Default::default()
+        })
+    }
+
+    // A character which doesn't continue the start of a name isn't consumed with it
+    token_test! {
+        partial_named_char_ref_in_attribute_values,
+        "<a b=\"&c\" c='&co' d=&cop e=\"&1\" f='?a=1&2b=3' g=&9;>",
+        Token::StartTag(StartTag {
+            name: TagName::A,
+            attributes: vec![
+                Attribute::new("b", "&c", false),
+                Attribute::new("c", "&co", false),
+                Attribute::new("d", "&cop", false),
+                Attribute::new("e", "&1", false),
+                Attribute::new("f", "?a=1&2b=3", false),
+                Attribute::new("g", "&9;", false),
+            ],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn feed() {
+        let html = "<p class=\"a\">x&amp;y</p>";
+        let expected: Vec<_> = Tokenizer::new(html.as_bytes(), true).collect();
+
+        let mut tokenizer = Tokenizer::new_push(true);
+        let mut tokens = Vec::new();
+        for chunk in html.as_bytes().chunks(2) {
+            tokenizer.feed(chunk);
+            tokens.extend(tokenizer.by_ref());
+        }
+        // Nothing can be emitted until the end of such a short input
+        assert!(tokens.is_empty());
+        tokenizer.finish();
+        tokens.extend(tokenizer.by_ref());
+
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn feed_one_byte_at_a_time() {
+        let html = "<a b='&notit;' c=&notx d=\"&not;x\">&notit; &notin; &amp\u{e9}</a>";
+        let expected: Vec<_> = Tokenizer::new(html.as_bytes(), false).collect();
+
+        let mut tokenizer = Tokenizer::new_push(false);
+        let mut tokens = Vec::new();
+        for chunk in html.as_bytes().chunks(1) {
+            tokenizer.feed(chunk);
+            tokens.extend(tokenizer.by_ref());
+        }
+        tokenizer.finish();
+        tokens.extend(tokenizer.by_ref());
+
+        assert_eq!(tokens, expected);
+        // References in attribute values followed by an alphanumeric are left alone
+        match &tokens[0].0 {
+            Token::StartTag(tag) => assert_eq!(
+                tag.attributes,
+                [
+                    Attribute::new("b", "&notit;", false),
+                    Attribute::new("c", "&notx", false),
+                    Attribute::new("d", "¬x", false),
+                ]
+            ),
+            token => panic!("expected a start tag, got {:?}", token),
+        }
+        let text: String = tokens
+            .iter()
+            .filter_map(|(token, _)| match token {
+                Token::Character(c) => Some(*c),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "¬it; ∉ &\u{e9}");
+    }
+
+    #[test]
+    fn text_runs() {
+        let html = "ab\ncd<p title='e f'>g&amp;h\0i</p>";
+        let mut tokenizer = Tokenizer::new(html.as_bytes(), true);
+        let tokens: Vec<_> = tokenizer
+            .by_ref()
+            .map(|(token, span)| (token, span.slice(html)))
+            .collect();
+        let p = StartTag {
+            name: TagName::P,
+            attributes: vec![Attribute::new("title", "e f", false)],
+            ..Default::default()
+        };
+        assert_eq!(
+            tokens,
+            [
+                (Token::Characters("ab\ncd".to_string()), "ab\ncd"),
+                (Token::StartTag(p), "<p title='e f'>"),
+                (Token::Characters("g&h\0i".to_string()), "g&amp;h\0i"),
+                (
+                    Token::EndTag(EndTag {
+                        name: TagName::P,
+                        ..Default::default()
+                    }),
+                    "</p>",
+                ),
+                (Token::Eof, ""),
+            ]
+        );
+        // Only the text and value characters were consumed, so positions are still counted
+        assert_eq!(tokenizer.position().line, 2);
+        assert_eq!(tokenizer.errors().len(), 1);
+    }
+
+    #[test]
+    fn whitespace_runs() {
+        let html = " \n&#32;a b<p>\nc<pre>d";
+        let mut tokenizer = Tokenizer::new(html.as_bytes(), true);
+        tokenizer.set_whitespace_runs(true);
+        let mut tokens = Vec::new();
+        while let Some((token, span)) = tokenizer.next() {
+            // As the parser does after a `pre` start tag
+            if let Token::StartTag(_) = token {
+                tokenizer.ignore_next_line_feed();
+            }
+            tokens.push((token, span.slice(html)));
+        }
+        let tag = |name| {
+            Token::StartTag(StartTag {
+                name,
+                ..Default::default()
+            })
+        };
+        assert_eq!(
+            tokens,
+            [
+                (Token::Characters(" \n ".to_string()), " \n&#32;"),
+                (Token::Characters("a b".to_string()), "a b"),
+                (tag(TagName::P), "<p>"),
+                (Token::Characters("c".to_string()), "c"),
+                (tag(TagName::Pre), "<pre>"),
+                (Token::Characters("d".to_string()), "d"),
+                (Token::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn token_spans() {
+        let html = "<p class=\"a\" id=b hidden>x&amp;\n</p><!-- c -->";
+        let tokenizer = Tokenizer::new(html.as_bytes(), false);
+        let mut spans = Vec::new();
+        for (token, span) in tokenizer {
+            if let Token::StartTag(ref tag) = token {
+                let attributes: Vec<_> = tag
+                    .attributes
+                    .iter()
+                    .map(|a| (a.name_span.slice(html), a.value_span.map(|v| v.slice(html))))
+                    .collect();
+                assert_eq!(
+                    attributes,
+                    [("class", Some("a")), ("id", Some("b")), ("hidden", None)]
+                );
+            }
+            spans.push(span.slice(html));
+        }
+        assert_eq!(
+            spans,
+            [
+                "<p class=\"a\" id=b hidden>",
+                "x",
+                "&amp;",
+                "\n",
+                "</p>",
+                "<!-- c -->",
+                ""
+            ]
+        );
+
+        // Line and column of the end tag, after the newline
+        let mut tokenizer = Tokenizer::new(html.as_bytes(), true);
+        let (_, span) = tokenizer.nth(1).unwrap();
+        assert_eq!(span.slice(html), "x&amp;\n");
+        let (_, span) = tokenizer.next().unwrap();
+        assert_eq!(
+            span.start,
+            Position {
+                offset: 32,
+                line: 2,
+                column: 1
+            }
+        );
+    }
+
+    #[test]
+    fn token_spans_count_source_bytes() {
+        let token_spans =
+            |tokenizer: Tokenizer<&[u8]>| tokenizer.map(|(_, span)| span).collect::<Vec<_>>();
+
+        // The BOM comes before the first token, and a CRLF is one character of two bytes
+        let html = "\u{FEFF}<p>\r\n\u{E9}</p>";
+        let tokenizer = Tokenizer::new(html.as_bytes(), true);
+        let spans: Vec<_> = token_spans(tokenizer).iter().map(|s| s.slice(html)).collect();
+        assert_eq!(spans, ["<p>", "\r\n\u{E9}", "</p>", ""]);
+
+        let html = b"<p title=\x80>\r\n\xE9</p>";
+        let tokenizer = Tokenizer::with_transport_encoding(&html[..], true, "windows-1252");
+        let spans = token_spans(tokenizer);
+        let slices: Vec<_> = spans.iter().map(|s| s.slice_bytes(html)).collect();
+        assert_eq!(
+            slices,
+            [&b"<p title=\x80>"[..], b"\r\n\xE9", b"</p>", b""]
+        );
+        assert_eq!(
+            spans[2].start,
+            Position {
+                offset: 14,
+                line: 2,
+                column: 2
+            }
+        );
+    }
+
+    #[test]
+    fn verbatim_tokens() {
+        let html = "<p a='x&amp;y' b=\"&c\" c=d&e f>a < b &lt; c\r\nd<b>e&f</b>&notit;";
+        let mut tokenizer = Tokenizer::new(html.as_bytes(), true);
+        let mut characters = Vec::new();
+        while let Some((token, span)) = tokenizer.next() {
+            match token {
+                Token::StartTag(ref tag) if tag.name == TagName::P => {
+                    let values: Vec<_> = tag
+                        .attributes
+                        .iter()
+                        .map(Attribute::value_is_verbatim)
+                        .collect();
+                    assert_eq!(values, [false, true, true, false]);
+                }
+                Token::Characters(ref s) => {
+                    let verbatim = tokenizer.token_is_verbatim();
+                    if verbatim {
+                        assert_eq!(span.slice(html), s);
+                    }
+                    characters.push((s.clone(), verbatim));
+                }
+                _ => assert!(!tokenizer.token_is_verbatim()),
+            }
+        }
+        assert_eq!(
+            characters,
+            [
+                ("a < b < c\nd".to_string(), false),
+                ("e&f".to_string(), true),
+                ("\u{AC}it;".to_string(), false)
+            ]
+        );
+
+        // Without collapsing, each character is verbatim unless it was replaced
+        let mut tokenizer = Tokenizer::new(&b"a<&lt;\r\n"[..], false);
+        let mut verbatim = Vec::new();
+        while let Some((token, _)) = tokenizer.next() {
+            if let Token::Character(c) = token {
+                verbatim.push((c, tokenizer.token_is_verbatim()));
+            }
+        }
+        assert_eq!(
+            verbatim,
+            [('a', true), ('<', true), ('<', false), ('\n', false)]
+        );
+    }
+
+    #[test]
+    fn parse_error_positions() {
+        let _ = pretty_env_logger::formatted_builder()
+            .is_test(true)
+            .try_init();
+        let mut f = Cursor::new("<p>\r\n<a b=1 b=2>\n\u{20ac}\0");
+        let mut tokenizer = Tokenizer::new(&mut f, false);
+        tokenizer.by_ref().for_each(drop);
+
+        let errors: Vec<_> = tokenizer
+            .errors()
+            .iter()
+            .map(|e| (e.error, e.position))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    ParseError::DuplicateAttribute,
+                    Position {
+                        offset: 13,
+                        line: 2,
+                        column: 9
+                    }
+                ),
+                (
+                    ParseError::UnexpectedNullCharacter,
+                    Position {
+                        offset: 20,
+                        line: 3,
+                        column: 2
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn numeric_char_ref_outside_unicode_range_errors() {
+        for html in &["&#99999999999;", "&#x110000;"] {
+            let mut tokenizer = Tokenizer::new(html.as_bytes(), true);
+            tokenizer.by_ref().for_each(drop);
+            let errors: Vec<_> = tokenizer.errors().iter().map(|e| e.error).collect();
+            assert_eq!(
+                errors,
+                vec![ParseError::CharacterReferenceOutsideUnicodeRange],
+                "{}",
+                html
+            );
+        }
+    }
+
+    #[test]
+    fn input_stream_errors() {
+        let mut f = Cursor::new("a\u{1}\t\u{FDD0}<p title='\u{7F}\u{9F}'>\u{10FFFF}&#1;\0");
+        let mut tokenizer = Tokenizer::new(&mut f, true);
+        tokenizer.by_ref().for_each(drop);
+
+        let errors: Vec<_> = tokenizer
+            .errors()
+            .iter()
+            .map(|e| (e.error, e.position.column))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (ParseError::ControlCharacterInInputStream, 2),
+                (ParseError::NoncharacterInInputStream, 4),
+                (ParseError::ControlCharacterInInputStream, 15),
+                (ParseError::ControlCharacterInInputStream, 16),
+                (ParseError::NoncharacterInInputStream, 19),
+                (ParseError::ControlCharacterReference, 23),
+                (ParseError::UnexpectedNullCharacter, 24),
+            ]
+        );
+    }
 }
@@ -16,9 +16,104 @@ pub struct Entity {
 
 // static ref GAMES: Vec<Game> = serde_json::from_str(&GAME_JSON).unwrap();
 static ENTITIES: OnceCell<Entities> = OnceCell::new();
+static ENTITY_TRIE: OnceCell<EntityTrie> = OnceCell::new();
 
 pub fn get_entities() -> &'static Entities {
     ENTITIES.get_or_init(|| {
         serde_json::from_str(&ENTITIES_JSON).unwrap()
     })
 }
+
+/// The names of the named character references as a prefix tree, built on first use
+pub(crate) fn get_entity_trie() -> &'static EntityTrie {
+    ENTITY_TRIE.get_or_init(|| EntityTrie::new(get_entities().keys().copied()))
+}
+
+/// A node of an `EntityTrie`, standing for the characters on the path to it from the root
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TrieNode(usize);
+
+/// A prefix tree of character reference names
+///
+/// Walking it a character at a time finds the longest name which matches the input in one pass,
+/// without looking at the names which can no longer match.
+#[derive(Debug)]
+pub(crate) struct EntityTrie {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    // Sorted by character
+    children: Vec<(char, usize)>,
+    // The whole name, if the path to this node spells one
+    name: Option<&'static str>,
+}
+
+impl EntityTrie {
+    /// The node for the empty string
+    pub(crate) const ROOT: TrieNode = TrieNode(0);
+
+    fn new<I: IntoIterator<Item = &'static str>>(names: I) -> Self {
+        let mut trie = EntityTrie {
+            nodes: vec![Node::default()],
+        };
+        for name in names {
+            let mut node = 0;
+            for c in name.chars() {
+                node = match trie.nodes[node].children.binary_search_by_key(&c, |&(c, _)| c) {
+                    Ok(i) => trie.nodes[node].children[i].1,
+                    Err(i) => {
+                        let child = trie.nodes.len();
+                        trie.nodes.push(Node::default());
+                        trie.nodes[node].children.insert(i, (c, child));
+                        child
+                    }
+                };
+            }
+            trie.nodes[node].name = Some(name);
+        }
+        trie
+    }
+
+    /// The node for the characters of `node` followed by `c`, if any name starts with them
+    pub(crate) fn child(&self, node: TrieNode, c: char) -> Option<TrieNode> {
+        let children = &self.nodes[node.0].children;
+        children
+            .binary_search_by_key(&c, |&(c, _)| c)
+            .ok()
+            .map(|i| TrieNode(children[i].1))
+    }
+
+    /// The node for `s`, if any name starts with it
+    pub(crate) fn find(&self, s: &str) -> Option<TrieNode> {
+        s.chars().try_fold(Self::ROOT, |node, c| self.child(node, c))
+    }
+
+    /// The name which `node` stands for, if it is a whole name rather than only a prefix
+    pub(crate) fn name(&self, node: TrieNode) -> Option<&'static str> {
+        self.nodes[node.0].name
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trie() {
+        let trie = get_entity_trie();
+        assert_eq!(trie.find("&amp").and_then(|n| trie.name(n)), Some("&amp"));
+        assert_eq!(trie.find("&amp;").and_then(|n| trie.name(n)), Some("&amp;"));
+        // A prefix of `&notin;`, but not a name itself
+        let noti = trie.find("&noti").unwrap();
+        assert_eq!(trie.name(noti), None);
+        assert_eq!(trie.child(noti, 'n').and_then(|n| trie.name(n)), None);
+        assert!(trie.find("&notx").is_none());
+        assert!(trie.find("amp").is_none());
+
+        for name in get_entities().keys() {
+            assert_eq!(trie.find(name).and_then(|n| trie.name(n)), Some(*name));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{cmp, fmt};
+
+use serde::{Deserialize, Serialize};
+
+/// A position in the input stream
+///
+/// `offset` is in bytes of the input before it was decoded, counting any byte order mark,
+/// `line` and `column` start from 1 and `column` counts characters.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    #[must_use]
+    pub fn new() -> Self {
+        Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Move the position past `c`, which was encoded in the input with `len` bytes
+    pub(crate) fn advance(&mut self, c: char, len: usize) {
+        self.offset += len;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new()
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A range of the input stream, from `start` up to but not including `end`
+///
+/// Characters inserted by a script aren't part of the input, so they have an empty span at the
+/// point they were inserted.
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    #[must_use]
+    pub fn new(start: Position, end: Position) -> Self {
+        Span { start, end }
+    }
+
+    /// The smallest span covering both `self` and `other`
+    #[must_use]
+    pub fn union(self, other: Span) -> Self {
+        Span {
+            start: cmp::min(self.start, other.start),
+            end: cmp::max(self.end, other.end),
+        }
+    }
+
+    /// The input covered by the span
+    ///
+    /// Offsets are in bytes of the input before it was decoded, so this is for UTF-8 input.
+    /// `input` must include any byte order mark.
+    #[must_use]
+    pub fn slice<'a>(&self, input: &'a str) -> &'a str {
+        &input[self.start.offset..self.end.offset]
+    }
+
+    /// The bytes of the input covered by the span, for input in any encoding
+    #[must_use]
+    pub fn slice_bytes<'a>(&self, input: &'a [u8]) -> &'a [u8] {
+        &input[self.start.offset..self.end.offset]
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
@@ -2,7 +2,7 @@ use derive_more::{AsRef, Deref, DerefMut, Display, From, Into};
 
 use crate::tokenizer::{
     errors,
-    token::{StartTag, Token},
+    token::{Attribute, StartTag, Token},
     Codepoint, TransitionResult,
 };
 
@@ -188,7 +188,10 @@ pub(super) struct ScriptDataLessThanSign {}
 pub(super) struct ScriptDataEndTagOpen {}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct ScriptDataEndTagName {}
+pub(super) struct ScriptDataEndTagName {
+    pub(crate) token: Token,
+    pub(crate) tmp: String,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub(super) struct ScriptDataEscapeStart {}
@@ -212,10 +215,15 @@ pub(super) struct ScriptDataEscapedLessThanSign {}
 pub(super) struct ScriptDataEscapedEndTagOpen {}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct ScriptDataEscapedEndTagName {}
+pub(super) struct ScriptDataEscapedEndTagName {
+    pub(crate) token: Token,
+    pub(crate) tmp: String,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct ScriptDataDoubleEscapeStart {}
+pub(super) struct ScriptDataDoubleEscapeStart {
+    pub(crate) tmp: String,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub(super) struct ScriptDataDoubleEscaped {}
@@ -230,7 +238,9 @@ pub(super) struct ScriptDataDoubleEscapedDashDash {}
 pub(super) struct ScriptDataDoubleEscapedLessThanSign {}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct ScriptDataDoubleEscapeEnd {}
+pub(super) struct ScriptDataDoubleEscapeEnd {
+    pub(crate) tmp: String,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub(super) struct BeforeAttributeName {
@@ -306,13 +316,19 @@ pub(super) struct CommentLessThanSign {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct CommentLessThanSignBang {}
+pub(super) struct CommentLessThanSignBang {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct CommentLessThanSignBangDash {}
+pub(super) struct CommentLessThanSignBangDash {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct CommentLessThanSignBangDashDash {}
+pub(super) struct CommentLessThanSignBangDashDash {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub(super) struct CommentEndDash {
@@ -341,43 +357,69 @@ pub(super) struct DoctypeName {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct AfterDoctypeName {}
+pub(super) struct AfterDoctypeName {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct AfterDoctypePublicKeyword {}
+pub(super) struct AfterDoctypePublicKeyword {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct BeforeDoctypePublicIdentifier {}
+pub(super) struct BeforeDoctypePublicIdentifier {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct DoctypePublicIdentifierDoubleQuoted {}
+pub(super) struct DoctypePublicIdentifierDoubleQuoted {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct DoctypePublicIdentifierSingleQuoted {}
+pub(super) struct DoctypePublicIdentifierSingleQuoted {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct AfterDoctypePublicIdentifier {}
+pub(super) struct AfterDoctypePublicIdentifier {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct BetweenDoctypePublicAndSystemIdentifiers {}
+pub(super) struct BetweenDoctypePublicAndSystemIdentifiers {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct AfterDoctypeSystemKeyword {}
+pub(super) struct AfterDoctypeSystemKeyword {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct BeforeDoctypeSystemIdentifier {}
+pub(super) struct BeforeDoctypeSystemIdentifier {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct DoctypeSystemIdentifierDoubleQuoted {}
+pub(super) struct DoctypeSystemIdentifierDoubleQuoted {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct DoctypeSystemIdentifierSingleQuoted {}
+pub(super) struct DoctypeSystemIdentifierSingleQuoted {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct AfterDoctypeSystemIdentifier {}
+pub(super) struct AfterDoctypeSystemIdentifier {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
-pub(super) struct BogusDoctype {}
+pub(super) struct BogusDoctype {
+    pub(crate) token: Token,
+}
 
 #[derive(Debug, PartialEq, Eq)]
 pub(super) struct CdataSection {}
@@ -528,8 +570,11 @@ impl States {
         States::ScriptDataEndTagOpen(ScriptDataEndTagOpen {})
     }
 
-    pub(super) fn script_data_end_tag_name() -> Self {
-        States::ScriptDataEndTagName(ScriptDataEndTagName {})
+    pub(super) fn script_data_end_tag_name<T: Into<Token>>(token: T, tmp: String) -> Self {
+        States::ScriptDataEndTagName(ScriptDataEndTagName {
+            token: token.into(),
+            tmp,
+        })
     }
 
     pub(super) fn script_data_escape_start() -> Self {
@@ -560,12 +605,18 @@ impl States {
         States::ScriptDataEscapedEndTagOpen(ScriptDataEscapedEndTagOpen {})
     }
 
-    pub(super) fn script_data_escaped_end_tag_name() -> Self {
-        States::ScriptDataEscapedEndTagName(ScriptDataEscapedEndTagName {})
+    pub(super) fn script_data_escaped_end_tag_name<T: Into<Token>>(
+        token: T,
+        tmp: String,
+    ) -> Self {
+        States::ScriptDataEscapedEndTagName(ScriptDataEscapedEndTagName {
+            token: token.into(),
+            tmp,
+        })
     }
 
-    pub(super) fn script_data_double_escape_start() -> Self {
-        States::ScriptDataDoubleEscapeStart(ScriptDataDoubleEscapeStart {})
+    pub(super) fn script_data_double_escape_start(tmp: String) -> Self {
+        States::ScriptDataDoubleEscapeStart(ScriptDataDoubleEscapeStart { tmp })
     }
 
     pub(super) fn script_data_double_escaped() -> Self {
@@ -584,8 +635,8 @@ impl States {
         States::ScriptDataDoubleEscapedLessThanSign(ScriptDataDoubleEscapedLessThanSign {})
     }
 
-    pub(super) fn script_data_double_escape_end() -> Self {
-        States::ScriptDataDoubleEscapeEnd(ScriptDataDoubleEscapeEnd {})
+    pub(super) fn script_data_double_escape_end(tmp: String) -> Self {
+        States::ScriptDataDoubleEscapeEnd(ScriptDataDoubleEscapeEnd { tmp })
     }
 
     pub(super) fn before_attribute_name<T: Into<Token>>(token: T) -> Self {
@@ -676,16 +727,22 @@ impl States {
         })
     }
 
-    pub(super) fn comment_less_than_sign_bang() -> Self {
-        States::CommentLessThanSignBang(CommentLessThanSignBang {})
+    pub(super) fn comment_less_than_sign_bang<T: Into<Token>>(token: T) -> Self {
+        States::CommentLessThanSignBang(CommentLessThanSignBang {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn comment_less_than_sign_bang_dash() -> Self {
-        States::CommentLessThanSignBangDash(CommentLessThanSignBangDash {})
+    pub(super) fn comment_less_than_sign_bang_dash<T: Into<Token>>(token: T) -> Self {
+        States::CommentLessThanSignBangDash(CommentLessThanSignBangDash {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn comment_less_than_sign_bang_dash_dash() -> Self {
-        States::CommentLessThanSignBangDashDash(CommentLessThanSignBangDashDash {})
+    pub(super) fn comment_less_than_sign_bang_dash_dash<T: Into<Token>>(token: T) -> Self {
+        States::CommentLessThanSignBangDashDash(CommentLessThanSignBangDashDash {
+            token: token.into(),
+        })
     }
 
     pub(super) fn comment_end_dash<T: Into<Token>>(token: T) -> Self {
@@ -720,58 +777,82 @@ impl States {
         })
     }
 
-    pub(super) fn after_doctype_name() -> Self {
-        States::AfterDoctypeName(AfterDoctypeName {})
+    pub(super) fn after_doctype_name<T: Into<Token>>(token: T) -> Self {
+        States::AfterDoctypeName(AfterDoctypeName {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn after_doctype_public_keyword() -> Self {
-        States::AfterDoctypePublicKeyword(AfterDoctypePublicKeyword {})
+    pub(super) fn after_doctype_public_keyword<T: Into<Token>>(token: T) -> Self {
+        States::AfterDoctypePublicKeyword(AfterDoctypePublicKeyword {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn before_doctype_public_identifier() -> Self {
-        States::BeforeDoctypePublicIdentifier(BeforeDoctypePublicIdentifier {})
+    pub(super) fn before_doctype_public_identifier<T: Into<Token>>(token: T) -> Self {
+        States::BeforeDoctypePublicIdentifier(BeforeDoctypePublicIdentifier {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn doctype_public_identifier_double_quoted() -> Self {
-        States::DoctypePublicIdentifierDoubleQuoted(DoctypePublicIdentifierDoubleQuoted {})
+    pub(super) fn doctype_public_identifier_double_quoted<T: Into<Token>>(token: T) -> Self {
+        States::DoctypePublicIdentifierDoubleQuoted(DoctypePublicIdentifierDoubleQuoted {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn doctype_public_identifier_single_quoted() -> Self {
-        States::DoctypePublicIdentifierSingleQuoted(DoctypePublicIdentifierSingleQuoted {})
+    pub(super) fn doctype_public_identifier_single_quoted<T: Into<Token>>(token: T) -> Self {
+        States::DoctypePublicIdentifierSingleQuoted(DoctypePublicIdentifierSingleQuoted {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn after_doctype_public_identifier() -> Self {
-        States::AfterDoctypePublicIdentifier(AfterDoctypePublicIdentifier {})
+    pub(super) fn after_doctype_public_identifier<T: Into<Token>>(token: T) -> Self {
+        States::AfterDoctypePublicIdentifier(AfterDoctypePublicIdentifier {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn between_doctype_public_and_system_identifiers() -> Self {
-        States::BetweenDoctypePublicAndSystemIdentifiers(
-            BetweenDoctypePublicAndSystemIdentifiers {},
-        )
+    pub(super) fn between_doctype_public_and_system_identifiers<T: Into<Token>>(token: T) -> Self {
+        States::BetweenDoctypePublicAndSystemIdentifiers(BetweenDoctypePublicAndSystemIdentifiers {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn after_doctype_system_keyword() -> Self {
-        States::AfterDoctypeSystemKeyword(AfterDoctypeSystemKeyword {})
+    pub(super) fn after_doctype_system_keyword<T: Into<Token>>(token: T) -> Self {
+        States::AfterDoctypeSystemKeyword(AfterDoctypeSystemKeyword {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn before_doctype_system_identifier() -> Self {
-        States::BeforeDoctypeSystemIdentifier(BeforeDoctypeSystemIdentifier {})
+    pub(super) fn before_doctype_system_identifier<T: Into<Token>>(token: T) -> Self {
+        States::BeforeDoctypeSystemIdentifier(BeforeDoctypeSystemIdentifier {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn doctype_system_identifier_double_quoted() -> Self {
-        States::DoctypeSystemIdentifierDoubleQuoted(DoctypeSystemIdentifierDoubleQuoted {})
+    pub(super) fn doctype_system_identifier_double_quoted<T: Into<Token>>(token: T) -> Self {
+        States::DoctypeSystemIdentifierDoubleQuoted(DoctypeSystemIdentifierDoubleQuoted {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn doctype_system_identifier_single_quoted() -> Self {
-        States::DoctypeSystemIdentifierSingleQuoted(DoctypeSystemIdentifierSingleQuoted {})
+    pub(super) fn doctype_system_identifier_single_quoted<T: Into<Token>>(token: T) -> Self {
+        States::DoctypeSystemIdentifierSingleQuoted(DoctypeSystemIdentifierSingleQuoted {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn after_doctype_system_identifier() -> Self {
-        States::AfterDoctypeSystemIdentifier(AfterDoctypeSystemIdentifier {})
+    pub(super) fn after_doctype_system_identifier<T: Into<Token>>(token: T) -> Self {
+        States::AfterDoctypeSystemIdentifier(AfterDoctypeSystemIdentifier {
+            token: token.into(),
+        })
     }
 
-    pub(super) fn bogus_doctype() -> Self {
-        States::BogusDoctype(BogusDoctype {})
+    pub(super) fn bogus_doctype<T: Into<Token>>(token: T) -> Self {
+        States::BogusDoctype(BogusDoctype {
+            token: token.into(),
+        })
     }
 
     pub(super) fn cdata_section() -> Self {
@@ -869,6 +950,62 @@ impl States {
         States::Term(Term {})
     }
 
+    // Spans
+
+    /// The states which consume character data, where a new token starts at each character
+    pub(super) fn is_data_state(&self) -> bool {
+        matches!(
+            self,
+            States::Data(_)
+                | States::RcData(_)
+                | States::RawText(_)
+                | States::ScriptData(_)
+                | States::PlainText(_)
+                | States::ScriptDataEscaped(_)
+                | States::ScriptDataDoubleEscaped(_)
+                | States::CdataSection(_)
+        )
+    }
+
+    pub(super) fn is_attribute_value_state(&self) -> bool {
+        matches!(
+            self,
+            States::AttributeValueDoubleQuoted(_)
+                | States::AttributeValueSingleQuoted(_)
+                | States::AttributeValueUnquoted(_)
+        )
+    }
+
+    /// The characters which need the state machine in a state where every other character is
+    /// emitted, or appended to the attribute value, unchanged
+    ///
+    /// `None` for the other states, where each character goes through the state machine.
+    pub(super) fn special_characters(&self) -> Option<&'static [char]> {
+        match self {
+            States::Data(_) | States::RcData(_) => Some(&['<', '&', '\0']),
+            States::RawText(_) | States::ScriptData(_) => Some(&['<', '\0']),
+            States::PlainText(_) => Some(&['\0']),
+            States::AttributeValueDoubleQuoted(_) => Some(&['"', '&', '\0']),
+            States::AttributeValueSingleQuoted(_) => Some(&['\'', '&', '\0']),
+            States::AttributeValueUnquoted(_) => Some(&[
+                '\t', '\n', '\x0C', ' ', '&', '>', '\0', '"', '\'', '<', '=', '`',
+            ]),
+            _ => None,
+        }
+    }
+
+    /// The attribute being built in the attribute name and attribute value states
+    pub(super) fn current_attribute_mut(&mut self) -> Option<&mut Attribute> {
+        let token = match self {
+            States::AttributeName(AttributeName { token })
+            | States::AttributeValueDoubleQuoted(AttributeValueDoubleQuoted { token })
+            | States::AttributeValueSingleQuoted(AttributeValueSingleQuoted { token })
+            | States::AttributeValueUnquoted(AttributeValueUnquoted { token }) => token,
+            _ => return None,
+        };
+        token.current_attribute_mut()
+    }
+
     // Transitions
 
     pub(super) fn on_character(self, input: Character) -> TransitionResult {
@@ -887,23 +1024,23 @@ impl States {
             States::RawTextLessThanSign(state) => state.on_character(input),
             States::RawTextEndTagOpen(state) => state.on_character(input),
             // States::RawTextEndTagName(state) => (see on_character_and_last_start_tag)
-            // States::ScriptDataLessThanSign(state) => state.on_character(input),
-            // States::ScriptDataEndTagOpen(state) => state.on_character(input),
+            States::ScriptDataLessThanSign(state) => state.on_character(input),
+            States::ScriptDataEndTagOpen(state) => state.on_character(input),
             // States::ScriptDataEndTagName(state) => (see on_character_and_last_start_tag)
-            // States::ScriptDataEscapeStart(state) => state.on_character(input),
-            // States::ScriptDataEscapeStartDash(state) => state.on_character(input),
-            // States::ScriptDataEscaped(state) => state.on_character(input),
-            // States::ScriptDataEscapedDash(state) => state.on_character(input),
-            // States::ScriptDataEscapedDashDash(state) => state.on_character(input),
-            // States::ScriptDataEscapedLessThanSign(state) => state.on_character(input),
-            // States::ScriptDataEscapedEndTagOpen(state) => state.on_character(input),
+            States::ScriptDataEscapeStart(state) => state.on_character(input),
+            States::ScriptDataEscapeStartDash(state) => state.on_character(input),
+            States::ScriptDataEscaped(state) => state.on_character(input),
+            States::ScriptDataEscapedDash(state) => state.on_character(input),
+            States::ScriptDataEscapedDashDash(state) => state.on_character(input),
+            States::ScriptDataEscapedLessThanSign(state) => state.on_character(input),
+            States::ScriptDataEscapedEndTagOpen(state) => state.on_character(input),
             // States::ScriptDataEscapedEndTagName(state) => (see on_character_and_last_start_tag)
-            // States::ScriptDataDoubleEscapeStart(state) => state.on_character(input),
-            // States::ScriptDataDoubleEscaped(state) => state.on_character(input),
-            // States::ScriptDataDoubleEscapedDash(state) => state.on_character(input),
-            // States::ScriptDataDoubleEscapedDashDash(state) => state.on_character(input),
-            // States::ScriptDataDoubleEscapedLessThanSign(state) => state.on_character(input),
-            // States::ScriptDataDoubleEscapeEnd(state) => state.on_character(input),
+            States::ScriptDataDoubleEscapeStart(state) => state.on_character(input),
+            States::ScriptDataDoubleEscaped(state) => state.on_character(input),
+            States::ScriptDataDoubleEscapedDash(state) => state.on_character(input),
+            States::ScriptDataDoubleEscapedDashDash(state) => state.on_character(input),
+            States::ScriptDataDoubleEscapedLessThanSign(state) => state.on_character(input),
+            States::ScriptDataDoubleEscapeEnd(state) => state.on_character(input),
             States::BeforeAttributeName(state) => state.on_character(input),
             States::AttributeName(state) => state.on_character(input),
             States::AfterAttributeName(state) => state.on_character(input),
@@ -918,32 +1055,32 @@ impl States {
             States::CommentStart(state) => state.on_character(input),
             States::CommentStartDash(state) => state.on_character(input),
             States::Comment(state) => state.on_character(input),
-            // States::CommentLessThanSign(state) => state.on_character(input),
-            // States::CommentLessThanSignBang(state) => state.on_character(input),
-            // States::CommentLessThanSignBangDash(state) => state.on_character(input),
-            // States::CommentLessThanSignBangDashDash(state) => state.on_character(input),
+            States::CommentLessThanSign(state) => state.on_character(input),
+            States::CommentLessThanSignBang(state) => state.on_character(input),
+            States::CommentLessThanSignBangDash(state) => state.on_character(input),
+            States::CommentLessThanSignBangDashDash(state) => state.on_character(input),
             States::CommentEndDash(state) => state.on_character(input),
             States::CommentEnd(state) => state.on_character(input),
             States::CommentEndBang(state) => state.on_character(input),
             States::Doctype(state) => state.on_character(input),
             States::BeforeDoctypeName(state) => state.on_character(input),
             States::DoctypeName(state) => state.on_character(input),
-            // States::AfterDoctypeName(state) => state.on_character(input),
-            // States::AfterDoctypePublicKeyword(state) => state.on_character(input),
-            // States::BeforeDoctypePublicIdentifier(state) => state.on_character(input),
-            // States::DoctypePublicIdentifierDoubleQuoted(state) => state.on_character(input),
-            // States::DoctypePublicIdentifierSingleQuoted(state) => state.on_character(input),
-            // States::AfterDoctypePublicIdentifier(state) => state.on_character(input),
-            // States::BetweenDoctypePublicAndSystemIdentifiers(state) => state.on_character(input),
-            // States::AfterDoctypeSystemKeyword(state) => state.on_character(input),
-            // States::BeforeDoctypeSystemIdentifier(state) => state.on_character(input),
-            // States::DoctypeSystemIdentifierDoubleQuoted(state) => state.on_character(input),
-            // States::DoctypeSystemIdentifierSingleQuoted(state) => state.on_character(input),
-            // States::AfterDoctypeSystemIdentifier(state) => state.on_character(input),
-            // States::BogusDoctype(state) => state.on_character(input),
-            // States::CdataSection(state) => state.on_character(input),
-            // States::CdataSectionBracket(state) => state.on_character(input),
-            // States::CdataSectionEnd(state) => state.on_character(input),
+            States::AfterDoctypeName(state) => state.on_character(input),
+            States::AfterDoctypePublicKeyword(state) => state.on_character(input),
+            States::BeforeDoctypePublicIdentifier(state) => state.on_character(input),
+            States::DoctypePublicIdentifierDoubleQuoted(state) => state.on_character(input),
+            States::DoctypePublicIdentifierSingleQuoted(state) => state.on_character(input),
+            States::AfterDoctypePublicIdentifier(state) => state.on_character(input),
+            States::BetweenDoctypePublicAndSystemIdentifiers(state) => state.on_character(input),
+            States::AfterDoctypeSystemKeyword(state) => state.on_character(input),
+            States::BeforeDoctypeSystemIdentifier(state) => state.on_character(input),
+            States::DoctypeSystemIdentifierDoubleQuoted(state) => state.on_character(input),
+            States::DoctypeSystemIdentifierSingleQuoted(state) => state.on_character(input),
+            States::AfterDoctypeSystemIdentifier(state) => state.on_character(input),
+            States::BogusDoctype(state) => state.on_character(input),
+            States::CdataSection(state) => state.on_character(input),
+            States::CdataSectionBracket(state) => state.on_character(input),
+            States::CdataSectionEnd(state) => state.on_character(input),
             States::CharacterReference(state) => state.on_character(input),
             // States::NamedCharacterReference (see on_possible_character_reference_with_next_char)
             States::AmbiguousAmpersand(state) => state.on_character(input),
@@ -964,8 +1101,10 @@ impl States {
         match self {
             States::RcDataEndTagName(state) => state.on_character_and_last_start_tag(input),
             States::RawTextEndTagName(state) => state.on_character_and_last_start_tag(input),
-            // States::ScriptDataEndTagName(state) => state.on_character_and_last_start_tag(input),
-            // States::ScriptDataEscapedEndTagName(state) => state.on_character_and_last_start_tag(input),
+            States::ScriptDataEndTagName(state) => state.on_character_and_last_start_tag(input),
+            States::ScriptDataEscapedEndTagName(state) => {
+                state.on_character_and_last_start_tag(input)
+            }
             _ => Err(errors::StateTransitionError::new(
                 self,
                 "CharacterAndLastStartTag",
@@ -981,9 +1120,16 @@ impl States {
         }
     }
 
-    pub(super) fn on_next_few_characters(self, input: &NextFewCharacters) -> TransitionResult {
+    pub(super) fn on_next_few_characters(
+        self,
+        input: &NextFewCharacters,
+        in_foreign_content: bool,
+    ) -> TransitionResult {
         match self {
-            States::MarkupDeclarationOpen(state) => state.on_next_few_characters(input),
+            States::MarkupDeclarationOpen(state) => {
+                state.on_next_few_characters(input, in_foreign_content)
+            }
+            States::AfterDoctypeName(state) => state.on_next_few_characters(input),
             _ => Err(errors::StateTransitionError::new(self, "NextFewCharacters")).into(),
         }
     }
@@ -1007,8 +1153,8 @@ impl States {
     pub(super) fn execute(self, input: StateMachineMessages) -> TransitionResult {
         match input {
             StateMachineMessages::Advance => self.on_advance(),
-            StateMachineMessages::NextFewCharacters(message) => {
-                self.on_next_few_characters(&message)
+            StateMachineMessages::NextFewCharacters(message, in_foreign_content) => {
+                self.on_next_few_characters(&message, in_foreign_content)
             }
             StateMachineMessages::PossibleCharacterReferenceWithNextChar(message) => {
                 self.on_possible_character_reference_with_next_char(message)
@@ -1034,7 +1180,7 @@ impl Default for States {
 #[derive(Clone, Debug, PartialEq, From)]
 pub(super) enum StateMachineMessages {
     Advance,
-    NextFewCharacters(NextFewCharacters),
+    NextFewCharacters(NextFewCharacters, bool),
     PossibleCharacterReferenceWithNextChar(PossibleCharacterReferenceWithNextChar),
     Character(Character),
     CharacterAndLastStartTag(CharacterAndLastStartTag),
@@ -352,7 +352,7 @@ impl std::str::FromStr for TagName {
             "mn" => Ok(Mn),
             "ms" => Ok(Ms),
             "mtext" => Ok(Mtext),
-            "foreignObject" => Ok(ForeignObject),
+            "foreignobject" => Ok(ForeignObject),
             "desc" => Ok(Desc),
 
             _ => {
@@ -700,7 +700,7 @@ impl std::fmt::Display for TagName {
 
             Mglyph => write!(f, "mglyph"),
             Malignmark => write!(f, "malignmark"),
-            AnnotationXml => write!(f, "annotationxml"),
+            AnnotationXml => write!(f, "annotation-xml"),
             Mi => write!(f, "mi"),
             Mo => write!(f, "mo"),
             Mn => write!(f, "mn"),
@@ -761,4 +761,13 @@ mod test {
     fn display_a() {
         assert_eq!(TagName::Canvas.to_string(), "canvas".to_string())
     }
+
+    #[test]
+    fn foreign_round_trip() {
+        for name in &["annotation-xml", "foreignobject"] {
+            let tag: TagName = name.parse().unwrap();
+            assert!(!tag.is_other());
+            assert_eq!(&tag.to_string(), name);
+        }
+    }
 }
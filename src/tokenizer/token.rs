@@ -2,7 +2,7 @@ use std::fmt;
 
 use derive_more::From;
 
-use crate::tokenizer::TagName;
+use crate::tokenizer::{Span, TagName};
 
 #[derive(Clone, Debug, PartialEq, Eq, From)]
 pub enum Token {
@@ -18,11 +18,19 @@ pub enum Token {
     Eof,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// An attribute of a tag token
+///
+/// The spans are where the name and value are in the input, and aren't compared.
+#[derive(Clone, Debug, Default)]
 pub struct Attribute {
     pub name: String,
     pub value: String,
+    pub name_span: Span,
+    /// The value's span doesn't include any quotes, and is `None` for an attribute without a value
+    pub value_span: Option<Span>,
     duplicate: bool,
+    // A character reference, newline or NUL in the value was replaced
+    value_replaced: bool,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -99,13 +107,14 @@ impl Token {
     pub(crate) fn push_token(&mut self, token: &Token) {
         match *token {
             Token::Character(c) => self.push(c),
+            Token::Characters(ref s) => self.push_str(s),
             _ => panic!("Cannot push_tokens on {:?}", self),
         }
     }
 
     pub(crate) fn is_character(&self) -> bool {
         match self {
-            Token::Character(_) => true,
+            Token::Character(_) | Token::Characters(_) => true,
             _ => false,
         }
     }
@@ -117,6 +126,15 @@ impl Token {
         }
     }
 
+    /// Whether a character token is only ASCII whitespace
+    pub(crate) fn is_whitespace(&self) -> bool {
+        match self {
+            Token::Character(c) => c.is_ascii_whitespace(),
+            Token::Characters(s) => s.bytes().all(|b| b.is_ascii_whitespace()),
+            _ => false,
+        }
+    }
+
     pub(crate) fn is_start_tag(&self) -> bool {
         match self {
             Token::StartTag(_) => true,
@@ -147,6 +165,13 @@ impl Token {
         }
     }
 
+    pub(crate) fn start_tag(&self) -> Option<&StartTag> {
+        match self {
+            Token::StartTag(tag) => Some(tag),
+            _ => None,
+        }
+    }
+
     pub(crate) fn add_attribute(&mut self, name: String, value: String) {
         match self {
             Token::StartTag(t) => t.add_attribute(name, value),
@@ -178,6 +203,34 @@ impl Token {
         }
     }
 
+    pub(crate) fn set_public_identifier(&mut self, identifier: String) {
+        match self {
+            Token::Doctype(t) => t.public_identifier = Some(identifier),
+            _ => panic!("Cannot set_public_identifier on {:?}", self),
+        }
+    }
+
+    pub(crate) fn push_public_identifier(&mut self, c: char) {
+        match self {
+            Token::Doctype(t) => t.push_public_identifier(c),
+            _ => panic!("Cannot push_public_identifier on {:?}", self),
+        }
+    }
+
+    pub(crate) fn set_system_identifier(&mut self, identifier: String) {
+        match self {
+            Token::Doctype(t) => t.system_identifier = Some(identifier),
+            _ => panic!("Cannot set_system_identifier on {:?}", self),
+        }
+    }
+
+    pub(crate) fn push_system_identifier(&mut self, c: char) {
+        match self {
+            Token::Doctype(t) => t.push_system_identifier(c),
+            _ => panic!("Cannot push_system_identifier on {:?}", self),
+        }
+    }
+
     pub(crate) fn set_self_closing(&mut self, f: SelfClosingFlag) {
         match self {
             Token::StartTag(t) => t.set_self_closing(f),
@@ -244,6 +297,22 @@ impl Doctype {
         self.force_quirks = f
     }
 
+    pub(crate) fn push_public_identifier(&mut self, c: char) {
+        if let Some(ref mut identifier) = self.public_identifier {
+            identifier.push(c);
+        } else {
+            panic!("Cannot push to token::Doctype with no public identifier");
+        }
+    }
+
+    pub(crate) fn push_system_identifier(&mut self, c: char) {
+        if let Some(ref mut identifier) = self.system_identifier {
+            identifier.push(c);
+        } else {
+            panic!("Cannot push to token::Doctype with no system identifier");
+        }
+    }
+
     pub(crate) fn is_force_quirks(&self) -> bool {
         self.force_quirks == ForceQuirksFlag::On
     }
@@ -277,7 +346,7 @@ impl StartTag {
         self.attributes.push(Attribute {
             name,
             value,
-            duplicate: false,
+            ..Attribute::default()
         })
     }
 
@@ -342,7 +411,7 @@ impl EndTag {
         self.attributes.push(Attribute {
             name,
             value,
-            duplicate: false,
+            ..Attribute::default()
         })
     }
 
@@ -391,6 +460,14 @@ impl fmt::Display for EndTag {
     }
 }
 
+impl PartialEq for Attribute {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value == other.value && self.duplicate == other.duplicate
+    }
+}
+
+impl Eq for Attribute {}
+
 impl Attribute {
     #[cfg(test)]
     pub fn new(name: &str, value: &str, duplicate: bool) -> Self {
@@ -398,6 +475,7 @@ impl Attribute {
             name: name.to_string(),
             value: value.to_string(),
             duplicate,
+            ..Attribute::default()
         }
     }
 
@@ -409,7 +487,24 @@ impl Attribute {
         self.value.push(c);
     }
 
+    pub(crate) fn push_str_value(&mut self, s: &str) {
+        self.value.push_str(s);
+    }
+
     pub(crate) fn set_duplicate(&mut self) {
         self.duplicate = true
     }
+
+    pub(crate) fn set_value_replaced(&mut self) {
+        self.value_replaced = true
+    }
+
+    /// Whether `value` is exactly the input covered by `value_span`, so it can be borrowed from
+    /// in-memory input with `Span::slice` instead
+    ///
+    /// This is only ever true for UTF-8 input.
+    #[must_use]
+    pub fn value_is_verbatim(&self) -> bool {
+        self.value_span.is_some() && !self.value_replaced
+    }
 }
@@ -1,7 +1,7 @@
 //! Transit result is the outcome of a transition
 //! It has a `next_state`, an optional error, and an array of things to be emitted
 
-use std::cell::Cell;
+use std::{cell::Cell, mem};
 
 use log::warn;
 
@@ -14,6 +14,7 @@ pub(crate) struct TransitionResult {
     state: Result<States>,
     reconsume: bool,
     emit: Cell<Emit>,
+    parse_errors: Vec<ParseError>,
 }
 
 impl<E> From<E> for TransitionResult
@@ -50,6 +51,7 @@ impl TransitionResult {
             state: res,
             reconsume: false,
             emit: Cell::new(vec![]),
+            parse_errors: Vec::new(),
         }
     }
 
@@ -87,8 +89,11 @@ impl TransitionResult {
     }
 
     pub(super) fn push_parse_error(&mut self, err: ParseError) {
-        let _ = self;
-        // TODO: Handle parse errors
         warn!("Parse Error: {}", err);
+        self.parse_errors.push(err);
+    }
+
+    pub(super) fn parse_errors(&mut self) -> Vec<ParseError> {
+        mem::take(&mut self.parse_errors)
     }
 }
@@ -29,6 +29,7 @@ const U_NUMBER_SIGN: char = '\u{0023}'; // '#'
 const U_QUESTION_MARK: char = '\u{003F}'; // '?'
 const U_QUOTATION_MARK: char = '\u{0022}'; // '"'
 const U_REPLACEMENT_CHARACTER: char = '\u{FFFD}'; // '�'
+const U_RIGHT_SQUARE_BRACKET: char = '\u{005D}'; // ']'
 const U_SEMICOLON: char = '\u{003B}'; // ';'
 const U_SOLIDUS: char = '\u{002F}'; // '/'
 const U_SPACE: char = '\u{0020}'; // ' '
@@ -520,27 +521,43 @@ impl RawTextEndTagName {
     }
 }
 
-impl BeforeAttributeName {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl ScriptDataLessThanSign {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_CHARACTER_TABULATION)
-            | Character::Char(U_LINE_FEED)
-            | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
-            Character::Char(U_SOLIDUS) | Character::Char(U_GREATER_THAN_SIGN) | Character::Eof => {
-                let mut ret = States::after_attribute_name(self.token).into_transition_result();
+            Character::Char(U_SOLIDUS) => {
+                States::script_data_end_tag_open().into_transition_result()
+            }
+            Character::Char(U_EXCLAMATION_MARK) => {
+                let mut ret = States::script_data_escape_start().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.push_emit(U_EXCLAMATION_MARK);
+                ret
+            }
+            _ => {
+                let mut ret = States::script_data().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
                 ret.set_reconsume();
                 ret
             }
-            Character::Char(U_EQUALS_SIGN) => {
-                self.token.add_attribute("=".to_string(), String::new());
-                let mut ret = States::attribute_name(self.token).into_transition_result();
-                ret.push_parse_error(ParseError::UnexpectedEqualsSignBeforeAttributeName);
+        }
+    }
+}
+
+impl ScriptDataEndTagOpen {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(a) if a.is_ascii_alphabetic() => {
+                let token = EndTag::default();
+
+                let mut ret =
+                    States::script_data_end_tag_name(token, String::new()).into_transition_result();
+                ret.set_reconsume();
                 ret
             }
             _ => {
-                self.token.add_attribute(String::new(), String::new());
-                let mut ret = States::attribute_name(self.token).into_transition_result();
+                let mut ret = States::script_data().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.push_emit(U_SOLIDUS);
                 ret.set_reconsume();
                 ret
             }
@@ -548,128 +565,104 @@ impl BeforeAttributeName {
     }
 }
 
-impl AttributeName {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl ScriptDataEndTagName {
+    pub(super) fn on_character_and_last_start_tag(
+        mut self,
+        c: CharacterAndLastStartTag,
+    ) -> TransitionResult {
+        let (c, last_start_tag_emitted) = c.into();
         match c {
             Character::Char(U_CHARACTER_TABULATION)
             | Character::Char(U_LINE_FEED)
             | Character::Char(U_FORM_FEED)
             | Character::Char(U_SPACE)
-            | Character::Char(U_SOLIDUS)
-            | Character::Char(U_GREATER_THAN_SIGN)
-            | Character::Eof => {
-                self.check_duplicate_attribuite();
-
-                let mut ret = States::after_attribute_name(self.token).into_transition_result();
-                ret.set_reconsume();
-                ret
+                if self.is_appropriate_end_tag_token(&last_start_tag_emitted) =>
+            {
+                States::before_attribute_name(self.token).into_transition_result()
             }
-            Character::Char(U_EQUALS_SIGN) => {
-                self.check_duplicate_attribuite();
-
-                States::before_attribute_value(self.token).into_transition_result()
+            Character::Char(U_SOLIDUS)
+                if self.is_appropriate_end_tag_token(&last_start_tag_emitted) =>
+            {
+                States::self_closing_start_tag(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN)
+                if self.is_appropriate_end_tag_token(&last_start_tag_emitted) =>
+            {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
             }
             Character::Char(c) if c.is_ascii_uppercase() => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeName");
-                attribute.push_name(c.to_lowercase().next().unwrap());
+                self.token.push(c.to_lowercase().next().unwrap());
+                self.tmp.push(c);
 
                 States::from(self).into_transition_result()
             }
-            Character::Char(U_NULL) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeName");
-                attribute.push_name(U_REPLACEMENT_CHARACTER);
+            Character::Char(c) if c.is_ascii_lowercase() => {
+                self.token.push(c);
+                self.tmp.push(c);
 
-                let mut ret = States::from(self).into_transition_result();
-                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
-                ret
+                States::from(self).into_transition_result()
             }
-            Character::Char(c) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeName");
-                attribute.push_name(c);
-
-                let mut ret = States::from(self).into_transition_result();
-
-                if c == U_QUOTATION_MARK || c == U_APOSTROPHE || c == U_LESS_THAN_SIGN {
-                    ret.push_parse_error(ParseError::UnexpectedCharacterInAttributeName);
+            _ => {
+                let mut ret = States::script_data().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.push_emit(U_SOLIDUS);
+                for c in self.tmp.chars() {
+                    ret.push_emit(c);
                 }
+                ret.set_reconsume();
+
                 ret
             }
         }
     }
 
-    fn check_duplicate_attribuite(&mut self) {
-        if let Token::StartTag(ref mut tag) = self.token {
-            let num_attributes = tag.attributes_iter().count();
-            let current_attribute_name = if let Some(current_attribute) = tag.current_attribute() {
-                current_attribute.name.to_owned()
-            } else {
-                return;
-            };
-
-            let duplicate = tag.attributes_iter().enumerate().any(|(n, attribute)| {
-                // The last (current) attribute is never a duplicate
-                if n == num_attributes - 1 {
-                    return false;
-                }
-
-                if attribute.name == current_attribute_name {
+    // See RawTextEndTagName::is_appropriate_end_tag_token
+    fn is_appropriate_end_tag_token(&self, last_start_tag_emitted: &Option<StartTag>) -> bool {
+        if let Token::EndTag(ref token) = self.token {
+            if let Some(tag) = last_start_tag_emitted {
+                if tag.name == token.name {
                     return true;
                 }
-                false
-            });
-            if duplicate {
-                if let Some(ref mut current_attribute) = tag.current_attribute_mut() {
-                    trace!("Found duplicate attribute: {:?}", current_attribute);
-                    current_attribute.set_duplicate()
-                }
             }
+            false
         } else {
             panic!(
-                "Unexpected token in AttributeName::check_duplicate_attribuite: {:?}",
+                "Unexpected token in ScriptDataEndTagName::is_appropriate_end_tag_token: {:?}",
                 self.token
             );
         }
     }
 }
 
-impl AfterAttributeName {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl ScriptDataEscapeStart {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_CHARACTER_TABULATION)
-            | Character::Char(U_LINE_FEED)
-            | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
-
-            Character::Char(U_SOLIDUS) => {
-                States::self_closing_start_tag(self.token).into_transition_result()
-            }
-            Character::Char(U_EQUALS_SIGN) => {
-                States::attribute_value_single_quoted(self.token).into_transition_result()
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret = States::script_data_escape_start_dash().into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
+                ret
             }
-            Character::Char(U_GREATER_THAN_SIGN) => {
-                let mut ret = States::data().into_transition_result();
-                ret.push_emit(self.token);
+            _ => {
+                let mut ret = States::script_data().into_transition_result();
+                ret.set_reconsume();
                 ret
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
-                ret.push_emit(Token::Eof);
+        }
+    }
+}
+
+impl ScriptDataEscapeStartDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret = States::script_data_escaped_dash_dash().into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
                 ret
             }
             _ => {
-                self.token.add_attribute(String::new(), String::new());
-
-                let mut ret = States::attribute_name(self.token).into_transition_result();
+                let mut ret = States::script_data().into_transition_result();
                 ret.set_reconsume();
                 ret
             }
@@ -677,94 +670,1078 @@ impl AfterAttributeName {
     }
 }
 
-impl BeforeAttributeValue {
+impl ScriptDataEscaped {
     pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_CHARACTER_TABULATION)
-            | Character::Char(U_LINE_FEED)
-            | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
-            Character::Char(U_QUOTATION_MARK) => {
-                States::attribute_value_double_quoted(self.token).into_transition_result()
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret = States::script_data_escaped_dash().into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
+                ret
             }
-            Character::Char(U_APOSTROPHE) => {
-                States::attribute_value_single_quoted(self.token).into_transition_result()
+            Character::Char(U_LESS_THAN_SIGN) => {
+                States::script_data_escaped_less_than_sign().into_transition_result()
             }
-            Character::Char(U_GREATER_THAN_SIGN) => {
-                let mut ret = States::data().into_transition_result();
-                ret.push_parse_error(ParseError::MissingAttributeValue);
-                ret.push_emit(self.token);
+            Character::Char(U_NULL) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_REPLACEMENT_CHARACTER);
                 ret
             }
-            _ => {
-                let mut ret = States::attribute_value_unquoted(self.token).into_transition_result();
-                ret.set_reconsume();
+            Character::Eof => eof_in_script_html_comment_like_text(),
+            Character::Char(c) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(c);
                 ret
             }
         }
     }
 }
 
-impl AttributeValueDoubleQuoted {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl ScriptDataEscapedDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_QUOTATION_MARK) => {
-                States::after_attribute_value_quoted(self.token).into_transition_result()
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret = States::script_data_escaped_dash_dash().into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
+                ret
             }
-            Character::Char(U_AMPERSAND) => {
-                States::character_reference(self, String::new()).into_transition_result()
+            Character::Char(U_LESS_THAN_SIGN) => {
+                States::script_data_escaped_less_than_sign().into_transition_result()
             }
             Character::Char(U_NULL) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeValueDoubleQuoted");
-                attribute.push_value(U_REPLACEMENT_CHARACTER);
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_REPLACEMENT_CHARACTER);
+                ret
+            }
+            Character::Eof => eof_in_script_html_comment_like_text(),
+            Character::Char(c) => {
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+        }
+    }
+}
 
+impl ScriptDataEscapedDashDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
                 let mut ret = States::from(self).into_transition_result();
-                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_HYPHEN_MINUS);
                 ret
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
-                ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
+            Character::Char(U_LESS_THAN_SIGN) => {
+                States::script_data_escaped_less_than_sign().into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::script_data().into_transition_result();
+                ret.push_emit(U_GREATER_THAN_SIGN);
+                ret
+            }
+            Character::Char(U_NULL) => {
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_REPLACEMENT_CHARACTER);
                 ret
             }
+            Character::Eof => eof_in_script_html_comment_like_text(),
             Character::Char(c) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeValueDoubleQuoted");
-                attribute.push_value(c);
-
-                States::from(self).into_transition_result()
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_emit(c);
+                ret
             }
         }
     }
 }
 
-impl AttributeValueSingleQuoted {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl ScriptDataEscapedLessThanSign {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_APOSTROPHE) => {
-                States::after_attribute_value_quoted(self.token).into_transition_result()
+            Character::Char(U_SOLIDUS) => {
+                States::script_data_escaped_end_tag_open().into_transition_result()
             }
-            Character::Char(U_AMPERSAND) => {
-                States::character_reference(self, String::new()).into_transition_result()
+            Character::Char(a) if a.is_ascii_alphabetic() => {
+                let mut ret =
+                    States::script_data_double_escape_start(String::new()).into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.set_reconsume();
+                ret
             }
-            Character::Char(U_NULL) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeValueSingleQuoted");
-                attribute.push_value(U_REPLACEMENT_CHARACTER);
+            _ => {
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
 
-                let mut ret = States::from(self).into_transition_result();
-                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+impl ScriptDataEscapedEndTagOpen {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(a) if a.is_ascii_alphabetic() => {
+                let token = EndTag::default();
+
+                let mut ret = States::script_data_escaped_end_tag_name(token, String::new())
+                    .into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+            _ => {
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.push_emit(U_SOLIDUS);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl ScriptDataEscapedEndTagName {
+    pub(super) fn on_character_and_last_start_tag(
+        mut self,
+        c: CharacterAndLastStartTag,
+    ) -> TransitionResult {
+        let (c, last_start_tag_emitted) = c.into();
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE)
+                if self.is_appropriate_end_tag_token(&last_start_tag_emitted) =>
+            {
+                States::before_attribute_name(self.token).into_transition_result()
+            }
+            Character::Char(U_SOLIDUS)
+                if self.is_appropriate_end_tag_token(&last_start_tag_emitted) =>
+            {
+                States::self_closing_start_tag(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN)
+                if self.is_appropriate_end_tag_token(&last_start_tag_emitted) =>
+            {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Char(c) if c.is_ascii_uppercase() => {
+                self.token.push(c.to_lowercase().next().unwrap());
+                self.tmp.push(c);
+
+                States::from(self).into_transition_result()
+            }
+            Character::Char(c) if c.is_ascii_lowercase() => {
+                self.token.push(c);
+                self.tmp.push(c);
+
+                States::from(self).into_transition_result()
+            }
+            _ => {
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret.push_emit(U_SOLIDUS);
+                for c in self.tmp.chars() {
+                    ret.push_emit(c);
+                }
+                ret.set_reconsume();
+
+                ret
+            }
+        }
+    }
+
+    // See RawTextEndTagName::is_appropriate_end_tag_token
+    fn is_appropriate_end_tag_token(&self, last_start_tag_emitted: &Option<StartTag>) -> bool {
+        if let Token::EndTag(ref token) = self.token {
+            if let Some(tag) = last_start_tag_emitted {
+                if tag.name == token.name {
+                    return true;
+                }
+            }
+            false
+        } else {
+            panic!(
+                "Unexpected token in \
+                 ScriptDataEscapedEndTagName::is_appropriate_end_tag_token: {:?}",
+                self.token
+            );
+        }
+    }
+}
+
+impl ScriptDataDoubleEscapeStart {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(c @ U_CHARACTER_TABULATION)
+            | Character::Char(c @ U_LINE_FEED)
+            | Character::Char(c @ U_FORM_FEED)
+            | Character::Char(c @ U_SPACE)
+            | Character::Char(c @ U_SOLIDUS)
+            | Character::Char(c @ U_GREATER_THAN_SIGN) => {
+                let mut ret = if self.tmp == "script" {
+                    States::script_data_double_escaped().into_transition_result()
+                } else {
+                    States::script_data_escaped().into_transition_result()
+                };
+                ret.push_emit(c);
+                ret
+            }
+            Character::Char(c) if c.is_ascii_alphabetic() => {
+                self.tmp.push(c.to_ascii_lowercase());
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+            _ => {
+                let mut ret = States::script_data_escaped().into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl ScriptDataDoubleEscaped {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret = States::script_data_double_escaped_dash().into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
+                ret
+            }
+            Character::Char(U_LESS_THAN_SIGN) => {
+                let mut ret =
+                    States::script_data_double_escaped_less_than_sign().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret
+            }
+            Character::Char(U_NULL) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_REPLACEMENT_CHARACTER);
+                ret
+            }
+            Character::Eof => eof_in_script_html_comment_like_text(),
+            Character::Char(c) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+        }
+    }
+}
+
+impl ScriptDataDoubleEscapedDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret =
+                    States::script_data_double_escaped_dash_dash().into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
+                ret
+            }
+            Character::Char(U_LESS_THAN_SIGN) => {
+                let mut ret =
+                    States::script_data_double_escaped_less_than_sign().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret
+            }
+            Character::Char(U_NULL) => {
+                let mut ret = States::script_data_double_escaped().into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_REPLACEMENT_CHARACTER);
+                ret
+            }
+            Character::Eof => eof_in_script_html_comment_like_text(),
+            Character::Char(c) => {
+                let mut ret = States::script_data_double_escaped().into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+        }
+    }
+}
+
+impl ScriptDataDoubleEscapedDashDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(U_HYPHEN_MINUS);
+                ret
+            }
+            Character::Char(U_LESS_THAN_SIGN) => {
+                let mut ret =
+                    States::script_data_double_escaped_less_than_sign().into_transition_result();
+                ret.push_emit(U_LESS_THAN_SIGN);
+                ret
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::script_data().into_transition_result();
+                ret.push_emit(U_GREATER_THAN_SIGN);
+                ret
+            }
+            Character::Char(U_NULL) => {
+                let mut ret = States::script_data_double_escaped().into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret.push_emit(U_REPLACEMENT_CHARACTER);
+                ret
+            }
+            Character::Eof => eof_in_script_html_comment_like_text(),
+            Character::Char(c) => {
+                let mut ret = States::script_data_double_escaped().into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+        }
+    }
+}
+
+impl ScriptDataDoubleEscapedLessThanSign {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_SOLIDUS) => {
+                let mut ret =
+                    States::script_data_double_escape_end(String::new()).into_transition_result();
+                ret.push_emit(U_SOLIDUS);
+                ret
+            }
+            _ => {
+                let mut ret = States::script_data_double_escaped().into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl ScriptDataDoubleEscapeEnd {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(c @ U_CHARACTER_TABULATION)
+            | Character::Char(c @ U_LINE_FEED)
+            | Character::Char(c @ U_FORM_FEED)
+            | Character::Char(c @ U_SPACE)
+            | Character::Char(c @ U_SOLIDUS)
+            | Character::Char(c @ U_GREATER_THAN_SIGN) => {
+                let mut ret = if self.tmp == "script" {
+                    States::script_data_escaped().into_transition_result()
+                } else {
+                    States::script_data_double_escaped().into_transition_result()
+                };
+                ret.push_emit(c);
+                ret
+            }
+            Character::Char(c) if c.is_ascii_alphabetic() => {
+                self.tmp.push(c.to_ascii_lowercase());
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+            _ => {
+                let mut ret = States::script_data_double_escaped().into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl BeforeAttributeName {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
+            Character::Char(U_SOLIDUS) | Character::Char(U_GREATER_THAN_SIGN) | Character::Eof => {
+                let mut ret = States::after_attribute_name(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+            Character::Char(U_EQUALS_SIGN) => {
+                self.token.add_attribute("=".to_string(), String::new());
+                let mut ret = States::attribute_name(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedEqualsSignBeforeAttributeName);
+                ret
+            }
+            _ => {
+                self.token.add_attribute(String::new(), String::new());
+                let mut ret = States::attribute_name(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl AttributeName {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE)
+            | Character::Char(U_SOLIDUS)
+            | Character::Char(U_GREATER_THAN_SIGN)
+            | Character::Eof => {
+                let duplicate = self.check_duplicate_attribuite();
+
+                let mut ret = States::after_attribute_name(self.token).into_transition_result();
+                ret.set_reconsume();
+                if duplicate {
+                    ret.push_parse_error(ParseError::DuplicateAttribute);
+                }
+                ret
+            }
+            Character::Char(U_EQUALS_SIGN) => {
+                let duplicate = self.check_duplicate_attribuite();
+
+                let mut ret = States::before_attribute_value(self.token).into_transition_result();
+                if duplicate {
+                    ret.push_parse_error(ParseError::DuplicateAttribute);
+                }
+                ret
+            }
+            Character::Char(c) if c.is_ascii_uppercase() => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeName");
+                attribute.push_name(c.to_lowercase().next().unwrap());
+
+                States::from(self).into_transition_result()
+            }
+            Character::Char(U_NULL) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeName");
+                attribute.push_name(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+            Character::Char(c) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeName");
+                attribute.push_name(c);
+
+                let mut ret = States::from(self).into_transition_result();
+
+                if c == U_QUOTATION_MARK || c == U_APOSTROPHE || c == U_LESS_THAN_SIGN {
+                    ret.push_parse_error(ParseError::UnexpectedCharacterInAttributeName);
+                }
+                ret
+            }
+        }
+    }
+
+    fn check_duplicate_attribuite(&mut self) -> bool {
+        // End tags can have attributes too, which the tree builder ignores
+        let attributes = match self.token {
+            Token::StartTag(ref mut tag) => &mut tag.attributes,
+            Token::EndTag(ref mut tag) => &mut tag.attributes,
+            _ => return false,
+        };
+        let (current_attribute, others) = match attributes.split_last_mut() {
+            Some(split) => split,
+            None => return false,
+        };
+
+        // The last (current) attribute is never a duplicate
+        let duplicate = others
+            .iter()
+            .any(|attribute| attribute.name == current_attribute.name);
+        if duplicate {
+            trace!("Found duplicate attribute: {:?}", current_attribute);
+            current_attribute.set_duplicate()
+        }
+        duplicate
+    }
+}
+
+impl AfterAttributeName {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
+
+            Character::Char(U_SOLIDUS) => {
+                States::self_closing_start_tag(self.token).into_transition_result()
+            }
+            Character::Char(U_EQUALS_SIGN) => {
+                States::attribute_value_single_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            _ => {
+                self.token.add_attribute(String::new(), String::new());
+
+                let mut ret = States::attribute_name(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl BeforeAttributeValue {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
+            Character::Char(U_QUOTATION_MARK) => {
+                States::attribute_value_double_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_APOSTROPHE) => {
+                States::attribute_value_single_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::MissingAttributeValue);
+                ret.push_emit(self.token);
+                ret
+            }
+            _ => {
+                let mut ret = States::attribute_value_unquoted(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl AttributeValueDoubleQuoted {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_QUOTATION_MARK) => {
+                States::after_attribute_value_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_AMPERSAND) => {
+                States::character_reference(self, String::new()).into_transition_result()
+            }
+            Character::Char(U_NULL) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeValueDoubleQuoted");
+                attribute.push_value(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            Character::Char(c) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeValueDoubleQuoted");
+                attribute.push_value(c);
+
+                States::from(self).into_transition_result()
+            }
+        }
+    }
+}
+
+impl AttributeValueSingleQuoted {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_APOSTROPHE) => {
+                States::after_attribute_value_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_AMPERSAND) => {
+                States::character_reference(self, String::new()).into_transition_result()
+            }
+            Character::Char(U_NULL) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeValueSingleQuoted");
+                attribute.push_value(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            Character::Char(c) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeValueSingleQuoted");
+                attribute.push_value(c);
+
+                States::from(self).into_transition_result()
+            }
+        }
+    }
+}
+
+impl AttributeValueUnquoted {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => {
+                States::before_attribute_name(self.token).into_transition_result()
+            }
+            Character::Char(U_AMPERSAND) => {
+                States::character_reference(self, String::new()).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Char(U_NULL) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeValueUnquoted");
+                attribute.push_value(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            Character::Char(c) => {
+                let attribute = self
+                    .token
+                    .current_attribute_mut()
+                    .expect("No attribute found when handling AttributeValueUnquoted");
+                attribute.push_value(c);
+
+                let mut ret = States::from(self).into_transition_result();
+                if c == U_QUOTATION_MARK
+                    || c == U_APOSTROPHE
+                    || c == U_LESS_THAN_SIGN
+                    || c == U_EQUALS_SIGN
+                    || c == U_GRAVE_ACCENT
+                {
+                    ret.push_parse_error(ParseError::UnexpectedCharacterInUnquotedAttributeValue);
+                }
+                ret
+            }
+        }
+    }
+}
+
+impl AfterAttributeValueQuoted {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => {
+                States::before_attribute_name(self.token).into_transition_result()
+            }
+            Character::Char(U_SOLIDUS) => {
+                States::self_closing_start_tag(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            _ => {
+                let mut ret = States::before_attribute_name(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingWhitespaceBetweenAttributes);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl SelfClosingStartTag {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_self_closing(SelfClosingFlag::Set);
+
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            _ => {
+                let mut ret = States::before_attribute_name(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedSolidusInTag);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl BogusComment {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            Character::Char(U_NULL) => {
+                self.token.push(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+            Character::Char(c) => {
+                self.token.push(c);
+
+                States::from(self).into_transition_result()
+            }
+        }
+    }
+}
+
+impl MarkupDeclarationOpen {
+    pub(super) fn on_next_few_characters(
+        self,
+        next: &NextFewCharacters,
+        in_foreign_content: bool,
+    ) -> TransitionResult {
+        if next.as_ref().is_none() {
+            let mut ret = States::bogus_comment(String::new()).into_transition_result();
+            ret.push_parse_error(ParseError::IncorrectlyOpenedComment);
+            ret
+        } else {
+            match next.as_ref().as_ref().unwrap().as_str() {
+                "DOCTYPE" => States::doctype().into_transition_result(),
+                "--" => States::comment_start(String::new()).into_transition_result(),
+                "[CDATA[" => {
+                    // If there is an adjusted current node and it is not an element in the HTML namespace, then switch to the CDATA section state.
+                    if in_foreign_content {
+                        return States::cdata_section().into_transition_result();
+                    }
+                    //     Otherwise, this is a cdata-in-html-content parse error.
+                    //     Create a comment token whose data is the "[CDATA[" string. Switch to the bogus comment state.
+                    let mut ret = States::bogus_comment("[CDATA[").into_transition_result();
+                    ret.push_parse_error(ParseError::CdataInHtmlContent);
+                    ret
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl CdataSection {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_RIGHT_SQUARE_BRACKET) => {
+                States::cdata_section_bracket().into_transition_result()
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInCdata);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            Character::Char(c) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(c);
+                ret
+            }
+        }
+    }
+}
+
+impl CdataSectionBracket {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_RIGHT_SQUARE_BRACKET) => {
+                States::cdata_section_end().into_transition_result()
+            }
+            _ => {
+                let mut ret = States::cdata_section().into_transition_result();
+                ret.push_emit(U_RIGHT_SQUARE_BRACKET);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CdataSectionEnd {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_RIGHT_SQUARE_BRACKET) => {
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_emit(U_RIGHT_SQUARE_BRACKET);
+                ret
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => States::data().into_transition_result(),
+            _ => {
+                let mut ret = States::cdata_section().into_transition_result();
+                ret.push_emit(U_RIGHT_SQUARE_BRACKET);
+                ret.push_emit(U_RIGHT_SQUARE_BRACKET);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentStart {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                States::comment_start_dash(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::AbruptClosingOfEmptyComment);
+                ret.push_emit(self.token);
+                ret
+            }
+            _ => {
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentStartDash {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                States::comment_end(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::AbruptClosingOfEmptyComment);
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInComment);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            _ => {
+                self.token.push(U_HYPHEN_MINUS);
+
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl Comment {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_LESS_THAN_SIGN) => {
+                self.token.push(U_LESS_THAN_SIGN);
+                States::comment_less_than_sign(self.token).into_transition_result()
+            }
+            Character::Char(U_HYPHEN_MINUS) => {
+                States::comment_end_dash(self.token).into_transition_result()
+            }
+            Character::Char(U_NULL) => {
+                self.token.push(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInComment);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            Character::Char(c) => {
+                self.token.push(c);
+
+                States::from(self).into_transition_result()
+            }
+        }
+    }
+}
+
+impl CommentLessThanSign {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_EXCLAMATION_MARK) => {
+                self.token.push(U_EXCLAMATION_MARK);
+                States::comment_less_than_sign_bang(self.token).into_transition_result()
+            }
+            Character::Char(U_LESS_THAN_SIGN) => {
+                self.token.push(U_LESS_THAN_SIGN);
+                States::from(self).into_transition_result()
+            }
+            _ => {
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentLessThanSignBang {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                States::comment_less_than_sign_bang_dash(self.token).into_transition_result()
+            }
+            _ => {
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentLessThanSignBangDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                States::comment_less_than_sign_bang_dash_dash(self.token).into_transition_result()
+            }
+            _ => {
+                let mut ret = States::comment_end_dash(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentLessThanSignBangDashDash {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_GREATER_THAN_SIGN) | Character::Eof => {
+                let mut ret = States::comment_end(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+            _ => {
+                let mut ret = States::comment_end(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::NestedComment);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentEndDash {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_HYPHEN_MINUS) => {
+                States::comment_end(self.token).into_transition_result()
+            }
+            Character::Eof => {
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
+            _ => {
+                self.token.push(U_HYPHEN_MINUS);
+
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl CommentEnd {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
                 ret
             }
+            Character::Char(U_EXCLAMATION_MARK) => {
+                States::comment_end_bang(self.token).into_transition_result()
+            }
+            Character::Char(U_HYPHEN_MINUS) => {
+                self.token.push(U_HYPHEN_MINUS);
+
+                States::from(self).into_transition_result()
+            }
             Character::Eof => {
                 let mut ret = States::term().into_transition_result();
                 ret.push_parse_error(ParseError::EofInTag);
@@ -772,104 +1749,81 @@ impl AttributeValueSingleQuoted {
                 ret.push_emit(Token::Eof);
                 ret
             }
-            Character::Char(c) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeValueSingleQuoted");
-                attribute.push_value(c);
+            _ => {
+                self.token.push(U_HYPHEN_MINUS);
+                self.token.push(U_HYPHEN_MINUS);
 
-                States::from(self).into_transition_result()
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
+                ret
             }
         }
     }
 }
 
-impl AttributeValueUnquoted {
+impl CommentEndBang {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_CHARACTER_TABULATION)
-            | Character::Char(U_LINE_FEED)
-            | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => {
-                States::before_attribute_name(self.token).into_transition_result()
-            }
-            Character::Char(U_AMPERSAND) => {
-                States::character_reference(self, String::new()).into_transition_result()
+            Character::Char(U_HYPHEN_MINUS) => {
+                self.token.push(U_HYPHEN_MINUS);
+                self.token.push(U_HYPHEN_MINUS);
+                self.token.push(U_EXCLAMATION_MARK);
+                States::comment_end_dash(self.token).into_transition_result()
             }
             Character::Char(U_GREATER_THAN_SIGN) => {
                 let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::IncorrectlyOpenedComment);
                 ret.push_emit(self.token);
                 ret
             }
-            Character::Char(U_NULL) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeValueUnquoted");
-                attribute.push_value(U_REPLACEMENT_CHARACTER);
-
-                let mut ret = States::from(self).into_transition_result();
-                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
-                ret
-            }
-
             Character::Eof => {
                 let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_parse_error(ParseError::EofInComment);
                 ret.push_emit(self.token);
                 ret.push_emit(Token::Eof);
                 ret
             }
-            Character::Char(c) => {
-                let attribute = self
-                    .token
-                    .current_attribute_mut()
-                    .expect("No attribute found when handling AttributeValueUnquoted");
-                attribute.push_value(c);
+            _ => {
+                self.token.push(U_HYPHEN_MINUS);
+                self.token.push(U_HYPHEN_MINUS);
+                self.token.push(U_EXCLAMATION_MARK);
 
-                let mut ret = States::from(self).into_transition_result();
-                if c == U_QUOTATION_MARK
-                    || c == U_APOSTROPHE
-                    || c == U_LESS_THAN_SIGN
-                    || c == U_EQUALS_SIGN
-                    || c == U_GRAVE_ACCENT
-                {
-                    ret.push_parse_error(ParseError::UnexpectedCharacterInUnquotedAttributeValue);
-                }
+                let mut ret = States::comment(self.token).into_transition_result();
+                ret.set_reconsume();
                 ret
             }
         }
     }
 }
 
-impl AfterAttributeValueQuoted {
+impl Doctype {
     pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
             Character::Char(U_CHARACTER_TABULATION)
             | Character::Char(U_LINE_FEED)
             | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => {
-                States::before_attribute_name(self.token).into_transition_result()
-            }
-            Character::Char(U_SOLIDUS) => {
-                States::self_closing_start_tag(self.token).into_transition_result()
-            }
+            | Character::Char(U_SPACE) => States::before_doctype_name().into_transition_result(),
             Character::Char(U_GREATER_THAN_SIGN) => {
-                let mut ret = States::data().into_transition_result();
-                ret.push_emit(self.token);
+                let mut ret = States::before_doctype_name().into_transition_result();
+                ret.set_reconsume();
                 ret
             }
             Character::Eof => {
+                let token = DoctypeToken {
+                    name: None,
+                    force_quirks: ForceQuirksFlag::On,
+                    ..DoctypeToken::default()
+                };
+
                 let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
-                ret.push_emit(self.token);
+                ret.push_parse_error(ParseError::EofInDoctype);
+                ret.push_emit(token);
                 ret.push_emit(Token::Eof);
                 ret
             }
             _ => {
-                let mut ret = States::before_attribute_name(self.token).into_transition_result();
-                ret.push_parse_error(ParseError::MissingWhitespaceBetweenAttributes);
+                let mut ret = States::before_doctype_name().into_transition_result();
+                ret.push_parse_error(ParseError::MissingWhitespaceBeforeDoctypeName);
                 ret.set_reconsume();
                 ret
             }
@@ -877,45 +1831,75 @@ impl AfterAttributeValueQuoted {
     }
 }
 
-impl SelfClosingStartTag {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl BeforeDoctypeName {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
+            Character::Char(c) if c.is_ascii_uppercase() => {
+                let token = DoctypeToken::from_char(c.to_lowercase().next().unwrap());
+                States::doctype_name(token).into_transition_result()
+            }
+            Character::Char(U_NULL) => {
+                let token = DoctypeToken::from_char(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::doctype_name(token).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
             Character::Char(U_GREATER_THAN_SIGN) => {
-                self.token.set_self_closing(SelfClosingFlag::Set);
+                let token = DoctypeToken {
+                    name: None,
+                    force_quirks: ForceQuirksFlag::On,
+                    ..DoctypeToken::default()
+                };
 
                 let mut ret = States::data().into_transition_result();
-                ret.push_emit(self.token);
+                ret.push_parse_error(ParseError::MissingDoctypeName);
+                ret.push_emit(token);
                 ret
             }
             Character::Eof => {
+                let token = DoctypeToken {
+                    name: None,
+                    force_quirks: ForceQuirksFlag::On,
+                    ..DoctypeToken::default()
+                };
+
                 let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
+                ret.push_parse_error(ParseError::EofInDoctype);
+                ret.push_emit(token);
                 ret.push_emit(Token::Eof);
                 ret
             }
-            _ => {
-                let mut ret = States::before_attribute_name(self.token).into_transition_result();
-                ret.push_parse_error(ParseError::UnexpectedSolidusInTag);
-                ret.set_reconsume();
-                ret
+            Character::Char(c) => {
+                let token = DoctypeToken::from_char(c);
+                States::doctype_name(token).into_transition_result()
             }
         }
     }
 }
 
-impl BogusComment {
+impl DoctypeName {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => {
+                States::after_doctype_name(self.token).into_transition_result()
+            }
             Character::Char(U_GREATER_THAN_SIGN) => {
                 let mut ret = States::data().into_transition_result();
                 ret.push_emit(self.token);
                 ret
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
-                ret
+            Character::Char(c) if c.is_ascii_uppercase() => {
+                self.token.push(c.to_lowercase().next().unwrap());
+
+                States::from(self).into_transition_result()
             }
             Character::Char(U_NULL) => {
                 self.token.push(U_REPLACEMENT_CHARACTER);
@@ -924,6 +1908,15 @@ impl BogusComment {
                 ret.push_parse_error(ParseError::UnexpectedNullCharacter);
                 ret
             }
+            Character::Eof => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::term().into_transition_result();
+                ret.push_parse_error(ParseError::EofInDoctype);
+                ret.push_emit(self.token);
+                ret.push_emit(Token::Eof);
+                ret
+            }
             Character::Char(c) => {
                 self.token.push(c);
 
@@ -933,53 +1926,82 @@ impl BogusComment {
     }
 }
 
-impl MarkupDeclarationOpen {
-    pub(super) fn on_next_few_characters(self, next: &NextFewCharacters) -> TransitionResult {
-        if next.as_ref().is_none() {
-            let mut ret = States::bogus_comment(String::new()).into_transition_result();
-            ret.push_parse_error(ParseError::IncorrectlyOpenedComment);
-            ret
-        } else {
-            match next.as_ref().as_ref().unwrap().as_str() {
-                "DOCTYPE" => States::doctype().into_transition_result(),
-                "--" => States::comment_start(String::new()).into_transition_result(),
-                "[CDATA[" => {
-                    // If there is an adjusted current node and it is not an element in the HTML namespace, then switch to the CDATA section state.
-                    if let Some(_node) = self.get_adjusted_current_node() {
-                        // if !node.is_element_in_html_namespace() {
-                        return States::cdata_section().into_transition_result();
-                        // }
-                    }
-                    //     Otherwise, this is a cdata-in-html-content parse error.
-                    //     Create a comment token whose data is the "[CDATA[" string. Switch to the bogus comment state.
-                    let mut ret = States::bogus_comment("[CDATA[").into_transition_result();
-                    ret.push_parse_error(ParseError::CdataInHtmlContent);
-                    ret
-                }
-                _ => unreachable!(),
+impl AfterDoctypeName {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::InvalidCharacterSequenceAfterDoctypeName);
+                ret.set_reconsume();
+                ret
             }
         }
     }
 
-    fn get_adjusted_current_node(&self) -> Option<()> {
-        todo!("MarkupDeclarationOpen::get_adjusted_current_node")
+    pub(super) fn on_next_few_characters(self, next: &NextFewCharacters) -> TransitionResult {
+        match next.as_ref().as_ref().map(String::as_str) {
+            Some("PUBLIC") => {
+                States::after_doctype_public_keyword(self.token).into_transition_result()
+            }
+            Some("SYSTEM") => {
+                States::after_doctype_system_keyword(self.token).into_transition_result()
+            }
+            _ => unreachable!(),
+        }
     }
 }
 
-impl CommentStart {
-    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+impl AfterDoctypePublicKeyword {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_HYPHEN_MINUS) => {
-                States::comment_start_dash(self.token).into_transition_result()
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => {
+                States::before_doctype_public_identifier(self.token).into_transition_result()
+            }
+            Character::Char(U_QUOTATION_MARK) => {
+                self.token.set_public_identifier(String::new());
+
+                let mut ret = States::doctype_public_identifier_double_quoted(self.token)
+                    .into_transition_result();
+                ret.push_parse_error(ParseError::MissingWhitespaceAfterDoctypePublicKeyword);
+                ret
+            }
+            Character::Char(U_APOSTROPHE) => {
+                self.token.set_public_identifier(String::new());
+
+                let mut ret = States::doctype_public_identifier_single_quoted(self.token)
+                    .into_transition_result();
+                ret.push_parse_error(ParseError::MissingWhitespaceAfterDoctypePublicKeyword);
+                ret
             }
             Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
                 let mut ret = States::data().into_transition_result();
-                ret.push_parse_error(ParseError::AbruptClosingOfEmptyComment);
+                ret.push_parse_error(ParseError::MissingDoctypePublicIdentifier);
                 ret.push_emit(self.token);
                 ret
             }
-            _ => {
-                let mut ret = States::comment(self.token).into_transition_result();
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingQuoteBeforeDoctypePublicIdentifier);
                 ret.set_reconsume();
                 ret
             }
@@ -987,29 +2009,35 @@ impl CommentStart {
     }
 }
 
-impl CommentStartDash {
+impl BeforeDoctypePublicIdentifier {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_HYPHEN_MINUS) => {
-                States::comment_end(self.token).into_transition_result()
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
+            Character::Char(U_QUOTATION_MARK) => {
+                self.token.set_public_identifier(String::new());
+                States::doctype_public_identifier_double_quoted(self.token).into_transition_result()
             }
-            Character::Char(U_GREATER_THAN_SIGN) => {
-                let mut ret = States::data().into_transition_result();
-                ret.push_parse_error(ParseError::AbruptClosingOfEmptyComment);
-                ret.push_emit(self.token);
-                ret
+            Character::Char(U_APOSTROPHE) => {
+                self.token.set_public_identifier(String::new());
+                States::doctype_public_identifier_single_quoted(self.token).into_transition_result()
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInComment);
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::MissingDoctypePublicIdentifier);
                 ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
                 ret
             }
-            _ => {
-                self.token.push(U_HYPHEN_MINUS);
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
 
-                let mut ret = States::comment(self.token).into_transition_result();
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingQuoteBeforeDoctypePublicIdentifier);
                 ret.set_reconsume();
                 ret
             }
@@ -1017,91 +2045,107 @@ impl CommentStartDash {
     }
 }
 
-impl Comment {
+impl DoctypePublicIdentifierDoubleQuoted {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_LESS_THAN_SIGN) => {
-                self.token.push(U_LESS_THAN_SIGN);
-                States::comment_less_than_sign(self.token).into_transition_result()
-            }
-            Character::Char(U_HYPHEN_MINUS) => {
-                States::comment_end_dash(self.token).into_transition_result()
+            Character::Char(U_QUOTATION_MARK) => {
+                States::after_doctype_public_identifier(self.token).into_transition_result()
             }
             Character::Char(U_NULL) => {
-                self.token.push(U_REPLACEMENT_CHARACTER);
+                self.token.push_public_identifier(U_REPLACEMENT_CHARACTER);
 
                 let mut ret = States::from(self).into_transition_result();
                 ret.push_parse_error(ParseError::UnexpectedNullCharacter);
                 ret
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInComment);
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::AbruptDoctypePublicIdentifier);
                 ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
                 ret
             }
+            Character::Eof => eof_in_doctype(self.token),
             Character::Char(c) => {
-                self.token.push(c);
-
+                self.token.push_public_identifier(c);
                 States::from(self).into_transition_result()
             }
         }
     }
 }
 
-impl CommentEndDash {
+impl DoctypePublicIdentifierSingleQuoted {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_HYPHEN_MINUS) => {
-                States::comment_end(self.token).into_transition_result()
+            Character::Char(U_APOSTROPHE) => {
+                States::after_doctype_public_identifier(self.token).into_transition_result()
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
-                ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
+            Character::Char(U_NULL) => {
+                self.token.push_public_identifier(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
                 ret
             }
-            _ => {
-                self.token.push(U_HYPHEN_MINUS);
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
 
-                let mut ret = States::comment(self.token).into_transition_result();
-                ret.set_reconsume();
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::AbruptDoctypePublicIdentifier);
+                ret.push_emit(self.token);
                 ret
             }
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(c) => {
+                self.token.push_public_identifier(c);
+                States::from(self).into_transition_result()
+            }
         }
     }
 }
 
-impl CommentEnd {
+impl AfterDoctypePublicIdentifier {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => {
+                States::between_doctype_public_and_system_identifiers(self.token)
+                    .into_transition_result()
+            }
             Character::Char(U_GREATER_THAN_SIGN) => {
                 let mut ret = States::data().into_transition_result();
                 ret.push_emit(self.token);
                 ret
             }
-            Character::Char(U_EXCLAMATION_MARK) => {
-                States::comment_end_bang(self.token).into_transition_result()
-            }
-            Character::Char(U_HYPHEN_MINUS) => {
-                self.token.push(U_HYPHEN_MINUS);
+            Character::Char(U_QUOTATION_MARK) => {
+                self.token.set_system_identifier(String::new());
 
-                States::from(self).into_transition_result()
+                let mut ret = States::doctype_system_identifier_double_quoted(self.token)
+                    .into_transition_result();
+                ret.push_parse_error(
+                    ParseError::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+                );
+                ret
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInTag);
-                ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
+            Character::Char(U_APOSTROPHE) => {
+                self.token.set_system_identifier(String::new());
+
+                let mut ret = States::doctype_system_identifier_single_quoted(self.token)
+                    .into_transition_result();
+                ret.push_parse_error(
+                    ParseError::MissingWhitespaceBetweenDoctypePublicAndSystemIdentifiers,
+                );
                 ret
             }
-            _ => {
-                self.token.push(U_HYPHEN_MINUS);
-                self.token.push(U_HYPHEN_MINUS);
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
 
-                let mut ret = States::comment(self.token).into_transition_result();
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingQuoteBeforeDoctypeSystemIdentifier);
                 ret.set_reconsume();
                 ret
             }
@@ -1109,34 +2153,32 @@ impl CommentEnd {
     }
 }
 
-impl CommentEndBang {
+impl BetweenDoctypePublicAndSystemIdentifiers {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
-            Character::Char(U_HYPHEN_MINUS) => {
-                self.token.push(U_HYPHEN_MINUS);
-                self.token.push(U_HYPHEN_MINUS);
-                self.token.push(U_EXCLAMATION_MARK);
-                States::comment_end_dash(self.token).into_transition_result()
-            }
+            Character::Char(U_CHARACTER_TABULATION)
+            | Character::Char(U_LINE_FEED)
+            | Character::Char(U_FORM_FEED)
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
             Character::Char(U_GREATER_THAN_SIGN) => {
                 let mut ret = States::data().into_transition_result();
-                ret.push_parse_error(ParseError::IncorrectlyOpenedComment);
                 ret.push_emit(self.token);
                 ret
             }
-            Character::Eof => {
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInComment);
-                ret.push_emit(self.token);
-                ret.push_emit(Token::Eof);
-                ret
+            Character::Char(U_QUOTATION_MARK) => {
+                self.token.set_system_identifier(String::new());
+                States::doctype_system_identifier_double_quoted(self.token).into_transition_result()
             }
-            _ => {
-                self.token.push(U_HYPHEN_MINUS);
-                self.token.push(U_HYPHEN_MINUS);
-                self.token.push(U_EXCLAMATION_MARK);
+            Character::Char(U_APOSTROPHE) => {
+                self.token.set_system_identifier(String::new());
+                States::doctype_system_identifier_single_quoted(self.token).into_transition_result()
+            }
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
 
-                let mut ret = States::comment(self.token).into_transition_result();
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingQuoteBeforeDoctypeSystemIdentifier);
                 ret.set_reconsume();
                 ret
             }
@@ -1144,34 +2186,45 @@ impl CommentEndBang {
     }
 }
 
-impl Doctype {
-    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+impl AfterDoctypeSystemKeyword {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
             Character::Char(U_CHARACTER_TABULATION)
             | Character::Char(U_LINE_FEED)
             | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => States::before_doctype_name().into_transition_result(),
-            Character::Char(U_GREATER_THAN_SIGN) => {
-                let mut ret = States::before_doctype_name().into_transition_result();
-                ret.set_reconsume();
+            | Character::Char(U_SPACE) => {
+                States::before_doctype_system_identifier(self.token).into_transition_result()
+            }
+            Character::Char(U_QUOTATION_MARK) => {
+                self.token.set_system_identifier(String::new());
+
+                let mut ret = States::doctype_system_identifier_double_quoted(self.token)
+                    .into_transition_result();
+                ret.push_parse_error(ParseError::MissingWhitespaceAfterDoctypeSystemKeyword);
                 ret
             }
-            Character::Eof => {
-                let token = DoctypeToken {
-                    name: None,
-                    force_quirks: ForceQuirksFlag::On,
-                    ..DoctypeToken::default()
-                };
+            Character::Char(U_APOSTROPHE) => {
+                self.token.set_system_identifier(String::new());
 
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInDoctype);
-                ret.push_emit(token);
-                ret.push_emit(Token::Eof);
+                let mut ret = States::doctype_system_identifier_single_quoted(self.token)
+                    .into_transition_result();
+                ret.push_parse_error(ParseError::MissingWhitespaceAfterDoctypeSystemKeyword);
                 ret
             }
-            _ => {
-                let mut ret = States::before_doctype_name().into_transition_result();
-                ret.push_parse_error(ParseError::MissingWhitespaceBeforeDoctypeName);
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::MissingDoctypeSystemIdentifier);
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingQuoteBeforeDoctypeSystemIdentifier);
                 ret.set_reconsume();
                 ret
             }
@@ -1179,95 +2232,146 @@ impl Doctype {
     }
 }
 
-impl BeforeDoctypeName {
-    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+impl BeforeDoctypeSystemIdentifier {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
             Character::Char(U_CHARACTER_TABULATION)
             | Character::Char(U_LINE_FEED)
             | Character::Char(U_FORM_FEED)
             | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
-            Character::Char(c) if c.is_ascii_uppercase() => {
-                let token = DoctypeToken::from_char(c.to_lowercase().next().unwrap());
-                States::doctype_name(token).into_transition_result()
+            Character::Char(U_QUOTATION_MARK) => {
+                self.token.set_system_identifier(String::new());
+                States::doctype_system_identifier_double_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_APOSTROPHE) => {
+                self.token.set_system_identifier(String::new());
+                States::doctype_system_identifier_single_quoted(self.token).into_transition_result()
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::MissingDoctypeSystemIdentifier);
+                ret.push_emit(self.token);
+                ret
+            }
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::MissingQuoteBeforeDoctypeSystemIdentifier);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
+
+impl DoctypeSystemIdentifierDoubleQuoted {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_QUOTATION_MARK) => {
+                States::after_doctype_system_identifier(self.token).into_transition_result()
             }
             Character::Char(U_NULL) => {
-                let token = DoctypeToken::from_char(U_REPLACEMENT_CHARACTER);
+                self.token.push_system_identifier(U_REPLACEMENT_CHARACTER);
 
-                let mut ret = States::doctype_name(token).into_transition_result();
+                let mut ret = States::from(self).into_transition_result();
                 ret.push_parse_error(ParseError::UnexpectedNullCharacter);
                 ret
             }
             Character::Char(U_GREATER_THAN_SIGN) => {
-                let token = DoctypeToken {
-                    name: None,
-                    force_quirks: ForceQuirksFlag::On,
-                    ..DoctypeToken::default()
-                };
+                self.token.set_force_quirks(ForceQuirksFlag::On);
 
                 let mut ret = States::data().into_transition_result();
-                ret.push_parse_error(ParseError::MissingDoctypeName);
-                ret.push_emit(token);
+                ret.push_parse_error(ParseError::AbruptDoctypeSystemIdentifier);
+                ret.push_emit(self.token);
                 ret
             }
-            Character::Eof => {
-                let token = DoctypeToken {
-                    name: None,
-                    force_quirks: ForceQuirksFlag::On,
-                    ..DoctypeToken::default()
-                };
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(c) => {
+                self.token.push_system_identifier(c);
+                States::from(self).into_transition_result()
+            }
+        }
+    }
+}
 
-                let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInDoctype);
-                ret.push_emit(token);
-                ret.push_emit(Token::Eof);
+impl DoctypeSystemIdentifierSingleQuoted {
+    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_APOSTROPHE) => {
+                States::after_doctype_system_identifier(self.token).into_transition_result()
+            }
+            Character::Char(U_NULL) => {
+                self.token.push_system_identifier(U_REPLACEMENT_CHARACTER);
+
+                let mut ret = States::from(self).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedNullCharacter);
+                ret
+            }
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                self.token.set_force_quirks(ForceQuirksFlag::On);
+
+                let mut ret = States::data().into_transition_result();
+                ret.push_parse_error(ParseError::AbruptDoctypeSystemIdentifier);
+                ret.push_emit(self.token);
                 ret
             }
+            Character::Eof => eof_in_doctype(self.token),
             Character::Char(c) => {
-                let token = DoctypeToken::from_char(c);
-                States::doctype_name(token).into_transition_result()
+                self.token.push_system_identifier(c);
+                States::from(self).into_transition_result()
             }
         }
     }
 }
 
-impl DoctypeName {
-    pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
+impl AfterDoctypeSystemIdentifier {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
         match c {
             Character::Char(U_CHARACTER_TABULATION)
             | Character::Char(U_LINE_FEED)
             | Character::Char(U_FORM_FEED)
-            | Character::Char(U_SPACE) => States::after_doctype_name().into_transition_result(),
+            | Character::Char(U_SPACE) => States::from(self).into_transition_result(),
             Character::Char(U_GREATER_THAN_SIGN) => {
                 let mut ret = States::data().into_transition_result();
                 ret.push_emit(self.token);
                 ret
             }
-            Character::Char(c) if c.is_ascii_uppercase() => {
-                self.token.push(c.to_lowercase().next().unwrap());
+            Character::Eof => eof_in_doctype(self.token),
+            Character::Char(_) => {
+                // This does not set the force-quirks flag
+                let mut ret = States::bogus_doctype(self.token).into_transition_result();
+                ret.push_parse_error(ParseError::UnexpectedCharacterAfterDoctypeSystemIdentifier);
+                ret.set_reconsume();
+                ret
+            }
+        }
+    }
+}
 
-                States::from(self).into_transition_result()
+impl BogusDoctype {
+    pub(super) fn on_character(self, c: Character) -> TransitionResult {
+        match c {
+            Character::Char(U_GREATER_THAN_SIGN) => {
+                let mut ret = States::data().into_transition_result();
+                ret.push_emit(self.token);
+                ret
             }
             Character::Char(U_NULL) => {
-                self.token.push(U_REPLACEMENT_CHARACTER);
-
                 let mut ret = States::from(self).into_transition_result();
                 ret.push_parse_error(ParseError::UnexpectedNullCharacter);
                 ret
             }
             Character::Eof => {
-                self.token.set_force_quirks(ForceQuirksFlag::On);
-
                 let mut ret = States::term().into_transition_result();
-                ret.push_parse_error(ParseError::EofInDoctype);
                 ret.push_emit(self.token);
                 ret.push_emit(Token::Eof);
                 ret
             }
-            Character::Char(c) => {
-                self.token.push(c);
-
-                States::from(self).into_transition_result()
-            }
+            Character::Char(_) => States::from(self).into_transition_result(),
         }
     }
 }
@@ -1328,7 +2432,7 @@ impl NamedCharacterReference {
 
         let next_char_equals_or_alpha = match next_c {
             Character::Char(U_EQUALS_SIGN) => true,
-            Character::Char(ch) if ch.is_alphanumeric() => true,
+            Character::Char(ch) if ch.is_ascii_alphanumeric() => true,
             _ => false,
         };
 
@@ -1342,7 +2446,7 @@ impl NamedCharacterReference {
             let was_consumed_as_part_of_attribute = self.get_attribute_token().is_some();
             let last_char_is_semicolon = char_ref.ends_with(U_SEMICOLON);
             let historical = was_consumed_as_part_of_attribute
-                && last_char_is_semicolon
+                && !last_char_is_semicolon
                 && next_char_equals_or_alpha;
 
             if historical {
@@ -1583,24 +2687,31 @@ impl HexadecimalCharacterReference {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
             Character::Char(ch) if ch.is_ascii_digit() => {
-                self.character_reference_code *= 16;
-                self.character_reference_code += ch.to_digit(10).unwrap();
+                // Saturates, any code above U+10FFFF is replaced the same way
+                self.character_reference_code = self
+                    .character_reference_code
+                    .saturating_mul(16)
+                    .saturating_add(ch.to_digit(10).unwrap());
 
                 States::from(self).into_transition_result()
             }
             Character::Char(ch)
                 if codepoint::is_ascii_upper_hex_digit(ch as codepoint::Codepoint) =>
             {
-                self.character_reference_code *= 16;
-                self.character_reference_code += ch.to_digit(16).unwrap();
+                self.character_reference_code = self
+                    .character_reference_code
+                    .saturating_mul(16)
+                    .saturating_add(ch.to_digit(16).unwrap());
 
                 States::from(self).into_transition_result()
             }
             Character::Char(ch)
                 if codepoint::is_ascii_lower_hex_digit(ch as codepoint::Codepoint) =>
             {
-                self.character_reference_code *= 16;
-                self.character_reference_code += ch.to_digit(16).unwrap();
+                self.character_reference_code = self
+                    .character_reference_code
+                    .saturating_mul(16)
+                    .saturating_add(ch.to_digit(16).unwrap());
 
                 States::from(self).into_transition_result()
             }
@@ -1630,8 +2741,11 @@ impl DecimalCharacterReference {
     pub(super) fn on_character(mut self, c: Character) -> TransitionResult {
         match c {
             Character::Char(ch) if ch.is_ascii_digit() => {
-                self.character_reference_code *= 10;
-                self.character_reference_code += ch.to_digit(10).unwrap();
+                // Saturates, any code above U+10FFFF is replaced the same way
+                self.character_reference_code = self
+                    .character_reference_code
+                    .saturating_mul(10)
+                    .saturating_add(ch.to_digit(10).unwrap());
 
                 States::from(self).into_transition_result()
             }
@@ -1661,6 +2775,10 @@ impl NumericCharacterReferenceEnd {
     pub(super) fn on_advance(mut self) -> TransitionResult {
         let (parse_err, character_reference_code) = match self.character_reference_code {
             0x00 => (Some(ParseError::NullCharacterReference), 0xFFFD),
+            c if c > 0x10FFFF => (
+                Some(ParseError::CharacterReferenceOutsideUnicodeRange),
+                0xFFFD,
+            ),
             c if codepoint::is_surrogate(c) => (
                 Some(ParseError::CharacterReferenceOutsideUnicodeRange),
                 0xFFFD,
@@ -1800,3 +2918,22 @@ fn flush_codepoints_consumed_as_character_reference(
     }
     to_emit
 }
+
+/// Emit the doctype with its force-quirks flag on, then the end-of-file token
+fn eof_in_doctype(mut token: Token) -> TransitionResult {
+    token.set_force_quirks(ForceQuirksFlag::On);
+
+    let mut ret = States::term().into_transition_result();
+    ret.push_parse_error(ParseError::EofInDoctype);
+    ret.push_emit(token);
+    ret.push_emit(Token::Eof);
+    ret
+}
+
+/// Emit the end-of-file token after an unclosed `<!--` in script data
+fn eof_in_script_html_comment_like_text() -> TransitionResult {
+    let mut ret = States::term().into_transition_result();
+    ret.push_parse_error(ParseError::EofInScriptHtmlCommentLikeText);
+    ret.push_emit(Token::Eof);
+    ret
+}
@@ -0,0 +1,461 @@
+//! Run the html5lib tests in `tests/html5lib-tests`, see its README for where they come from
+//!
+//! Every `tree-construction/*.dat` and `tokenizer/*.test` file is run and a table of how many
+//! of its tests pass is printed, see it with `cargo test --test html5lib -- --nocapture`.
+//!
+//! The tests which are known to pass are listed in `passing.txt` next to the test files,
+//! if any of them fail so does the test. Run with `HTML5LIB_UPDATE_PASSING=1` to rewrite
+//! the lists once more tests pass. Any test which panics fails the test, listed or not.
+use std::{
+    cell::Cell,
+    collections::BTreeSet,
+    env, fs,
+    io::Cursor,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Once,
+};
+
+use serde_json::{json, Map, Value};
+
+use html_parser::{
+    dom::{Element, Namespace, ToTestTree},
+    tokenizer::{ForceQuirksFlag, SelfClosingFlag, TagName, Token},
+    Parser, Tokenizer,
+};
+
+const TEST_DIR: &str = "./tests/html5lib-tests";
+
+#[test]
+fn tree_construction() {
+    let results = run_dir("tree-construction", "dat", |path| {
+        parse_dat_file(&fs::read_to_string(path).unwrap())
+            .into_iter()
+            .map(|test| {
+                let id = format!("{}", test.line);
+                let outcome = if test.scripting {
+                    // The parser doesn't yet support scripting
+                    Outcome::Skipped
+                } else {
+                    catch_panics(|| test.run())
+                };
+                (id, outcome)
+            })
+            .collect()
+    });
+    check_results("tree-construction", &results);
+}
+
+#[test]
+fn tokenizer() {
+    let results = run_dir("tokenizer", "test", |path| {
+        let json: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        let tests = json["tests"].as_array().cloned().unwrap_or_default();
+
+        let mut results = Vec::new();
+        for (i, test) in tests.iter().enumerate() {
+            let initial_states = match test["initialStates"].as_array() {
+                Some(states) => states.iter().map(|s| s.as_str().unwrap()).collect(),
+                None => vec!["Data state"],
+            };
+            for state in initial_states {
+                let id = format!("{} ({})", i, state);
+                let outcome = match TokenizerTest::new(test, state) {
+                    Some(test) => catch_panics(|| test.run()),
+                    None => Outcome::Skipped,
+                };
+                results.push((id, outcome));
+            }
+        }
+        results
+    });
+    check_results("tokenizer", &results);
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Passed,
+    Failed,
+    /// The parser or tokenizer panicked, with this message
+    Panicked(String),
+    Skipped,
+}
+
+/// The outcome of each test in each file, by test id
+type Results = Vec<(String, Vec<(String, Outcome)>)>;
+
+fn run_dir<F>(dir: &str, extension: &str, run_file: F) -> Results
+where
+    F: Fn(&Path) -> Vec<(String, Outcome)>,
+{
+    let mut paths: Vec<PathBuf> = fs::read_dir(Path::new(TEST_DIR).join(dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == extension))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            (name, run_file(path))
+        })
+        .collect()
+}
+
+/// Print the pass/fail matrix, and fail if any test panicked or any test in `passing.txt` no
+/// longer passes
+fn check_results(dir: &str, results: &Results) {
+    let passing_path = Path::new(TEST_DIR).join(dir).join("passing.txt");
+    let known_passing: BTreeSet<String> = fs::read_to_string(&passing_path)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+
+    println!(
+        "{:<32} {:>7} {:>7} {:>8} {:>7}",
+        format!("{}/", dir),
+        "passed",
+        "failed",
+        "panicked",
+        "skipped"
+    );
+    let mut passing = BTreeSet::new();
+    let mut failing = Vec::new();
+    let mut panicked = Vec::new();
+    let mut skipped = Vec::new();
+    let mut regressions = Vec::new();
+    let mut totals = [0; 4];
+    for (file, outcomes) in results {
+        let mut counts = [0; 4];
+        for (id, outcome) in outcomes {
+            let id = format!("{}:{}", file, id);
+            match outcome {
+                Outcome::Passed => {
+                    counts[0] += 1;
+                    passing.insert(id);
+                }
+                Outcome::Failed => {
+                    counts[1] += 1;
+                    if known_passing.contains(&id) {
+                        regressions.push(id.clone());
+                    }
+                    failing.push(id);
+                }
+                Outcome::Panicked(message) => {
+                    counts[2] += 1;
+                    panicked.push(format!("{}: {}", id, message));
+                }
+                Outcome::Skipped => {
+                    counts[3] += 1;
+                    skipped.push(id);
+                }
+            }
+        }
+        println!(
+            "{:<32} {:>7} {:>7} {:>8} {:>7}",
+            file, counts[0], counts[1], counts[2], counts[3]
+        );
+        for (total, count) in totals.iter_mut().zip(&counts) {
+            *total += count;
+        }
+    }
+    println!(
+        "{:<32} {:>7} {:>7} {:>8} {:>7}",
+        "total", totals[0], totals[1], totals[2], totals[3]
+    );
+    for id in &failing {
+        println!("failed: {}", id);
+    }
+    for id in &panicked {
+        println!("panicked: {}", id);
+    }
+    for id in &skipped {
+        println!("skipped: {}", id);
+    }
+
+    let newly_passing = passing.difference(&known_passing).count();
+    if env::var_os("HTML5LIB_UPDATE_PASSING").is_some() {
+        let mut contents: String = passing.iter().map(|id| format!("{}\n", id)).collect();
+        if contents.is_empty() {
+            contents.push('\n');
+        }
+        fs::write(&passing_path, contents).unwrap();
+    } else if newly_passing > 0 {
+        println!(
+            "{} more tests now pass, run with HTML5LIB_UPDATE_PASSING=1 to record them",
+            newly_passing
+        );
+    }
+
+    // Input should never make the parser panic, whether or not the test is known to pass
+    assert!(panicked.is_empty(), "Tests panicked: {:#?}", panicked);
+    assert!(
+        regressions.is_empty(),
+        "Tests which used to pass now fail: {:#?}",
+        regressions
+    );
+}
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run a test, telling a panic apart from output which doesn't match
+fn catch_panics<F>(f: F) -> Outcome
+where
+    F: FnOnce() -> bool,
+{
+    // The panic messages are printed with the results instead
+    static INSTALL_HOOK: Once = Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !QUIET.with(Cell::get) {
+                default_hook(info)
+            }
+        }));
+    });
+
+    QUIET.with(|quiet| quiet.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    QUIET.with(|quiet| quiet.set(false));
+
+    match result {
+        Ok(true) => Outcome::Passed,
+        Ok(false) => Outcome::Failed,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                (*message).to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            Outcome::Panicked(message)
+        }
+    }
+}
+
+/// A test from a tree construction `.dat` file
+#[derive(Debug, Default)]
+struct TreeTest {
+    // The line the test starts on, which identifies it
+    line: usize,
+    data: String,
+    fragment_context: Option<String>,
+    scripting: bool,
+    document: String,
+}
+
+impl TreeTest {
+    fn run(&self) -> bool {
+        let tree = match self.fragment_context {
+            Some(ref context) => {
+                let context = fragment_context_element(context);
+                let context = context.borrow();
+                match Parser::parse_fragment(Cursor::new(&self.data), &context) {
+                    Ok(fragment) => fragment.to_test_tree(),
+                    Err(_) => return false,
+                }
+            }
+            None => match Parser::new(Cursor::new(&self.data)).parse() {
+                Ok(document) => document.to_test_tree(),
+                Err(_) => return false,
+            },
+        };
+        tree == self.document
+    }
+}
+
+/// The context element of a fragment test, eg. `td` or `svg path`
+fn fragment_context_element(context: &str) -> std::rc::Rc<std::cell::RefCell<Element>> {
+    let (namespace, name) = match context.split_once(' ') {
+        Some(("svg", name)) => (Namespace::SVG, name),
+        Some(("math", name)) => (Namespace::MathML, name),
+        _ => (Namespace::HTML, context),
+    };
+    let name: TagName = name.parse().unwrap();
+    Element::new(name, namespace, Vec::new())
+}
+
+/// <https://github.com/html5lib/html5lib-tests/tree/master/tree-construction#test-files>
+fn parse_dat_file(contents: &str) -> Vec<TreeTest> {
+    let mut tests = Vec::new();
+    let mut test = TreeTest::default();
+    let mut section = "";
+    let mut lines: Vec<&str> = Vec::new();
+
+    let finish_section = |test: &mut TreeTest, section: &str, lines: &mut Vec<&str>| {
+        match section {
+            "#data" => test.data = lines.join("\n"),
+            "#document-fragment" => test.fragment_context = lines.first().map(|l| l.to_string()),
+            "#document" => {
+                // Tests are separated by a blank line
+                if lines.last() == Some(&"") {
+                    let _ = lines.pop();
+                }
+                test.document = lines.iter().map(|line| format!("{}\n", line)).collect();
+            }
+            _ => {}
+        }
+        lines.clear();
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        let header = matches!(
+            line,
+            "#data"
+                | "#errors"
+                | "#new-errors"
+                | "#document-fragment"
+                | "#script-off"
+                | "#script-on"
+                | "#document"
+        );
+        if !header {
+            lines.push(line);
+            continue;
+        }
+
+        finish_section(&mut test, section, &mut lines);
+        if line == "#data" && !section.is_empty() {
+            tests.push(std::mem::take(&mut test));
+        }
+        if line == "#data" {
+            test.line = i + 1;
+        }
+        if line == "#script-on" {
+            test.scripting = true;
+        }
+        section = line;
+    }
+    if !section.is_empty() {
+        finish_section(&mut test, section, &mut lines);
+        tests.push(test);
+    }
+    tests
+}
+
+/// A test from a tokenizer `.test` file, run from one of its initial states
+#[derive(Debug)]
+struct TokenizerTest {
+    input: String,
+    output: Value,
+    initial_state: String,
+    last_start_tag: Option<String>,
+}
+
+impl TokenizerTest {
+    /// None if the test can't be run
+    fn new(test: &Value, initial_state: &str) -> Option<Self> {
+        // The CDATA section state can only be reached in foreign content
+        if initial_state == "CDATA section state" {
+            return None;
+        }
+
+        let double_escaped = test["doubleEscaped"].as_bool().unwrap_or(false);
+        let unescape = |s: &str| {
+            if double_escaped {
+                unescape(s)
+            } else {
+                Some(s.to_string())
+            }
+        };
+
+        let mut output = test["output"].clone();
+        for token in output.as_array_mut()? {
+            for value in token.as_array_mut()? {
+                if let Value::String(s) = value {
+                    *s = unescape(s)?;
+                }
+            }
+        }
+
+        Some(TokenizerTest {
+            input: unescape(test["input"].as_str()?)?,
+            output,
+            initial_state: initial_state.to_string(),
+            last_start_tag: test["lastStartTag"].as_str().map(String::from),
+        })
+    }
+
+    fn run(&self) -> bool {
+        let mut tokenizer = Tokenizer::new(Cursor::new(self.input.as_bytes()), false);
+        match self.initial_state.as_str() {
+            "Data state" => {}
+            "PLAINTEXT state" => tokenizer.switch_to_plaintext_state(),
+            "RCDATA state" => tokenizer.switch_to_rcdata_state(),
+            "RAWTEXT state" => tokenizer.switch_to_rawtext_state(),
+            "Script data state" => tokenizer.switch_to_script_data_state(),
+            state => panic!("Unknown initial state {:?}", state),
+        }
+        if let Some(ref name) = self.last_start_tag {
+            tokenizer.set_last_start_tag(name);
+        }
+
+        let mut output: Vec<Value> = Vec::new();
+        for (token, _) in tokenizer {
+            let (value, characters) = match token {
+                Token::Doctype(doctype) => (
+                    json!([
+                        "DOCTYPE",
+                        doctype.name,
+                        doctype.public_identifier,
+                        doctype.system_identifier,
+                        doctype.force_quirks == ForceQuirksFlag::Off
+                    ]),
+                    None,
+                ),
+                Token::StartTag(tag) => {
+                    let attributes: Map<String, Value> = tag
+                        .attributes
+                        .iter()
+                        .map(|a| (a.name.clone(), Value::from(a.value.clone())))
+                        .collect();
+                    let mut value = json!(["StartTag", tag.name.to_string(), attributes]);
+                    if tag.self_closing == SelfClosingFlag::Set {
+                        value.as_array_mut().unwrap().push(Value::Bool(true));
+                    }
+                    (value, None)
+                }
+                Token::EndTag(tag) => (json!(["EndTag", tag.name.to_string()]), None),
+                Token::Comment(data) => (json!(["Comment", data]), None),
+                Token::Character(c) => (Value::Null, Some(c.to_string())),
+                Token::Characters(s) => (Value::Null, Some(s)),
+                Token::Eof => break,
+            };
+
+            // Adjacent character tokens are combined
+            match (characters, output.last_mut()) {
+                (Some(s), Some(Value::Array(last))) if last[0] == "Character" => {
+                    let data = format!("{}{}", last[1].as_str().unwrap(), s);
+                    last[1] = Value::from(data);
+                }
+                (Some(s), _) => output.push(json!(["Character", s])),
+                (None, _) => output.push(value),
+            }
+        }
+
+        Value::Array(output) == self.output
+    }
+}
+
+/// Replace the `\uXXXX` escapes of a double escaped test
+///
+/// Returns None if the string contains a lone surrogate, which a Rust string can't hold.
+fn unescape(s: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find("\\u") {
+        unescaped.push_str(&rest[..i]);
+        let code = u32::from_str_radix(rest.get(i + 2..i + 6)?, 16).ok()?;
+        unescaped.push(char::from_u32(code)?);
+        rest = &rest[i + 6..];
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}